@@ -3,23 +3,27 @@
 use lean_runtime::*;
 
 // add : Nat → Nat → Nat
-unsafe fn l_add(a: *mut LeanObject, b: *mut LeanObject) -> *mut LeanObject {
+unsafe extern "C" fn l_add(a: *mut LeanObject, b: *mut LeanObject) -> *mut LeanObject {
     lean_nat_add(a, b)
 }
 
 // mul : Nat → Nat → Nat
-unsafe fn l_mul(a: *mut LeanObject, b: *mut LeanObject) -> *mut LeanObject {
+unsafe extern "C" fn l_mul(a: *mut LeanObject, b: *mut LeanObject) -> *mut LeanObject {
     lean_nat_mul(a, b)
 }
 
 // compose : (β → γ) → (α → β) → α → γ
-unsafe fn l_compose(f: *mut LeanObject, g: *mut LeanObject, x: *mut LeanObject) -> *mut LeanObject {
+unsafe extern "C" fn l_compose(
+    f: *mut LeanObject,
+    g: *mut LeanObject,
+    x: *mut LeanObject,
+) -> *mut LeanObject {
     let gx = lean_apply_1(g, x);
     lean_apply_1(f, gx)
 }
 
 // twice : (α → α) → α → α
-unsafe fn l_twice(f: *mut LeanObject, x: *mut LeanObject) -> *mut LeanObject {
+unsafe extern "C" fn l_twice(f: *mut LeanObject, x: *mut LeanObject) -> *mut LeanObject {
     lean_inc(f);
     let fx = lean_apply_1(f, x);
     lean_apply_1(f, fx)
@@ -113,7 +113,7 @@ unsafe fn print_list_nat(list: *mut LeanObject) {
 }
 
 // Helper: create a closure for (· + 1)
-unsafe fn add_one(x: *mut LeanObject) -> *mut LeanObject {
+unsafe extern "C" fn add_one(x: *mut LeanObject) -> *mut LeanObject {
     lean_nat_add(x, lean_box(1))
 }
 
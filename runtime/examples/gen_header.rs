@@ -0,0 +1,8 @@
+//! Regenerate the C header for the `c-api` exports:
+//! cargo run -p lean-runtime --features c-api --example gen_header
+
+fn main() {
+    let path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/lean_runtime.h");
+    std::fs::write(path, lean_runtime::capi::c_header()).unwrap();
+    println!("wrote {}", path);
+}
@@ -0,0 +1,812 @@
+/* Generated by `cargo run -p lean-runtime --features c-api --example gen_header`.
+ * Do not edit. */
+#ifndef LEAN_RUNTIME_H
+#define LEAN_RUNTIME_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#if defined(__GNUC__)
+#define LEAN_RUNTIME_NORETURN __attribute__((noreturn))
+#else
+#define LEAN_RUNTIME_NORETURN
+#endif
+
+typedef struct {
+    int m_rc;
+    unsigned m_cs_sz:16;
+    unsigned m_other:8;
+    unsigned m_tag:8;
+} lean_object;
+
+typedef void (*lean_external_finalize_proc)(void *);
+typedef void (*lean_external_foreach_proc)(void *, lean_object *);
+
+typedef struct {
+    lean_external_finalize_proc m_finalize;
+    lean_external_foreach_proc m_foreach;
+} lean_external_class;
+
+/* adapters */
+lean_object *lean_mk_string(char const *s);
+lean_object *lean_mk_string_from_bytes(char const *s, size_t sz);
+lean_object *lean_cstr_to_nat(char const *s);
+lean_external_class *lean_register_external_class(lean_external_finalize_proc finalize, lean_external_foreach_proc foreach);
+uint8_t lean_ctor_get_uint8(lean_object *o, uint32_t offset);
+void lean_ctor_set_uint8(lean_object *o, uint32_t offset, uint8_t v);
+uint16_t lean_ctor_get_uint16(lean_object *o, uint32_t offset);
+void lean_ctor_set_uint16(lean_object *o, uint32_t offset, uint16_t v);
+uint32_t lean_ctor_get_uint32(lean_object *o, uint32_t offset);
+void lean_ctor_set_uint32(lean_object *o, uint32_t offset, uint32_t v);
+uint64_t lean_ctor_get_uint64(lean_object *o, uint32_t offset);
+void lean_ctor_set_uint64(lean_object *o, uint32_t offset, uint64_t v);
+size_t lean_ctor_get_usize(lean_object *o, uint32_t i);
+void lean_ctor_set_usize(lean_object *o, uint32_t i, size_t v);
+double lean_ctor_get_float(lean_object *o, uint32_t offset);
+void lean_ctor_set_float(lean_object *o, uint32_t offset, double v);
+float lean_ctor_get_float32(lean_object *o, uint32_t offset);
+void lean_ctor_set_float32(lean_object *o, uint32_t offset, float v);
+
+/* allocprof */
+uint64_t lean_alloc_prof_count(void);
+
+/* array */
+lean_object *lean_alloc_array(size_t size, size_t capacity);
+lean_object *lean_mk_empty_array(void);
+lean_object *lean_mk_empty_array_with_capacity(lean_object *cap);
+size_t lean_array_size(lean_object *a);
+lean_object *lean_array_get_size(lean_object *a);
+lean_object **lean_array_data(lean_object *a);
+lean_object *lean_array_get_core(lean_object *a, size_t i);
+lean_object *lean_array_get_borrowed(lean_object *def, lean_object *a, lean_object *i);
+lean_object *lean_array_get(lean_object *def, lean_object *a, lean_object *i);
+lean_object *lean_array_push(lean_object *a, lean_object *v);
+lean_object *lean_array_fget(lean_object *a, lean_object *i);
+lean_object *lean_array_fget_borrowed(lean_object *a, lean_object *i);
+lean_object *lean_array_fset(lean_object *a, lean_object *i, lean_object *v);
+lean_object *lean_array_mk(lean_object *list);
+lean_object *lean_array_to_list(lean_object *a);
+lean_object *lean_array_set(lean_object *a, lean_object *i, lean_object *v);
+lean_object *lean_array_uget(lean_object *a, size_t i);
+lean_object *lean_array_uset(lean_object *a, size_t i, lean_object *v);
+lean_object *lean_array_pop(lean_object *a);
+lean_object *lean_array_fswap(lean_object *a, lean_object *i, lean_object *j);
+lean_object *lean_array_swap(lean_object *a, lean_object *i, lean_object *j);
+lean_object *lean_mk_array(lean_object *n, lean_object *v);
+lean_object *lean_copy_array(lean_object *a);
+
+/* bignat */
+void lean_bignat_drop(lean_object *o);
+void lean_free_bignat(lean_object *o);
+
+/* box */
+lean_object *lean_box(size_t n);
+size_t lean_unbox(lean_object *o);
+bool lean_is_scalar(lean_object *o);
+lean_object *lean_box_uint32(uint32_t n);
+uint32_t lean_unbox_uint32(lean_object *o);
+lean_object *lean_box_uint64(uint64_t n);
+uint64_t lean_unbox_uint64(lean_object *o);
+lean_object *lean_box_usize(size_t n);
+size_t lean_unbox_usize(lean_object *o);
+uint8_t lean_uint32_dec_eq(uint32_t a, uint32_t b);
+uint8_t lean_uint32_dec_lt(uint32_t a, uint32_t b);
+
+/* closure */
+lean_object *lean_alloc_closure(void *fun, uint32_t arity, uint32_t num_fixed);
+lean_object *lean_closure_get(lean_object *o, uint32_t i);
+void lean_closure_set(lean_object *o, uint32_t i, lean_object *v);
+lean_object *lean_apply_1(lean_object *f, lean_object *a1);
+lean_object *lean_apply_2(lean_object *f, lean_object *a1, lean_object *a2);
+lean_object *lean_apply_3(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3);
+lean_object *lean_apply_4(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4);
+lean_object *lean_apply_5(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5);
+lean_object *lean_apply_6(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6);
+lean_object *lean_apply_7(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7);
+lean_object *lean_apply_8(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8);
+lean_object *lean_apply_9(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8, lean_object *a9);
+lean_object *lean_apply_10(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8, lean_object *a9, lean_object *a10);
+lean_object *lean_apply_11(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8, lean_object *a9, lean_object *a10, lean_object *a11);
+lean_object *lean_apply_12(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8, lean_object *a9, lean_object *a10, lean_object *a11, lean_object *a12);
+lean_object *lean_apply_13(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8, lean_object *a9, lean_object *a10, lean_object *a11, lean_object *a12, lean_object *a13);
+lean_object *lean_apply_14(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8, lean_object *a9, lean_object *a10, lean_object *a11, lean_object *a12, lean_object *a13, lean_object *a14);
+lean_object *lean_apply_15(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8, lean_object *a9, lean_object *a10, lean_object *a11, lean_object *a12, lean_object *a13, lean_object *a14, lean_object *a15);
+lean_object *lean_apply_16(lean_object *f, lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4, lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8, lean_object *a9, lean_object *a10, lean_object *a11, lean_object *a12, lean_object *a13, lean_object *a14, lean_object *a15, lean_object *a16);
+lean_object *lean_apply_n(lean_object *f, uint32_t n, lean_object * const *args);
+lean_object *lean_apply_m(lean_object *f, uint32_t n, lean_object * const *args);
+
+/* ctor */
+lean_object *lean_alloc_ctor(uint32_t tag, uint32_t num_objs, uint32_t scalar_sz);
+lean_object *lean_ctor_get(lean_object *o, uint32_t i);
+void lean_ctor_set(lean_object *o, uint32_t i, lean_object *v);
+void lean_ctor_release(lean_object *o, uint32_t i);
+void lean_ctor_set_tag(lean_object *o, uint8_t tag);
+uint8_t lean_obj_tag(lean_object *o);
+
+/* debug */
+void lean_debug_poison(lean_object *o);
+bool lean_debug_is_persistent(lean_object *o);
+
+/* external */
+lean_object *lean_alloc_external(lean_external_class *cls, void *data);
+lean_external_class *lean_get_external_class(lean_object *o);
+void *lean_get_external_data(lean_object *o);
+lean_object *lean_set_external_data(lean_object *o, void *data);
+
+/* float */
+lean_object *lean_box_float(double v);
+double lean_unbox_float(lean_object *o);
+double lean_float_add(double a, double b);
+double lean_float_sub(double a, double b);
+double lean_float_mul(double a, double b);
+double lean_float_div(double a, double b);
+double lean_float_negate(double a);
+uint8_t lean_float_beq(double a, double b);
+uint8_t lean_float_decLe(double a, double b);
+uint8_t lean_float_decLt(double a, double b);
+uint8_t lean_float_isnan(double a);
+uint8_t lean_float_isfinite(double a);
+uint8_t lean_float_isinf(double a);
+double lean_float_of_bits(uint64_t u);
+uint64_t lean_float_to_bits(double d);
+lean_object *lean_float_to_string(double a);
+lean_object *lean_float_frexp(double a);
+double lean_float_scaleb(double a, lean_object *b);
+uint8_t lean_float_to_uint8(double a);
+uint16_t lean_float_to_uint16(double a);
+uint32_t lean_float_to_uint32(double a);
+uint64_t lean_float_to_uint64(double a);
+size_t lean_float_to_usize(double a);
+uint8_t lean_float_to_int8(double a);
+uint16_t lean_float_to_int16(double a);
+uint32_t lean_float_to_int32(double a);
+uint64_t lean_float_to_int64(double a);
+size_t lean_float_to_isize(double a);
+double lean_uint8_to_float(uint8_t a);
+double lean_uint16_to_float(uint16_t a);
+double lean_uint32_to_float(uint32_t a);
+double lean_uint64_to_float(uint64_t a);
+double lean_usize_to_float(size_t a);
+double lean_int8_to_float(uint8_t a);
+double lean_int16_to_float(uint16_t a);
+double lean_int32_to_float(uint32_t a);
+double lean_int64_to_float(uint64_t a);
+double lean_isize_to_float(size_t a);
+lean_object *lean_box_float32(float v);
+float lean_unbox_float32(lean_object *o);
+float lean_float32_add(float a, float b);
+float lean_float32_sub(float a, float b);
+float lean_float32_mul(float a, float b);
+float lean_float32_div(float a, float b);
+float lean_float32_negate(float a);
+uint8_t lean_float32_beq(float a, float b);
+uint8_t lean_float32_decLe(float a, float b);
+uint8_t lean_float32_decLt(float a, float b);
+uint8_t lean_float32_isnan(float a);
+uint8_t lean_float32_isfinite(float a);
+uint8_t lean_float32_isinf(float a);
+float lean_float32_of_bits(uint32_t u);
+uint32_t lean_float32_to_bits(float d);
+lean_object *lean_float32_to_string(float a);
+lean_object *lean_float32_frexp(float a);
+float lean_float32_scaleb(float a, lean_object *b);
+uint8_t lean_float32_to_uint8(float a);
+uint16_t lean_float32_to_uint16(float a);
+uint32_t lean_float32_to_uint32(float a);
+uint64_t lean_float32_to_uint64(float a);
+size_t lean_float32_to_usize(float a);
+uint8_t lean_float32_to_int8(float a);
+uint16_t lean_float32_to_int16(float a);
+uint32_t lean_float32_to_int32(float a);
+uint64_t lean_float32_to_int64(float a);
+size_t lean_float32_to_isize(float a);
+float lean_uint8_to_float32(uint8_t a);
+float lean_uint16_to_float32(uint16_t a);
+float lean_uint32_to_float32(uint32_t a);
+float lean_uint64_to_float32(uint64_t a);
+float lean_usize_to_float32(size_t a);
+float lean_int8_to_float32(uint8_t a);
+float lean_int16_to_float32(uint16_t a);
+float lean_int32_to_float32(uint32_t a);
+float lean_int64_to_float32(uint64_t a);
+float lean_isize_to_float32(size_t a);
+float lean_float_to_float32(double a);
+double lean_float32_to_float(float a);
+
+/* floatarray */
+lean_object *lean_mk_empty_float_array(lean_object *cap);
+lean_object *lean_float_array_size(lean_object *a);
+lean_object *lean_float_array_push(lean_object *a, double v);
+double lean_float_array_uget(lean_object *a, size_t i);
+double lean_float_array_fget(lean_object *a, lean_object *i);
+double lean_float_array_get(lean_object *a, double dflt, lean_object *i);
+lean_object *lean_float_array_uset(lean_object *a, size_t i, double v);
+lean_object *lean_float_array_fset(lean_object *a, lean_object *i, double v);
+lean_object *lean_float_array_set(lean_object *a, lean_object *i, double v);
+lean_object *lean_float_array_mk(lean_object *list);
+lean_object *lean_float_array_data(lean_object *a);
+lean_object *lean_copy_float_array(lean_object *a);
+
+/* int */
+void lean_bigint_drop(lean_object *o);
+void lean_free_bigint(lean_object *o);
+int64_t lean_scalar_to_int64(lean_object *a);
+lean_object *lean_int64_to_int(int64_t n);
+lean_object *lean_int_to_int(int32_t n);
+lean_object *lean_nat_to_int(lean_object *a);
+lean_object *lean_int_neg_succ_of_nat(lean_object *a);
+lean_object *lean_nat_succ(lean_object *a);
+lean_object *lean_int_neg(lean_object *a);
+lean_object *lean_int_add(lean_object *a1, lean_object *a2);
+lean_object *lean_int_sub(lean_object *a1, lean_object *a2);
+lean_object *lean_int_mul(lean_object *a1, lean_object *a2);
+lean_object *lean_int_div(lean_object *a1, lean_object *a2);
+lean_object *lean_int_div_exact(lean_object *a1, lean_object *a2);
+lean_object *lean_int_mod(lean_object *a1, lean_object *a2);
+lean_object *lean_int_ediv(lean_object *a1, lean_object *a2);
+lean_object *lean_int_emod(lean_object *a1, lean_object *a2);
+bool lean_int_eq(lean_object *a1, lean_object *a2);
+bool lean_int_ne(lean_object *a1, lean_object *a2);
+bool lean_int_le(lean_object *a1, lean_object *a2);
+bool lean_int_lt(lean_object *a1, lean_object *a2);
+uint8_t lean_int_dec_eq(lean_object *a1, lean_object *a2);
+uint8_t lean_int_dec_le(lean_object *a1, lean_object *a2);
+uint8_t lean_int_dec_lt(lean_object *a1, lean_object *a2);
+uint8_t lean_int_dec_nonneg(lean_object *a);
+lean_object *lean_int_to_nat(lean_object *a);
+lean_object *lean_nat_abs(lean_object *i);
+
+/* io */
+lean_object *lean_io_mk_world(void);
+lean_object *lean_io_result_mk_ok(lean_object *value);
+lean_object *lean_io_result_mk_error(lean_object *error);
+bool lean_io_result_is_ok(lean_object *r);
+lean_object *lean_io_result_get_value(lean_object *r);
+lean_object *lean_io_result_get_error(lean_object *r);
+lean_object *lean_io_prim_println(lean_object *s, lean_object *_rw);
+lean_object *lean_io_prim_print(lean_object *s, lean_object *_rw);
+lean_object *lean_io_prim_eprintln(lean_object *s, lean_object *_rw);
+lean_object *lean_io_pure(lean_object *value, lean_object *_rw);
+lean_object *lean_io_bind(lean_object *action, lean_object *cont, lean_object *rw);
+LEAN_RUNTIME_NORETURN void lean_io_exit(lean_object *code, lean_object *_rw);
+lean_object *lean_io_getenv(lean_object *name, lean_object *_rw);
+lean_object *lean_get_stdout(void);
+lean_object *lean_mk_io_user_error(lean_object *s);
+lean_object *lean_mk_io_error_already_exists(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_already_exists_file(lean_object *fname, uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_other_error(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_resource_busy(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_resource_vanished(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_unsupported_operation(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_hardware_fault(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_unsatisfied_constraints(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_illegal_operation(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_protocol_error(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_time_expired(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_interrupted(lean_object *fname, uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_no_file_or_directory(lean_object *fname, uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_invalid_argument(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_invalid_argument_file(lean_object *fname, uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_permission_denied(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_permission_denied_file(lean_object *fname, uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_resource_exhausted(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_resource_exhausted_file(lean_object *fname, uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_inappropriate_type(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_inappropriate_type_file(lean_object *fname, uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_no_such_thing(uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_no_such_thing_file(lean_object *fname, uint32_t errno, lean_object *details);
+lean_object *lean_mk_io_error_eof(lean_object *_unit);
+lean_object *lean_io_error_to_string(lean_object *err);
+lean_object *lean_decode_io_error(int32_t errno, lean_object *fname);
+lean_object *lean_get_stdin(lean_object *_unit);
+lean_object *lean_get_stderr(lean_object *_unit);
+lean_object *lean_get_set_stdin(lean_object *h, lean_object *_unit);
+lean_object *lean_get_set_stdout(lean_object *h, lean_object *_unit);
+lean_object *lean_get_set_stderr(lean_object *h, lean_object *_unit);
+lean_object *lean_io_mono_ms_now(lean_object *_unit);
+lean_object *lean_io_mono_nanos_now(lean_object *_unit);
+lean_object *lean_io_get_random_bytes(lean_object *n, lean_object *_unit);
+lean_object *lean_io_timeit(lean_object *msg, lean_object *thunk, lean_object *_unit);
+lean_object *lean_io_allocprof(lean_object *msg, lean_object *thunk, lean_object *_unit);
+lean_object *lean_io_get_num_heartbeats(lean_object *_unit);
+lean_object *lean_io_set_heartbeats(lean_object *n, lean_object *_unit);
+lean_object *lean_io_get_tid(lean_object *_unit);
+lean_object *lean_io_prim_handle_mk(lean_object *fname, uint8_t mode, uint8_t _bin, lean_object *_unit);
+lean_object *lean_io_prim_handle_flush(lean_object *h, lean_object *_unit);
+lean_object *lean_io_prim_handle_read(lean_object *h, lean_object *nbytes, lean_object *_unit);
+lean_object *lean_io_prim_handle_write(lean_object *h, lean_object *buf, lean_object *_unit);
+lean_object *lean_io_prim_handle_get_line(lean_object *h, lean_object *_unit);
+lean_object *lean_io_prim_handle_put_str(lean_object *h, lean_object *s, lean_object *_unit);
+lean_object *lean_io_prim_handle_is_tty(lean_object *_h, lean_object *_unit);
+lean_object *lean_io_prim_handle_rewind(lean_object *_h, lean_object *_unit);
+lean_object *lean_io_prim_handle_truncate(lean_object *_h, lean_object *_unit);
+lean_object *lean_io_prim_handle_lock(lean_object *h, uint8_t exclusive, lean_object *_unit);
+lean_object *lean_io_prim_handle_try_lock(lean_object *h, uint8_t exclusive, lean_object *_unit);
+lean_object *lean_io_prim_handle_unlock(lean_object *h, lean_object *_unit);
+lean_object *lean_io_realpath(lean_object *fname, lean_object *_unit);
+lean_object *lean_io_remove_file(lean_object *fname, lean_object *_unit);
+lean_object *lean_io_remove_dir(lean_object *fname, uint8_t recursive, lean_object *_unit);
+lean_object *lean_io_create_dir(lean_object *fname, lean_object *_unit);
+lean_object *lean_io_rename(lean_object *old, lean_object *new_name, lean_object *_unit);
+lean_object *lean_io_hard_link(lean_object *target, lean_object *link, lean_object *_unit);
+lean_object *lean_io_create_tempfile(lean_object *_unit);
+lean_object *lean_io_create_tempdir(lean_object *_unit);
+lean_object *lean_io_current_dir(lean_object *_unit);
+lean_object *lean_io_app_path(lean_object *_unit);
+lean_object *lean_chmod(lean_object *path, uint32_t _mode, lean_object *_unit);
+uint8_t lean_io_check_canceled_core(void);
+void lean_io_cancel_core(lean_object *t);
+uint8_t lean_io_get_task_state_core(lean_object *t);
+lean_object *lean_io_wait_any_core(lean_object *task_list);
+lean_object *lean_io_as_task(lean_object *action, lean_object *prio, lean_object *_unit);
+lean_object *lean_io_map_task(lean_object *f, lean_object *t, lean_object *prio, uint8_t sync, lean_object *_unit);
+lean_object *lean_io_bind_task(lean_object *t, lean_object *f, lean_object *prio, uint8_t sync, lean_object *_unit);
+lean_object *lean_io_promise_new(lean_object *_unit);
+lean_object *lean_io_promise_resolve(lean_object *val, lean_object *promise, lean_object *_unit);
+lean_object *lean_io_promise_result_opt(lean_object *promise);
+lean_object *lean_option_get_or_block(lean_object *o);
+lean_object *lean_io_process_spawn(lean_object *args, lean_object *_unit);
+lean_object *lean_io_process_child_wait(lean_object *_cfg, lean_object *child, lean_object *_unit);
+lean_object *lean_io_process_child_try_wait(lean_object *_cfg, lean_object *child, lean_object *_unit);
+lean_object *lean_io_process_child_kill(lean_object *_cfg, lean_object *child, lean_object *_unit);
+lean_object *lean_io_process_child_take_stdin(lean_object *_cfg, lean_object *child, lean_object *_unit);
+uint32_t lean_io_process_child_pid(lean_object *_cfg, lean_object *child);
+lean_object *lean_io_process_get_current_dir(lean_object *_unit);
+lean_object *lean_io_process_set_current_dir(lean_object *dir, lean_object *_unit);
+lean_object *lean_io_process_get_pid(lean_object *_unit);
+lean_object *lean_io_read_dir(lean_object *path, lean_object *_unit);
+lean_object *lean_io_metadata(lean_object *path, lean_object *_unit);
+lean_object *lean_io_symlink_metadata(lean_object *path, lean_object *_unit);
+
+/* sharecommon */
+uint8_t lean_sharecommon_eq(lean_object *a, lean_object *b);
+uint64_t lean_sharecommon_hash(lean_object *a);
+lean_object *lean_state_sharecommon(lean_object *tc, lean_object *s, lean_object *a);
+lean_object *lean_sharecommon_quick(lean_object *a);
+
+/* misc */
+uint8_t lean_name_eq(lean_object *n1, lean_object *n2);
+lean_object *lean_sorry(uint8_t _synthetic);
+lean_object *lean_system_platform_nbits(lean_object *_unit);
+lean_object *lean_dbg_trace(lean_object *s, lean_object *thunk);
+lean_object *lean_dbg_trace_if_shared(lean_object *s, lean_object *a);
+
+/* nat */
+lean_object *lean_unsigned_to_nat(size_t n);
+lean_object *lean_usize_to_nat(size_t n);
+lean_object *lean_uint64_to_nat(uint64_t n);
+lean_object *lean_big_usize_to_nat(size_t n);
+lean_object *lean_big_uint64_to_nat(uint64_t n);
+lean_object *lean_nat_add(lean_object *a, lean_object *b);
+lean_object *lean_nat_sub(lean_object *a, lean_object *b);
+lean_object *lean_nat_mul(lean_object *a, lean_object *b);
+lean_object *lean_nat_div(lean_object *a, lean_object *b);
+lean_object *lean_nat_mod(lean_object *a, lean_object *b);
+bool lean_nat_eq(lean_object *a, lean_object *b);
+uint8_t lean_nat_dec_eq(lean_object *a, lean_object *b);
+uint8_t lean_nat_dec_lt(lean_object *a, lean_object *b);
+uint8_t lean_nat_dec_le(lean_object *a, lean_object *b);
+lean_object *lean_nat_shiftr(lean_object *a, lean_object *b);
+lean_object *lean_nat_pow(lean_object *a, lean_object *b);
+lean_object *lean_nat_pred(lean_object *n);
+lean_object *lean_nat_land(lean_object *a, lean_object *b);
+lean_object *lean_nat_lor(lean_object *a, lean_object *b);
+lean_object *lean_nat_lxor(lean_object *a, lean_object *b);
+lean_object *lean_nat_shiftl(lean_object *a, lean_object *b);
+lean_object *lean_nat_log2(lean_object *a);
+
+/* object */
+void lean_inc_heartbeat(void);
+uint64_t lean_get_num_heartbeats(void);
+void lean_set_heartbeats(uint64_t n);
+size_t lean_get_allocated_bytes(void);
+lean_object *lean_alloc_object(size_t size);
+void lean_free_object(lean_object *o, size_t size);
+
+/* panic */
+lean_object *lean_panic_fn(lean_object *msg, lean_object *_rw);
+LEAN_RUNTIME_NORETURN void lean_internal_panic(char const *msg);
+LEAN_RUNTIME_NORETURN void lean_internal_panic_unreachable(void);
+LEAN_RUNTIME_NORETURN void lean_internal_panic_out_of_memory(void);
+
+/* platform */
+lean_object *lean_version_get_major(lean_object *_unit);
+lean_object *lean_version_get_minor(lean_object *_unit);
+lean_object *lean_version_get_patch(lean_object *_unit);
+uint8_t lean_version_get_is_release(lean_object *_unit);
+lean_object *lean_version_get_special_desc(lean_object *_unit);
+lean_object *lean_system_platform_target(lean_object *_unit);
+uint8_t lean_internal_is_stage0(lean_object *_unit);
+uint8_t lean_internal_has_llvm_backend(lean_object *_unit);
+lean_object *lean_get_max_ctor_fields(lean_object *_unit);
+lean_object *lean_get_max_ctor_scalars_size(lean_object *_unit);
+lean_object *lean_get_usize_size(lean_object *_unit);
+lean_object *lean_get_max_ctor_tag(lean_object *_unit);
+uint8_t lean_strict_or(uint8_t a, uint8_t b);
+uint8_t lean_strict_and(uint8_t a, uint8_t b);
+size_t lean_ptr_addr(lean_object *a);
+lean_object *lean_void_mk(lean_object *a);
+size_t lean_hashmap_mk_idx(lean_object *sz, uint64_t hash);
+size_t lean_hashset_mk_idx(lean_object *sz, uint64_t hash);
+lean_object *lean_initialize_runtime_module(void);
+void lean_io_mark_end_initialization(void);
+void lean_set_panic_messages(uint8_t _val);
+void lean_io_result_show_error(lean_object *r);
+lean_object *lean_io_initializing(lean_object *_unit);
+lean_object *lean_dbg_sleep(uint32_t ms, lean_object *thunk);
+lean_object *lean_is_exclusive_obj(lean_object *a);
+uint64_t lean_name_hash(lean_object *n);
+uint64_t lean_expr_data(lean_object *expr);
+lean_object *lean_nat_gcd(lean_object *a, lean_object *b);
+lean_object *lean_nat_div_exact(lean_object *a, lean_object *b);
+lean_object *lean_get_githash(lean_object *_unit);
+lean_object *lean_manual_get_root(lean_object *_unit);
+lean_object *lean_runtime_mark_multi_threaded(lean_object *a, lean_object *_unit);
+lean_object *lean_runtime_mark_persistent(lean_object *a, lean_object *_unit);
+lean_object *lean_runtime_forget(lean_object *a, lean_object *_unit);
+
+/* rc */
+void lean_inc(lean_object *o);
+void lean_dec(lean_object *o);
+void lean_inc_n(lean_object *o, uint32_t n);
+void lean_inc_ref(lean_object *o);
+void lean_inc_ref_n(lean_object *o, uint32_t n);
+void lean_dec_ref(lean_object *o);
+void lean_free_object_full(lean_object *o);
+void lean_free_object_only(lean_object *o);
+bool lean_is_exclusive(lean_object *o);
+bool lean_is_shared(lean_object *o);
+bool lean_is_mt(lean_object *o);
+void lean_mark_mt(lean_object *o);
+void lean_mark_persistent(lean_object *o);
+
+/* sarray */
+size_t lean_sarray_object_size(size_t capacity, uint8_t elem_size);
+uint8_t *lean_sarray_data(lean_object *a);
+lean_object *lean_alloc_sarray(uint32_t elem_size, size_t size, size_t capacity);
+lean_object *lean_mk_empty_byte_array(lean_object *cap);
+lean_object *lean_byte_array_size(lean_object *a);
+lean_object *lean_byte_array_push(lean_object *a, uint8_t b);
+uint8_t lean_byte_array_fget(lean_object *a, lean_object *i);
+lean_object *lean_byte_array_mk(lean_object *list);
+lean_object *lean_byte_array_data(lean_object *a);
+size_t lean_sarray_size(lean_object *a);
+uint8_t lean_sarray_get_byte(lean_object *a, size_t i);
+uint8_t lean_byte_array_get(lean_object *a, lean_object *i);
+lean_object *lean_byte_array_set(lean_object *a, lean_object *i, uint8_t v);
+lean_object *lean_byte_array_fset(lean_object *a, lean_object *i, uint8_t v);
+uint8_t lean_byte_array_uget(lean_object *a, size_t i);
+lean_object *lean_byte_array_uset(lean_object *a, size_t i, uint8_t v);
+uint8_t lean_string_validate_utf8(lean_object *a);
+lean_object *lean_copy_byte_array(lean_object *a);
+
+/* sint */
+uint8_t lean_int8_neg(uint8_t a);
+uint8_t lean_int8_add(uint8_t a, uint8_t b);
+uint8_t lean_int8_sub(uint8_t a, uint8_t b);
+uint8_t lean_int8_mul(uint8_t a, uint8_t b);
+uint8_t lean_int8_div(uint8_t a, uint8_t b);
+uint8_t lean_int8_mod(uint8_t a, uint8_t b);
+uint8_t lean_int8_land(uint8_t a, uint8_t b);
+uint8_t lean_int8_lor(uint8_t a, uint8_t b);
+uint8_t lean_int8_xor(uint8_t a, uint8_t b);
+uint8_t lean_int8_shift_right(uint8_t a, uint8_t b);
+uint8_t lean_int8_shift_left(uint8_t a, uint8_t b);
+uint8_t lean_int8_complement(uint8_t a);
+uint8_t lean_int8_abs(uint8_t a);
+uint8_t lean_int8_dec_eq(uint8_t a, uint8_t b);
+uint8_t lean_int8_dec_lt(uint8_t a, uint8_t b);
+uint8_t lean_int8_dec_le(uint8_t a, uint8_t b);
+uint16_t lean_int8_to_int16(uint8_t a);
+uint32_t lean_int8_to_int32(uint8_t a);
+uint64_t lean_int8_to_int64(uint8_t a);
+size_t lean_int8_to_isize(uint8_t a);
+uint8_t lean_int8_of_int(lean_object *a);
+uint8_t lean_int8_of_nat(lean_object *a);
+lean_object *lean_int8_to_int(uint8_t a);
+uint16_t lean_int16_neg(uint16_t a);
+uint16_t lean_int16_add(uint16_t a, uint16_t b);
+uint16_t lean_int16_sub(uint16_t a, uint16_t b);
+uint16_t lean_int16_mul(uint16_t a, uint16_t b);
+uint16_t lean_int16_div(uint16_t a, uint16_t b);
+uint16_t lean_int16_mod(uint16_t a, uint16_t b);
+uint16_t lean_int16_land(uint16_t a, uint16_t b);
+uint16_t lean_int16_lor(uint16_t a, uint16_t b);
+uint16_t lean_int16_xor(uint16_t a, uint16_t b);
+uint16_t lean_int16_shift_right(uint16_t a, uint16_t b);
+uint16_t lean_int16_shift_left(uint16_t a, uint16_t b);
+uint16_t lean_int16_complement(uint16_t a);
+uint16_t lean_int16_abs(uint16_t a);
+uint8_t lean_int16_dec_eq(uint16_t a, uint16_t b);
+uint8_t lean_int16_dec_lt(uint16_t a, uint16_t b);
+uint8_t lean_int16_dec_le(uint16_t a, uint16_t b);
+uint8_t lean_int16_to_int8(uint16_t a);
+uint32_t lean_int16_to_int32(uint16_t a);
+uint64_t lean_int16_to_int64(uint16_t a);
+size_t lean_int16_to_isize(uint16_t a);
+uint16_t lean_int16_of_int(lean_object *a);
+uint16_t lean_int16_of_nat(lean_object *a);
+lean_object *lean_int16_to_int(uint16_t a);
+uint32_t lean_int32_neg(uint32_t a);
+uint32_t lean_int32_add(uint32_t a, uint32_t b);
+uint32_t lean_int32_sub(uint32_t a, uint32_t b);
+uint32_t lean_int32_mul(uint32_t a, uint32_t b);
+uint32_t lean_int32_div(uint32_t a, uint32_t b);
+uint32_t lean_int32_mod(uint32_t a, uint32_t b);
+uint32_t lean_int32_land(uint32_t a, uint32_t b);
+uint32_t lean_int32_lor(uint32_t a, uint32_t b);
+uint32_t lean_int32_xor(uint32_t a, uint32_t b);
+uint32_t lean_int32_shift_right(uint32_t a, uint32_t b);
+uint32_t lean_int32_shift_left(uint32_t a, uint32_t b);
+uint32_t lean_int32_complement(uint32_t a);
+uint32_t lean_int32_abs(uint32_t a);
+uint8_t lean_int32_dec_eq(uint32_t a, uint32_t b);
+uint8_t lean_int32_dec_lt(uint32_t a, uint32_t b);
+uint8_t lean_int32_dec_le(uint32_t a, uint32_t b);
+uint8_t lean_int32_to_int8(uint32_t a);
+uint16_t lean_int32_to_int16(uint32_t a);
+uint64_t lean_int32_to_int64(uint32_t a);
+size_t lean_int32_to_isize(uint32_t a);
+uint32_t lean_int32_of_int(lean_object *a);
+uint32_t lean_int32_of_nat(lean_object *a);
+lean_object *lean_int32_to_int(uint32_t a);
+uint64_t lean_int64_neg(uint64_t a);
+uint64_t lean_int64_add(uint64_t a, uint64_t b);
+uint64_t lean_int64_sub(uint64_t a, uint64_t b);
+uint64_t lean_int64_mul(uint64_t a, uint64_t b);
+uint64_t lean_int64_div(uint64_t a, uint64_t b);
+uint64_t lean_int64_mod(uint64_t a, uint64_t b);
+uint64_t lean_int64_land(uint64_t a, uint64_t b);
+uint64_t lean_int64_lor(uint64_t a, uint64_t b);
+uint64_t lean_int64_xor(uint64_t a, uint64_t b);
+uint64_t lean_int64_shift_right(uint64_t a, uint64_t b);
+uint64_t lean_int64_shift_left(uint64_t a, uint64_t b);
+uint64_t lean_int64_complement(uint64_t a);
+uint64_t lean_int64_abs(uint64_t a);
+uint8_t lean_int64_dec_eq(uint64_t a, uint64_t b);
+uint8_t lean_int64_dec_lt(uint64_t a, uint64_t b);
+uint8_t lean_int64_dec_le(uint64_t a, uint64_t b);
+uint8_t lean_int64_to_int8(uint64_t a);
+uint16_t lean_int64_to_int16(uint64_t a);
+uint32_t lean_int64_to_int32(uint64_t a);
+size_t lean_int64_to_isize(uint64_t a);
+uint64_t lean_int64_of_int(lean_object *a);
+uint64_t lean_int64_of_nat(lean_object *a);
+size_t lean_isize_neg(size_t a);
+size_t lean_isize_add(size_t a, size_t b);
+size_t lean_isize_sub(size_t a, size_t b);
+size_t lean_isize_mul(size_t a, size_t b);
+size_t lean_isize_div(size_t a, size_t b);
+size_t lean_isize_mod(size_t a, size_t b);
+size_t lean_isize_land(size_t a, size_t b);
+size_t lean_isize_lor(size_t a, size_t b);
+size_t lean_isize_xor(size_t a, size_t b);
+size_t lean_isize_shift_right(size_t a, size_t b);
+size_t lean_isize_shift_left(size_t a, size_t b);
+size_t lean_isize_complement(size_t a);
+size_t lean_isize_abs(size_t a);
+uint8_t lean_isize_dec_eq(size_t a, size_t b);
+uint8_t lean_isize_dec_lt(size_t a, size_t b);
+uint8_t lean_isize_dec_le(size_t a, size_t b);
+uint8_t lean_isize_to_int8(size_t a);
+uint16_t lean_isize_to_int16(size_t a);
+uint32_t lean_isize_to_int32(size_t a);
+uint64_t lean_isize_to_int64(size_t a);
+size_t lean_isize_of_int(lean_object *a);
+size_t lean_isize_of_nat(lean_object *a);
+lean_object *lean_isize_to_int(size_t a);
+uint8_t lean_bool_to_int8(uint8_t a);
+uint16_t lean_bool_to_int16(uint8_t a);
+uint32_t lean_bool_to_int32(uint8_t a);
+uint64_t lean_bool_to_int64(uint8_t a);
+size_t lean_bool_to_isize(uint8_t a);
+uint8_t lean_bool_to_uint8(uint8_t a);
+uint16_t lean_bool_to_uint16(uint8_t a);
+uint32_t lean_bool_to_uint32(uint8_t a);
+uint64_t lean_bool_to_uint64(uint8_t a);
+size_t lean_bool_to_usize(uint8_t a);
+
+/* stref */
+lean_object *lean_st_mk_ref(lean_object *a);
+lean_object *lean_st_ref_get(lean_object *ref_obj);
+lean_object *lean_st_ref_set(lean_object *ref_obj, lean_object *new_val);
+lean_object *lean_st_ref_reset(lean_object *ref_obj);
+lean_object *lean_st_ref_swap(lean_object *ref_obj, lean_object *new_val);
+lean_object *lean_st_ref_take(lean_object *ref_obj);
+lean_object *lean_st_ref_ptr_eq(lean_object *a, lean_object *b);
+
+/* string */
+lean_object *lean_mk_string_unchecked(char const *s, size_t byte_len, size_t utf8_len);
+size_t lean_string_byte_len(lean_object *s);
+size_t lean_string_utf8_len(lean_object *s);
+char const *lean_string_cstr(lean_object *s);
+lean_object *lean_string_append(lean_object *s1, lean_object *s2);
+bool lean_string_eq(lean_object *s1, lean_object *s2);
+uint8_t lean_string_dec_eq(lean_object *s1, lean_object *s2);
+lean_object *lean_nat_to_string(lean_object *n);
+lean_object *lean_string_utf8_byte_size(lean_object *s);
+lean_object *lean_string_to_utf8(lean_object *s);
+lean_object *lean_string_from_utf8_unchecked(lean_object *a);
+lean_object *lean_string_push(lean_object *s, uint32_t c);
+uint32_t lean_string_utf8_get(lean_object *s, lean_object *i);
+lean_object *lean_string_utf8_next(lean_object *s, lean_object *i);
+lean_object *lean_string_utf8_prev(lean_object *s, lean_object *i);
+uint8_t lean_string_utf8_at_end(lean_object *s, lean_object *i);
+lean_object *lean_string_utf8_extract(lean_object *s, lean_object *b, lean_object *e);
+lean_object *lean_string_utf8_set(lean_object *s, lean_object *i, uint32_t c);
+lean_object *lean_string_length(lean_object *s);
+lean_object *lean_string_mk(lean_object *cs);
+lean_object *lean_string_data(lean_object *s);
+uint64_t lean_string_hash(lean_object *s);
+bool lean_string_lt(lean_object *s1, lean_object *s2);
+uint8_t lean_string_dec_lt(lean_object *s1, lean_object *s2);
+uint32_t lean_string_utf8_get_fast(lean_object *s, lean_object *i);
+uint32_t lean_string_utf8_get_fast_cold(lean_object *s, lean_object *i);
+lean_object *lean_string_utf8_next_fast(lean_object *s, lean_object *i);
+lean_object *lean_string_utf8_next_fast_cold(lean_object *s, lean_object *i);
+uint8_t lean_string_get_byte_fast(lean_object *s, lean_object *i);
+lean_object *lean_string_utf8_get_opt(lean_object *s, lean_object *i);
+uint32_t lean_string_utf8_get_bang(lean_object *s, lean_object *i);
+uint8_t lean_string_is_valid_pos(lean_object *s, lean_object *i);
+lean_object *lean_string_of_usize(size_t c);
+uint8_t lean_string_memcmp(lean_object *s1, lean_object *s2, lean_object *lstart, lean_object *rstart, lean_object *len);
+uint64_t lean_slice_hash(lean_object *s);
+uint8_t lean_slice_dec_lt(lean_object *s1, lean_object *s2);
+
+/* task */
+void lean_init_task_manager(void);
+void lean_init_task_manager_using(uint32_t num_workers);
+void lean_finalize_task_manager(void);
+lean_object *lean_task_pure(lean_object *a);
+lean_object *lean_task_spawn_core(lean_object *c, uint32_t prio);
+lean_object *lean_task_spawn(lean_object *c, lean_object *prio);
+lean_object *lean_task_map_core(lean_object *f, lean_object *t, uint32_t prio, bool _sync);
+lean_object *lean_task_map(lean_object *f, lean_object *t, lean_object *prio, uint8_t sync);
+lean_object *lean_task_bind_core(lean_object *x, lean_object *f, uint32_t prio, bool _sync);
+lean_object *lean_task_bind(lean_object *x, lean_object *f, lean_object *prio, uint8_t sync);
+lean_object *lean_promise_task_new(void);
+bool lean_task_resolve(lean_object *t, lean_object *v);
+lean_object *lean_task_get(lean_object *t);
+lean_object *lean_task_get_own(lean_object *t);
+bool lean_task_is_finished(lean_object *t);
+void lean_task_cancel(lean_object *t);
+bool lean_task_check_canceled(void);
+uint8_t lean_task_get_state(lean_object *t);
+
+/* thunk */
+lean_object *lean_mk_thunk(lean_object *c);
+lean_object *lean_thunk_pure(lean_object *v);
+lean_object *lean_thunk_get(lean_object *t);
+lean_object *lean_thunk_get_core(lean_object *t);
+lean_object *lean_thunk_get_own(lean_object *t);
+
+/* uint */
+uint8_t lean_uint8_of_nat(lean_object *a);
+uint8_t lean_uint8_of_nat_mk(lean_object *a);
+lean_object *lean_uint8_to_nat(uint8_t a);
+uint8_t lean_uint8_dec_eq(uint8_t a, uint8_t b);
+uint8_t lean_uint8_dec_lt(uint8_t a, uint8_t b);
+uint8_t lean_uint8_dec_le(uint8_t a, uint8_t b);
+uint8_t lean_uint8_add(uint8_t a, uint8_t b);
+uint8_t lean_uint8_sub(uint8_t a, uint8_t b);
+uint8_t lean_uint8_mul(uint8_t a, uint8_t b);
+uint8_t lean_uint8_div(uint8_t a, uint8_t b);
+uint8_t lean_uint8_mod(uint8_t a, uint8_t b);
+uint8_t lean_uint8_land(uint8_t a, uint8_t b);
+uint8_t lean_uint8_lor(uint8_t a, uint8_t b);
+uint8_t lean_uint8_lxor(uint8_t a, uint8_t b);
+uint8_t lean_uint8_shift_left(uint8_t a, uint8_t b);
+uint8_t lean_uint8_shift_right(uint8_t a, uint8_t b);
+uint8_t lean_uint8_complement(uint8_t a);
+uint32_t lean_uint8_to_uint32(uint8_t a);
+uint16_t lean_uint8_to_uint16(uint8_t a);
+uint64_t lean_uint8_to_uint64(uint8_t a);
+uint16_t lean_uint16_of_nat(lean_object *a);
+uint16_t lean_uint16_of_nat_mk(lean_object *a);
+lean_object *lean_uint16_to_nat(uint16_t a);
+uint8_t lean_uint16_dec_eq(uint16_t a, uint16_t b);
+uint8_t lean_uint16_dec_lt(uint16_t a, uint16_t b);
+uint8_t lean_uint16_dec_le(uint16_t a, uint16_t b);
+uint16_t lean_uint16_add(uint16_t a, uint16_t b);
+uint16_t lean_uint16_sub(uint16_t a, uint16_t b);
+uint16_t lean_uint16_mul(uint16_t a, uint16_t b);
+uint16_t lean_uint16_div(uint16_t a, uint16_t b);
+uint16_t lean_uint16_mod(uint16_t a, uint16_t b);
+uint16_t lean_uint16_land(uint16_t a, uint16_t b);
+uint16_t lean_uint16_lor(uint16_t a, uint16_t b);
+uint16_t lean_uint16_lxor(uint16_t a, uint16_t b);
+uint16_t lean_uint16_shift_left(uint16_t a, uint16_t b);
+uint16_t lean_uint16_shift_right(uint16_t a, uint16_t b);
+uint16_t lean_uint16_complement(uint16_t a);
+uint8_t lean_uint16_to_uint8(uint16_t a);
+uint32_t lean_uint16_to_uint32(uint16_t a);
+uint64_t lean_uint16_to_uint64(uint16_t a);
+uint32_t lean_uint32_of_nat(lean_object *a);
+uint32_t lean_uint32_of_nat_mk(lean_object *a);
+lean_object *lean_uint32_to_nat(uint32_t a);
+uint8_t lean_uint32_dec_le(uint32_t a, uint32_t b);
+uint32_t lean_uint32_add(uint32_t a, uint32_t b);
+uint32_t lean_uint32_sub(uint32_t a, uint32_t b);
+uint32_t lean_uint32_mul(uint32_t a, uint32_t b);
+uint32_t lean_uint32_div(uint32_t a, uint32_t b);
+uint32_t lean_uint32_mod(uint32_t a, uint32_t b);
+uint32_t lean_uint32_land(uint32_t a, uint32_t b);
+uint32_t lean_uint32_lor(uint32_t a, uint32_t b);
+uint32_t lean_uint32_lxor(uint32_t a, uint32_t b);
+uint32_t lean_uint32_shift_left(uint32_t a, uint32_t b);
+uint32_t lean_uint32_shift_right(uint32_t a, uint32_t b);
+uint32_t lean_uint32_complement(uint32_t a);
+uint8_t lean_uint32_to_uint8(uint32_t a);
+uint16_t lean_uint32_to_uint16(uint32_t a);
+uint64_t lean_uint32_to_uint64(uint32_t a);
+uint64_t lean_uint64_of_nat(lean_object *a);
+uint64_t lean_uint64_of_nat_mk(lean_object *a);
+uint8_t lean_uint64_dec_eq(uint64_t a, uint64_t b);
+uint8_t lean_uint64_dec_lt(uint64_t a, uint64_t b);
+uint8_t lean_uint64_dec_le(uint64_t a, uint64_t b);
+uint64_t lean_uint64_add(uint64_t a, uint64_t b);
+uint64_t lean_uint64_sub(uint64_t a, uint64_t b);
+uint64_t lean_uint64_mul(uint64_t a, uint64_t b);
+uint64_t lean_uint64_div(uint64_t a, uint64_t b);
+uint64_t lean_uint64_mod(uint64_t a, uint64_t b);
+uint64_t lean_uint64_land(uint64_t a, uint64_t b);
+uint64_t lean_uint64_lor(uint64_t a, uint64_t b);
+uint64_t lean_uint64_lxor(uint64_t a, uint64_t b);
+uint64_t lean_uint64_shift_left(uint64_t a, uint64_t b);
+uint64_t lean_uint64_shift_right(uint64_t a, uint64_t b);
+uint64_t lean_uint64_complement(uint64_t a);
+uint8_t lean_uint64_to_uint8(uint64_t a);
+uint16_t lean_uint64_to_uint16(uint64_t a);
+uint32_t lean_uint64_to_uint32(uint64_t a);
+uint64_t lean_uint64_mix_hash(uint64_t a, uint64_t b);
+size_t lean_usize_of_nat(lean_object *a);
+size_t lean_usize_of_nat_mk(lean_object *a);
+uint8_t lean_usize_dec_eq(size_t a, size_t b);
+uint8_t lean_usize_dec_lt(size_t a, size_t b);
+uint8_t lean_usize_dec_le(size_t a, size_t b);
+size_t lean_usize_add(size_t a, size_t b);
+size_t lean_usize_sub(size_t a, size_t b);
+size_t lean_usize_mul(size_t a, size_t b);
+size_t lean_usize_div(size_t a, size_t b);
+size_t lean_usize_mod(size_t a, size_t b);
+size_t lean_usize_land(size_t a, size_t b);
+size_t lean_usize_lor(size_t a, size_t b);
+size_t lean_usize_lxor(size_t a, size_t b);
+size_t lean_usize_shift_left(size_t a, size_t b);
+size_t lean_usize_shift_right(size_t a, size_t b);
+size_t lean_usize_complement(size_t a);
+size_t lean_usize_mix_hash(size_t a, size_t b);
+uint8_t lean_uint8_neg(uint8_t a);
+uint16_t lean_uint16_neg(uint16_t a);
+uint32_t lean_uint32_neg(uint32_t a);
+uint64_t lean_uint64_neg(uint64_t a);
+size_t lean_usize_neg(size_t a);
+uint8_t lean_uint8_log2(uint8_t a);
+uint16_t lean_uint16_log2(uint16_t a);
+uint32_t lean_uint32_log2(uint32_t a);
+uint64_t lean_uint64_log2(uint64_t a);
+size_t lean_usize_log2(size_t a);
+size_t lean_uint8_to_usize(uint8_t a);
+size_t lean_uint16_to_usize(uint16_t a);
+size_t lean_uint32_to_usize(uint32_t a);
+size_t lean_uint64_to_usize(uint64_t a);
+uint8_t lean_usize_to_uint8(size_t a);
+uint16_t lean_usize_to_uint16(size_t a);
+uint32_t lean_usize_to_uint32(size_t a);
+uint64_t lean_usize_to_uint64(size_t a);
+uint8_t lean_uint8_xor(uint8_t a, uint8_t b);
+uint16_t lean_uint16_xor(uint16_t a, uint16_t b);
+uint32_t lean_uint32_xor(uint32_t a, uint32_t b);
+uint64_t lean_uint64_xor(uint64_t a, uint64_t b);
+size_t lean_usize_xor(size_t a, size_t b);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LEAN_RUNTIME_H */
@@ -0,0 +1,206 @@
+//! Allocation profiling for `IO.allocprof`
+//!
+//! While a profile is active, `lean_alloc_object` records every new object
+//! and `lean_free_object` classifies it by its tag when it dies. Objects still
+//! alive when a report is taken are classified in place. Allocations from all
+//! threads are counted, so tasks spawned inside the profiled action show up.
+
+use crate::object::*;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Object kinds reported by the allocation profiler.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AllocKind {
+    Ctor,
+    Closure,
+    Array,
+    String,
+    Bignum,
+    Thunk,
+    Other,
+}
+
+impl AllocKind {
+    pub const ALL: [AllocKind; 7] = [
+        AllocKind::Ctor,
+        AllocKind::Closure,
+        AllocKind::Array,
+        AllocKind::String,
+        AllocKind::Bignum,
+        AllocKind::Thunk,
+        AllocKind::Other,
+    ];
+
+    pub fn of_tag(tag: u8) -> AllocKind {
+        match tag {
+            0..=LEAN_MAX_CTOR_TAG => AllocKind::Ctor,
+            LEAN_CLOSURE_TAG => AllocKind::Closure,
+            LEAN_ARRAY_TAG | LEAN_SCALAR_ARRAY_TAG => AllocKind::Array,
+            LEAN_STRING_TAG => AllocKind::String,
+            LEAN_MPZ_TAG | LEAN_BIGINT_TAG => AllocKind::Bignum,
+            LEAN_THUNK_TAG => AllocKind::Thunk,
+            _ => AllocKind::Other,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            AllocKind::Ctor => "ctor",
+            AllocKind::Closure => "closure",
+            AllocKind::Array => "array",
+            AllocKind::String => "string",
+            AllocKind::Bignum => "bignum",
+            AllocKind::Thunk => "thunk",
+            AllocKind::Other => "other",
+        }
+    }
+}
+
+/// Number and total size of allocated objects, per kind.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct AllocStats {
+    pub num: [u64; 7],
+    pub bytes: [u64; 7],
+}
+
+impl AllocStats {
+    fn record(&mut self, kind: AllocKind, size: usize) {
+        self.num[kind as usize] += 1;
+        self.bytes[kind as usize] += size as u64;
+    }
+
+    /// Statistics for one kind: (objects, bytes).
+    pub fn get(&self, kind: AllocKind) -> (u64, u64) {
+        (self.num[kind as usize], self.bytes[kind as usize])
+    }
+
+    /// Totals over all kinds: (objects, bytes).
+    pub fn total(&self) -> (u64, u64) {
+        (self.num.iter().sum(), self.bytes.iter().sum())
+    }
+
+    fn sub(&self, other: &AllocStats) -> AllocStats {
+        let mut r = *self;
+        for i in 0..r.num.len() {
+            r.num[i] = r.num[i].saturating_sub(other.num[i]);
+            r.bytes[i] = r.bytes[i].saturating_sub(other.bytes[i]);
+        }
+        r
+    }
+}
+
+struct Profile {
+    /// Nesting depth of active `lean_alloc_prof_begin` calls.
+    depth: usize,
+    /// Objects allocated while profiling that are still alive, with sizes.
+    live: HashMap<usize, usize>,
+    /// Objects allocated while profiling that have since been freed.
+    freed: AllocStats,
+}
+
+impl Profile {
+    /// Everything allocated since profiling started.
+    unsafe fn snapshot(&self) -> AllocStats {
+        let mut stats = self.freed;
+        for (&ptr, &size) in &self.live {
+            stats.record(AllocKind::of_tag((*(ptr as *mut LeanObject)).tag), size);
+        }
+        stats
+    }
+}
+
+static ACTIVE: AtomicBool = AtomicBool::new(false);
+static PROFILE: Mutex<Option<Profile>> = Mutex::new(None);
+/// Objects allocated while any profile was active, never reset.
+static NUM_ALLOCATED: AtomicU64 = AtomicU64::new(0);
+
+fn profile() -> std::sync::MutexGuard<'static, Option<Profile>> {
+    PROFILE.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Hook called by `lean_alloc_object`.
+#[inline(always)]
+pub(crate) fn on_alloc(o: *mut LeanObject, size: usize) {
+    if ACTIVE.load(Ordering::Relaxed) {
+        NUM_ALLOCATED.fetch_add(1, Ordering::Relaxed);
+        if let Some(p) = profile().as_mut() {
+            p.live.insert(o as usize, size);
+        }
+    }
+}
+
+/// Running count of objects allocated while profiling. Cheaper than a
+/// snapshot, for attributing allocations to code by taking differences.
+pub fn lean_alloc_prof_count() -> u64 {
+    NUM_ALLOCATED.load(Ordering::Relaxed)
+}
+
+/// Hook called by `lean_free_object`, before the object is released.
+#[inline(always)]
+pub(crate) unsafe fn on_free(o: *mut LeanObject) {
+    if ACTIVE.load(Ordering::Relaxed) {
+        if let Some(p) = profile().as_mut() {
+            if let Some(size) = p.live.remove(&(o as usize)) {
+                p.freed.record(AllocKind::of_tag((*o).tag), size);
+            }
+        }
+    }
+}
+
+/// Start (or nest) an allocation profile. Returns a token for
+/// `lean_alloc_prof_end`.
+pub unsafe fn lean_alloc_prof_begin() -> AllocStats {
+    let mut guard = profile();
+    let p = guard.get_or_insert_with(|| Profile {
+        depth: 0,
+        live: HashMap::new(),
+        freed: AllocStats::default(),
+    });
+    p.depth += 1;
+    ACTIVE.store(true, Ordering::Relaxed);
+    p.snapshot()
+}
+
+/// Finish the profile started by the matching `lean_alloc_prof_begin` and
+/// return what was allocated in between.
+pub unsafe fn lean_alloc_prof_end(start: AllocStats) -> AllocStats {
+    let mut guard = profile();
+    let Some(p) = guard.as_mut() else {
+        return AllocStats::default();
+    };
+    let stats = p.snapshot().sub(&start);
+    p.depth -= 1;
+    if p.depth == 0 {
+        ACTIVE.store(false, Ordering::Relaxed);
+        *guard = None;
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_by_kind() {
+        unsafe {
+            let start = lean_alloc_prof_begin();
+            let c = crate::lean_alloc_ctor(0, 1, 0);
+            crate::lean_ctor_set(c, 0, crate::lean_mk_string("hello"));
+            let inner = lean_alloc_prof_begin();
+            crate::lean_dec(crate::lean_mk_empty_array_with_capacity(crate::lean_box(4)));
+            let inner = lean_alloc_prof_end(inner);
+            crate::lean_dec(c);
+            let stats = lean_alloc_prof_end(start);
+
+            // Other threads may allocate concurrently, so only check lower bounds.
+            assert!(inner.get(AllocKind::Array).0 >= 1);
+            assert!(stats.get(AllocKind::Ctor).0 >= 1);
+            assert!(stats.get(AllocKind::String).0 >= 1);
+            assert!(stats.get(AllocKind::Array).0 >= 1);
+            assert!(stats.get(AllocKind::Ctor).1 >= lean_ctor_object_size(1, 0) as u64);
+        }
+    }
+}
@@ -0,0 +1,1050 @@
+//! C ABI exports
+//!
+//! With the `c-api` feature, the runtime API is exported with C linkage under
+//! the `lean.h` names, so the static library can be linked with C code:
+//! output of the Lean compiler, or hand-written FFI shims. Every function
+//! whose signature is C-compatible is listed in [`exports!`] below, along
+//! with small adapters for the ones that take Rust types (`lean_mk_string`
+//! takes a C string here).
+//!
+//! `include/lean_runtime.h` declares all exports. It is generated from the
+//! same list by `cargo run -p lean-runtime --features c-api --example
+//! gen_header`; a test checks that it is up to date. The header declares
+//! functions that `lean.h` defines inline, so use it instead of `lean.h`.
+//!
+//! Panics in the runtime abort the process when called from C. Closure code
+//! pointers passed to `lean_alloc_closure` are C functions called as in
+//! `lean.h`: with up to 16 `lean_object *` arguments directly, and above that
+//! with a single `lean_object **` array of all arguments.
+
+use crate::external::{LeanExternalClass, LeanExternalFinalize, LeanExternalForeach};
+use crate::object::LeanObject;
+use crate::{
+    allocprof, array, bignat, closure, ctor, debug, external, float, floatarray, int, io, misc,
+    nat, object, panic, platform, r#box, rc, sarray, sharecommon, sint, stref, string, task, thunk,
+    uint,
+};
+use std::ffi::{c_char, c_void, CStr};
+
+type Obj = *mut LeanObject;
+
+macro_rules! exports {
+    ($(fn $name:ident($($arg:ident: $ty:ty),*) $(-> $ret:ty)? => $target:path;)*) => {
+        $(
+            #[no_mangle]
+            #[allow(clippy::too_many_arguments)]
+            pub unsafe extern "C" fn $name($($arg: $ty),*) $(-> $ret)? {
+                $target($($arg),*)
+            }
+        )*
+
+        /// (name, parameters, return type, target) of every export, as Rust
+        /// source text.
+        const EXPORTS: &[(&str, &[(&str, &str)], &str, &str)] = &[$((
+            stringify!($name),
+            &[$((stringify!($arg), stringify!($ty))),*],
+            stringify!($($ret)?),
+            stringify!($target),
+        )),*];
+    };
+}
+
+unsafe fn mk_string(s: *const c_char) -> Obj {
+    string::lean_mk_string(&CStr::from_ptr(s).to_string_lossy())
+}
+
+unsafe fn mk_string_from_bytes(s: *const u8, sz: usize) -> Obj {
+    string::lean_mk_string(&String::from_utf8_lossy(std::slice::from_raw_parts(s, sz)))
+}
+
+unsafe fn cstr_to_nat(s: *const c_char) -> Obj {
+    string::lean_cstr_to_nat(&CStr::from_ptr(s).to_string_lossy())
+}
+
+/// Classes live as long as the program, like in `lean.h`.
+fn register_external_class(
+    finalize: Option<LeanExternalFinalize>,
+    foreach: Option<LeanExternalForeach>,
+) -> *mut LeanExternalClass {
+    Box::into_raw(Box::new(LeanExternalClass { finalize, foreach }))
+}
+
+/// `lean.h` addresses scalar fields from the start of the object fields, so
+/// C offsets include the `sizeof(void *) * num_objs` bytes of pointers before
+/// the scalar area, and `usize` field indices the `num_objs` pointer slots.
+/// The Rust accessors count from the start of the scalar area.
+macro_rules! scalar_field_adapters {
+    ($($get:ident, $set:ident: $ty:ty => $rget:path, $rset:path, $unit:expr;)*) => {
+        $(
+            unsafe fn $get(o: Obj, offset: u32) -> $ty {
+                $rget(o, scalar_offset(o, offset, $unit))
+            }
+
+            unsafe fn $set(o: Obj, offset: u32, v: $ty) {
+                $rset(o, scalar_offset(o, offset, $unit), v)
+            }
+        )*
+    };
+}
+
+/// Offset past the pointer fields of `o`, each `unit` offsets wide.
+unsafe fn scalar_offset(o: Obj, offset: u32, unit: u32) -> u32 {
+    let pointers = (*o).num_objs() * unit;
+    debug_assert!(
+        offset >= pointers,
+        "scalar field offset inside the object fields"
+    );
+    offset - pointers
+}
+
+const PTR_SIZE: u32 = std::mem::size_of::<Obj>() as u32;
+
+scalar_field_adapters! {
+    ctor_get_uint8, ctor_set_uint8: u8 => ctor::lean_ctor_get_uint8, ctor::lean_ctor_set_uint8, PTR_SIZE;
+    ctor_get_uint16, ctor_set_uint16: u16 => ctor::lean_ctor_get_uint16, ctor::lean_ctor_set_uint16, PTR_SIZE;
+    ctor_get_uint32, ctor_set_uint32: u32 => ctor::lean_ctor_get_uint32, ctor::lean_ctor_set_uint32, PTR_SIZE;
+    ctor_get_uint64, ctor_set_uint64: u64 => ctor::lean_ctor_get_uint64, ctor::lean_ctor_set_uint64, PTR_SIZE;
+    ctor_get_usize, ctor_set_usize: usize => ctor::lean_ctor_get_usize, ctor::lean_ctor_set_usize, 1;
+    ctor_get_float, ctor_set_float: f64 => float::lean_ctor_get_float, float::lean_ctor_set_float, PTR_SIZE;
+    ctor_get_float32, ctor_set_float32: f32 => float::lean_ctor_get_float32, float::lean_ctor_set_float32, PTR_SIZE;
+}
+
+exports! {
+    // Adapters
+    fn lean_mk_string(s: *const c_char) -> Obj => mk_string;
+    fn lean_mk_string_from_bytes(s: *const u8, sz: usize) -> Obj => mk_string_from_bytes;
+    fn lean_cstr_to_nat(s: *const c_char) -> Obj => cstr_to_nat;
+    fn lean_register_external_class(
+        finalize: Option<LeanExternalFinalize>,
+        foreach: Option<LeanExternalForeach>
+    ) -> *mut LeanExternalClass => register_external_class;
+    fn lean_ctor_get_uint8(o: Obj, offset: u32) -> u8 => ctor_get_uint8;
+    fn lean_ctor_set_uint8(o: Obj, offset: u32, v: u8) => ctor_set_uint8;
+    fn lean_ctor_get_uint16(o: Obj, offset: u32) -> u16 => ctor_get_uint16;
+    fn lean_ctor_set_uint16(o: Obj, offset: u32, v: u16) => ctor_set_uint16;
+    fn lean_ctor_get_uint32(o: Obj, offset: u32) -> u32 => ctor_get_uint32;
+    fn lean_ctor_set_uint32(o: Obj, offset: u32, v: u32) => ctor_set_uint32;
+    fn lean_ctor_get_uint64(o: Obj, offset: u32) -> u64 => ctor_get_uint64;
+    fn lean_ctor_set_uint64(o: Obj, offset: u32, v: u64) => ctor_set_uint64;
+    fn lean_ctor_get_usize(o: Obj, i: u32) -> usize => ctor_get_usize;
+    fn lean_ctor_set_usize(o: Obj, i: u32, v: usize) => ctor_set_usize;
+    fn lean_ctor_get_float(o: Obj, offset: u32) -> f64 => ctor_get_float;
+    fn lean_ctor_set_float(o: Obj, offset: u32, v: f64) => ctor_set_float;
+    fn lean_ctor_get_float32(o: Obj, offset: u32) -> f32 => ctor_get_float32;
+    fn lean_ctor_set_float32(o: Obj, offset: u32, v: f32) => ctor_set_float32;
+    // allocprof
+    fn lean_alloc_prof_count() -> u64 => allocprof::lean_alloc_prof_count;
+    // array
+    fn lean_alloc_array(size: usize, capacity: usize) -> Obj => array::lean_alloc_array;
+    fn lean_mk_empty_array() -> Obj => array::lean_mk_empty_array;
+    fn lean_mk_empty_array_with_capacity(cap: Obj) -> Obj => array::lean_mk_empty_array_with_capacity;
+    fn lean_array_size(a: Obj) -> usize => array::lean_array_size;
+    fn lean_array_get_size(a: Obj) -> Obj => array::lean_array_get_size;
+    fn lean_array_data(a: Obj) -> *mut Obj => array::lean_array_data;
+    fn lean_array_get_core(a: Obj, i: usize) -> Obj => array::lean_array_get_core;
+    fn lean_array_get_borrowed(def: Obj, a: Obj, i: Obj) -> Obj => array::lean_array_get_borrowed;
+    fn lean_array_get(def: Obj, a: Obj, i: Obj) -> Obj => array::lean_array_get;
+    fn lean_array_push(a: Obj, v: Obj) -> Obj => array::lean_array_push;
+    fn lean_array_fget(a: Obj, i: Obj) -> Obj => array::lean_array_fget;
+    fn lean_array_fget_borrowed(a: Obj, i: Obj) -> Obj => array::lean_array_fget_borrowed;
+    fn lean_array_fset(a: Obj, i: Obj, v: Obj) -> Obj => array::lean_array_fset;
+    fn lean_array_mk(list: Obj) -> Obj => array::lean_array_mk;
+    fn lean_array_to_list(a: Obj) -> Obj => array::lean_array_to_list;
+    fn lean_array_set(a: Obj, i: Obj, v: Obj) -> Obj => array::lean_array_set;
+    fn lean_array_uget(a: Obj, i: usize) -> Obj => array::lean_array_uget;
+    fn lean_array_uset(a: Obj, i: usize, v: Obj) -> Obj => array::lean_array_uset;
+    fn lean_array_pop(a: Obj) -> Obj => array::lean_array_pop;
+    fn lean_array_fswap(a: Obj, i: Obj, j: Obj) -> Obj => array::lean_array_fswap;
+    fn lean_array_swap(a: Obj, i: Obj, j: Obj) -> Obj => array::lean_array_swap;
+    fn lean_mk_array(n: Obj, v: Obj) -> Obj => array::lean_mk_array;
+    fn lean_copy_array(a: Obj) -> Obj => array::lean_copy_array;
+    // bignat
+    fn lean_bignat_drop(o: Obj) => bignat::lean_bignat_drop;
+    fn lean_free_bignat(o: Obj) => bignat::lean_free_bignat;
+    // box
+    fn lean_box(n: usize) -> Obj => r#box::lean_box;
+    fn lean_unbox(o: Obj) -> usize => r#box::lean_unbox;
+    fn lean_is_scalar(o: Obj) -> bool => r#box::lean_is_scalar;
+    fn lean_box_uint32(n: u32) -> Obj => r#box::lean_box_uint32;
+    fn lean_unbox_uint32(o: Obj) -> u32 => r#box::lean_unbox_uint32;
+    fn lean_box_uint64(n: u64) -> Obj => r#box::lean_box_uint64;
+    fn lean_unbox_uint64(o: Obj) -> u64 => r#box::lean_unbox_uint64;
+    fn lean_box_usize(n: usize) -> Obj => r#box::lean_box_usize;
+    fn lean_unbox_usize(o: Obj) -> usize => r#box::lean_unbox_usize;
+    fn lean_uint32_dec_eq(a: u32, b: u32) -> u8 => r#box::lean_uint32_dec_eq;
+    fn lean_uint32_dec_lt(a: u32, b: u32) -> u8 => r#box::lean_uint32_dec_lt;
+    // closure
+    fn lean_alloc_closure(fun: *const (), arity: u32, num_fixed: u32) -> Obj => closure::lean_alloc_closure;
+    fn lean_closure_get(o: Obj, i: u32) -> Obj => closure::lean_closure_get;
+    fn lean_closure_set(o: Obj, i: u32, v: Obj) => closure::lean_closure_set;
+    fn lean_apply_1(f: Obj, a1: Obj) -> Obj => closure::lean_apply_1;
+    fn lean_apply_2(f: Obj, a1: Obj, a2: Obj) -> Obj => closure::lean_apply_2;
+    fn lean_apply_3(f: Obj, a1: Obj, a2: Obj, a3: Obj) -> Obj => closure::lean_apply_3;
+    fn lean_apply_4(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj) -> Obj => closure::lean_apply_4;
+    fn lean_apply_5(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj) -> Obj => closure::lean_apply_5;
+    fn lean_apply_6(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj) -> Obj => closure::lean_apply_6;
+    fn lean_apply_7(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj) -> Obj => closure::lean_apply_7;
+    fn lean_apply_8(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj) -> Obj => closure::lean_apply_8;
+    fn lean_apply_9(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj, a9: Obj) -> Obj => closure::lean_apply_9;
+    fn lean_apply_10(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj, a9: Obj, a10: Obj) -> Obj => closure::lean_apply_10;
+    fn lean_apply_11(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj, a9: Obj, a10: Obj, a11: Obj) -> Obj => closure::lean_apply_11;
+    fn lean_apply_12(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj, a9: Obj, a10: Obj, a11: Obj, a12: Obj) -> Obj => closure::lean_apply_12;
+    fn lean_apply_13(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj, a9: Obj, a10: Obj, a11: Obj, a12: Obj, a13: Obj) -> Obj => closure::lean_apply_13;
+    fn lean_apply_14(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj, a9: Obj, a10: Obj, a11: Obj, a12: Obj, a13: Obj, a14: Obj) -> Obj => closure::lean_apply_14;
+    fn lean_apply_15(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj, a9: Obj, a10: Obj, a11: Obj, a12: Obj, a13: Obj, a14: Obj, a15: Obj) -> Obj => closure::lean_apply_15;
+    fn lean_apply_16(f: Obj, a1: Obj, a2: Obj, a3: Obj, a4: Obj, a5: Obj, a6: Obj, a7: Obj, a8: Obj, a9: Obj, a10: Obj, a11: Obj, a12: Obj, a13: Obj, a14: Obj, a15: Obj, a16: Obj) -> Obj => closure::lean_apply_16;
+    fn lean_apply_n(f: Obj, n: u32, args: *const Obj) -> Obj => closure::lean_apply_n;
+    fn lean_apply_m(f: Obj, n: u32, args: *const Obj) -> Obj => closure::lean_apply_m;
+    // ctor
+    fn lean_alloc_ctor(tag: u32, num_objs: u32, scalar_sz: u32) -> Obj => ctor::lean_alloc_ctor;
+    fn lean_ctor_get(o: Obj, i: u32) -> Obj => ctor::lean_ctor_get;
+    fn lean_ctor_set(o: Obj, i: u32, v: Obj) => ctor::lean_ctor_set;
+    fn lean_ctor_release(o: Obj, i: u32) => ctor::lean_ctor_release;
+    fn lean_ctor_set_tag(o: Obj, tag: u8) => ctor::lean_ctor_set_tag;
+    fn lean_obj_tag(o: Obj) -> u8 => ctor::lean_obj_tag;
+    // debug
+    fn lean_debug_poison(o: Obj) => debug::lean_debug_poison;
+    fn lean_debug_is_persistent(o: Obj) -> bool => debug::lean_debug_is_persistent;
+    // external
+    fn lean_alloc_external(cls: *mut LeanExternalClass, data: *mut c_void) -> Obj => external::lean_alloc_external;
+    fn lean_get_external_class(o: Obj) -> *mut LeanExternalClass => external::lean_get_external_class;
+    fn lean_get_external_data(o: Obj) -> *mut c_void => external::lean_get_external_data;
+    fn lean_set_external_data(o: Obj, data: *mut c_void) -> Obj => external::lean_set_external_data;
+    // float
+    fn lean_box_float(v: f64) -> Obj => float::lean_box_float;
+    fn lean_unbox_float(o: Obj) -> f64 => float::lean_unbox_float;
+    fn lean_float_add(a: f64, b: f64) -> f64 => float::lean_float_add;
+    fn lean_float_sub(a: f64, b: f64) -> f64 => float::lean_float_sub;
+    fn lean_float_mul(a: f64, b: f64) -> f64 => float::lean_float_mul;
+    fn lean_float_div(a: f64, b: f64) -> f64 => float::lean_float_div;
+    fn lean_float_negate(a: f64) -> f64 => float::lean_float_negate;
+    fn lean_float_beq(a: f64, b: f64) -> u8 => float::lean_float_beq;
+    fn lean_float_decLe(a: f64, b: f64) -> u8 => float::lean_float_decLe;
+    fn lean_float_decLt(a: f64, b: f64) -> u8 => float::lean_float_decLt;
+    fn lean_float_isnan(a: f64) -> u8 => float::lean_float_isnan;
+    fn lean_float_isfinite(a: f64) -> u8 => float::lean_float_isfinite;
+    fn lean_float_isinf(a: f64) -> u8 => float::lean_float_isinf;
+    fn lean_float_of_bits(u: u64) -> f64 => float::lean_float_of_bits;
+    fn lean_float_to_bits(d: f64) -> u64 => float::lean_float_to_bits;
+    fn lean_float_to_string(a: f64) -> Obj => float::lean_float_to_string;
+    fn lean_float_frexp(a: f64) -> Obj => float::lean_float_frexp;
+    fn lean_float_scaleb(a: f64, b: Obj) -> f64 => float::lean_float_scaleb;
+    fn lean_float_to_uint8(a: f64) -> u8 => float::lean_float_to_uint8;
+    fn lean_float_to_uint16(a: f64) -> u16 => float::lean_float_to_uint16;
+    fn lean_float_to_uint32(a: f64) -> u32 => float::lean_float_to_uint32;
+    fn lean_float_to_uint64(a: f64) -> u64 => float::lean_float_to_uint64;
+    fn lean_float_to_usize(a: f64) -> usize => float::lean_float_to_usize;
+    fn lean_float_to_int8(a: f64) -> u8 => float::lean_float_to_int8;
+    fn lean_float_to_int16(a: f64) -> u16 => float::lean_float_to_int16;
+    fn lean_float_to_int32(a: f64) -> u32 => float::lean_float_to_int32;
+    fn lean_float_to_int64(a: f64) -> u64 => float::lean_float_to_int64;
+    fn lean_float_to_isize(a: f64) -> usize => float::lean_float_to_isize;
+    fn lean_uint8_to_float(a: u8) -> f64 => float::lean_uint8_to_float;
+    fn lean_uint16_to_float(a: u16) -> f64 => float::lean_uint16_to_float;
+    fn lean_uint32_to_float(a: u32) -> f64 => float::lean_uint32_to_float;
+    fn lean_uint64_to_float(a: u64) -> f64 => float::lean_uint64_to_float;
+    fn lean_usize_to_float(a: usize) -> f64 => float::lean_usize_to_float;
+    fn lean_int8_to_float(a: u8) -> f64 => float::lean_int8_to_float;
+    fn lean_int16_to_float(a: u16) -> f64 => float::lean_int16_to_float;
+    fn lean_int32_to_float(a: u32) -> f64 => float::lean_int32_to_float;
+    fn lean_int64_to_float(a: u64) -> f64 => float::lean_int64_to_float;
+    fn lean_isize_to_float(a: usize) -> f64 => float::lean_isize_to_float;
+    fn lean_box_float32(v: f32) -> Obj => float::lean_box_float32;
+    fn lean_unbox_float32(o: Obj) -> f32 => float::lean_unbox_float32;
+    fn lean_float32_add(a: f32, b: f32) -> f32 => float::lean_float32_add;
+    fn lean_float32_sub(a: f32, b: f32) -> f32 => float::lean_float32_sub;
+    fn lean_float32_mul(a: f32, b: f32) -> f32 => float::lean_float32_mul;
+    fn lean_float32_div(a: f32, b: f32) -> f32 => float::lean_float32_div;
+    fn lean_float32_negate(a: f32) -> f32 => float::lean_float32_negate;
+    fn lean_float32_beq(a: f32, b: f32) -> u8 => float::lean_float32_beq;
+    fn lean_float32_decLe(a: f32, b: f32) -> u8 => float::lean_float32_decLe;
+    fn lean_float32_decLt(a: f32, b: f32) -> u8 => float::lean_float32_decLt;
+    fn lean_float32_isnan(a: f32) -> u8 => float::lean_float32_isnan;
+    fn lean_float32_isfinite(a: f32) -> u8 => float::lean_float32_isfinite;
+    fn lean_float32_isinf(a: f32) -> u8 => float::lean_float32_isinf;
+    fn lean_float32_of_bits(u: u32) -> f32 => float::lean_float32_of_bits;
+    fn lean_float32_to_bits(d: f32) -> u32 => float::lean_float32_to_bits;
+    fn lean_float32_to_string(a: f32) -> Obj => float::lean_float32_to_string;
+    fn lean_float32_frexp(a: f32) -> Obj => float::lean_float32_frexp;
+    fn lean_float32_scaleb(a: f32, b: Obj) -> f32 => float::lean_float32_scaleb;
+    fn lean_float32_to_uint8(a: f32) -> u8 => float::lean_float32_to_uint8;
+    fn lean_float32_to_uint16(a: f32) -> u16 => float::lean_float32_to_uint16;
+    fn lean_float32_to_uint32(a: f32) -> u32 => float::lean_float32_to_uint32;
+    fn lean_float32_to_uint64(a: f32) -> u64 => float::lean_float32_to_uint64;
+    fn lean_float32_to_usize(a: f32) -> usize => float::lean_float32_to_usize;
+    fn lean_float32_to_int8(a: f32) -> u8 => float::lean_float32_to_int8;
+    fn lean_float32_to_int16(a: f32) -> u16 => float::lean_float32_to_int16;
+    fn lean_float32_to_int32(a: f32) -> u32 => float::lean_float32_to_int32;
+    fn lean_float32_to_int64(a: f32) -> u64 => float::lean_float32_to_int64;
+    fn lean_float32_to_isize(a: f32) -> usize => float::lean_float32_to_isize;
+    fn lean_uint8_to_float32(a: u8) -> f32 => float::lean_uint8_to_float32;
+    fn lean_uint16_to_float32(a: u16) -> f32 => float::lean_uint16_to_float32;
+    fn lean_uint32_to_float32(a: u32) -> f32 => float::lean_uint32_to_float32;
+    fn lean_uint64_to_float32(a: u64) -> f32 => float::lean_uint64_to_float32;
+    fn lean_usize_to_float32(a: usize) -> f32 => float::lean_usize_to_float32;
+    fn lean_int8_to_float32(a: u8) -> f32 => float::lean_int8_to_float32;
+    fn lean_int16_to_float32(a: u16) -> f32 => float::lean_int16_to_float32;
+    fn lean_int32_to_float32(a: u32) -> f32 => float::lean_int32_to_float32;
+    fn lean_int64_to_float32(a: u64) -> f32 => float::lean_int64_to_float32;
+    fn lean_isize_to_float32(a: usize) -> f32 => float::lean_isize_to_float32;
+    fn lean_float_to_float32(a: f64) -> f32 => float::lean_float_to_float32;
+    fn lean_float32_to_float(a: f32) -> f64 => float::lean_float32_to_float;
+    // floatarray
+    fn lean_mk_empty_float_array(cap: Obj) -> Obj => floatarray::lean_mk_empty_float_array;
+    fn lean_float_array_size(a: Obj) -> Obj => floatarray::lean_float_array_size;
+    fn lean_float_array_push(a: Obj, v: f64) -> Obj => floatarray::lean_float_array_push;
+    fn lean_float_array_uget(a: Obj, i: usize) -> f64 => floatarray::lean_float_array_uget;
+    fn lean_float_array_fget(a: Obj, i: Obj) -> f64 => floatarray::lean_float_array_fget;
+    fn lean_float_array_get(a: Obj, dflt: f64, i: Obj) -> f64 => floatarray::lean_float_array_get;
+    fn lean_float_array_uset(a: Obj, i: usize, v: f64) -> Obj => floatarray::lean_float_array_uset;
+    fn lean_float_array_fset(a: Obj, i: Obj, v: f64) -> Obj => floatarray::lean_float_array_fset;
+    fn lean_float_array_set(a: Obj, i: Obj, v: f64) -> Obj => floatarray::lean_float_array_set;
+    fn lean_float_array_mk(list: Obj) -> Obj => floatarray::lean_float_array_mk;
+    fn lean_float_array_data(a: Obj) -> Obj => floatarray::lean_float_array_data;
+    fn lean_copy_float_array(a: Obj) -> Obj => floatarray::lean_copy_float_array;
+    // int
+    fn lean_bigint_drop(o: Obj) => int::lean_bigint_drop;
+    fn lean_free_bigint(o: Obj) => int::lean_free_bigint;
+    fn lean_scalar_to_int64(a: Obj) -> i64 => int::lean_scalar_to_int64;
+    fn lean_int64_to_int(n: i64) -> Obj => int::lean_int64_to_int;
+    fn lean_int_to_int(n: i32) -> Obj => int::lean_int_to_int;
+    fn lean_nat_to_int(a: Obj) -> Obj => int::lean_nat_to_int;
+    fn lean_int_neg_succ_of_nat(a: Obj) -> Obj => int::lean_int_neg_succ_of_nat;
+    fn lean_nat_succ(a: Obj) -> Obj => int::lean_nat_succ;
+    fn lean_int_neg(a: Obj) -> Obj => int::lean_int_neg;
+    fn lean_int_add(a1: Obj, a2: Obj) -> Obj => int::lean_int_add;
+    fn lean_int_sub(a1: Obj, a2: Obj) -> Obj => int::lean_int_sub;
+    fn lean_int_mul(a1: Obj, a2: Obj) -> Obj => int::lean_int_mul;
+    fn lean_int_div(a1: Obj, a2: Obj) -> Obj => int::lean_int_div;
+    fn lean_int_div_exact(a1: Obj, a2: Obj) -> Obj => int::lean_int_div_exact;
+    fn lean_int_mod(a1: Obj, a2: Obj) -> Obj => int::lean_int_mod;
+    fn lean_int_ediv(a1: Obj, a2: Obj) -> Obj => int::lean_int_ediv;
+    fn lean_int_emod(a1: Obj, a2: Obj) -> Obj => int::lean_int_emod;
+    fn lean_int_eq(a1: Obj, a2: Obj) -> bool => int::lean_int_eq;
+    fn lean_int_ne(a1: Obj, a2: Obj) -> bool => int::lean_int_ne;
+    fn lean_int_le(a1: Obj, a2: Obj) -> bool => int::lean_int_le;
+    fn lean_int_lt(a1: Obj, a2: Obj) -> bool => int::lean_int_lt;
+    fn lean_int_dec_eq(a1: Obj, a2: Obj) -> u8 => int::lean_int_dec_eq;
+    fn lean_int_dec_le(a1: Obj, a2: Obj) -> u8 => int::lean_int_dec_le;
+    fn lean_int_dec_lt(a1: Obj, a2: Obj) -> u8 => int::lean_int_dec_lt;
+    fn lean_int_dec_nonneg(a: Obj) -> u8 => int::lean_int_dec_nonneg;
+    fn lean_int_to_nat(a: Obj) -> Obj => int::lean_int_to_nat;
+    fn lean_nat_abs(i: Obj) -> Obj => int::lean_nat_abs;
+    // io
+    fn lean_io_mk_world() -> Obj => io::lean_io_mk_world;
+    fn lean_io_result_mk_ok(value: Obj) -> Obj => io::lean_io_result_mk_ok;
+    fn lean_io_result_mk_error(error: Obj) -> Obj => io::lean_io_result_mk_error;
+    fn lean_io_result_is_ok(r: Obj) -> bool => io::lean_io_result_is_ok;
+    fn lean_io_result_get_value(r: Obj) -> Obj => io::lean_io_result_get_value;
+    fn lean_io_result_get_error(r: Obj) -> Obj => io::lean_io_result_get_error;
+    fn lean_io_prim_println(s: Obj, _rw: Obj) -> Obj => io::lean_io_prim_println;
+    fn lean_io_prim_print(s: Obj, _rw: Obj) -> Obj => io::lean_io_prim_print;
+    fn lean_io_prim_eprintln(s: Obj, _rw: Obj) -> Obj => io::lean_io_prim_eprintln;
+    fn lean_io_pure(value: Obj, _rw: Obj) -> Obj => io::lean_io_pure;
+    fn lean_io_bind(action: Obj, cont: Obj, rw: Obj) -> Obj => io::lean_io_bind;
+    fn lean_io_exit(code: Obj, _rw: Obj) -> ! => io::lean_io_exit;
+    fn lean_io_getenv(name: Obj, _rw: Obj) -> Obj => io::lean_io_getenv;
+    fn lean_get_stdout() -> Obj => io::lean_get_stdout;
+    fn lean_mk_io_user_error(s: Obj) -> Obj => io::lean_mk_io_user_error;
+    fn lean_mk_io_error_already_exists(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_already_exists;
+    fn lean_mk_io_error_already_exists_file(fname: Obj, errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_already_exists_file;
+    fn lean_mk_io_error_other_error(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_other_error;
+    fn lean_mk_io_error_resource_busy(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_resource_busy;
+    fn lean_mk_io_error_resource_vanished(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_resource_vanished;
+    fn lean_mk_io_error_unsupported_operation(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_unsupported_operation;
+    fn lean_mk_io_error_hardware_fault(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_hardware_fault;
+    fn lean_mk_io_error_unsatisfied_constraints(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_unsatisfied_constraints;
+    fn lean_mk_io_error_illegal_operation(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_illegal_operation;
+    fn lean_mk_io_error_protocol_error(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_protocol_error;
+    fn lean_mk_io_error_time_expired(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_time_expired;
+    fn lean_mk_io_error_interrupted(fname: Obj, errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_interrupted;
+    fn lean_mk_io_error_no_file_or_directory(fname: Obj, errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_no_file_or_directory;
+    fn lean_mk_io_error_invalid_argument(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_invalid_argument;
+    fn lean_mk_io_error_invalid_argument_file(fname: Obj, errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_invalid_argument_file;
+    fn lean_mk_io_error_permission_denied(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_permission_denied;
+    fn lean_mk_io_error_permission_denied_file(fname: Obj, errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_permission_denied_file;
+    fn lean_mk_io_error_resource_exhausted(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_resource_exhausted;
+    fn lean_mk_io_error_resource_exhausted_file(fname: Obj, errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_resource_exhausted_file;
+    fn lean_mk_io_error_inappropriate_type(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_inappropriate_type;
+    fn lean_mk_io_error_inappropriate_type_file(fname: Obj, errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_inappropriate_type_file;
+    fn lean_mk_io_error_no_such_thing(errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_no_such_thing;
+    fn lean_mk_io_error_no_such_thing_file(fname: Obj, errno: u32, details: Obj) -> Obj => io::lean_mk_io_error_no_such_thing_file;
+    fn lean_mk_io_error_eof(_unit: Obj) -> Obj => io::lean_mk_io_error_eof;
+    fn lean_io_error_to_string(err: Obj) -> Obj => io::lean_io_error_to_string;
+    fn lean_decode_io_error(errno: i32, fname: Obj) -> Obj => io::lean_decode_io_error;
+    fn lean_get_stdin(_unit: Obj) -> Obj => io::lean_get_stdin;
+    fn lean_get_stderr(_unit: Obj) -> Obj => io::lean_get_stderr;
+    fn lean_get_set_stdin(h: Obj, _unit: Obj) -> Obj => io::lean_get_set_stdin;
+    fn lean_get_set_stdout(h: Obj, _unit: Obj) -> Obj => io::lean_get_set_stdout;
+    fn lean_get_set_stderr(h: Obj, _unit: Obj) -> Obj => io::lean_get_set_stderr;
+    fn lean_io_mono_ms_now(_unit: Obj) -> Obj => io::lean_io_mono_ms_now;
+    fn lean_io_mono_nanos_now(_unit: Obj) -> Obj => io::lean_io_mono_nanos_now;
+    fn lean_io_get_random_bytes(n: Obj, _unit: Obj) -> Obj => io::lean_io_get_random_bytes;
+    fn lean_io_timeit(msg: Obj, thunk: Obj, _unit: Obj) -> Obj => io::lean_io_timeit;
+    fn lean_io_allocprof(msg: Obj, thunk: Obj, _unit: Obj) -> Obj => io::lean_io_allocprof;
+    fn lean_io_get_num_heartbeats(_unit: Obj) -> Obj => io::lean_io_get_num_heartbeats;
+    fn lean_io_set_heartbeats(n: Obj, _unit: Obj) -> Obj => io::lean_io_set_heartbeats;
+    fn lean_io_get_tid(_unit: Obj) -> Obj => io::lean_io_get_tid;
+    fn lean_io_prim_handle_mk(fname: Obj, mode: u8, _bin: u8, _unit: Obj) -> Obj => io::lean_io_prim_handle_mk;
+    fn lean_io_prim_handle_flush(h: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_flush;
+    fn lean_io_prim_handle_read(h: Obj, nbytes: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_read;
+    fn lean_io_prim_handle_write(h: Obj, buf: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_write;
+    fn lean_io_prim_handle_get_line(h: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_get_line;
+    fn lean_io_prim_handle_put_str(h: Obj, s: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_put_str;
+    fn lean_io_prim_handle_is_tty(_h: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_is_tty;
+    fn lean_io_prim_handle_rewind(_h: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_rewind;
+    fn lean_io_prim_handle_truncate(_h: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_truncate;
+    fn lean_io_prim_handle_lock(h: Obj, exclusive: u8, _unit: Obj) -> Obj => io::lean_io_prim_handle_lock;
+    fn lean_io_prim_handle_try_lock(h: Obj, exclusive: u8, _unit: Obj) -> Obj => io::lean_io_prim_handle_try_lock;
+    fn lean_io_prim_handle_unlock(h: Obj, _unit: Obj) -> Obj => io::lean_io_prim_handle_unlock;
+    fn lean_io_realpath(fname: Obj, _unit: Obj) -> Obj => io::lean_io_realpath;
+    fn lean_io_remove_file(fname: Obj, _unit: Obj) -> Obj => io::lean_io_remove_file;
+    fn lean_io_remove_dir(fname: Obj, recursive: u8, _unit: Obj) -> Obj => io::lean_io_remove_dir;
+    fn lean_io_create_dir(fname: Obj, _unit: Obj) -> Obj => io::lean_io_create_dir;
+    fn lean_io_rename(old: Obj, new_name: Obj, _unit: Obj) -> Obj => io::lean_io_rename;
+    fn lean_io_hard_link(target: Obj, link: Obj, _unit: Obj) -> Obj => io::lean_io_hard_link;
+    fn lean_io_create_tempfile(_unit: Obj) -> Obj => io::lean_io_create_tempfile;
+    fn lean_io_create_tempdir(_unit: Obj) -> Obj => io::lean_io_create_tempdir;
+    fn lean_io_current_dir(_unit: Obj) -> Obj => io::lean_io_current_dir;
+    fn lean_io_app_path(_unit: Obj) -> Obj => io::lean_io_app_path;
+    fn lean_chmod(path: Obj, _mode: u32, _unit: Obj) -> Obj => io::lean_chmod;
+    fn lean_io_check_canceled_core() -> u8 => io::lean_io_check_canceled_core;
+    fn lean_io_cancel_core(t: Obj) => io::lean_io_cancel_core;
+    fn lean_io_get_task_state_core(t: Obj) -> u8 => io::lean_io_get_task_state_core;
+    fn lean_io_wait_any_core(task_list: Obj) -> Obj => io::lean_io_wait_any_core;
+    fn lean_io_as_task(action: Obj, prio: Obj, _unit: Obj) -> Obj => io::lean_io_as_task;
+    fn lean_io_map_task(f: Obj, t: Obj, prio: Obj, sync: u8, _unit: Obj) -> Obj => io::lean_io_map_task;
+    fn lean_io_bind_task(t: Obj, f: Obj, prio: Obj, sync: u8, _unit: Obj) -> Obj => io::lean_io_bind_task;
+    fn lean_io_promise_new(_unit: Obj) -> Obj => io::lean_io_promise_new;
+    fn lean_io_promise_resolve(val: Obj, promise: Obj, _unit: Obj) -> Obj => io::lean_io_promise_resolve;
+    fn lean_io_promise_result_opt(promise: Obj) -> Obj => io::lean_io_promise_result_opt;
+    fn lean_option_get_or_block(o: Obj) -> Obj => io::lean_option_get_or_block;
+    fn lean_io_process_spawn(args: Obj, _unit: Obj) -> Obj => io::lean_io_process_spawn;
+    fn lean_io_process_child_wait(_cfg: Obj, child: Obj, _unit: Obj) -> Obj => io::lean_io_process_child_wait;
+    fn lean_io_process_child_try_wait(_cfg: Obj, child: Obj, _unit: Obj) -> Obj => io::lean_io_process_child_try_wait;
+    fn lean_io_process_child_kill(_cfg: Obj, child: Obj, _unit: Obj) -> Obj => io::lean_io_process_child_kill;
+    fn lean_io_process_child_take_stdin(_cfg: Obj, child: Obj, _unit: Obj) -> Obj => io::lean_io_process_child_take_stdin;
+    fn lean_io_process_child_pid(_cfg: Obj, child: Obj) -> u32 => io::lean_io_process_child_pid;
+    fn lean_io_process_get_current_dir(_unit: Obj) -> Obj => io::lean_io_process_get_current_dir;
+    fn lean_io_process_set_current_dir(dir: Obj, _unit: Obj) -> Obj => io::lean_io_process_set_current_dir;
+    fn lean_io_process_get_pid(_unit: Obj) -> Obj => io::lean_io_process_get_pid;
+    fn lean_io_read_dir(path: Obj, _unit: Obj) -> Obj => io::lean_io_read_dir;
+    fn lean_io_metadata(path: Obj, _unit: Obj) -> Obj => io::lean_io_metadata;
+    fn lean_io_symlink_metadata(path: Obj, _unit: Obj) -> Obj => io::lean_io_symlink_metadata;
+    fn lean_sharecommon_eq(a: Obj, b: Obj) -> u8 => sharecommon::lean_sharecommon_eq;
+    fn lean_sharecommon_hash(a: Obj) -> u64 => sharecommon::lean_sharecommon_hash;
+    fn lean_state_sharecommon(tc: Obj, s: Obj, a: Obj) -> Obj => sharecommon::lean_state_sharecommon;
+    fn lean_sharecommon_quick(a: Obj) -> Obj => sharecommon::lean_sharecommon_quick;
+    // misc
+    fn lean_name_eq(n1: Obj, n2: Obj) -> u8 => misc::lean_name_eq;
+    fn lean_sorry(_synthetic: u8) -> Obj => misc::lean_sorry;
+    fn lean_system_platform_nbits(_unit: Obj) -> Obj => misc::lean_system_platform_nbits;
+    fn lean_dbg_trace(s: Obj, thunk: Obj) -> Obj => misc::lean_dbg_trace;
+    fn lean_dbg_trace_if_shared(s: Obj, a: Obj) -> Obj => misc::lean_dbg_trace_if_shared;
+    // nat
+    fn lean_unsigned_to_nat(n: usize) -> Obj => nat::lean_unsigned_to_nat;
+    fn lean_usize_to_nat(n: usize) -> Obj => nat::lean_usize_to_nat;
+    fn lean_uint64_to_nat(n: u64) -> Obj => nat::lean_uint64_to_nat;
+    fn lean_big_usize_to_nat(n: usize) -> Obj => nat::lean_big_usize_to_nat;
+    fn lean_big_uint64_to_nat(n: u64) -> Obj => nat::lean_big_uint64_to_nat;
+    fn lean_nat_add(a: Obj, b: Obj) -> Obj => nat::lean_nat_add;
+    fn lean_nat_sub(a: Obj, b: Obj) -> Obj => nat::lean_nat_sub;
+    fn lean_nat_mul(a: Obj, b: Obj) -> Obj => nat::lean_nat_mul;
+    fn lean_nat_div(a: Obj, b: Obj) -> Obj => nat::lean_nat_div;
+    fn lean_nat_mod(a: Obj, b: Obj) -> Obj => nat::lean_nat_mod;
+    fn lean_nat_eq(a: Obj, b: Obj) -> bool => nat::lean_nat_eq;
+    fn lean_nat_dec_eq(a: Obj, b: Obj) -> u8 => nat::lean_nat_dec_eq;
+    fn lean_nat_dec_lt(a: Obj, b: Obj) -> u8 => nat::lean_nat_dec_lt;
+    fn lean_nat_dec_le(a: Obj, b: Obj) -> u8 => nat::lean_nat_dec_le;
+    fn lean_nat_shiftr(a: Obj, b: Obj) -> Obj => nat::lean_nat_shiftr;
+    fn lean_nat_pow(a: Obj, b: Obj) -> Obj => nat::lean_nat_pow;
+    fn lean_nat_pred(n: Obj) -> Obj => nat::lean_nat_pred;
+    fn lean_nat_land(a: Obj, b: Obj) -> Obj => nat::lean_nat_land;
+    fn lean_nat_lor(a: Obj, b: Obj) -> Obj => nat::lean_nat_lor;
+    fn lean_nat_lxor(a: Obj, b: Obj) -> Obj => nat::lean_nat_lxor;
+    fn lean_nat_shiftl(a: Obj, b: Obj) -> Obj => nat::lean_nat_shiftl;
+    fn lean_nat_log2(a: Obj) -> Obj => nat::lean_nat_log2;
+    // object
+    fn lean_inc_heartbeat() => object::lean_inc_heartbeat;
+    fn lean_get_num_heartbeats() -> u64 => object::lean_get_num_heartbeats;
+    fn lean_set_heartbeats(n: u64) => object::lean_set_heartbeats;
+    fn lean_get_allocated_bytes() -> usize => object::lean_get_allocated_bytes;
+    fn lean_alloc_object(size: usize) -> Obj => object::lean_alloc_object;
+    fn lean_free_object(o: Obj, size: usize) => object::lean_free_object;
+    // panic
+    fn lean_panic_fn(msg: Obj, _rw: Obj) -> Obj => panic::lean_panic_fn;
+    fn lean_internal_panic(msg: *const i8) -> ! => panic::lean_internal_panic;
+    fn lean_internal_panic_unreachable() -> ! => panic::lean_internal_panic_unreachable;
+    fn lean_internal_panic_out_of_memory() -> ! => panic::lean_internal_panic_out_of_memory;
+    // platform
+    fn lean_version_get_major(_unit: Obj) -> Obj => platform::lean_version_get_major;
+    fn lean_version_get_minor(_unit: Obj) -> Obj => platform::lean_version_get_minor;
+    fn lean_version_get_patch(_unit: Obj) -> Obj => platform::lean_version_get_patch;
+    fn lean_version_get_is_release(_unit: Obj) -> u8 => platform::lean_version_get_is_release;
+    fn lean_version_get_special_desc(_unit: Obj) -> Obj => platform::lean_version_get_special_desc;
+    fn lean_system_platform_target(_unit: Obj) -> Obj => platform::lean_system_platform_target;
+    fn lean_internal_is_stage0(_unit: Obj) -> u8 => platform::lean_internal_is_stage0;
+    fn lean_internal_has_llvm_backend(_unit: Obj) -> u8 => platform::lean_internal_has_llvm_backend;
+    fn lean_get_max_ctor_fields(_unit: Obj) -> Obj => platform::lean_get_max_ctor_fields;
+    fn lean_get_max_ctor_scalars_size(_unit: Obj) -> Obj => platform::lean_get_max_ctor_scalars_size;
+    fn lean_get_usize_size(_unit: Obj) -> Obj => platform::lean_get_usize_size;
+    fn lean_get_max_ctor_tag(_unit: Obj) -> Obj => platform::lean_get_max_ctor_tag;
+    fn lean_strict_or(a: u8, b: u8) -> u8 => platform::lean_strict_or;
+    fn lean_strict_and(a: u8, b: u8) -> u8 => platform::lean_strict_and;
+    fn lean_ptr_addr(a: Obj) -> usize => platform::lean_ptr_addr;
+    fn lean_void_mk(a: Obj) -> Obj => platform::lean_void_mk;
+    fn lean_hashmap_mk_idx(sz: Obj, hash: u64) -> usize => platform::lean_hashmap_mk_idx;
+    fn lean_hashset_mk_idx(sz: Obj, hash: u64) -> usize => platform::lean_hashset_mk_idx;
+    fn lean_initialize_runtime_module() -> Obj => platform::lean_initialize_runtime_module;
+    fn lean_io_mark_end_initialization() => platform::lean_io_mark_end_initialization;
+    fn lean_set_panic_messages(_val: u8) => platform::lean_set_panic_messages;
+    fn lean_io_result_show_error(r: Obj) => platform::lean_io_result_show_error;
+    fn lean_io_initializing(_unit: Obj) -> Obj => platform::lean_io_initializing;
+    fn lean_dbg_sleep(ms: u32, thunk: Obj) -> Obj => platform::lean_dbg_sleep;
+    fn lean_is_exclusive_obj(a: Obj) -> Obj => platform::lean_is_exclusive_obj;
+    fn lean_name_hash(n: Obj) -> u64 => platform::lean_name_hash;
+    fn lean_expr_data(expr: Obj) -> u64 => platform::lean_expr_data;
+    fn lean_nat_gcd(a: Obj, b: Obj) -> Obj => platform::lean_nat_gcd;
+    fn lean_nat_div_exact(a: Obj, b: Obj) -> Obj => platform::lean_nat_div_exact;
+    fn lean_get_githash(_unit: Obj) -> Obj => platform::lean_get_githash;
+    fn lean_manual_get_root(_unit: Obj) -> Obj => platform::lean_manual_get_root;
+    fn lean_runtime_mark_multi_threaded(a: Obj, _unit: Obj) -> Obj => platform::lean_runtime_mark_multi_threaded;
+    fn lean_runtime_mark_persistent(a: Obj, _unit: Obj) -> Obj => platform::lean_runtime_mark_persistent;
+    fn lean_runtime_forget(a: Obj, _unit: Obj) -> Obj => platform::lean_runtime_forget;
+    // rc
+    fn lean_inc(o: Obj) => rc::lean_inc;
+    fn lean_dec(o: Obj) => rc::lean_dec;
+    fn lean_inc_n(o: Obj, n: u32) => rc::lean_inc_n;
+    fn lean_inc_ref(o: Obj) => rc::lean_inc_ref;
+    fn lean_inc_ref_n(o: Obj, n: u32) => rc::lean_inc_ref_n;
+    fn lean_dec_ref(o: Obj) => rc::lean_dec_ref;
+    fn lean_free_object_full(o: Obj) => rc::lean_free_object_full;
+    fn lean_free_object_only(o: Obj) => rc::lean_free_object_only;
+    fn lean_is_exclusive(o: Obj) -> bool => rc::lean_is_exclusive;
+    fn lean_is_shared(o: Obj) -> bool => rc::lean_is_shared;
+    fn lean_is_mt(o: Obj) -> bool => rc::lean_is_mt;
+    fn lean_mark_mt(o: Obj) => rc::lean_mark_mt;
+    fn lean_mark_persistent(o: Obj) => rc::lean_mark_persistent;
+    // sarray
+    fn lean_sarray_object_size(capacity: usize, elem_size: u8) -> usize => sarray::lean_sarray_object_size;
+    fn lean_sarray_data(a: Obj) -> *mut u8 => sarray::lean_sarray_data;
+    fn lean_alloc_sarray(elem_size: u32, size: usize, capacity: usize) -> Obj => sarray::lean_alloc_sarray;
+    fn lean_mk_empty_byte_array(cap: Obj) -> Obj => sarray::lean_mk_empty_byte_array;
+    fn lean_byte_array_size(a: Obj) -> Obj => sarray::lean_byte_array_size;
+    fn lean_byte_array_push(a: Obj, b: u8) -> Obj => sarray::lean_byte_array_push;
+    fn lean_byte_array_fget(a: Obj, i: Obj) -> u8 => sarray::lean_byte_array_fget;
+    fn lean_byte_array_mk(list: Obj) -> Obj => sarray::lean_byte_array_mk;
+    fn lean_byte_array_data(a: Obj) -> Obj => sarray::lean_byte_array_data;
+    fn lean_sarray_size(a: Obj) -> usize => sarray::lean_sarray_size;
+    fn lean_sarray_get_byte(a: Obj, i: usize) -> u8 => sarray::lean_sarray_get_byte;
+    fn lean_byte_array_get(a: Obj, i: Obj) -> u8 => sarray::lean_byte_array_get;
+    fn lean_byte_array_set(a: Obj, i: Obj, v: u8) -> Obj => sarray::lean_byte_array_set;
+    fn lean_byte_array_fset(a: Obj, i: Obj, v: u8) -> Obj => sarray::lean_byte_array_fset;
+    fn lean_byte_array_uget(a: Obj, i: usize) -> u8 => sarray::lean_byte_array_uget;
+    fn lean_byte_array_uset(a: Obj, i: usize, v: u8) -> Obj => sarray::lean_byte_array_uset;
+    fn lean_string_validate_utf8(a: Obj) -> u8 => sarray::lean_string_validate_utf8;
+    fn lean_copy_byte_array(a: Obj) -> Obj => sarray::lean_copy_byte_array;
+    // sint
+    fn lean_int8_neg(a: u8) -> u8 => sint::lean_int8_neg;
+    fn lean_int8_add(a: u8, b: u8) -> u8 => sint::lean_int8_add;
+    fn lean_int8_sub(a: u8, b: u8) -> u8 => sint::lean_int8_sub;
+    fn lean_int8_mul(a: u8, b: u8) -> u8 => sint::lean_int8_mul;
+    fn lean_int8_div(a: u8, b: u8) -> u8 => sint::lean_int8_div;
+    fn lean_int8_mod(a: u8, b: u8) -> u8 => sint::lean_int8_mod;
+    fn lean_int8_land(a: u8, b: u8) -> u8 => sint::lean_int8_land;
+    fn lean_int8_lor(a: u8, b: u8) -> u8 => sint::lean_int8_lor;
+    fn lean_int8_xor(a: u8, b: u8) -> u8 => sint::lean_int8_xor;
+    fn lean_int8_shift_right(a: u8, b: u8) -> u8 => sint::lean_int8_shift_right;
+    fn lean_int8_shift_left(a: u8, b: u8) -> u8 => sint::lean_int8_shift_left;
+    fn lean_int8_complement(a: u8) -> u8 => sint::lean_int8_complement;
+    fn lean_int8_abs(a: u8) -> u8 => sint::lean_int8_abs;
+    fn lean_int8_dec_eq(a: u8, b: u8) -> u8 => sint::lean_int8_dec_eq;
+    fn lean_int8_dec_lt(a: u8, b: u8) -> u8 => sint::lean_int8_dec_lt;
+    fn lean_int8_dec_le(a: u8, b: u8) -> u8 => sint::lean_int8_dec_le;
+    fn lean_int8_to_int16(a: u8) -> u16 => sint::lean_int8_to_int16;
+    fn lean_int8_to_int32(a: u8) -> u32 => sint::lean_int8_to_int32;
+    fn lean_int8_to_int64(a: u8) -> u64 => sint::lean_int8_to_int64;
+    fn lean_int8_to_isize(a: u8) -> usize => sint::lean_int8_to_isize;
+    fn lean_int8_of_int(a: Obj) -> u8 => sint::lean_int8_of_int;
+    fn lean_int8_of_nat(a: Obj) -> u8 => sint::lean_int8_of_nat;
+    fn lean_int8_to_int(a: u8) -> Obj => sint::lean_int8_to_int;
+    fn lean_int16_neg(a: u16) -> u16 => sint::lean_int16_neg;
+    fn lean_int16_add(a: u16, b: u16) -> u16 => sint::lean_int16_add;
+    fn lean_int16_sub(a: u16, b: u16) -> u16 => sint::lean_int16_sub;
+    fn lean_int16_mul(a: u16, b: u16) -> u16 => sint::lean_int16_mul;
+    fn lean_int16_div(a: u16, b: u16) -> u16 => sint::lean_int16_div;
+    fn lean_int16_mod(a: u16, b: u16) -> u16 => sint::lean_int16_mod;
+    fn lean_int16_land(a: u16, b: u16) -> u16 => sint::lean_int16_land;
+    fn lean_int16_lor(a: u16, b: u16) -> u16 => sint::lean_int16_lor;
+    fn lean_int16_xor(a: u16, b: u16) -> u16 => sint::lean_int16_xor;
+    fn lean_int16_shift_right(a: u16, b: u16) -> u16 => sint::lean_int16_shift_right;
+    fn lean_int16_shift_left(a: u16, b: u16) -> u16 => sint::lean_int16_shift_left;
+    fn lean_int16_complement(a: u16) -> u16 => sint::lean_int16_complement;
+    fn lean_int16_abs(a: u16) -> u16 => sint::lean_int16_abs;
+    fn lean_int16_dec_eq(a: u16, b: u16) -> u8 => sint::lean_int16_dec_eq;
+    fn lean_int16_dec_lt(a: u16, b: u16) -> u8 => sint::lean_int16_dec_lt;
+    fn lean_int16_dec_le(a: u16, b: u16) -> u8 => sint::lean_int16_dec_le;
+    fn lean_int16_to_int8(a: u16) -> u8 => sint::lean_int16_to_int8;
+    fn lean_int16_to_int32(a: u16) -> u32 => sint::lean_int16_to_int32;
+    fn lean_int16_to_int64(a: u16) -> u64 => sint::lean_int16_to_int64;
+    fn lean_int16_to_isize(a: u16) -> usize => sint::lean_int16_to_isize;
+    fn lean_int16_of_int(a: Obj) -> u16 => sint::lean_int16_of_int;
+    fn lean_int16_of_nat(a: Obj) -> u16 => sint::lean_int16_of_nat;
+    fn lean_int16_to_int(a: u16) -> Obj => sint::lean_int16_to_int;
+    fn lean_int32_neg(a: u32) -> u32 => sint::lean_int32_neg;
+    fn lean_int32_add(a: u32, b: u32) -> u32 => sint::lean_int32_add;
+    fn lean_int32_sub(a: u32, b: u32) -> u32 => sint::lean_int32_sub;
+    fn lean_int32_mul(a: u32, b: u32) -> u32 => sint::lean_int32_mul;
+    fn lean_int32_div(a: u32, b: u32) -> u32 => sint::lean_int32_div;
+    fn lean_int32_mod(a: u32, b: u32) -> u32 => sint::lean_int32_mod;
+    fn lean_int32_land(a: u32, b: u32) -> u32 => sint::lean_int32_land;
+    fn lean_int32_lor(a: u32, b: u32) -> u32 => sint::lean_int32_lor;
+    fn lean_int32_xor(a: u32, b: u32) -> u32 => sint::lean_int32_xor;
+    fn lean_int32_shift_right(a: u32, b: u32) -> u32 => sint::lean_int32_shift_right;
+    fn lean_int32_shift_left(a: u32, b: u32) -> u32 => sint::lean_int32_shift_left;
+    fn lean_int32_complement(a: u32) -> u32 => sint::lean_int32_complement;
+    fn lean_int32_abs(a: u32) -> u32 => sint::lean_int32_abs;
+    fn lean_int32_dec_eq(a: u32, b: u32) -> u8 => sint::lean_int32_dec_eq;
+    fn lean_int32_dec_lt(a: u32, b: u32) -> u8 => sint::lean_int32_dec_lt;
+    fn lean_int32_dec_le(a: u32, b: u32) -> u8 => sint::lean_int32_dec_le;
+    fn lean_int32_to_int8(a: u32) -> u8 => sint::lean_int32_to_int8;
+    fn lean_int32_to_int16(a: u32) -> u16 => sint::lean_int32_to_int16;
+    fn lean_int32_to_int64(a: u32) -> u64 => sint::lean_int32_to_int64;
+    fn lean_int32_to_isize(a: u32) -> usize => sint::lean_int32_to_isize;
+    fn lean_int32_of_int(a: Obj) -> u32 => sint::lean_int32_of_int;
+    fn lean_int32_of_nat(a: Obj) -> u32 => sint::lean_int32_of_nat;
+    fn lean_int32_to_int(a: u32) -> Obj => sint::lean_int32_to_int;
+    fn lean_int64_neg(a: u64) -> u64 => sint::lean_int64_neg;
+    fn lean_int64_add(a: u64, b: u64) -> u64 => sint::lean_int64_add;
+    fn lean_int64_sub(a: u64, b: u64) -> u64 => sint::lean_int64_sub;
+    fn lean_int64_mul(a: u64, b: u64) -> u64 => sint::lean_int64_mul;
+    fn lean_int64_div(a: u64, b: u64) -> u64 => sint::lean_int64_div;
+    fn lean_int64_mod(a: u64, b: u64) -> u64 => sint::lean_int64_mod;
+    fn lean_int64_land(a: u64, b: u64) -> u64 => sint::lean_int64_land;
+    fn lean_int64_lor(a: u64, b: u64) -> u64 => sint::lean_int64_lor;
+    fn lean_int64_xor(a: u64, b: u64) -> u64 => sint::lean_int64_xor;
+    fn lean_int64_shift_right(a: u64, b: u64) -> u64 => sint::lean_int64_shift_right;
+    fn lean_int64_shift_left(a: u64, b: u64) -> u64 => sint::lean_int64_shift_left;
+    fn lean_int64_complement(a: u64) -> u64 => sint::lean_int64_complement;
+    fn lean_int64_abs(a: u64) -> u64 => sint::lean_int64_abs;
+    fn lean_int64_dec_eq(a: u64, b: u64) -> u8 => sint::lean_int64_dec_eq;
+    fn lean_int64_dec_lt(a: u64, b: u64) -> u8 => sint::lean_int64_dec_lt;
+    fn lean_int64_dec_le(a: u64, b: u64) -> u8 => sint::lean_int64_dec_le;
+    fn lean_int64_to_int8(a: u64) -> u8 => sint::lean_int64_to_int8;
+    fn lean_int64_to_int16(a: u64) -> u16 => sint::lean_int64_to_int16;
+    fn lean_int64_to_int32(a: u64) -> u32 => sint::lean_int64_to_int32;
+    fn lean_int64_to_isize(a: u64) -> usize => sint::lean_int64_to_isize;
+    fn lean_int64_of_int(a: Obj) -> u64 => sint::lean_int64_of_int;
+    fn lean_int64_of_nat(a: Obj) -> u64 => sint::lean_int64_of_nat;
+    fn lean_isize_neg(a: usize) -> usize => sint::lean_isize_neg;
+    fn lean_isize_add(a: usize, b: usize) -> usize => sint::lean_isize_add;
+    fn lean_isize_sub(a: usize, b: usize) -> usize => sint::lean_isize_sub;
+    fn lean_isize_mul(a: usize, b: usize) -> usize => sint::lean_isize_mul;
+    fn lean_isize_div(a: usize, b: usize) -> usize => sint::lean_isize_div;
+    fn lean_isize_mod(a: usize, b: usize) -> usize => sint::lean_isize_mod;
+    fn lean_isize_land(a: usize, b: usize) -> usize => sint::lean_isize_land;
+    fn lean_isize_lor(a: usize, b: usize) -> usize => sint::lean_isize_lor;
+    fn lean_isize_xor(a: usize, b: usize) -> usize => sint::lean_isize_xor;
+    fn lean_isize_shift_right(a: usize, b: usize) -> usize => sint::lean_isize_shift_right;
+    fn lean_isize_shift_left(a: usize, b: usize) -> usize => sint::lean_isize_shift_left;
+    fn lean_isize_complement(a: usize) -> usize => sint::lean_isize_complement;
+    fn lean_isize_abs(a: usize) -> usize => sint::lean_isize_abs;
+    fn lean_isize_dec_eq(a: usize, b: usize) -> u8 => sint::lean_isize_dec_eq;
+    fn lean_isize_dec_lt(a: usize, b: usize) -> u8 => sint::lean_isize_dec_lt;
+    fn lean_isize_dec_le(a: usize, b: usize) -> u8 => sint::lean_isize_dec_le;
+    fn lean_isize_to_int8(a: usize) -> u8 => sint::lean_isize_to_int8;
+    fn lean_isize_to_int16(a: usize) -> u16 => sint::lean_isize_to_int16;
+    fn lean_isize_to_int32(a: usize) -> u32 => sint::lean_isize_to_int32;
+    fn lean_isize_to_int64(a: usize) -> u64 => sint::lean_isize_to_int64;
+    fn lean_isize_of_int(a: Obj) -> usize => sint::lean_isize_of_int;
+    fn lean_isize_of_nat(a: Obj) -> usize => sint::lean_isize_of_nat;
+    fn lean_isize_to_int(a: usize) -> Obj => sint::lean_isize_to_int;
+    fn lean_bool_to_int8(a: u8) -> u8 => sint::lean_bool_to_int8;
+    fn lean_bool_to_int16(a: u8) -> u16 => sint::lean_bool_to_int16;
+    fn lean_bool_to_int32(a: u8) -> u32 => sint::lean_bool_to_int32;
+    fn lean_bool_to_int64(a: u8) -> u64 => sint::lean_bool_to_int64;
+    fn lean_bool_to_isize(a: u8) -> usize => sint::lean_bool_to_isize;
+    fn lean_bool_to_uint8(a: u8) -> u8 => sint::lean_bool_to_uint8;
+    fn lean_bool_to_uint16(a: u8) -> u16 => sint::lean_bool_to_uint16;
+    fn lean_bool_to_uint32(a: u8) -> u32 => sint::lean_bool_to_uint32;
+    fn lean_bool_to_uint64(a: u8) -> u64 => sint::lean_bool_to_uint64;
+    fn lean_bool_to_usize(a: u8) -> usize => sint::lean_bool_to_usize;
+    // stref
+    fn lean_st_mk_ref(a: Obj) -> Obj => stref::lean_st_mk_ref;
+    fn lean_st_ref_get(ref_obj: Obj) -> Obj => stref::lean_st_ref_get;
+    fn lean_st_ref_set(ref_obj: Obj, new_val: Obj) -> Obj => stref::lean_st_ref_set;
+    fn lean_st_ref_reset(ref_obj: Obj) -> Obj => stref::lean_st_ref_reset;
+    fn lean_st_ref_swap(ref_obj: Obj, new_val: Obj) -> Obj => stref::lean_st_ref_swap;
+    fn lean_st_ref_take(ref_obj: Obj) -> Obj => stref::lean_st_ref_take;
+    fn lean_st_ref_ptr_eq(a: Obj, b: Obj) -> Obj => stref::lean_st_ref_ptr_eq;
+    // string
+    fn lean_mk_string_unchecked(s: *const u8, byte_len: usize, utf8_len: usize) -> Obj => string::lean_mk_string_unchecked;
+    fn lean_string_byte_len(s: Obj) -> usize => string::lean_string_byte_len;
+    fn lean_string_utf8_len(s: Obj) -> usize => string::lean_string_utf8_len;
+    fn lean_string_cstr(s: Obj) -> *const u8 => string::lean_string_cstr;
+    fn lean_string_append(s1: Obj, s2: Obj) -> Obj => string::lean_string_append;
+    fn lean_string_eq(s1: Obj, s2: Obj) -> bool => string::lean_string_eq;
+    fn lean_string_dec_eq(s1: Obj, s2: Obj) -> u8 => string::lean_string_dec_eq;
+    fn lean_nat_to_string(n: Obj) -> Obj => string::lean_nat_to_string;
+    fn lean_string_utf8_byte_size(s: Obj) -> Obj => string::lean_string_utf8_byte_size;
+    fn lean_string_to_utf8(s: Obj) -> Obj => string::lean_string_to_utf8;
+    fn lean_string_from_utf8_unchecked(a: Obj) -> Obj => string::lean_string_from_utf8_unchecked;
+    fn lean_string_push(s: Obj, c: u32) -> Obj => string::lean_string_push;
+    fn lean_string_utf8_get(s: Obj, i: Obj) -> u32 => string::lean_string_utf8_get;
+    fn lean_string_utf8_next(s: Obj, i: Obj) -> Obj => string::lean_string_utf8_next;
+    fn lean_string_utf8_prev(s: Obj, i: Obj) -> Obj => string::lean_string_utf8_prev;
+    fn lean_string_utf8_at_end(s: Obj, i: Obj) -> u8 => string::lean_string_utf8_at_end;
+    fn lean_string_utf8_extract(s: Obj, b: Obj, e: Obj) -> Obj => string::lean_string_utf8_extract;
+    fn lean_string_utf8_set(s: Obj, i: Obj, c: u32) -> Obj => string::lean_string_utf8_set;
+    fn lean_string_length(s: Obj) -> Obj => string::lean_string_length;
+    fn lean_string_mk(cs: Obj) -> Obj => string::lean_string_mk;
+    fn lean_string_data(s: Obj) -> Obj => string::lean_string_data;
+    fn lean_string_hash(s: Obj) -> u64 => string::lean_string_hash;
+    fn lean_string_lt(s1: Obj, s2: Obj) -> bool => string::lean_string_lt;
+    fn lean_string_dec_lt(s1: Obj, s2: Obj) -> u8 => string::lean_string_dec_lt;
+    fn lean_string_utf8_get_fast(s: Obj, i: Obj) -> u32 => string::lean_string_utf8_get_fast;
+    fn lean_string_utf8_get_fast_cold(s: Obj, i: Obj) -> u32 => string::lean_string_utf8_get_fast_cold;
+    fn lean_string_utf8_next_fast(s: Obj, i: Obj) -> Obj => string::lean_string_utf8_next_fast;
+    fn lean_string_utf8_next_fast_cold(s: Obj, i: Obj) -> Obj => string::lean_string_utf8_next_fast_cold;
+    fn lean_string_get_byte_fast(s: Obj, i: Obj) -> u8 => string::lean_string_get_byte_fast;
+    fn lean_string_utf8_get_opt(s: Obj, i: Obj) -> Obj => string::lean_string_utf8_get_opt;
+    fn lean_string_utf8_get_bang(s: Obj, i: Obj) -> u32 => string::lean_string_utf8_get_bang;
+    fn lean_string_is_valid_pos(s: Obj, i: Obj) -> u8 => string::lean_string_is_valid_pos;
+    fn lean_string_of_usize(c: usize) -> Obj => string::lean_string_of_usize;
+    fn lean_string_memcmp(s1: Obj, s2: Obj, lstart: Obj, rstart: Obj, len: Obj) -> u8 => string::lean_string_memcmp;
+    fn lean_slice_hash(s: Obj) -> u64 => string::lean_slice_hash;
+    fn lean_slice_dec_lt(s1: Obj, s2: Obj) -> u8 => string::lean_slice_dec_lt;
+    // task
+    fn lean_init_task_manager() => task::lean_init_task_manager;
+    fn lean_init_task_manager_using(num_workers: u32) => task::lean_init_task_manager_using;
+    fn lean_finalize_task_manager() => task::lean_finalize_task_manager;
+    fn lean_task_pure(a: Obj) -> Obj => task::lean_task_pure;
+    fn lean_task_spawn_core(c: Obj, prio: u32) -> Obj => task::lean_task_spawn_core;
+    fn lean_task_spawn(c: Obj, prio: Obj) -> Obj => task::lean_task_spawn;
+    fn lean_task_map_core(f: Obj, t: Obj, prio: u32, _sync: bool) -> Obj => task::lean_task_map_core;
+    fn lean_task_map(f: Obj, t: Obj, prio: Obj, sync: u8) -> Obj => task::lean_task_map;
+    fn lean_task_bind_core(x: Obj, f: Obj, prio: u32, _sync: bool) -> Obj => task::lean_task_bind_core;
+    fn lean_task_bind(x: Obj, f: Obj, prio: Obj, sync: u8) -> Obj => task::lean_task_bind;
+    fn lean_promise_task_new() -> Obj => task::lean_promise_task_new;
+    fn lean_task_resolve(t: Obj, v: Obj) -> bool => task::lean_task_resolve;
+    fn lean_task_get(t: Obj) -> Obj => task::lean_task_get;
+    fn lean_task_get_own(t: Obj) -> Obj => task::lean_task_get_own;
+    fn lean_task_is_finished(t: Obj) -> bool => task::lean_task_is_finished;
+    fn lean_task_cancel(t: Obj) => task::lean_task_cancel;
+    fn lean_task_check_canceled() -> bool => task::lean_task_check_canceled;
+    fn lean_task_get_state(t: Obj) -> u8 => task::lean_task_get_state;
+    // thunk
+    fn lean_mk_thunk(c: Obj) -> Obj => thunk::lean_mk_thunk;
+    fn lean_thunk_pure(v: Obj) -> Obj => thunk::lean_thunk_pure;
+    fn lean_thunk_get(t: Obj) -> Obj => thunk::lean_thunk_get;
+    fn lean_thunk_get_core(t: Obj) -> Obj => thunk::lean_thunk_get_core;
+    fn lean_thunk_get_own(t: Obj) -> Obj => thunk::lean_thunk_get_own;
+    // uint
+    fn lean_uint8_of_nat(a: Obj) -> u8 => uint::lean_uint8_of_nat;
+    fn lean_uint8_of_nat_mk(a: Obj) -> u8 => uint::lean_uint8_of_nat_mk;
+    fn lean_uint8_to_nat(a: u8) -> Obj => uint::lean_uint8_to_nat;
+    fn lean_uint8_dec_eq(a: u8, b: u8) -> u8 => uint::lean_uint8_dec_eq;
+    fn lean_uint8_dec_lt(a: u8, b: u8) -> u8 => uint::lean_uint8_dec_lt;
+    fn lean_uint8_dec_le(a: u8, b: u8) -> u8 => uint::lean_uint8_dec_le;
+    fn lean_uint8_add(a: u8, b: u8) -> u8 => uint::lean_uint8_add;
+    fn lean_uint8_sub(a: u8, b: u8) -> u8 => uint::lean_uint8_sub;
+    fn lean_uint8_mul(a: u8, b: u8) -> u8 => uint::lean_uint8_mul;
+    fn lean_uint8_div(a: u8, b: u8) -> u8 => uint::lean_uint8_div;
+    fn lean_uint8_mod(a: u8, b: u8) -> u8 => uint::lean_uint8_mod;
+    fn lean_uint8_land(a: u8, b: u8) -> u8 => uint::lean_uint8_land;
+    fn lean_uint8_lor(a: u8, b: u8) -> u8 => uint::lean_uint8_lor;
+    fn lean_uint8_lxor(a: u8, b: u8) -> u8 => uint::lean_uint8_lxor;
+    fn lean_uint8_shift_left(a: u8, b: u8) -> u8 => uint::lean_uint8_shift_left;
+    fn lean_uint8_shift_right(a: u8, b: u8) -> u8 => uint::lean_uint8_shift_right;
+    fn lean_uint8_complement(a: u8) -> u8 => uint::lean_uint8_complement;
+    fn lean_uint8_to_uint32(a: u8) -> u32 => uint::lean_uint8_to_uint32;
+    fn lean_uint8_to_uint16(a: u8) -> u16 => uint::lean_uint8_to_uint16;
+    fn lean_uint8_to_uint64(a: u8) -> u64 => uint::lean_uint8_to_uint64;
+    fn lean_uint16_of_nat(a: Obj) -> u16 => uint::lean_uint16_of_nat;
+    fn lean_uint16_of_nat_mk(a: Obj) -> u16 => uint::lean_uint16_of_nat_mk;
+    fn lean_uint16_to_nat(a: u16) -> Obj => uint::lean_uint16_to_nat;
+    fn lean_uint16_dec_eq(a: u16, b: u16) -> u8 => uint::lean_uint16_dec_eq;
+    fn lean_uint16_dec_lt(a: u16, b: u16) -> u8 => uint::lean_uint16_dec_lt;
+    fn lean_uint16_dec_le(a: u16, b: u16) -> u8 => uint::lean_uint16_dec_le;
+    fn lean_uint16_add(a: u16, b: u16) -> u16 => uint::lean_uint16_add;
+    fn lean_uint16_sub(a: u16, b: u16) -> u16 => uint::lean_uint16_sub;
+    fn lean_uint16_mul(a: u16, b: u16) -> u16 => uint::lean_uint16_mul;
+    fn lean_uint16_div(a: u16, b: u16) -> u16 => uint::lean_uint16_div;
+    fn lean_uint16_mod(a: u16, b: u16) -> u16 => uint::lean_uint16_mod;
+    fn lean_uint16_land(a: u16, b: u16) -> u16 => uint::lean_uint16_land;
+    fn lean_uint16_lor(a: u16, b: u16) -> u16 => uint::lean_uint16_lor;
+    fn lean_uint16_lxor(a: u16, b: u16) -> u16 => uint::lean_uint16_lxor;
+    fn lean_uint16_shift_left(a: u16, b: u16) -> u16 => uint::lean_uint16_shift_left;
+    fn lean_uint16_shift_right(a: u16, b: u16) -> u16 => uint::lean_uint16_shift_right;
+    fn lean_uint16_complement(a: u16) -> u16 => uint::lean_uint16_complement;
+    fn lean_uint16_to_uint8(a: u16) -> u8 => uint::lean_uint16_to_uint8;
+    fn lean_uint16_to_uint32(a: u16) -> u32 => uint::lean_uint16_to_uint32;
+    fn lean_uint16_to_uint64(a: u16) -> u64 => uint::lean_uint16_to_uint64;
+    fn lean_uint32_of_nat(a: Obj) -> u32 => uint::lean_uint32_of_nat;
+    fn lean_uint32_of_nat_mk(a: Obj) -> u32 => uint::lean_uint32_of_nat_mk;
+    fn lean_uint32_to_nat(a: u32) -> Obj => uint::lean_uint32_to_nat;
+    fn lean_uint32_dec_le(a: u32, b: u32) -> u8 => uint::lean_uint32_dec_le;
+    fn lean_uint32_add(a: u32, b: u32) -> u32 => uint::lean_uint32_add;
+    fn lean_uint32_sub(a: u32, b: u32) -> u32 => uint::lean_uint32_sub;
+    fn lean_uint32_mul(a: u32, b: u32) -> u32 => uint::lean_uint32_mul;
+    fn lean_uint32_div(a: u32, b: u32) -> u32 => uint::lean_uint32_div;
+    fn lean_uint32_mod(a: u32, b: u32) -> u32 => uint::lean_uint32_mod;
+    fn lean_uint32_land(a: u32, b: u32) -> u32 => uint::lean_uint32_land;
+    fn lean_uint32_lor(a: u32, b: u32) -> u32 => uint::lean_uint32_lor;
+    fn lean_uint32_lxor(a: u32, b: u32) -> u32 => uint::lean_uint32_lxor;
+    fn lean_uint32_shift_left(a: u32, b: u32) -> u32 => uint::lean_uint32_shift_left;
+    fn lean_uint32_shift_right(a: u32, b: u32) -> u32 => uint::lean_uint32_shift_right;
+    fn lean_uint32_complement(a: u32) -> u32 => uint::lean_uint32_complement;
+    fn lean_uint32_to_uint8(a: u32) -> u8 => uint::lean_uint32_to_uint8;
+    fn lean_uint32_to_uint16(a: u32) -> u16 => uint::lean_uint32_to_uint16;
+    fn lean_uint32_to_uint64(a: u32) -> u64 => uint::lean_uint32_to_uint64;
+    fn lean_uint64_of_nat(a: Obj) -> u64 => uint::lean_uint64_of_nat;
+    fn lean_uint64_of_nat_mk(a: Obj) -> u64 => uint::lean_uint64_of_nat_mk;
+    fn lean_uint64_dec_eq(a: u64, b: u64) -> u8 => uint::lean_uint64_dec_eq;
+    fn lean_uint64_dec_lt(a: u64, b: u64) -> u8 => uint::lean_uint64_dec_lt;
+    fn lean_uint64_dec_le(a: u64, b: u64) -> u8 => uint::lean_uint64_dec_le;
+    fn lean_uint64_add(a: u64, b: u64) -> u64 => uint::lean_uint64_add;
+    fn lean_uint64_sub(a: u64, b: u64) -> u64 => uint::lean_uint64_sub;
+    fn lean_uint64_mul(a: u64, b: u64) -> u64 => uint::lean_uint64_mul;
+    fn lean_uint64_div(a: u64, b: u64) -> u64 => uint::lean_uint64_div;
+    fn lean_uint64_mod(a: u64, b: u64) -> u64 => uint::lean_uint64_mod;
+    fn lean_uint64_land(a: u64, b: u64) -> u64 => uint::lean_uint64_land;
+    fn lean_uint64_lor(a: u64, b: u64) -> u64 => uint::lean_uint64_lor;
+    fn lean_uint64_lxor(a: u64, b: u64) -> u64 => uint::lean_uint64_lxor;
+    fn lean_uint64_shift_left(a: u64, b: u64) -> u64 => uint::lean_uint64_shift_left;
+    fn lean_uint64_shift_right(a: u64, b: u64) -> u64 => uint::lean_uint64_shift_right;
+    fn lean_uint64_complement(a: u64) -> u64 => uint::lean_uint64_complement;
+    fn lean_uint64_to_uint8(a: u64) -> u8 => uint::lean_uint64_to_uint8;
+    fn lean_uint64_to_uint16(a: u64) -> u16 => uint::lean_uint64_to_uint16;
+    fn lean_uint64_to_uint32(a: u64) -> u32 => uint::lean_uint64_to_uint32;
+    fn lean_uint64_mix_hash(a: u64, b: u64) -> u64 => uint::lean_uint64_mix_hash;
+    fn lean_usize_of_nat(a: Obj) -> usize => uint::lean_usize_of_nat;
+    fn lean_usize_of_nat_mk(a: Obj) -> usize => uint::lean_usize_of_nat_mk;
+    fn lean_usize_dec_eq(a: usize, b: usize) -> u8 => uint::lean_usize_dec_eq;
+    fn lean_usize_dec_lt(a: usize, b: usize) -> u8 => uint::lean_usize_dec_lt;
+    fn lean_usize_dec_le(a: usize, b: usize) -> u8 => uint::lean_usize_dec_le;
+    fn lean_usize_add(a: usize, b: usize) -> usize => uint::lean_usize_add;
+    fn lean_usize_sub(a: usize, b: usize) -> usize => uint::lean_usize_sub;
+    fn lean_usize_mul(a: usize, b: usize) -> usize => uint::lean_usize_mul;
+    fn lean_usize_div(a: usize, b: usize) -> usize => uint::lean_usize_div;
+    fn lean_usize_mod(a: usize, b: usize) -> usize => uint::lean_usize_mod;
+    fn lean_usize_land(a: usize, b: usize) -> usize => uint::lean_usize_land;
+    fn lean_usize_lor(a: usize, b: usize) -> usize => uint::lean_usize_lor;
+    fn lean_usize_lxor(a: usize, b: usize) -> usize => uint::lean_usize_lxor;
+    fn lean_usize_shift_left(a: usize, b: usize) -> usize => uint::lean_usize_shift_left;
+    fn lean_usize_shift_right(a: usize, b: usize) -> usize => uint::lean_usize_shift_right;
+    fn lean_usize_complement(a: usize) -> usize => uint::lean_usize_complement;
+    fn lean_usize_mix_hash(a: usize, b: usize) -> usize => uint::lean_usize_mix_hash;
+    fn lean_uint8_neg(a: u8) -> u8 => uint::lean_uint8_neg;
+    fn lean_uint16_neg(a: u16) -> u16 => uint::lean_uint16_neg;
+    fn lean_uint32_neg(a: u32) -> u32 => uint::lean_uint32_neg;
+    fn lean_uint64_neg(a: u64) -> u64 => uint::lean_uint64_neg;
+    fn lean_usize_neg(a: usize) -> usize => uint::lean_usize_neg;
+    fn lean_uint8_log2(a: u8) -> u8 => uint::lean_uint8_log2;
+    fn lean_uint16_log2(a: u16) -> u16 => uint::lean_uint16_log2;
+    fn lean_uint32_log2(a: u32) -> u32 => uint::lean_uint32_log2;
+    fn lean_uint64_log2(a: u64) -> u64 => uint::lean_uint64_log2;
+    fn lean_usize_log2(a: usize) -> usize => uint::lean_usize_log2;
+    fn lean_uint8_to_usize(a: u8) -> usize => uint::lean_uint8_to_usize;
+    fn lean_uint16_to_usize(a: u16) -> usize => uint::lean_uint16_to_usize;
+    fn lean_uint32_to_usize(a: u32) -> usize => uint::lean_uint32_to_usize;
+    fn lean_uint64_to_usize(a: u64) -> usize => uint::lean_uint64_to_usize;
+    fn lean_usize_to_uint8(a: usize) -> u8 => uint::lean_usize_to_uint8;
+    fn lean_usize_to_uint16(a: usize) -> u16 => uint::lean_usize_to_uint16;
+    fn lean_usize_to_uint32(a: usize) -> u32 => uint::lean_usize_to_uint32;
+    fn lean_usize_to_uint64(a: usize) -> u64 => uint::lean_usize_to_uint64;
+    fn lean_uint8_xor(a: u8, b: u8) -> u8 => uint::lean_uint8_xor;
+    fn lean_uint16_xor(a: u16, b: u16) -> u16 => uint::lean_uint16_xor;
+    fn lean_uint32_xor(a: u32, b: u32) -> u32 => uint::lean_uint32_xor;
+    fn lean_uint64_xor(a: u64, b: u64) -> u64 => uint::lean_uint64_xor;
+    fn lean_usize_xor(a: usize, b: usize) -> usize => uint::lean_usize_xor;
+}
+
+/// The C type for a Rust parameter or return type in [`EXPORTS`].
+fn c_type(ty: &str) -> &'static str {
+    match ty.replace(' ', "").as_str() {
+        "" => "void",
+        "!" => "void",
+        "Obj" => "lean_object *",
+        "*mutObj" => "lean_object **",
+        "*constObj" => "lean_object * const *",
+        "bool" => "bool",
+        "u8" => "uint8_t",
+        "u16" => "uint16_t",
+        "u32" => "uint32_t",
+        "u64" => "uint64_t",
+        "usize" => "size_t",
+        "i8" => "int8_t",
+        "i16" => "int16_t",
+        "i32" => "int32_t",
+        "i64" => "int64_t",
+        "isize" => "ptrdiff_t",
+        "f32" => "float",
+        "f64" => "double",
+        "*constu8" | "*consti8" | "*constc_char" => "char const *",
+        "*mutu8" => "uint8_t *",
+        "*const()" | "*mutc_void" => "void *",
+        "*mutLeanExternalClass" => "lean_external_class *",
+        "Option<LeanExternalFinalize>" => "lean_external_finalize_proc",
+        "Option<LeanExternalForeach>" => "lean_external_foreach_proc",
+        _ => panic!("no C type for {}", ty),
+    }
+}
+
+const HEADER_PRELUDE: &str = r#"/* Generated by `cargo run -p lean-runtime --features c-api --example gen_header`.
+ * Do not edit. */
+#ifndef LEAN_RUNTIME_H
+#define LEAN_RUNTIME_H
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+#if defined(__GNUC__)
+#define LEAN_RUNTIME_NORETURN __attribute__((noreturn))
+#else
+#define LEAN_RUNTIME_NORETURN
+#endif
+
+typedef struct {
+    int m_rc;
+    unsigned m_cs_sz:16;
+    unsigned m_other:8;
+    unsigned m_tag:8;
+} lean_object;
+
+typedef void (*lean_external_finalize_proc)(void *);
+typedef void (*lean_external_foreach_proc)(void *, lean_object *);
+
+typedef struct {
+    lean_external_finalize_proc m_finalize;
+    lean_external_foreach_proc m_foreach;
+} lean_external_class;
+"#;
+
+const HEADER_EPILOGUE: &str = r#"
+#ifdef __cplusplus
+}
+#endif
+
+#endif /* LEAN_RUNTIME_H */
+"#;
+
+/// The C header declaring every export, grouped by runtime module.
+pub fn c_header() -> String {
+    let mut h = String::from(HEADER_PRELUDE);
+    let mut group = "";
+    for &(name, params, ret, target) in EXPORTS {
+        let module = match target.split_once("::") {
+            Some((module, _)) => module.trim().trim_start_matches("r#"),
+            None => "adapters",
+        };
+        if module != group {
+            h.push_str(&format!("\n/* {} */\n", module));
+            group = module;
+        }
+        let params = if params.is_empty() {
+            "void".to_string()
+        } else {
+            params
+                .iter()
+                .map(|&(name, ty)| format!("{}{}", c_type(ty), declarator(ty, name)))
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        let noreturn = if ret.trim() == "!" {
+            "LEAN_RUNTIME_NORETURN "
+        } else {
+            ""
+        };
+        h.push_str(&format!(
+            "{}{}{}({});\n",
+            noreturn,
+            c_type(ret),
+            declarator(ret, name),
+            params
+        ));
+    }
+    h.push_str(HEADER_EPILOGUE);
+    h
+}
+
+/// `name` placed after a C type: pointer types end in `*` and need no space.
+fn declarator(ty: &str, name: &str) -> String {
+    if c_type(ty).ends_with('*') {
+        name.to_string()
+    } else {
+        format!(" {}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_is_up_to_date() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/include/lean_runtime.h");
+        let header = std::fs::read_to_string(path).unwrap();
+        assert!(
+            header == c_header(),
+            "include/lean_runtime.h is stale; regenerate it with \
+             `cargo run -p lean-runtime --features c-api --example gen_header`"
+        );
+    }
+
+    #[test]
+    fn exported_symbols_call_the_runtime() {
+        unsafe {
+            let s = lean_mk_string(c"hello".as_ptr());
+            assert_eq!(crate::lean_string_to_str(s), "hello");
+            let n = lean_cstr_to_nat(c"123456789012345678901234567890".as_ptr());
+            assert_eq!(
+                crate::lean_string_to_str(crate::lean_nat_to_string(n)),
+                "123456789012345678901234567890"
+            );
+            assert_eq!(lean_unbox(lean_string_length(s)), 5);
+            crate::lean_dec(s);
+        }
+    }
+
+    /// Scalar field offsets follow `lean.h`: they start at the object fields,
+    /// and `usize` fields are indexed after the pointer slots.
+    #[test]
+    fn ctor_scalar_fields_use_c_offsets() {
+        unsafe {
+            let p = std::mem::size_of::<*mut c_void>() as u32;
+            // Two object fields, then a usize, a u64, a double, a u32, a
+            // float, a u16 and a u8
+            let o = lean_alloc_ctor(0, 2, 8 + 8 + 8 + 4 + 4 + 2 + 1);
+            lean_ctor_set(o, 0, lean_box(1));
+            lean_ctor_set(o, 1, lean_box(2));
+            lean_ctor_set_usize(o, 2, 0x0123_4567);
+            lean_ctor_set_uint64(o, 3 * p, 0x89ab_cdef_0123_4567);
+            lean_ctor_set_float(o, 3 * p + 8, 2.5);
+            lean_ctor_set_uint32(o, 3 * p + 16, 0xdead_beef);
+            lean_ctor_set_float32(o, 3 * p + 20, -0.75);
+            lean_ctor_set_uint16(o, 3 * p + 24, 0xbeef);
+            lean_ctor_set_uint8(o, 3 * p + 26, 0xab);
+
+            assert_eq!(lean_unbox(lean_ctor_get(o, 0)), 1);
+            assert_eq!(lean_unbox(lean_ctor_get(o, 1)), 2);
+            assert_eq!(lean_ctor_get_usize(o, 2), 0x0123_4567);
+            assert_eq!(lean_ctor_get_uint64(o, 3 * p), 0x89ab_cdef_0123_4567);
+            assert_eq!(lean_ctor_get_float(o, 3 * p + 8), 2.5);
+            assert_eq!(lean_ctor_get_uint32(o, 3 * p + 16), 0xdead_beef);
+            assert_eq!(lean_ctor_get_float32(o, 3 * p + 20), -0.75);
+            assert_eq!(lean_ctor_get_uint16(o, 3 * p + 24), 0xbeef);
+            assert_eq!(lean_ctor_get_uint8(o, 3 * p + 26), 0xab);
+            // The Rust API counts from the start of the scalar area
+            assert_eq!(ctor::lean_ctor_get_usize(o, 0), 0x0123_4567);
+            assert_eq!(ctor::lean_ctor_get_uint64(o, 8), 0x89ab_cdef_0123_4567);
+            assert_eq!(ctor::lean_ctor_get_uint8(o, 34), 0xab);
+            crate::lean_dec(o);
+        }
+    }
+}
@@ -47,9 +47,13 @@ pub unsafe fn lean_closure_set(o: *mut LeanObject, i: u32, v: *mut LeanObject) {
     *(*(o as *mut LeanClosure)).fixed_args_ptr().add(i as usize) = v;
 }
 
+/// Closures take at most this many arguments directly; code of a larger
+/// arity is called as [`LeanFnN`] with all arguments in an array.
+pub const LEAN_CLOSURE_MAX_ARGS: u32 = 16;
+
 macro_rules! lean_fn_type {
     ($name:ident, $($param:ident),+) => {
-        pub type $name = unsafe fn($($param: *mut LeanObject),+) -> *mut LeanObject;
+        pub type $name = unsafe extern "C" fn($($param: *mut LeanObject),+) -> *mut LeanObject;
     };
 }
 
@@ -61,6 +65,18 @@ lean_fn_type!(LeanFn5, a1, a2, a3, a4, a5);
 lean_fn_type!(LeanFn6, a1, a2, a3, a4, a5, a6);
 lean_fn_type!(LeanFn7, a1, a2, a3, a4, a5, a6, a7);
 lean_fn_type!(LeanFn8, a1, a2, a3, a4, a5, a6, a7, a8);
+lean_fn_type!(LeanFn9, a1, a2, a3, a4, a5, a6, a7, a8, a9);
+lean_fn_type!(LeanFn10, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10);
+lean_fn_type!(LeanFn11, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11);
+lean_fn_type!(LeanFn12, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12);
+lean_fn_type!(LeanFn13, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13);
+lean_fn_type!(LeanFn14, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14);
+lean_fn_type!(LeanFn15, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14, a15);
+lean_fn_type!(LeanFn16, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14, a15, a16);
+
+/// Code of a closure with more than [`LEAN_CLOSURE_MAX_ARGS`] arguments,
+/// which receives them all in one array.
+pub type LeanFnN = unsafe extern "C" fn(args: *mut *mut LeanObject) -> *mut LeanObject;
 
 /// Collect fixed args from a closure into a buffer, incrementing their refcounts.
 unsafe fn collect_fixed(f: *mut LeanObject, num_fixed: u32, buf: &mut [*mut LeanObject]) {
@@ -92,7 +108,7 @@ unsafe fn fix_args(f: *mut LeanObject, new_args: &[*mut LeanObject]) -> *mut Lea
 
 /// Call a fully-saturated closure. `args` must contain exactly `arity` arguments
 /// (fixed args already prepended by caller).
-unsafe fn curry(fun: *const (), arity: u32, args: &[*mut LeanObject]) -> *mut LeanObject {
+unsafe fn curry(fun: *const (), arity: u32, args: &mut [*mut LeanObject]) -> *mut LeanObject {
     macro_rules! curry_call {
         ($fn_type:ty, [$($i:literal),+]) => {{
             let fp: $fn_type = std::mem::transmute(fun);
@@ -108,16 +124,46 @@ unsafe fn curry(fun: *const (), arity: u32, args: &[*mut LeanObject]) -> *mut Le
         6 => curry_call!(LeanFn6, [0, 1, 2, 3, 4, 5]),
         7 => curry_call!(LeanFn7, [0, 1, 2, 3, 4, 5, 6]),
         8 => curry_call!(LeanFn8, [0, 1, 2, 3, 4, 5, 6, 7]),
-        9..=16 => {
-            type LeanFnM =
-                unsafe fn(*mut LeanObject, u32, *const *mut LeanObject) -> *mut LeanObject;
-            let fp: LeanFnM = std::mem::transmute(fun);
-            fp(args[0], arity - 1, args[1..].as_ptr())
+        9 => curry_call!(LeanFn9, [0, 1, 2, 3, 4, 5, 6, 7, 8]),
+        10 => curry_call!(LeanFn10, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9]),
+        11 => curry_call!(LeanFn11, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10]),
+        12 => curry_call!(LeanFn12, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11]),
+        13 => curry_call!(LeanFn13, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12]),
+        14 => curry_call!(LeanFn14, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13]),
+        15 => curry_call!(LeanFn15, [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14]),
+        16 => curry_call!(
+            LeanFn16,
+            [0, 1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11, 12, 13, 14, 15]
+        ),
+        _ => {
+            let fp: LeanFnN = std::mem::transmute(fun);
+            fp(args.as_mut_ptr())
         }
-        _ => panic!("curry: arity {arity} not supported"),
     }
 }
 
+/// Call the closure `f`, consuming it, with its fixed args followed by
+/// `args`, which must make up exactly its arity.
+unsafe fn saturate(f: *mut LeanObject, args: &[*mut LeanObject]) -> *mut LeanObject {
+    let closure = f as *mut LeanClosure;
+    let fun = (*closure).fun;
+    let arity = (*closure).arity as u32;
+    let num_fixed = (*closure).num_fixed as usize;
+    let mut buf = [std::ptr::null_mut::<LeanObject>(); LEAN_CLOSURE_MAX_ARGS as usize];
+    let mut vec;
+    let all_args = if arity <= LEAN_CLOSURE_MAX_ARGS {
+        &mut buf[..arity as usize]
+    } else {
+        vec = vec![std::ptr::null_mut(); arity as usize];
+        &mut vec[..]
+    };
+    collect_fixed(f, num_fixed as u32, all_args);
+    all_args[num_fixed..].copy_from_slice(args);
+    let result = curry(fun, arity, all_args);
+    crate::lean_dec(f);
+    result
+}
+
 /// Apply `n` arguments to a closure. Handles partial application, exact saturation,
 /// and over-saturation (chaining).
 unsafe fn apply_n(mut f: *mut LeanObject, new_args: &[*mut LeanObject]) -> *mut LeanObject {
@@ -134,28 +180,12 @@ unsafe fn apply_n(mut f: *mut LeanObject, new_args: &[*mut LeanObject]) -> *mut
             return fix_args(f, args_remaining);
         } else if total == arity {
             // Exact saturation
-            let fun = (*closure).fun;
-            let mut all_args = [std::ptr::null_mut::<LeanObject>(); 16];
-            collect_fixed(f, num_fixed, &mut all_args);
-            for (j, &arg) in args_remaining.iter().enumerate() {
-                all_args[num_fixed as usize + j] = arg;
-            }
-            let result = curry(fun, arity, &all_args[..arity as usize]);
-            crate::lean_dec(f);
-            return result;
+            return saturate(f, args_remaining);
         } else {
             // Over-saturation: saturate first, then loop with remaining args
             let needed = (arity - num_fixed) as usize;
             let (first, rest) = args_remaining.split_at(needed);
-            let fun = (*closure).fun;
-            let mut all_args = [std::ptr::null_mut::<LeanObject>(); 16];
-            collect_fixed(f, num_fixed, &mut all_args);
-            for (j, &arg) in first.iter().enumerate() {
-                all_args[num_fixed as usize + j] = arg;
-            }
-            let result = curry(fun, arity, &all_args[..arity as usize]);
-            crate::lean_dec(f);
-            f = result;
+            f = saturate(f, first);
             args_remaining = rest;
         }
     }
@@ -179,10 +209,98 @@ lean_apply!(lean_apply_5, a1, a2, a3, a4, a5);
 lean_apply!(lean_apply_6, a1, a2, a3, a4, a5, a6);
 lean_apply!(lean_apply_7, a1, a2, a3, a4, a5, a6, a7);
 lean_apply!(lean_apply_8, a1, a2, a3, a4, a5, a6, a7, a8);
+lean_apply!(lean_apply_9, a1, a2, a3, a4, a5, a6, a7, a8, a9);
+lean_apply!(lean_apply_10, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10);
+lean_apply!(lean_apply_11, a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11);
+lean_apply!(
+    lean_apply_12,
+    a1,
+    a2,
+    a3,
+    a4,
+    a5,
+    a6,
+    a7,
+    a8,
+    a9,
+    a10,
+    a11,
+    a12
+);
+lean_apply!(
+    lean_apply_13,
+    a1,
+    a2,
+    a3,
+    a4,
+    a5,
+    a6,
+    a7,
+    a8,
+    a9,
+    a10,
+    a11,
+    a12,
+    a13
+);
+lean_apply!(
+    lean_apply_14,
+    a1,
+    a2,
+    a3,
+    a4,
+    a5,
+    a6,
+    a7,
+    a8,
+    a9,
+    a10,
+    a11,
+    a12,
+    a13,
+    a14
+);
+lean_apply!(
+    lean_apply_15,
+    a1,
+    a2,
+    a3,
+    a4,
+    a5,
+    a6,
+    a7,
+    a8,
+    a9,
+    a10,
+    a11,
+    a12,
+    a13,
+    a14,
+    a15
+);
+lean_apply!(
+    lean_apply_16,
+    a1,
+    a2,
+    a3,
+    a4,
+    a5,
+    a6,
+    a7,
+    a8,
+    a9,
+    a10,
+    a11,
+    a12,
+    a13,
+    a14,
+    a15,
+    a16
+);
 
 /// Apply `n` arguments passed as a raw pointer array.
 #[inline]
-pub unsafe fn lean_apply_m(
+pub unsafe fn lean_apply_n(
     f: *mut LeanObject,
     n: u32,
     args: *const *mut LeanObject,
@@ -191,23 +309,34 @@ pub unsafe fn lean_apply_m(
     apply_n(f, args_slice)
 }
 
+/// Apply more than [`LEAN_CLOSURE_MAX_ARGS`] arguments passed as a raw
+/// pointer array. The same as [`lean_apply_n`], which also takes fewer.
+#[inline]
+pub unsafe fn lean_apply_m(
+    f: *mut LeanObject,
+    n: u32,
+    args: *const *mut LeanObject,
+) -> *mut LeanObject {
+    lean_apply_n(f, n, args)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
-    unsafe fn test_fn_1(a: *mut LeanObject) -> *mut LeanObject {
+    unsafe extern "C" fn test_fn_1(a: *mut LeanObject) -> *mut LeanObject {
         // identity
         a
     }
 
-    unsafe fn test_fn_2(a: *mut LeanObject, b: *mut LeanObject) -> *mut LeanObject {
+    unsafe extern "C" fn test_fn_2(a: *mut LeanObject, b: *mut LeanObject) -> *mut LeanObject {
         // add two scalars
         let va = crate::lean_unbox(a);
         let vb = crate::lean_unbox(b);
         crate::lean_box(va + vb)
     }
 
-    unsafe fn test_fn_3(
+    unsafe extern "C" fn test_fn_3(
         a: *mut LeanObject,
         b: *mut LeanObject,
         c: *mut LeanObject,
@@ -299,7 +428,7 @@ mod tests {
 
     // -- higher arity test functions --
 
-    unsafe fn test_fn_4(
+    unsafe extern "C" fn test_fn_4(
         a: *mut LeanObject,
         b: *mut LeanObject,
         c: *mut LeanObject,
@@ -312,7 +441,7 @@ mod tests {
         crate::lean_box(r)
     }
 
-    unsafe fn test_fn_5(
+    unsafe extern "C" fn test_fn_5(
         a: *mut LeanObject,
         b: *mut LeanObject,
         c: *mut LeanObject,
@@ -327,7 +456,7 @@ mod tests {
         crate::lean_box(r)
     }
 
-    unsafe fn test_fn_6(
+    unsafe extern "C" fn test_fn_6(
         a: *mut LeanObject,
         b: *mut LeanObject,
         c: *mut LeanObject,
@@ -344,7 +473,7 @@ mod tests {
         crate::lean_box(r)
     }
 
-    unsafe fn test_fn_7(
+    unsafe extern "C" fn test_fn_7(
         a: *mut LeanObject,
         b: *mut LeanObject,
         c: *mut LeanObject,
@@ -364,7 +493,7 @@ mod tests {
     }
 
     #[allow(clippy::too_many_arguments)]
-    unsafe fn test_fn_8(
+    unsafe extern "C" fn test_fn_8(
         a: *mut LeanObject,
         b: *mut LeanObject,
         c: *mut LeanObject,
@@ -481,7 +610,7 @@ mod tests {
             // Let's use: apply_2(arity-1-returns-closure, a, b)
             //   -> step 1: call fn(a) -> new closure
             //   -> step 2: apply(new_closure, b)
-            unsafe fn make_adder(x: *mut LeanObject) -> *mut LeanObject {
+            unsafe extern "C" fn make_adder(x: *mut LeanObject) -> *mut LeanObject {
                 // returns a closure that adds x to its argument
                 let f = lean_alloc_closure(test_fn_2 as *const (), 2, 1);
                 lean_closure_set(f, 0, x);
@@ -498,7 +627,10 @@ mod tests {
     fn over_saturate_apply_3_on_arity_2() {
         unsafe {
             // fn(a,b) -> closure that adds a+b to its arg
-            unsafe fn make_adder2(a: *mut LeanObject, b: *mut LeanObject) -> *mut LeanObject {
+            unsafe extern "C" fn make_adder2(
+                a: *mut LeanObject,
+                b: *mut LeanObject,
+            ) -> *mut LeanObject {
                 let sum = crate::lean_unbox(a) + crate::lean_unbox(b);
                 let f = lean_alloc_closure(test_fn_2 as *const (), 2, 1);
                 lean_closure_set(f, 0, crate::lean_box(sum));
@@ -0,0 +1,620 @@
+//! Compacted regions
+//!
+//! [`lean_compact_object`] serializes the graph of objects reachable from a
+//! root into a relocatable image, writing each object once so sharing is
+//! preserved. [`CompactedRegion`] maps an image back into memory, where its
+//! objects are used in place as persistent objects (rc 0), like upstream
+//! Lean's `.olean` data. `saveModuleData`/`readModuleData` are built on these.
+//!
+//! An image is a header followed by the objects, 8-byte aligned and in their
+//! in-memory layout, except that references to other objects in the image
+//! hold their offset from the start of the objects (scalars are kept as is).
+//! The header is little-endian. The objects are in the byte order of the
+//! machine that wrote them, which the header records; images of the other
+//! byte order are rejected, since their objects can't be used in place.
+//! Loading adds the base address to every reference, in one pass that also
+//! checks the image, so a corrupt file is rejected rather than crashing
+//! later. Big numbers store their digits after the object; loading builds
+//! their `BigUint`/`BigInt` on the Rust heap.
+//!
+//! Constructors, arrays, scalar arrays, strings and big numbers can be
+//! compacted. Closures, thunks, tasks, references and external objects
+//! cannot.
+
+use crate::array::LeanArray;
+use crate::bignat::{lean_bignat_object_size, lean_bignat_value};
+use crate::int::{lean_bigint_object_size, lean_bigint_value};
+use crate::io::{lean_io_error_from_std, lean_io_result_mk_error, lean_io_result_mk_ok};
+use crate::object::*;
+use crate::r#box::{lean_box, lean_box_usize, lean_is_scalar};
+use crate::sarray::LeanSArray;
+use crate::string::{lean_string_to_str, LeanString};
+use num_bigint::{BigInt, BigUint, Sign};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind, Result};
+use std::mem::size_of;
+use std::path::Path;
+
+const MAGIC: &[u8; 8] = b"LEANCRGN";
+const VERSION: u32 = 1;
+
+/// Magic, version, byte order, size of the objects, root reference.
+const HEADER_SIZE: usize = 32;
+
+/// Byte order of the objects: 0 for little-endian, 1 for big-endian.
+const BYTE_ORDER: u32 = cfg!(target_endian = "big") as u32;
+
+fn invalid(msg: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("invalid compacted region: {}", msg),
+    )
+}
+
+#[inline]
+fn align8(n: usize) -> usize {
+    n.next_multiple_of(8)
+}
+
+#[inline]
+unsafe fn read_u64(p: *const u8) -> u64 {
+    (p as *const u64).read()
+}
+
+/// Size of `o` in an image, including the digits of big numbers.
+unsafe fn record_size(o: *mut LeanObject) -> Result<usize> {
+    let tag = (*o).tag;
+    let size = match tag {
+        0..=LEAN_MAX_CTOR_TAG => lean_ctor_object_size((*o).num_objs(), (*o).cs_sz as u32),
+        LEAN_ARRAY_TAG => size_of::<LeanArray>() + (*(o as *mut LeanArray)).size * 8,
+        LEAN_SCALAR_ARRAY_TAG => {
+            size_of::<LeanSArray>() + (*(o as *mut LeanSArray)).size * (*o).other as usize
+        }
+        LEAN_STRING_TAG => size_of::<LeanString>() + (*(o as *mut LeanString)).byte_len + 1,
+        LEAN_MPZ_TAG => lean_bignat_object_size() + lean_bignat_value(o).to_u32_digits().len() * 4,
+        LEAN_BIGINT_TAG => {
+            lean_bigint_object_size() + lean_bigint_value(o).magnitude().to_u32_digits().len() * 4
+        }
+        _ => {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                format!("cannot compact object with tag {}", tag),
+            ))
+        }
+    };
+    Ok(align8(size))
+}
+
+/// Serialize the objects reachable from `root` into a compacted region
+/// image. Arrays and strings are stored without spare capacity.
+pub unsafe fn lean_compact_object(root: *mut LeanObject) -> Result<Vec<u8>> {
+    // Assign offsets, children after the objects that reach them
+    let mut offsets: HashMap<*mut LeanObject, usize> = HashMap::new();
+    let mut objects = Vec::new();
+    let mut size = 0;
+    let mut worklist = vec![root];
+    while let Some(o) = worklist.pop() {
+        if lean_is_scalar(o) || offsets.contains_key(&o) {
+            continue;
+        }
+        offsets.insert(o, size);
+        objects.push(o);
+        size += record_size(o)?;
+        let tag = (*o).tag;
+        if tag <= LEAN_MAX_CTOR_TAG {
+            let fields = (*o).obj_fields_ptr();
+            worklist.extend((0..(*o).num_objs() as usize).rev().map(|i| *fields.add(i)));
+        } else if tag == LEAN_ARRAY_TAG {
+            let arr = o as *mut LeanArray;
+            let data = (*arr).data_ptr();
+            worklist.extend((0..(*arr).size).rev().map(|i| *data.add(i)));
+        }
+    }
+    let encode = |o: *mut LeanObject| -> u64 {
+        if lean_is_scalar(o) {
+            o as u64
+        } else {
+            offsets[&o] as u64
+        }
+    };
+
+    let mut image = Vec::with_capacity(HEADER_SIZE + size);
+    image.extend_from_slice(MAGIC);
+    image.extend_from_slice(&VERSION.to_le_bytes());
+    image.extend_from_slice(&BYTE_ORDER.to_le_bytes());
+    image.extend_from_slice(&(size as u64).to_le_bytes());
+    image.extend_from_slice(&encode(root).to_le_bytes());
+    let header = |o: *mut LeanObject| {
+        let mut bytes = *(o as *const [u8; 8]);
+        // rc 0: persistent
+        bytes[..4].fill(0);
+        bytes
+    };
+    let word = |image: &mut Vec<u8>, w: u64| image.extend_from_slice(&w.to_ne_bytes());
+    for &o in &objects {
+        let start = image.len();
+        image.extend_from_slice(&header(o));
+        let tag = (*o).tag;
+        if tag <= LEAN_MAX_CTOR_TAG {
+            let fields = (*o).obj_fields_ptr();
+            for i in 0..(*o).num_objs() as usize {
+                word(&mut image, encode(*fields.add(i)));
+            }
+            let scalars = std::slice::from_raw_parts((*o).scalar_data_ptr(), (*o).cs_sz as usize);
+            image.extend_from_slice(scalars);
+        } else if tag == LEAN_ARRAY_TAG {
+            let arr = o as *mut LeanArray;
+            word(&mut image, (*arr).size as u64);
+            word(&mut image, (*arr).size as u64);
+            let data = (*arr).data_ptr();
+            for i in 0..(*arr).size {
+                word(&mut image, encode(*data.add(i)));
+            }
+        } else if tag == LEAN_SCALAR_ARRAY_TAG {
+            let arr = o as *mut LeanSArray;
+            word(&mut image, (*arr).size as u64);
+            word(&mut image, (*arr).size as u64);
+            let len = (*arr).size * (*o).other as usize;
+            image.extend_from_slice(std::slice::from_raw_parts((*arr).data_ptr(), len));
+        } else if tag == LEAN_STRING_TAG {
+            let s = o as *mut LeanString;
+            word(&mut image, (*s).byte_len as u64);
+            word(&mut image, (*s).utf8_len as u64);
+            word(&mut image, (*s).byte_len as u64);
+            let len = (*s).byte_len + 1;
+            image.extend_from_slice(std::slice::from_raw_parts((*s).data_ptr(), len));
+        } else {
+            // Digit count and sign in place of the Rust value, digits after it
+            let (sign, digits, object_size) = if tag == LEAN_MPZ_TAG {
+                let n = lean_bignat_value(o);
+                (Sign::Plus, n.to_u32_digits(), lean_bignat_object_size())
+            } else {
+                let n = lean_bigint_value(o);
+                (
+                    n.sign(),
+                    n.magnitude().to_u32_digits(),
+                    lean_bigint_object_size(),
+                )
+            };
+            word(&mut image, digits.len() as u64);
+            word(&mut image, (sign == Sign::Minus) as u64);
+            image.resize(start + object_size, 0);
+            for d in digits {
+                image.extend_from_slice(&d.to_le_bytes());
+            }
+        }
+        image.resize(start + record_size(o)?, 0);
+    }
+    Ok(image)
+}
+
+/// Write the image of the objects reachable from `root` to `path`.
+pub unsafe fn lean_save_compacted_region(path: &Path, root: *mut LeanObject) -> Result<()> {
+    std::fs::write(path, lean_compact_object(root)?)
+}
+
+enum Storage {
+    #[cfg(unix)]
+    Mapped(*mut libc::c_void, usize),
+    /// Words, for alignment
+    Buffer(Vec<u64>),
+}
+
+/// A loaded compacted region. Its objects are persistent and may be
+/// referenced from anywhere, so the region stays alive until explicitly
+/// released with [`CompactedRegion::free`].
+pub struct CompactedRegion {
+    storage: Storage,
+    root: *mut LeanObject,
+    /// Big numbers, whose Rust values are dropped by `free`
+    bignums: Vec<*mut LeanObject>,
+}
+
+impl CompactedRegion {
+    /// Map the image in the file at `path`.
+    #[cfg(unix)]
+    pub fn load(path: impl AsRef<Path>) -> Result<CompactedRegion> {
+        use std::os::unix::io::AsRawFd;
+        let file = std::fs::File::open(path)?;
+        let len = file.metadata()?.len() as usize;
+        if len < HEADER_SIZE {
+            return Err(invalid("file too short"));
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE,
+                file.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(Error::last_os_error());
+        }
+        let storage = Storage::Mapped(ptr, len);
+        unsafe { Self::relocate(storage, ptr as *mut u8, len) }
+    }
+
+    /// Read the image in the file at `path`.
+    #[cfg(not(unix))]
+    pub fn load(path: impl AsRef<Path>) -> Result<CompactedRegion> {
+        Self::from_bytes(&std::fs::read(path)?)
+    }
+
+    /// Load an image from memory, copying it.
+    pub fn from_bytes(bytes: &[u8]) -> Result<CompactedRegion> {
+        let mut buf = vec![0u64; bytes.len().div_ceil(8)];
+        let ptr = buf.as_mut_ptr() as *mut u8;
+        unsafe {
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr, bytes.len());
+            Self::relocate(Storage::Buffer(buf), ptr, bytes.len())
+        }
+    }
+
+    /// Check the image at `ptr` and turn its offsets into pointers.
+    unsafe fn relocate(storage: Storage, ptr: *mut u8, len: usize) -> Result<CompactedRegion> {
+        let mut region = CompactedRegion {
+            storage,
+            root: lean_box(0),
+            bignums: Vec::new(),
+        };
+        // Release the storage and the big numbers built so far
+        let fail = |region: CompactedRegion, e: Error| {
+            region.free();
+            Err(e)
+        };
+        if len < HEADER_SIZE || std::slice::from_raw_parts(ptr, 8) != MAGIC {
+            return fail(region, invalid("bad magic"));
+        }
+        let version = u32::from_le_bytes(*(ptr.add(8) as *const [u8; 4]));
+        if version != VERSION {
+            return fail(region, invalid(&format!("unsupported version {}", version)));
+        }
+        if u32::from_le_bytes(*(ptr.add(12) as *const [u8; 4])) != BYTE_ORDER {
+            return fail(
+                region,
+                invalid("written on a machine of the other byte order"),
+            );
+        }
+        let size = u64::from_le_bytes(*(ptr.add(16) as *const [u8; 8])) as usize;
+        if size != len - HEADER_SIZE {
+            return fail(region, invalid("size mismatch"));
+        }
+        let base = ptr.add(HEADER_SIZE);
+
+        // Find the object boundaries
+        let mut starts = vec![false; size / 8];
+        let mut pos = 0;
+        while pos < size {
+            match Self::object_size(base.add(pos), size - pos) {
+                Some(n) => {
+                    starts[pos / 8] = true;
+                    pos += n;
+                }
+                None => return fail(region, invalid(&format!("bad object at offset {}", pos))),
+            }
+        }
+
+        let reloc = |r: *mut *mut LeanObject| -> bool {
+            let v = *r as usize;
+            if v & 1 == 1 {
+                return true;
+            }
+            if !v.is_multiple_of(8) || v >= size || !starts[v / 8] {
+                return false;
+            }
+            *r = base.add(v) as *mut LeanObject;
+            true
+        };
+        let mut pos = 0;
+        while pos < size {
+            let o = base.add(pos) as *mut LeanObject;
+            let n = Self::object_size(o as *mut u8, size - pos).unwrap();
+            let ok = match (*o).tag {
+                0..=LEAN_MAX_CTOR_TAG => {
+                    let fields = (*o).obj_fields_ptr();
+                    (0..(*o).num_objs() as usize).all(|i| reloc(fields.add(i)))
+                }
+                LEAN_ARRAY_TAG => {
+                    let arr = o as *mut LeanArray;
+                    let data = (*arr).data_ptr();
+                    (0..(*arr).size).all(|i| reloc(data.add(i)))
+                }
+                LEAN_STRING_TAG => {
+                    let s = o as *mut LeanString;
+                    let bytes = std::slice::from_raw_parts((*s).data_ptr(), (*s).byte_len + 1);
+                    bytes[(*s).byte_len] == 0
+                        && std::str::from_utf8(&bytes[..(*s).byte_len])
+                            .is_ok_and(|s| s.chars().count() == (*(o as *mut LeanString)).utf8_len)
+                }
+                LEAN_MPZ_TAG | LEAN_BIGINT_TAG => {
+                    let value = (o as *mut u8).add(LEAN_OBJECT_SIZE);
+                    let num_digits = read_u64(value) as usize;
+                    let negative = read_u64(value.add(8)) != 0;
+                    let object_size = if (*o).tag == LEAN_MPZ_TAG {
+                        lean_bignat_object_size()
+                    } else {
+                        lean_bigint_object_size()
+                    };
+                    let digits = (o as *mut u8).add(object_size);
+                    let digits: Vec<u32> = (0..num_digits)
+                        .map(|i| u32::from_le_bytes(*(digits.add(i * 4) as *const [u8; 4])))
+                        .collect();
+                    let n = BigUint::from_slice(&digits);
+                    if (*o).tag == LEAN_MPZ_TAG {
+                        (value as *mut BigUint).write(n);
+                    } else {
+                        let sign = if negative { Sign::Minus } else { Sign::Plus };
+                        (value as *mut BigInt).write(BigInt::from_biguint(sign, n));
+                    }
+                    region.bignums.push(o);
+                    true
+                }
+                _ => true,
+            };
+            if !ok {
+                return fail(region, invalid(&format!("bad object at offset {}", pos)));
+            }
+            (*o).rc = 0;
+            pos += n;
+        }
+        let mut root = u64::from_le_bytes(*(ptr.add(24) as *const [u8; 8])) as *mut LeanObject;
+        if !reloc(&mut root) {
+            return fail(region, invalid("bad root"));
+        }
+        region.root = root;
+        Ok(region)
+    }
+
+    /// Size of the object at `p`, if its header is valid and it fits in
+    /// `avail` bytes.
+    unsafe fn object_size(p: *mut u8, avail: usize) -> Option<usize> {
+        let o = p as *mut LeanObject;
+        let word = |i: usize| -> Option<usize> {
+            if (i + 1) * 8 > avail {
+                None
+            } else {
+                Some(read_u64(p.add(i * 8)) as usize)
+            }
+        };
+        word(0)?;
+        let size = match (*o).tag {
+            0..=LEAN_MAX_CTOR_TAG => lean_ctor_object_size((*o).num_objs(), (*o).cs_sz as u32),
+            LEAN_ARRAY_TAG => {
+                let (n, capacity) = (word(1)?, word(2)?);
+                if n != capacity {
+                    return None;
+                }
+                size_of::<LeanArray>().checked_add(n.checked_mul(8)?)?
+            }
+            LEAN_SCALAR_ARRAY_TAG => {
+                let (n, capacity) = (word(1)?, word(2)?);
+                if n != capacity {
+                    return None;
+                }
+                size_of::<LeanSArray>().checked_add(n.checked_mul((*o).other as usize)?)?
+            }
+            LEAN_STRING_TAG => {
+                let (byte_len, capacity) = (word(1)?, word(3)?);
+                if byte_len != capacity {
+                    return None;
+                }
+                size_of::<LeanString>()
+                    .checked_add(byte_len)?
+                    .checked_add(1)?
+            }
+            LEAN_MPZ_TAG => lean_bignat_object_size().checked_add(word(1)?.checked_mul(4)?)?,
+            LEAN_BIGINT_TAG => lean_bigint_object_size().checked_add(word(1)?.checked_mul(4)?)?,
+            _ => return None,
+        };
+        let size = size.checked_next_multiple_of(8)?;
+        (size <= avail).then_some(size)
+    }
+
+    /// The root object.
+    pub fn root(&self) -> *mut LeanObject {
+        self.root
+    }
+
+    /// Whether the image is mapped from its file rather than copied.
+    pub fn is_memory_mapped(&self) -> bool {
+        match self.storage {
+            #[cfg(unix)]
+            Storage::Mapped(..) => true,
+            Storage::Buffer(_) => false,
+        }
+    }
+
+    /// Release the region. No object in it may be used afterwards.
+    pub unsafe fn free(self) {
+        for &o in &self.bignums {
+            if (*o).tag == LEAN_MPZ_TAG {
+                crate::bignat::lean_bignat_drop(o);
+            } else {
+                crate::int::lean_bigint_drop(o);
+            }
+        }
+        match self.storage {
+            #[cfg(unix)]
+            Storage::Mapped(ptr, len) => {
+                libc::munmap(ptr, len);
+            }
+            Storage::Buffer(words) => drop(words),
+        }
+    }
+}
+
+// ---------------------------------------------------------------------------
+// IO primitives
+// ---------------------------------------------------------------------------
+
+/// `saveModuleData`: write the compacted image of `data` to `fname`. The
+/// module name is not stored. All arguments are borrowed.
+pub unsafe fn lean_save_module_data(
+    fname: *mut LeanObject,
+    _mod_name: *mut LeanObject,
+    data: *mut LeanObject,
+    _unit: *mut LeanObject,
+) -> *mut LeanObject {
+    let path = lean_string_to_str(fname);
+    match lean_save_compacted_region(Path::new(path), data) {
+        Ok(()) => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => {
+            crate::lean_inc(fname);
+            lean_io_result_mk_error(lean_io_error_from_std(&e, fname))
+        }
+    }
+}
+
+/// `readModuleData`: load the image in `fname` (borrowed), returning the
+/// root and the region.
+pub unsafe fn lean_read_module_data(
+    fname: *mut LeanObject,
+    _unit: *mut LeanObject,
+) -> *mut LeanObject {
+    match CompactedRegion::load(lean_string_to_str(fname)) {
+        Ok(region) => {
+            let pair = crate::lean_alloc_ctor(0, 2, 0);
+            crate::lean_ctor_set(pair, 0, region.root());
+            let region = Box::into_raw(Box::new(region));
+            crate::lean_ctor_set(pair, 1, lean_box_usize(region as usize));
+            lean_io_result_mk_ok(pair)
+        }
+        Err(e) => {
+            crate::lean_inc(fname);
+            lean_io_result_mk_error(lean_io_error_from_std(&e, fname))
+        }
+    }
+}
+
+/// `CompactedRegion.isMemoryMapped`
+pub unsafe fn lean_compacted_region_is_memory_mapped(region: usize) -> u8 {
+    (*(region as *const CompactedRegion)).is_memory_mapped() as u8
+}
+
+/// `CompactedRegion.free`
+pub unsafe fn lean_compacted_region_free(region: usize, _unit: *mut LeanObject) -> *mut LeanObject {
+    Box::from_raw(region as *mut CompactedRegion).free();
+    lean_io_result_mk_ok(lean_box(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    /// `#[(1, "a"), (1, "a"), 2^100, -(2^70), ByteArray [1, 2, 3]]` with
+    /// the pair shared.
+    unsafe fn sample() -> *mut LeanObject {
+        let pair = lean_alloc_ctor(0, 2, 8);
+        lean_ctor_set(pair, 0, lean_box(1));
+        lean_ctor_set(pair, 1, lean_mk_string("a"));
+        lean_ctor_set_uint64(pair, 0, 42);
+        let arr = lean_mk_empty_array_with_capacity(lean_box(8));
+        lean_inc(pair);
+        let arr = lean_array_push(arr, pair);
+        let arr = lean_array_push(arr, pair);
+        let arr = lean_array_push(arr, lean_alloc_bignat(BigUint::from(1u8) << 100u32));
+        let arr = lean_array_push(arr, lean_alloc_bigint(-(BigInt::from(1u8) << 70u32)));
+        let bytes = lean_alloc_sarray(1, 3, 3);
+        std::ptr::copy_nonoverlapping([1u8, 2, 3].as_ptr(), lean_sarray_data(bytes), 3);
+        lean_array_push(arr, bytes)
+    }
+
+    unsafe fn check(root: *mut LeanObject) {
+        assert_eq!(lean_array_size(root), 5);
+        let pair = lean_array_get_core(root, 0);
+        assert_eq!(pair, lean_array_get_core(root, 1), "sharing is preserved");
+        assert_eq!(lean_ctor_get(pair, 0), lean_box(1));
+        assert_eq!(lean_string_to_str(lean_ctor_get(pair, 1)), "a");
+        assert_eq!(lean_ctor_get_uint64(pair, 0), 42);
+        assert_eq!(
+            *lean_bignat_value(lean_array_get_core(root, 2)),
+            BigUint::from(1u8) << 100u32
+        );
+        assert_eq!(
+            *lean_bigint_value(lean_array_get_core(root, 3)),
+            -(BigInt::from(1u8) << 70u32)
+        );
+        let bytes = lean_array_get_core(root, 4);
+        assert_eq!(
+            std::slice::from_raw_parts(lean_sarray_data(bytes), 3),
+            [1, 2, 3]
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        unsafe {
+            let root = sample();
+            let image = lean_compact_object(root).unwrap();
+            lean_dec(root);
+            let region = CompactedRegion::from_bytes(&image).unwrap();
+            assert!(!region.is_memory_mapped());
+            let root = region.root();
+            check(root);
+            assert_eq!((*root).rc, 0);
+            assert_eq!((*lean_array_get_core(root, 0)).rc, 0);
+            // Persistent objects ignore reference counting
+            lean_inc(root);
+            lean_dec(root);
+            lean_dec(root);
+            check(root);
+            region.free();
+        }
+    }
+
+    #[test]
+    fn load_from_file() {
+        unsafe {
+            let path = std::env::temp_dir().join(format!("compact-{}.bin", std::process::id()));
+            let root = sample();
+            lean_save_compacted_region(&path, root).unwrap();
+            lean_dec(root);
+            let region = CompactedRegion::load(&path).unwrap();
+            assert_eq!(region.is_memory_mapped(), cfg!(unix));
+            check(region.root());
+            region.free();
+            std::fs::remove_file(&path).unwrap();
+        }
+    }
+
+    #[test]
+    fn scalar_root() {
+        unsafe {
+            let image = lean_compact_object(lean_box(7)).unwrap();
+            assert_eq!(image.len(), HEADER_SIZE);
+            let region = CompactedRegion::from_bytes(&image).unwrap();
+            assert_eq!(region.root(), lean_box(7));
+            region.free();
+        }
+    }
+
+    #[test]
+    fn rejects_bad_images() {
+        unsafe {
+            let root = sample();
+            let image = lean_compact_object(root).unwrap();
+            lean_dec(root);
+            assert!(CompactedRegion::from_bytes(&image[..image.len() - 8]).is_err());
+            let mut bad = image.clone();
+            bad[0] = b'X';
+            assert!(CompactedRegion::from_bytes(&bad).is_err());
+            let mut bad = image.clone();
+            bad[12] ^= 1;
+            let err = CompactedRegion::from_bytes(&bad).err().unwrap();
+            assert!(err.to_string().contains("byte order"), "{}", err);
+            // The array's first element points into the middle of an object
+            let mut bad = image.clone();
+            bad[HEADER_SIZE + 24..HEADER_SIZE + 32].copy_from_slice(&8u64.to_ne_bytes());
+            assert!(CompactedRegion::from_bytes(&bad).is_err());
+
+            let closure = lean_alloc_closure(std::ptr::null(), 1, 0);
+            let err = lean_compact_object(closure).unwrap_err();
+            assert_eq!(err.kind(), ErrorKind::InvalidInput);
+            lean_free_object(closure, crate::closure::lean_closure_object_size(0));
+        }
+    }
+}
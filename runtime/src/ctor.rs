@@ -266,4 +266,51 @@ mod tests {
             }
         }
     }
+
+    /// Each small allocation is one heartbeat on the allocating thread.
+    #[test]
+    fn alloc_counts_heartbeats() {
+        unsafe {
+            crate::lean_set_heartbeats(0);
+            let objs: Vec<_> = (0..3).map(|_| lean_alloc_ctor(0, 1, 0)).collect();
+            assert_eq!(crate::lean_get_num_heartbeats(), 3);
+            crate::lean_set_heartbeats(100);
+            crate::lean_dec(lean_alloc_ctor(0, 0, 0));
+            assert_eq!(crate::lean_get_num_heartbeats(), 101);
+            for o in objs {
+                crate::lean_ctor_set(o, 0, crate::lean_box(0));
+                crate::lean_dec(o);
+            }
+        }
+    }
+
+    /// Allocated bytes are counted on the allocating and the freeing thread,
+    /// so other threads' allocations don't show up in this thread's count.
+    #[test]
+    fn alloc_counts_bytes_per_thread() {
+        unsafe {
+            let base = crate::lean_get_allocated_bytes();
+            let obj = lean_alloc_ctor(0, 0, 8);
+            let size = crate::lean_get_allocated_bytes().wrapping_sub(base);
+            assert!(size >= 16);
+            let obj = obj as usize;
+            std::thread::spawn(move || {
+                let base = crate::lean_get_allocated_bytes();
+                let objs: Vec<_> = (0..100).map(|_| lean_alloc_ctor(0, 0, 8)).collect();
+                assert_eq!(
+                    crate::lean_get_allocated_bytes().wrapping_sub(base),
+                    100 * size
+                );
+                for o in objs {
+                    crate::lean_dec(o);
+                }
+                crate::lean_dec(obj as *mut crate::LeanObject);
+                let freed = crate::lean_get_allocated_bytes().wrapping_sub(base) as isize;
+                assert_eq!(freed, -(size as isize));
+            })
+            .join()
+            .unwrap();
+            assert_eq!(crate::lean_get_allocated_bytes().wrapping_sub(base), size);
+        }
+    }
 }
@@ -136,6 +136,7 @@ pub unsafe extern "C" fn lean_debug_dump(o: *mut LeanObject) {
         249 => "string",
         250 => "mpz",
         251 => "bigint",
+        252 => "task",
         253 => "ref",
         254 => "external",
         _ => "unknown",
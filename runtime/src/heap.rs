@@ -0,0 +1,672 @@
+//! Small-object heap
+//!
+//! Every object of at most `LEAN_MAX_SMALL_OBJECT_SIZE` bytes is served from
+//! a segregated heap, in the style of the Lean C runtime: each thread owns a
+//! heap with one free list per size class, carved out of aligned pages.
+//! Larger objects go to the system allocator, as does everything when the
+//! `system-alloc` feature is enabled (useful under Valgrind or sanitizers).
+//!
+//! A page holds slots of a single size class and belongs to one heap. Its
+//! header sits at the start of the page, so the page of an object is found by
+//! masking its address. Freeing an object on the owning thread pushes it on
+//! the page's free list; another thread instead pushes it on the page's
+//! lock-free `remote` list and queues the page on the owner's `delayed` list,
+//! which the owner drains when it runs out of slots. A page whose last object
+//! is freed goes back to a global pool, where any size class can reuse it;
+//! pages are not returned to the system.
+//!
+//! Heaps are never deallocated, since other threads may still queue pages on
+//! them. When a thread exits, its heap is orphaned together with its pages,
+//! and the next thread that starts allocating adopts it.
+//!
+//! Freed slots keep their first word, so the header poisoned by
+//! `lean_free_object` in debug builds survives until the slot is reused.
+
+use crate::object::LEAN_MAX_SMALL_OBJECT_SIZE;
+use std::alloc::{alloc, dealloc, handle_alloc_error, Layout};
+use std::cell::Cell;
+use std::ptr::null_mut;
+use std::sync::atomic::{AtomicBool, AtomicPtr, AtomicUsize, Ordering};
+use std::sync::Mutex;
+
+/// Size and alignment of a page.
+pub const LEAN_PAGE_SIZE: usize = 64 * 1024;
+
+/// Smallest slot: the header word plus the free list link.
+const MIN_SLOT_SIZE: usize = 16;
+
+/// Slot sizes: every multiple of 8 up to 128, then four classes per
+/// doubling, so at most a fifth of a slot is wasted.
+const CLASS_SIZES: [u32; NUM_CLASSES] = class_sizes();
+const NUM_CLASSES: usize = 35;
+
+/// Size class of each size, indexed by the size in words (rounded up).
+const CLASS_OF: [u8; LEAN_MAX_SMALL_OBJECT_SIZE / 8 + 1] = class_table();
+
+const fn class_sizes() -> [u32; NUM_CLASSES] {
+    let mut sizes = [0; NUM_CLASSES];
+    let mut i = 0;
+    let mut size = MIN_SLOT_SIZE;
+    while size <= 128 {
+        sizes[i] = size as u32;
+        i += 1;
+        size += 8;
+    }
+    let mut step = 32;
+    size = 128 + step;
+    while size <= LEAN_MAX_SMALL_OBJECT_SIZE {
+        sizes[i] = size as u32;
+        i += 1;
+        if size == 8 * step {
+            step *= 2;
+        }
+        size += step;
+    }
+    assert!(i == NUM_CLASSES);
+    sizes
+}
+
+const fn class_table() -> [u8; LEAN_MAX_SMALL_OBJECT_SIZE / 8 + 1] {
+    let mut table = [0; LEAN_MAX_SMALL_OBJECT_SIZE / 8 + 1];
+    let mut class = 0;
+    let mut words = 0;
+    while words < table.len() {
+        while (CLASS_SIZES[class] as usize) < words * 8 {
+            class += 1;
+        }
+        table[words] = class as u8;
+        words += 1;
+    }
+    table
+}
+
+#[inline(always)]
+fn size_class(size: usize) -> usize {
+    CLASS_OF[size.div_ceil(8)] as usize
+}
+
+/// Page header. Fields other than the atomics belong to the owning thread.
+#[repr(C)]
+struct Page {
+    /// Owning heap; null while the page is in the pool
+    owner: AtomicPtr<Heap>,
+    /// Slots freed by other threads, linked like `free`
+    remote: AtomicPtr<u8>,
+    /// Whether the page is on some heap's `delayed` list
+    queued: AtomicBool,
+    /// Link of the `delayed` list
+    next_delayed: AtomicPtr<Page>,
+    /// Slots freed by the owner
+    free: *mut u8,
+    /// Next slot that was never used, and the end of the last one
+    bump: *mut u8,
+    end: *mut u8,
+    class: u32,
+    slot_size: u32,
+    /// Slots allocated and not yet returned to `free`
+    used: u32,
+    /// Whether the page is on its class's `available` list
+    listed: bool,
+    prev: *mut Page,
+    next: *mut Page,
+}
+
+/// Offset of the first slot.
+const PAGE_HEADER_SIZE: usize = std::mem::size_of::<Page>().next_multiple_of(16);
+
+const PAGE_LAYOUT: Layout = match Layout::from_size_align(LEAN_PAGE_SIZE, LEAN_PAGE_SIZE) {
+    Ok(layout) => layout,
+    Err(_) => panic!("invalid page layout"),
+};
+
+/// Free list link, stored after the header word of a free slot.
+#[inline(always)]
+unsafe fn next_free(slot: *mut u8) -> *mut u8 {
+    *(slot.add(8) as *mut *mut u8)
+}
+
+#[inline(always)]
+unsafe fn set_next_free(slot: *mut u8, next: *mut u8) {
+    *(slot.add(8) as *mut *mut u8) = next;
+}
+
+#[inline(always)]
+fn page_of(ptr: *mut u8) -> *mut Page {
+    (ptr as usize & !(LEAN_PAGE_SIZE - 1)) as *mut Page
+}
+
+impl Page {
+    /// Take a free slot, or return null if the page is full.
+    #[inline(always)]
+    unsafe fn take(&mut self) -> *mut u8 {
+        let slot = if !self.free.is_null() {
+            let slot = self.free;
+            self.free = next_free(slot);
+            slot
+        } else if self.bump < self.end {
+            let slot = self.bump;
+            self.bump = slot.add(self.slot_size as usize);
+            slot
+        } else {
+            return null_mut();
+        };
+        self.used += 1;
+        slot
+    }
+
+    /// Queue the page on its owner's `delayed` list, unless it is already
+    /// queued on some heap.
+    unsafe fn notify_owner(page: *mut Page) {
+        let owner = (*page).owner.load(Ordering::Acquire);
+        if owner.is_null() || (*page).queued.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        let delayed = &(*owner).delayed;
+        let mut head = delayed.load(Ordering::Relaxed);
+        loop {
+            (*page).next_delayed.store(head, Ordering::Relaxed);
+            match delayed.compare_exchange_weak(head, page, Ordering::Release, Ordering::Relaxed) {
+                Ok(_) => return,
+                Err(h) => head = h,
+            }
+        }
+    }
+}
+
+/// Pages of one size class owned by a heap.
+#[derive(Clone, Copy)]
+struct SizeClass {
+    /// Page allocations are served from
+    current: *mut Page,
+    /// Other pages with free slots, doubly linked through `prev`/`next`
+    available: *mut Page,
+}
+
+/// A thread's small-object heap.
+struct Heap {
+    classes: [SizeClass; NUM_CLASSES],
+    /// Pages with slots freed by other threads, linked by `next_delayed`
+    delayed: AtomicPtr<Page>,
+    allocs: u64,
+    frees: u64,
+    remote_frees: u64,
+}
+
+impl Heap {
+    fn new() -> Self {
+        Heap {
+            classes: [SizeClass {
+                current: null_mut(),
+                available: null_mut(),
+            }; NUM_CLASSES],
+            delayed: AtomicPtr::new(null_mut()),
+            allocs: 0,
+            frees: 0,
+            remote_frees: 0,
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn alloc(&mut self, class: usize) -> *mut u8 {
+        self.allocs += 1;
+        let page = self.classes[class].current;
+        if !page.is_null() {
+            let slot = (*page).take();
+            if !slot.is_null() {
+                return slot;
+            }
+        }
+        self.alloc_slow(class)
+    }
+
+    /// The current page is full: take back remote frees, then move on to the
+    /// next available page or a new one.
+    #[cold]
+    #[inline(never)]
+    unsafe fn alloc_slow(&mut self, class: usize) -> *mut u8 {
+        self.collect_delayed();
+        loop {
+            let page = self.classes[class].current;
+            if !page.is_null() {
+                let slot = (*page).take();
+                if !slot.is_null() {
+                    return slot;
+                }
+            }
+            let next = self.classes[class].available;
+            self.classes[class].current = if next.is_null() {
+                self.new_page(class)
+            } else {
+                self.unlist(next);
+                next
+            };
+        }
+    }
+
+    #[inline(always)]
+    unsafe fn free_local(&mut self, page: *mut Page, slot: *mut u8) {
+        let p = &mut *page;
+        set_next_free(slot, p.free);
+        p.free = slot;
+        p.used -= 1;
+        if page == self.classes[p.class as usize].current {
+            return;
+        }
+        if p.used == 0 {
+            if p.listed {
+                self.unlist(page);
+            }
+            release_page(page);
+        } else if !p.listed {
+            self.list(page);
+        }
+    }
+
+    /// Return the slots other threads freed in pages of this heap.
+    unsafe fn collect_delayed(&mut self) {
+        let me = self as *mut Heap;
+        let mut page = self.delayed.swap(null_mut(), Ordering::Acquire);
+        while !page.is_null() {
+            // Read the link before the page can be queued again
+            let next = (*page).next_delayed.load(Ordering::Relaxed);
+            (*page).queued.store(false, Ordering::Release);
+            if (*page).owner.load(Ordering::Acquire) == me {
+                let mut slot = (*page).remote.swap(null_mut(), Ordering::Acquire);
+                while !slot.is_null() {
+                    let next_slot = next_free(slot);
+                    self.free_local(page, slot);
+                    slot = next_slot;
+                }
+            } else if !(*page).remote.load(Ordering::Acquire).is_null() {
+                // Queued here before the page changed hands
+                Page::notify_owner(page);
+            }
+            page = next;
+        }
+    }
+
+    unsafe fn list(&mut self, page: *mut Page) {
+        let class = &mut self.classes[(*page).class as usize];
+        (*page).prev = null_mut();
+        (*page).next = class.available;
+        if !class.available.is_null() {
+            (*class.available).prev = page;
+        }
+        class.available = page;
+        (*page).listed = true;
+    }
+
+    unsafe fn unlist(&mut self, page: *mut Page) {
+        let p = &mut *page;
+        if p.prev.is_null() {
+            self.classes[p.class as usize].available = p.next;
+        } else {
+            (*p.prev).next = p.next;
+        }
+        if !p.next.is_null() {
+            (*p.next).prev = p.prev;
+        }
+        p.listed = false;
+    }
+
+    /// A page for `class` owned by this heap, from the pool or the system.
+    unsafe fn new_page(&mut self, class: usize) -> *mut Page {
+        let pooled = lock_pool().free.pop();
+        let page = match pooled {
+            Some(page) => page as *mut Page,
+            None => {
+                let page = alloc(PAGE_LAYOUT) as *mut Page;
+                if page.is_null() {
+                    handle_alloc_error(PAGE_LAYOUT);
+                }
+                page.write(Page {
+                    owner: AtomicPtr::new(null_mut()),
+                    remote: AtomicPtr::new(null_mut()),
+                    queued: AtomicBool::new(false),
+                    next_delayed: AtomicPtr::new(null_mut()),
+                    free: null_mut(),
+                    bump: null_mut(),
+                    end: null_mut(),
+                    class: 0,
+                    slot_size: 0,
+                    used: 0,
+                    listed: false,
+                    prev: null_mut(),
+                    next: null_mut(),
+                });
+                PAGES.fetch_add(1, Ordering::Relaxed);
+                page
+            }
+        };
+        // A pooled page may still be queued on its previous owner, so the
+        // atomics are left alone
+        let p = &mut *page;
+        let slot_size = CLASS_SIZES[class] as usize;
+        let first = (page as *mut u8).add(PAGE_HEADER_SIZE);
+        let num_slots = (LEAN_PAGE_SIZE - PAGE_HEADER_SIZE) / slot_size;
+        p.free = null_mut();
+        p.bump = first;
+        p.end = first.add(num_slots * slot_size);
+        p.class = class as u32;
+        p.slot_size = slot_size as u32;
+        p.used = 0;
+        p.listed = false;
+        p.owner.store(self, Ordering::Release);
+        page
+    }
+}
+
+/// Return an empty page to the pool. Pages are never given back to the
+/// system: another thread may still be about to queue the page on a heap.
+unsafe fn release_page(page: *mut Page) {
+    (*page).owner.store(null_mut(), Ordering::Release);
+    lock_pool().free.push(page as usize);
+}
+
+/// Free an object allocated from another thread's heap.
+#[cold]
+#[inline(never)]
+unsafe fn free_remote(heap: *mut Heap, page: *mut Page, slot: *mut u8) {
+    if !heap.is_null() {
+        (*heap).frees += 1;
+        (*heap).remote_frees += 1;
+    }
+    let remote = &(*page).remote;
+    let mut head = remote.load(Ordering::Relaxed);
+    loop {
+        set_next_free(slot, head);
+        match remote.compare_exchange_weak(head, slot, Ordering::Release, Ordering::Relaxed) {
+            Ok(_) => break,
+            Err(h) => head = h,
+        }
+    }
+    if head.is_null() {
+        Page::notify_owner(page);
+    }
+}
+
+/// Pages shared between threads. Addresses are stored as `usize` to keep
+/// the pool `Send`.
+struct Pool {
+    free: Vec<usize>,
+    /// Heaps of exited threads
+    orphans: Vec<usize>,
+}
+
+static POOL: Mutex<Pool> = Mutex::new(Pool {
+    free: Vec::new(),
+    orphans: Vec::new(),
+});
+
+fn lock_pool() -> std::sync::MutexGuard<'static, Pool> {
+    POOL.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Pages obtained from the system.
+static PAGES: AtomicUsize = AtomicUsize::new(0);
+
+/// Objects served by the system allocator and not yet freed.
+static LARGE_OBJECTS: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    static HEAP: Cell<*mut Heap> = const { Cell::new(null_mut()) };
+    static HEAP_GUARD: HeapGuard = const { HeapGuard };
+}
+
+/// Orphans the thread's heap when the thread exits.
+struct HeapGuard;
+
+impl Drop for HeapGuard {
+    fn drop(&mut self) {
+        let heap = HEAP.with(|h| h.replace(null_mut()));
+        if !heap.is_null() {
+            lock_pool().orphans.push(heap as usize);
+        }
+    }
+}
+
+#[inline(always)]
+fn current_heap() -> *mut Heap {
+    let heap = HEAP.with(|h| h.get());
+    if heap.is_null() {
+        init_heap()
+    } else {
+        heap
+    }
+}
+
+#[cold]
+#[inline(never)]
+fn init_heap() -> *mut Heap {
+    // If the thread is already exiting the guard cannot be registered, and
+    // a fresh heap is leaked rather than orphaning one that is never freed
+    let adopted = if HEAP_GUARD.try_with(|_| ()).is_ok() {
+        lock_pool().orphans.pop()
+    } else {
+        None
+    };
+    let heap = match adopted {
+        Some(heap) => {
+            let heap = heap as *mut Heap;
+            unsafe {
+                (*heap).allocs = 0;
+                (*heap).frees = 0;
+                (*heap).remote_frees = 0;
+            }
+            heap
+        }
+        None => Box::into_raw(Box::new(Heap::new())),
+    };
+    HEAP.with(|h| h.set(heap));
+    heap
+}
+
+#[cold]
+unsafe fn alloc_large(size: usize) -> *mut u8 {
+    let layout = Layout::from_size_align_unchecked(size, 8);
+    let ptr = alloc(layout);
+    if ptr.is_null() {
+        handle_alloc_error(layout);
+    }
+    LARGE_OBJECTS.fetch_add(1, Ordering::Relaxed);
+    ptr
+}
+
+#[cold]
+unsafe fn free_large(ptr: *mut u8, size: usize) {
+    LARGE_OBJECTS.fetch_sub(1, Ordering::Relaxed);
+    dealloc(ptr, Layout::from_size_align_unchecked(size, 8));
+}
+
+/// Allocate `size` bytes, 8-byte aligned.
+#[inline]
+pub unsafe fn lean_heap_alloc(size: usize) -> *mut u8 {
+    if cfg!(feature = "system-alloc") || size > LEAN_MAX_SMALL_OBJECT_SIZE {
+        return alloc_large(size);
+    }
+    (*current_heap()).alloc(size_class(size))
+}
+
+/// Free memory from [`lean_heap_alloc`]; `size` must be the allocated size.
+#[inline]
+pub unsafe fn lean_heap_free(ptr: *mut u8, size: usize) {
+    if cfg!(feature = "system-alloc") || size > LEAN_MAX_SMALL_OBJECT_SIZE {
+        return free_large(ptr, size);
+    }
+    let page = page_of(ptr);
+    let heap = HEAP.with(|h| h.get());
+    if (*page).owner.load(Ordering::Relaxed) == heap {
+        (*heap).frees += 1;
+        (*heap).free_local(page, ptr);
+    } else {
+        free_remote(heap, page, ptr);
+    }
+}
+
+/// Allocator statistics. Object counts are for the current thread, page
+/// counts for the whole process.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HeapStats {
+    /// Small objects allocated by this thread
+    pub small_allocs: u64,
+    /// Small objects freed by this thread, wherever they were allocated
+    pub small_frees: u64,
+    /// Frees of objects from other threads' heaps, included in `small_frees`
+    pub remote_frees: u64,
+    /// Live objects served by the system allocator
+    pub large_objects: usize,
+    /// Pages obtained from the system allocator
+    pub pages: usize,
+    /// Pages in the pool, ready to be reused by any size class
+    pub free_pages: usize,
+}
+
+/// Statistics of the allocator.
+pub fn lean_heap_stats() -> HeapStats {
+    let mut stats = HeapStats {
+        large_objects: LARGE_OBJECTS.load(Ordering::Relaxed),
+        pages: PAGES.load(Ordering::Relaxed),
+        free_pages: lock_pool().free.len(),
+        ..Default::default()
+    };
+    let heap = HEAP.with(|h| h.get());
+    if !heap.is_null() {
+        unsafe {
+            stats.small_allocs = (*heap).allocs;
+            stats.small_frees = (*heap).frees;
+            stats.remote_frees = (*heap).remote_frees;
+        }
+    }
+    stats
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A heap not registered with any thread.
+    fn detached_heap() -> *mut Heap {
+        Box::into_raw(Box::new(Heap::new()))
+    }
+
+    #[test]
+    fn size_classes() {
+        for size in 1..=LEAN_MAX_SMALL_OBJECT_SIZE {
+            let slot = CLASS_SIZES[size_class(size)] as usize;
+            let size = size.max(MIN_SLOT_SIZE);
+            assert!(slot >= size, "size {}", size);
+            assert!(slot.is_multiple_of(8));
+            assert!(
+                slot - size < 8 || (slot - size) * 5 <= slot,
+                "size {}",
+                size
+            );
+        }
+        assert!(CLASS_SIZES.windows(2).all(|w| w[0] < w[1]));
+        assert_eq!(
+            CLASS_SIZES[NUM_CLASSES - 1] as usize,
+            LEAN_MAX_SMALL_OBJECT_SIZE
+        );
+    }
+
+    #[test]
+    fn reuses_freed_slots() {
+        unsafe {
+            let heap = &mut *detached_heap();
+            let class = size_class(24);
+            let a = heap.alloc(class);
+            let b = heap.alloc(class);
+            assert_eq!(b as usize - a as usize, 24);
+            (a as *mut u64).write(0xdead_beef);
+            heap.free_local(page_of(a), a);
+            // The header word survives on the free list
+            assert_eq!((a as *mut u64).read(), 0xdead_beef);
+            assert_eq!(heap.alloc(class), a);
+        }
+    }
+
+    #[test]
+    fn empty_pages_leave_the_heap() {
+        unsafe {
+            let heap = &mut *detached_heap();
+            let class = size_class(4096);
+            let per_page = (LEAN_PAGE_SIZE - PAGE_HEADER_SIZE) / 4096;
+            let objs: Vec<_> = (0..per_page * 3).map(|_| heap.alloc(class)).collect();
+            let pages: Vec<_> = objs.iter().map(|&o| page_of(o)).collect();
+            assert_eq!(pages[0], pages[per_page - 1]);
+            assert_ne!(pages[0], pages[per_page]);
+            let current = heap.classes[class].current;
+            assert_eq!(current, pages[per_page * 2]);
+
+            // Partly freed pages become available, empty ones are released
+            heap.free_local(pages[0], objs[0]);
+            assert_eq!(heap.classes[class].available, pages[0]);
+            for &o in &objs[1..per_page] {
+                heap.free_local(pages[0], o);
+            }
+            assert!(heap.classes[class].available.is_null());
+            for &o in &objs[per_page..] {
+                heap.free_local(page_of(o), o);
+            }
+            // The current page stays
+            assert_eq!(heap.classes[class].current, current);
+            assert_eq!((*current).used, 0);
+        }
+    }
+
+    #[test]
+    #[cfg(not(feature = "system-alloc"))]
+    fn remote_frees_return_to_the_owner() {
+        unsafe {
+            let heap = &mut *detached_heap();
+            let class = size_class(64);
+            let per_page = (LEAN_PAGE_SIZE - PAGE_HEADER_SIZE) / 64;
+            let objs: Vec<usize> = (0..per_page * 2)
+                .map(|_| heap.alloc(class) as usize)
+                .collect();
+            let current = heap.classes[class].current;
+            assert_eq!((*current).used as usize, per_page);
+
+            std::thread::spawn(move || {
+                for &o in &objs {
+                    lean_heap_free(o as *mut u8, 64);
+                }
+                let stats = lean_heap_stats();
+                assert_eq!(stats.small_frees, 0, "no heap on this thread");
+            })
+            .join()
+            .unwrap();
+
+            assert!(!heap.delayed.load(Ordering::Relaxed).is_null());
+            // The full current page forces the owner to take them back
+            let o = heap.alloc(class);
+            assert!(heap.delayed.load(Ordering::Relaxed).is_null());
+            assert_eq!(heap.classes[class].current, current);
+            assert_eq!(page_of(o), current);
+            assert_eq!((*current).used, 1);
+        }
+    }
+
+    #[test]
+    fn thread_stats() {
+        unsafe {
+            let before = lean_heap_stats();
+            let objs: Vec<_> = (0..10).map(|i| lean_heap_alloc(8 + i * 40)).collect();
+            let big = lean_heap_alloc(LEAN_MAX_SMALL_OBJECT_SIZE + 1);
+            let mid = lean_heap_stats();
+            for (i, &o) in objs.iter().enumerate() {
+                lean_heap_free(o, 8 + i * 40);
+            }
+            lean_heap_free(big, LEAN_MAX_SMALL_OBJECT_SIZE + 1);
+            let after = lean_heap_stats();
+            if cfg!(feature = "system-alloc") {
+                assert_eq!(after.small_allocs, before.small_allocs);
+            } else {
+                assert_eq!(mid.small_allocs - before.small_allocs, 10);
+                assert_eq!(after.small_frees - before.small_frees, 10);
+                assert_eq!(after.remote_frees, before.remote_frees);
+                assert!(after.pages >= 1);
+            }
+        }
+    }
+}
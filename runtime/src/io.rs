@@ -7,6 +7,12 @@ use crate::r#box::*;
 #[allow(unused_imports)]
 use crate::string::*;
 
+/// The world token passed to `IO` actions.
+#[inline(always)]
+pub fn lean_io_mk_world() -> *mut LeanObject {
+    lean_box(0)
+}
+
 #[inline]
 pub unsafe fn lean_io_result_mk_ok(value: *mut LeanObject) -> *mut LeanObject {
     let obj = crate::lean_alloc_ctor(0, 2, 0);
@@ -33,6 +39,11 @@ pub unsafe fn lean_io_result_get_value(r: *mut LeanObject) -> *mut LeanObject {
     crate::lean_ctor_get(r, 0)
 }
 
+#[inline]
+pub unsafe fn lean_io_result_get_error(r: *mut LeanObject) -> *mut LeanObject {
+    crate::lean_ctor_get(r, 0)
+}
+
 pub unsafe fn lean_io_prim_println(s: *mut LeanObject, _rw: *mut LeanObject) -> *mut LeanObject {
     println!("{}", lean_string_to_str(s));
     crate::lean_dec(s);
@@ -93,7 +104,10 @@ pub unsafe fn lean_io_getenv(name: *mut LeanObject, _rw: *mut LeanObject) -> *mu
     lean_io_result_mk_ok(result)
 }
 
-unsafe fn stdout_write_impl(s: *mut LeanObject, _rw: *mut LeanObject) -> *mut LeanObject {
+unsafe extern "C" fn stdout_write_impl(
+    s: *mut LeanObject,
+    _rw: *mut LeanObject,
+) -> *mut LeanObject {
     print!("{}", lean_string_to_str(s));
     crate::lean_dec(s);
     lean_io_result_mk_ok(lean_box(0))
@@ -152,21 +166,19 @@ unsafe fn mk_io_error_2obj(
     field1: *mut LeanObject,
     errno: u32,
 ) -> *mut LeanObject {
-    let ptr_sz = std::mem::size_of::<*mut LeanObject>() as u32;
     let err = crate::lean_alloc_ctor(tag, 2, 4);
     crate::lean_ctor_set(err, 0, field0);
     crate::lean_ctor_set(err, 1, field1);
-    crate::lean_ctor_set_uint32(err, 2 * ptr_sz, errno);
+    crate::lean_ctor_set_uint32(err, 0, errno);
     err
 }
 
 /// Helper: build a 1-obj-field + UInt32-scalar error.
 #[inline]
 unsafe fn mk_io_error_1obj(tag: u32, details: *mut LeanObject, errno: u32) -> *mut LeanObject {
-    let ptr_sz = std::mem::size_of::<*mut LeanObject>() as u32;
     let err = crate::lean_alloc_ctor(tag, 1, 4);
     crate::lean_ctor_set(err, 0, details);
-    crate::lean_ctor_set_uint32(err, ptr_sz, errno);
+    crate::lean_ctor_set_uint32(err, 0, errno);
     err
 }
 
@@ -391,6 +403,80 @@ pub unsafe fn lean_mk_io_error_eof(_unit: *mut LeanObject) -> *mut LeanObject {
     lean_box(17)
 }
 
+/// Render an IO.Error like `IO.Error.toString`, which compiled code exports
+/// under this name. Takes ownership of `err`.
+pub unsafe fn lean_io_error_to_string(err: *mut LeanObject) -> *mut LeanObject {
+    let s = io_error_message(err);
+    crate::lean_dec(err);
+    lean_mk_string(&s)
+}
+
+fn down_case_first(s: &str) -> String {
+    let mut chars = s.chars();
+    match chars.next() {
+        Some(c) => c.to_lowercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// `IO.Error.otherErrorToString` and `fopenErrorToString`
+fn format_io_error(gist: &str, file: Option<&str>, code: u32, details: Option<&str>) -> String {
+    let mut s = format!("{} (error code: {}", down_case_first(gist), code);
+    if let Some(details) = details {
+        s.push_str(", ");
+        s.push_str(&down_case_first(details));
+    }
+    s.push(')');
+    if let Some(file) = file {
+        s.push_str("\n  file: ");
+        s.push_str(file);
+    }
+    s
+}
+
+unsafe fn io_error_message(err: *mut LeanObject) -> String {
+    if lean_is_scalar(err) {
+        // unexpectedEof, the only nullary constructor
+        return "end of file".to_string();
+    }
+    let tag = crate::lean_obj_tag(err);
+    if tag == 18 {
+        return lean_string_to_str(crate::lean_ctor_get(err, 0)).to_string();
+    }
+    let code = crate::lean_ctor_get_uint32(err, 0);
+    let num_objs = (*err).num_objs();
+    let details = lean_string_to_str(crate::lean_ctor_get(err, num_objs - 1));
+    // The filename field: `String` for tags 10 and 11, else `Option String`
+    let file = if num_objs < 2 {
+        None
+    } else if tag == 10 || tag == 11 {
+        Some(lean_string_to_str(crate::lean_ctor_get(err, 0)))
+    } else {
+        let opt = crate::lean_ctor_get(err, 0);
+        (!lean_is_scalar(opt)).then(|| lean_string_to_str(crate::lean_ctor_get(opt, 0)))
+    };
+    let (gist, details) = match tag {
+        0 => ("already exists", Some(details)),
+        1 => (details, None),
+        2 => ("resource busy", Some(details)),
+        3 => ("resource vanished", Some(details)),
+        4 => ("unsupported operation", Some(details)),
+        5 => ("hardware fault", None),
+        6 => ("directory not empty", None),
+        7 => ("illegal operation", Some(details)),
+        8 => ("protocol error", Some(details)),
+        9 => ("time expired", Some(details)),
+        10 => ("interrupted system call", Some(details)),
+        11 => ("no such file or directory", None),
+        12 => ("invalid argument", Some(details)),
+        13 => (details, None),
+        14 => ("resource exhausted", Some(details)),
+        15 => ("inappropriate type", Some(details)),
+        _ => ("no such thing", Some(details)),
+    };
+    format_io_error(gist, file, code, details)
+}
+
 // ---------------------------------------------------------------------------
 // decode_io_error: convert errno to IO.Error
 // ---------------------------------------------------------------------------
@@ -493,6 +579,32 @@ pub unsafe fn lean_decode_io_error(errno: i32, fname: *mut LeanObject) -> *mut L
     }
 }
 
+/// Convert a `std::io::Error` into an IO.Error, decoding the OS error code when
+/// there is one. Errors without one become `userError`, except `Unsupported`,
+/// which is `unsupportedOperation`. Takes ownership of `fname` (`lean_box(0)`
+/// for none).
+pub unsafe fn lean_io_error_from_std(
+    e: &std::io::Error,
+    fname: *mut LeanObject,
+) -> *mut LeanObject {
+    match e.raw_os_error() {
+        Some(errno) => {
+            let err = lean_decode_io_error(errno, fname);
+            crate::lean_dec(fname);
+            err
+        }
+        None => {
+            crate::lean_dec(fname);
+            let msg = lean_mk_string(&e.to_string());
+            if e.kind() == std::io::ErrorKind::Unsupported {
+                lean_mk_io_error_unsupported_operation(0, msg)
+            } else {
+                lean_mk_io_user_error(msg)
+            }
+        }
+    }
+}
+
 // ---------------------------------------------------------------------------
 // stdin / stderr handles
 // ---------------------------------------------------------------------------
@@ -565,43 +677,103 @@ pub unsafe fn lean_io_get_random_bytes(
 }
 
 // ---------------------------------------------------------------------------
-// Timeit / allocprof stubs
+// Timeit / allocprof
 // ---------------------------------------------------------------------------
 
-/// Time a thunk and print the elapsed time. In this runtime, just run the thunk.
+/// Format like C++ streams with `setprecision(3)` (i.e. `%.3g`).
+fn fmt_sig3(x: f64) -> String {
+    if x == 0.0 || !x.is_finite() {
+        return format!("{}", x);
+    }
+    // Round first so that e.g. 999.9 picks the exponent of 1.00e3.
+    let sci = format!("{:.2e}", x);
+    let (mantissa, exp) = sci.split_once('e').unwrap();
+    let exp: i32 = exp.parse().unwrap();
+    let strip = |s: String| {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_string()
+        } else {
+            s
+        }
+    };
+    if !(-4..3).contains(&exp) {
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{}e{}{:02}", strip(mantissa.to_string()), sign, exp.abs())
+    } else {
+        strip(format!("{:.*}", (2 - exp) as usize, x))
+    }
+}
+
+/// Run an IO action and print `msg` followed by the elapsed time to stderr,
+/// in milliseconds below one second and in seconds otherwise.
 pub unsafe fn lean_io_timeit(
     msg: *mut LeanObject,
     thunk: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
+    let start = std::time::Instant::now();
+    let r = crate::lean_apply_1(thunk, lean_box(0));
+    let secs = start.elapsed().as_secs_f64();
+    let time = if secs < 1.0 {
+        format!("{}ms", fmt_sig3(secs * 1000.0))
+    } else {
+        format!("{}s", fmt_sig3(secs))
+    };
+    eprintln!("{} {}", lean_string_to_str(msg), time);
     crate::lean_dec(msg);
-    crate::lean_apply_1(thunk, lean_box(0))
+    r
 }
 
-/// Profile allocations in a thunk. In this runtime, just run the thunk.
+/// Run an IO action and print `msg` followed by the number and size of the
+/// objects it allocated, by object kind, to stderr.
 pub unsafe fn lean_io_allocprof(
     msg: *mut LeanObject,
     thunk: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
+    let start = crate::lean_alloc_prof_begin();
+    let r = crate::lean_apply_1(thunk, lean_box(0));
+    let stats = crate::lean_alloc_prof_end(start);
+    let mut out = format!("{}\n", lean_string_to_str(msg));
+    for kind in crate::AllocKind::ALL {
+        let (num, bytes) = stats.get(kind);
+        if num > 0 {
+            out += &format!(
+                "  {:<8} {:>10} objects {:>12} bytes\n",
+                kind.name(),
+                num,
+                bytes
+            );
+        }
+    }
+    let (num, bytes) = stats.total();
+    out += &format!("  {:<8} {:>10} objects {:>12} bytes", "total", num, bytes);
+    eprintln!("{}", out);
     crate::lean_dec(msg);
-    crate::lean_apply_1(thunk, lean_box(0))
+    r
 }
 
 // ---------------------------------------------------------------------------
-// Heartbeat stubs
+// Heartbeats
 // ---------------------------------------------------------------------------
 
-/// Get the number of heartbeats consumed so far.
+/// Get the number of heartbeats (small allocations) on this thread.
 pub unsafe fn lean_io_get_num_heartbeats(_unit: *mut LeanObject) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(0))
+    lean_io_result_mk_ok(crate::lean_uint64_to_nat(crate::lean_get_num_heartbeats()))
 }
 
-/// Set the heartbeat counter.
+/// Set this thread's heartbeat counter.
 pub unsafe fn lean_io_set_heartbeats(
-    _n: *mut LeanObject,
+    n: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
+    let count = if lean_is_scalar(n) {
+        lean_unbox(n) as u64
+    } else {
+        u64::MAX
+    };
+    crate::lean_dec(n);
+    crate::lean_set_heartbeats(count);
     lean_io_result_mk_ok(lean_box(0))
 }
 
@@ -618,6 +790,120 @@ pub unsafe fn lean_io_get_tid(_unit: *mut LeanObject) -> *mut LeanObject {
 // File handle operations
 // ---------------------------------------------------------------------------
 
+/// The stream behind a handle object.
+pub enum HandleStream {
+    File(std::fs::File),
+    ChildStdin(std::process::ChildStdin),
+    ChildStdout(std::process::ChildStdout),
+    ChildStderr(std::process::ChildStderr),
+}
+
+impl std::io::Read for HandleStream {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        match self {
+            HandleStream::File(f) => f.read(buf),
+            HandleStream::ChildStdout(p) => p.read(buf),
+            HandleStream::ChildStderr(p) => p.read(buf),
+            HandleStream::ChildStdin(_) => Err(std::io::ErrorKind::Unsupported.into()),
+        }
+    }
+}
+
+impl std::io::Write for HandleStream {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            HandleStream::File(f) => f.write(buf),
+            HandleStream::ChildStdin(p) => p.write(buf),
+            _ => Err(std::io::ErrorKind::Unsupported.into()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            HandleStream::File(f) => f.flush(),
+            HandleStream::ChildStdin(p) => p.flush(),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Data of a handle object (an external object). Reads go through a buffer so
+/// that `getLine` does not lose bytes read past the newline.
+pub struct LeanHandle {
+    pub stream: HandleStream,
+    rbuf: Vec<u8>,
+}
+
+impl LeanHandle {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        use std::io::Read;
+        if self.rbuf.is_empty() {
+            return self.stream.read(buf);
+        }
+        let n = buf.len().min(self.rbuf.len());
+        buf[..n].copy_from_slice(&self.rbuf[..n]);
+        self.rbuf.drain(..n);
+        Ok(n)
+    }
+
+    fn read_line(&mut self, line: &mut Vec<u8>) -> std::io::Result<()> {
+        use std::io::Read;
+        loop {
+            if let Some(i) = self.rbuf.iter().position(|&b| b == b'\n') {
+                line.extend(self.rbuf.drain(..=i));
+                return Ok(());
+            }
+            line.append(&mut self.rbuf);
+            let mut chunk = [0u8; 4096];
+            let n = self.stream.read(&mut chunk)?;
+            if n == 0 {
+                return Ok(());
+            }
+            self.rbuf.extend_from_slice(&chunk[..n]);
+        }
+    }
+
+    fn write_all(&mut self, bytes: &[u8]) -> std::io::Result<()> {
+        use std::io::{Seek, SeekFrom, Write};
+        // Give back read-ahead so the write lands at the logical position.
+        if !self.rbuf.is_empty() {
+            if let HandleStream::File(f) = &mut self.stream {
+                f.seek(SeekFrom::Current(-(self.rbuf.len() as i64)))?;
+            }
+            self.rbuf.clear();
+        }
+        self.stream.write_all(bytes)
+    }
+}
+
+static HANDLE_CLASS: crate::LeanExternalClass = crate::LeanExternalClass {
+    finalize: Some(handle_finalize),
+    foreach: None,
+};
+
+unsafe extern "C" fn handle_finalize(data: *mut std::ffi::c_void) {
+    drop(Box::from_raw(data as *mut std::sync::Mutex<LeanHandle>));
+}
+
+/// Wrap a stream in a new handle object.
+pub unsafe fn lean_io_mk_handle(stream: HandleStream) -> *mut LeanObject {
+    let h = std::sync::Mutex::new(LeanHandle {
+        stream,
+        rbuf: Vec::new(),
+    });
+    let cls = &HANDLE_CLASS as *const crate::LeanExternalClass as *mut crate::LeanExternalClass;
+    crate::lean_alloc_external(cls, Box::into_raw(Box::new(h)) as *mut std::ffi::c_void)
+}
+
+/// Run `f` with exclusive access to the handle object `h`.
+pub unsafe fn lean_io_with_handle<R>(
+    h: *mut LeanObject,
+    f: impl FnOnce(&mut LeanHandle) -> R,
+) -> R {
+    let m = &*(crate::lean_get_external_data(h) as *const std::sync::Mutex<LeanHandle>);
+    f(&mut m.lock().unwrap_or_else(|e| e.into_inner()))
+}
+
 /// Open a file handle.
 /// mode: 0=read, 1=write, 2=readWrite, 3=append
 pub unsafe fn lean_io_prim_handle_mk(
@@ -627,7 +913,6 @@ pub unsafe fn lean_io_prim_handle_mk(
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
     let name = lean_string_to_str(fname).to_string();
-    crate::lean_dec(fname);
     use std::fs::{File, OpenOptions};
     let result = match mode {
         0 => File::open(&name),
@@ -638,27 +923,30 @@ pub unsafe fn lean_io_prim_handle_mk(
     };
     match result {
         Ok(file) => {
-            let handle = Box::into_raw(Box::new(file)) as usize;
-            lean_io_result_mk_ok(lean_box(handle))
-        }
-        Err(e) => {
-            let msg = lean_mk_string(&e.to_string());
-            lean_io_result_mk_error(lean_mk_io_user_error(msg))
+            crate::lean_dec(fname);
+            lean_io_result_mk_ok(lean_io_mk_handle(HandleStream::File(file)))
         }
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, fname)),
     }
 }
 
-/// Flush a file handle. For scalar handles (stdin=0, stdout=1, stderr=2), flush stdio.
+/// Flush a handle. For scalar handles (stdin=0, stdout=1, stderr=2), flush stdio.
 pub unsafe fn lean_io_prim_handle_flush(
     h: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    if lean_is_scalar(h) {
-        use std::io::Write;
-        let _ = std::io::stdout().flush();
-        let _ = std::io::stderr().flush();
+    use std::io::Write;
+    let r = if lean_is_scalar(h) {
+        std::io::stdout()
+            .flush()
+            .and_then(|_| std::io::stderr().flush())
+    } else {
+        lean_io_with_handle(h, |hd| hd.stream.flush())
+    };
+    match r {
+        Ok(()) => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
     }
-    lean_io_result_mk_ok(lean_box(0))
 }
 
 /// Read up to `nbytes` bytes from a handle into a ByteArray.
@@ -669,26 +957,28 @@ pub unsafe fn lean_io_prim_handle_read(
 ) -> *mut LeanObject {
     let n = crate::lean_unbox(nbytes);
     let arr = crate::sarray::lean_alloc_sarray(1, 0, n);
-    let data = crate::sarray::lean_sarray_data(arr);
+    let buf = std::slice::from_raw_parts_mut(crate::sarray::lean_sarray_data(arr), n);
 
     use std::io::Read;
-    let bytes_read = if lean_is_scalar(h) {
-        let handle_id = lean_unbox(h);
-        if handle_id == 0 {
-            std::io::stdin()
-                .read(std::slice::from_raw_parts_mut(data, n))
-                .unwrap_or(0)
+    let r = if lean_is_scalar(h) {
+        if lean_unbox(h) == 0 {
+            std::io::stdin().read(buf)
         } else {
-            0
+            Ok(0)
         }
     } else {
-        let file = &mut *(lean_unbox(h) as *mut std::fs::File);
-        file.read(std::slice::from_raw_parts_mut(data, n))
-            .unwrap_or(0)
+        lean_io_with_handle(h, |hd| hd.read(buf))
     };
-
-    (*(arr as *mut crate::sarray::LeanSArray)).size = bytes_read;
-    lean_io_result_mk_ok(arr)
+    match r {
+        Ok(bytes_read) => {
+            (*(arr as *mut crate::sarray::LeanSArray)).size = bytes_read;
+            lean_io_result_mk_ok(arr)
+        }
+        Err(e) => {
+            crate::lean_dec(arr);
+            lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0)))
+        }
+    }
 }
 
 /// Write a ByteArray to a handle.
@@ -701,45 +991,50 @@ pub unsafe fn lean_io_prim_handle_write(
     let size = (*sarr).size;
     let data = crate::sarray::lean_sarray_data(buf);
     let bytes = std::slice::from_raw_parts(data, size);
+    let r = write_bytes(h, bytes);
+    crate::lean_dec(buf);
+    match r {
+        Ok(()) => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
+    }
+}
 
+unsafe fn write_bytes(h: *mut LeanObject, bytes: &[u8]) -> std::io::Result<()> {
     use std::io::Write;
     if lean_is_scalar(h) {
-        let handle_id = lean_unbox(h);
-        match handle_id {
-            1 => {
-                let _ = std::io::stdout().write_all(bytes);
-            }
-            2 => {
-                let _ = std::io::stderr().write_all(bytes);
-            }
-            _ => {}
+        match lean_unbox(h) {
+            0 | 1 => std::io::stdout().write_all(bytes),
+            2 => std::io::stderr().write_all(bytes),
+            _ => Ok(()),
         }
     } else {
-        let file = &mut *(lean_unbox(h) as *mut std::fs::File);
-        let _ = file.write_all(bytes);
+        lean_io_with_handle(h, |hd| hd.write_all(bytes))
     }
-    crate::lean_dec(buf);
-    lean_io_result_mk_ok(lean_box(0))
 }
 
-/// Read a line from a handle (returns a Lean String).
+/// Read a line from a handle (returns a Lean String, including the newline).
 pub unsafe fn lean_io_prim_handle_get_line(
     h: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
     use std::io::BufRead;
-    let mut line = String::new();
-    if lean_is_scalar(h) {
-        let handle_id = lean_unbox(h);
-        if handle_id == 0 {
-            let _ = std::io::stdin().lock().read_line(&mut line);
+    let mut line = Vec::new();
+    let r = if lean_is_scalar(h) {
+        if lean_unbox(h) == 0 {
+            std::io::stdin()
+                .lock()
+                .read_until(b'\n', &mut line)
+                .map(|_| ())
+        } else {
+            Ok(())
         }
     } else {
-        let file = &mut *(lean_unbox(h) as *mut std::fs::File);
-        let mut buf_reader = std::io::BufReader::new(file);
-        let _ = buf_reader.read_line(&mut line);
+        lean_io_with_handle(h, |hd| hd.read_line(&mut line))
+    };
+    match r {
+        Ok(()) => lean_io_result_mk_ok(lean_mk_string(&String::from_utf8_lossy(&line))),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
     }
-    lean_io_result_mk_ok(lean_mk_string(&line))
 }
 
 /// Write a string to a handle.
@@ -748,25 +1043,12 @@ pub unsafe fn lean_io_prim_handle_put_str(
     s: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    let str_val = lean_string_to_str(s);
-    use std::io::Write;
-    if lean_is_scalar(h) {
-        let handle_id = lean_unbox(h);
-        match handle_id {
-            0 | 1 => {
-                let _ = std::io::stdout().write_all(str_val.as_bytes());
-            }
-            2 => {
-                let _ = std::io::stderr().write_all(str_val.as_bytes());
-            }
-            _ => {}
-        }
-    } else {
-        let file = &mut *(lean_unbox(h) as *mut std::fs::File);
-        let _ = file.write_all(str_val.as_bytes());
-    }
+    let r = write_bytes(h, lean_string_to_str(s).as_bytes());
     crate::lean_dec(s);
-    lean_io_result_mk_ok(lean_box(0))
+    match r {
+        Ok(()) => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
+    }
 }
 
 /// Check if a handle is a TTY (stub: always false).
@@ -793,31 +1075,99 @@ pub unsafe fn lean_io_prim_handle_truncate(
     lean_io_result_mk_ok(lean_box(0))
 }
 
-/// Lock a file handle (stub: always succeeds).
-pub unsafe fn lean_io_prim_handle_lock(
+/// File descriptor behind a handle. Scalar handles are stdin/stdout/stderr.
+#[cfg(unix)]
+unsafe fn handle_fd(h: *mut LeanObject) -> std::os::unix::io::RawFd {
+    use std::os::unix::io::AsRawFd;
+    if lean_is_scalar(h) {
+        return lean_unbox(h) as std::os::unix::io::RawFd;
+    }
+    lean_io_with_handle(h, |hd| match &hd.stream {
+        HandleStream::File(f) => f.as_raw_fd(),
+        HandleStream::ChildStdin(p) => p.as_raw_fd(),
+        HandleStream::ChildStdout(p) => p.as_raw_fd(),
+        HandleStream::ChildStderr(p) => p.as_raw_fd(),
+    })
+}
+
+/// `flock` the handle's descriptor: `Some(exclusive)` locks, `None` unlocks.
+/// The handle's mutex is not held while blocking, so other threads can keep
+/// using the handle.
+#[cfg(unix)]
+unsafe fn handle_flock(
+    h: *mut LeanObject,
+    exclusive: Option<u8>,
+    nonblocking: bool,
+) -> std::io::Result<()> {
+    let mut op = match exclusive {
+        None => libc::LOCK_UN,
+        Some(0) => libc::LOCK_SH,
+        Some(_) => libc::LOCK_EX,
+    };
+    if nonblocking {
+        op |= libc::LOCK_NB;
+    }
+    let fd = handle_fd(h);
+    loop {
+        if libc::flock(fd, op) == 0 {
+            return Ok(());
+        }
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// Without `flock`, locking fails rather than silently not locking.
+#[cfg(not(unix))]
+unsafe fn handle_flock(
     _h: *mut LeanObject,
-    _exclusive: u8,
-    _blocking: u8,
+    _exclusive: Option<u8>,
+    _nonblocking: bool,
+) -> std::io::Result<()> {
+    Err(std::io::Error::new(
+        std::io::ErrorKind::Unsupported,
+        "file locking is not supported on this platform",
+    ))
+}
+
+/// Acquire an advisory (`flock`) lock on a handle, shared or exclusive,
+/// blocking until it is available.
+pub unsafe fn lean_io_prim_handle_lock(
+    h: *mut LeanObject,
+    exclusive: u8,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(0))
+    match handle_flock(h, Some(exclusive), false) {
+        Ok(()) => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
+    }
 }
 
-/// Try to lock a file handle (stub: always succeeds).
+/// Try to acquire an advisory lock without blocking.
+/// Returns false if the lock is held elsewhere.
 pub unsafe fn lean_io_prim_handle_try_lock(
-    _h: *mut LeanObject,
-    _exclusive: u8,
+    h: *mut LeanObject,
+    exclusive: u8,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(1)) // true = success
+    match handle_flock(h, Some(exclusive), true) {
+        Ok(()) => lean_io_result_mk_ok(lean_box(1)),
+        Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
+    }
 }
 
-/// Unlock a file handle (stub).
+/// Release an advisory lock held on a handle.
 pub unsafe fn lean_io_prim_handle_unlock(
-    _h: *mut LeanObject,
+    h: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(0))
+    match handle_flock(h, None, false) {
+        Ok(()) => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
+    }
 }
 
 // ---------------------------------------------------------------------------
@@ -934,10 +1284,9 @@ pub unsafe fn lean_io_create_tempfile(_unit: *mut LeanObject) -> *mut LeanObject
     let path = std::env::temp_dir().join(format!("lean_tmp_{}", std::process::id()));
     match std::fs::File::create(&path) {
         Ok(file) => {
-            let handle = Box::into_raw(Box::new(file)) as usize;
             let pair = crate::lean_alloc_ctor(0, 2, 0);
             crate::lean_ctor_set(pair, 0, lean_mk_string(&path.to_string_lossy()));
-            crate::lean_ctor_set(pair, 1, lean_box(handle));
+            crate::lean_ctor_set(pair, 1, lean_io_mk_handle(HandleStream::File(file)));
             lean_io_result_mk_ok(pair)
         }
         Err(e) => {
@@ -992,214 +1341,489 @@ pub unsafe fn lean_chmod(
 }
 
 // ---------------------------------------------------------------------------
-// Task stubs (single-threaded implementation)
+// Tasks
 // ---------------------------------------------------------------------------
 
-/// Spawn a task. In single-threaded mode, evaluate the closure immediately.
-pub unsafe fn lean_task_spawn(c: *mut LeanObject, _prio: *mut LeanObject) -> *mut LeanObject {
-    crate::lean_apply_1(c, lean_box(0))
-}
-
-/// Wrap a pure value as a task result.
-pub unsafe fn lean_task_pure(a: *mut LeanObject) -> *mut LeanObject {
-    a
-}
-
-/// Bind a task to a continuation.
-pub unsafe fn lean_task_bind(
-    x: *mut LeanObject,
-    f: *mut LeanObject,
-    _prio: *mut LeanObject,
-    _sync: u8,
-) -> *mut LeanObject {
-    crate::lean_apply_1(f, x)
-}
-
-/// Map a function over a task.
-pub unsafe fn lean_task_map(
-    f: *mut LeanObject,
-    t: *mut LeanObject,
-    _prio: *mut LeanObject,
-    _sync: u8,
-) -> *mut LeanObject {
-    crate::lean_apply_1(f, t)
+/// Check if the current task has been canceled.
+pub unsafe fn lean_io_check_canceled_core() -> u8 {
+    crate::lean_task_check_canceled() as u8
 }
 
-/// Get the value of a completed task (borrowed reference).
-pub unsafe fn lean_task_get(t: *mut LeanObject) -> *mut LeanObject {
-    crate::lean_inc(t);
-    t
+/// Request cooperative cancellation of a task.
+pub unsafe fn lean_io_cancel_core(t: *mut LeanObject) {
+    crate::lean_task_cancel(t);
 }
 
-/// Get the value of a completed task (owned reference).
-pub unsafe fn lean_task_get_own(t: *mut LeanObject) -> *mut LeanObject {
-    t
+/// Get the state of a task (0 = waiting, 1 = running, 2 = finished).
+pub unsafe fn lean_io_get_task_state_core(t: *mut LeanObject) -> u8 {
+    crate::lean_task_get_state(t)
 }
 
-/// Check if the current task has been canceled (stub: always false).
-pub unsafe fn lean_io_check_canceled_core() -> u8 {
-    0
+/// Wait for any of a non-empty list of tasks to finish. Returns the first
+/// finished task, borrowed from the list. An empty list would block forever,
+/// so it panics instead.
+pub unsafe fn lean_io_wait_any_core(task_list: *mut LeanObject) -> *mut LeanObject {
+    let mut tasks = Vec::new();
+    let mut l = task_list;
+    while !lean_is_scalar(l) {
+        tasks.push(crate::lean_ctor_get(l, 0));
+        l = crate::lean_ctor_get(l, 1);
+    }
+    assert!(!tasks.is_empty(), "IO.waitAny: empty task list");
+    crate::lean_task_wait_any(&tasks)
 }
 
-/// Cancel a task (stub: no-op).
-pub unsafe fn lean_io_cancel_core(_t: *mut LeanObject) {}
-
-/// Get the state of a task (stub: always 2 = finished).
-pub unsafe fn lean_io_get_task_state_core(_t: *mut LeanObject) -> u8 {
-    2
+/// Convert an IO result into `Except IO.Error α` (error = tag 0, ok = tag 1).
+unsafe fn io_result_to_except(r: *mut LeanObject) -> *mut LeanObject {
+    let tag = if lean_io_result_is_ok(r) { 1 } else { 0 };
+    let v = crate::lean_ctor_get(r, 0);
+    crate::lean_inc(v);
+    crate::lean_dec(r);
+    let e = crate::lean_alloc_ctor(tag, 1, 0);
+    crate::lean_ctor_set(e, 0, v);
+    e
 }
 
-/// Wait for any of a list of tasks to complete. Returns the first task.
-pub unsafe fn lean_io_wait_any_core(task_list: *mut LeanObject) -> *mut LeanObject {
-    if !lean_is_scalar(task_list) && crate::lean_obj_tag(task_list) == 1 {
-        let head = crate::lean_ctor_get(task_list, 0);
-        crate::lean_inc(head);
-        head
-    } else {
-        lean_box(0)
-    }
+unsafe extern "C" fn io_as_task_fn(
+    action: *mut LeanObject,
+    _unit: *mut LeanObject,
+) -> *mut LeanObject {
+    io_result_to_except(crate::lean_apply_1(action, lean_box(0)))
 }
 
-/// Run an IO action as a task.
+/// Run an IO action as a task. The task's value is `Except IO.Error α`.
 pub unsafe fn lean_io_as_task(
     action: *mut LeanObject,
-    _prio: *mut LeanObject,
+    prio: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    let result = crate::lean_apply_1(action, lean_box(0));
-    if lean_io_result_is_ok(result) {
-        let val = crate::lean_ctor_get(result, 0);
-        crate::lean_inc(val);
-        crate::lean_dec(result);
-        lean_io_result_mk_ok(val)
-    } else {
-        result
-    }
+    let c = crate::lean_alloc_closure(io_as_task_fn as *const (), 2, 1);
+    crate::lean_closure_set(c, 0, action);
+    lean_io_result_mk_ok(crate::lean_task_spawn(c, prio))
 }
 
-/// Map a function over a task result in IO.
+unsafe extern "C" fn io_map_task_fn(f: *mut LeanObject, a: *mut LeanObject) -> *mut LeanObject {
+    io_result_to_except(crate::lean_apply_2(f, a, lean_box(0)))
+}
+
+/// Map an IO function over a task result.
 pub unsafe fn lean_io_map_task(
     f: *mut LeanObject,
     t: *mut LeanObject,
-    _prio: *mut LeanObject,
-    _sync: u8,
+    prio: *mut LeanObject,
+    sync: u8,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    let result = crate::lean_apply_1(f, t);
-    lean_io_result_mk_ok(result)
+    let c = crate::lean_alloc_closure(io_map_task_fn as *const (), 2, 1);
+    crate::lean_closure_set(c, 0, f);
+    lean_io_result_mk_ok(crate::lean_task_map(c, t, prio, sync))
+}
+
+unsafe extern "C" fn io_bind_task_fn(f: *mut LeanObject, a: *mut LeanObject) -> *mut LeanObject {
+    let r = crate::lean_apply_2(f, a, lean_box(0));
+    if lean_io_result_is_ok(r) {
+        let t = crate::lean_ctor_get(r, 0);
+        crate::lean_inc(t);
+        crate::lean_dec(r);
+        t
+    } else {
+        crate::lean_task_pure(io_result_to_except(r))
+    }
 }
 
-/// Bind a task result to an IO continuation.
+/// Bind a task result to an IO continuation that returns a task.
 pub unsafe fn lean_io_bind_task(
     t: *mut LeanObject,
     f: *mut LeanObject,
-    _prio: *mut LeanObject,
-    _sync: u8,
+    prio: *mut LeanObject,
+    sync: u8,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    let result = crate::lean_apply_1(f, t);
-    lean_io_result_mk_ok(result)
+    let c = crate::lean_alloc_closure(io_bind_task_fn as *const (), 2, 1);
+    crate::lean_closure_set(c, 0, f);
+    lean_io_result_mk_ok(crate::lean_task_bind(t, c, prio, sync))
 }
 
 // ---------------------------------------------------------------------------
-// Promise stubs
+// Promises
 // ---------------------------------------------------------------------------
 
-/// Create a new promise.
+// A promise is an external object owning its result task (`Task (Option α)`).
+// Dropping an unresolved promise resolves that task to `none`.
+
+static PROMISE_CLASS: crate::LeanExternalClass = crate::LeanExternalClass {
+    finalize: Some(promise_finalize),
+    foreach: None,
+};
+
+unsafe extern "C" fn promise_finalize(data: *mut std::ffi::c_void) {
+    let t = data as *mut LeanObject;
+    crate::lean_task_resolve(t, lean_box(0));
+    crate::lean_dec(t);
+}
+
+unsafe fn promise_task(promise: *mut LeanObject) -> *mut LeanObject {
+    crate::lean_get_external_data(promise) as *mut LeanObject
+}
+
+/// Create a new, unresolved promise.
 pub unsafe fn lean_io_promise_new(_unit: *mut LeanObject) -> *mut LeanObject {
-    let promise = crate::lean_alloc_ctor(0, 1, 0);
-    crate::lean_ctor_set(promise, 0, lean_box(0));
+    let t = crate::lean_promise_task_new();
+    let cls = &PROMISE_CLASS as *const crate::LeanExternalClass as *mut crate::LeanExternalClass;
+    let promise = crate::lean_alloc_external(cls, t as *mut std::ffi::c_void);
     lean_io_result_mk_ok(promise)
 }
 
-/// Resolve a promise with a value.
+/// Resolve a promise with a value. Only the first resolution takes effect.
 pub unsafe fn lean_io_promise_resolve(
     val: *mut LeanObject,
     promise: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    if !lean_is_scalar(promise) {
-        crate::lean_ctor_set(promise, 0, val);
-    }
+    let some = crate::lean_alloc_ctor(1, 1, 0);
+    crate::lean_ctor_set(some, 0, val);
+    crate::lean_task_resolve(promise_task(promise), some);
     lean_io_result_mk_ok(lean_box(0))
 }
 
-/// Get the result of a promise if available (returns Option).
+/// The result task of a promise: `some v` once resolved, `none` if the promise
+/// was dropped unresolved.
 pub unsafe fn lean_io_promise_result_opt(promise: *mut LeanObject) -> *mut LeanObject {
-    if !lean_is_scalar(promise) {
-        let v = crate::lean_ctor_get(promise, 0);
-        if !lean_is_scalar(v) || lean_unbox(v) != 0 {
-            crate::lean_inc(v);
-            let some = crate::lean_alloc_ctor(1, 1, 0);
-            crate::lean_ctor_set(some, 0, v);
-            return some;
-        }
-    }
-    lean_box(0) // none
+    let t = promise_task(promise);
+    crate::lean_inc(t);
+    t
 }
 
-/// Get the value from an Option, blocking if none (stub: returns value or default).
-pub unsafe fn lean_option_get_or_block(promise: *mut LeanObject) -> *mut LeanObject {
-    if !lean_is_scalar(promise) {
-        let v = crate::lean_ctor_get(promise, 0);
+/// Get the value from an Option, blocking forever if it is `none`.
+pub unsafe fn lean_option_get_or_block(o: *mut LeanObject) -> *mut LeanObject {
+    if !lean_is_scalar(o) {
+        let v = crate::lean_ctor_get(o, 0);
         crate::lean_inc(v);
         return v;
     }
-    lean_box(0)
+    loop {
+        std::thread::park();
+    }
 }
 
 // ---------------------------------------------------------------------------
-// Process stubs
+// Processes
 // ---------------------------------------------------------------------------
 
-/// Spawn a child process (stub: returns error).
+type ChildRef = std::sync::Arc<std::sync::Mutex<std::process::Child>>;
+
+/// Children that have not been reaped yet, by pid. A `Child` object only
+/// stores the pid; the `std::process::Child` stays here until its exit
+/// status has been collected by `wait` or `tryWait`.
+fn children() -> &'static std::sync::Mutex<std::collections::HashMap<u32, ChildRef>> {
+    static CHILDREN: std::sync::OnceLock<
+        std::sync::Mutex<std::collections::HashMap<u32, ChildRef>>,
+    > = std::sync::OnceLock::new();
+    CHILDREN.get_or_init(Default::default)
+}
+
+fn find_child(pid: u32) -> Option<ChildRef> {
+    children()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(&pid)
+        .cloned()
+}
+
+unsafe fn not_a_child(pid: u32) -> *mut LeanObject {
+    let msg = lean_mk_string(&format!("process {pid} is not a child"));
+    lean_io_result_mk_error(lean_mk_io_user_error(msg))
+}
+
+/// Whether the child has exited, waiting for it if `block`. The child is
+/// left unreaped, so its pid cannot be reused until [`reap`] is called.
+#[cfg(unix)]
+fn has_exited(pid: u32, block: bool) -> std::io::Result<bool> {
+    let mut flags = libc::WEXITED | libc::WNOWAIT;
+    if !block {
+        flags |= libc::WNOHANG;
+    }
+    loop {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        if unsafe { libc::waitid(libc::P_PID, pid as libc::id_t, &mut info, flags) } == 0 {
+            return Ok(unsafe { info.si_pid() } != 0);
+        }
+        let e = std::io::Error::last_os_error();
+        if e.kind() != std::io::ErrorKind::Interrupted {
+            return Err(e);
+        }
+    }
+}
+
+/// Remove an exited child from the registry and collect its exit status.
+/// `None` if another thread reaped it first.
+#[cfg(unix)]
+fn reap(pid: u32) -> Option<std::io::Result<std::process::ExitStatus>> {
+    let c = children()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .remove(&pid)?;
+    let r = c.lock().unwrap_or_else(|e| e.into_inner()).wait();
+    Some(r)
+}
+
+/// Decode a `Stdio` enum (piped=0, inherit=1, null=2).
+fn mk_stdio(tag: u8) -> std::process::Stdio {
+    match tag {
+        0 => std::process::Stdio::piped(),
+        2 => std::process::Stdio::null(),
+        _ => std::process::Stdio::inherit(),
+    }
+}
+
+fn exit_code(status: std::process::ExitStatus) -> u32 {
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::ExitStatusExt;
+        if let Some(sig) = status.signal() {
+            return 128 + sig as u32;
+        }
+    }
+    status.code().unwrap_or(0) as u32
+}
+
+unsafe fn mk_child(
+    stdin: *mut LeanObject,
+    stdout: *mut LeanObject,
+    stderr: *mut LeanObject,
+    pid: u32,
+) -> *mut LeanObject {
+    let child = crate::lean_alloc_ctor(0, 3, 4);
+    crate::lean_ctor_set(child, 0, stdin);
+    crate::lean_ctor_set(child, 1, stdout);
+    crate::lean_ctor_set(child, 2, stderr);
+    crate::lean_ctor_set_uint32(child, 0, pid);
+    child
+}
+
+unsafe fn child_pid(child: *mut LeanObject) -> u32 {
+    crate::lean_ctor_get_uint32(child, 0)
+}
+
+/// Spawn a child process from `IO.Process.SpawnArgs`.
+/// Piped streams become handles usable with the `lean_io_prim_handle_*`
+/// functions; inherited and null streams are `()`.
 pub unsafe fn lean_io_process_spawn(
-    cfg: *mut LeanObject,
+    args: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    crate::lean_dec(cfg);
-    let msg = lean_mk_string("process spawn not fully implemented");
-    lean_io_result_mk_error(lean_mk_io_user_error(msg))
+    let stdio = crate::lean_ctor_get(args, 0);
+    let cmd_name = crate::lean_ctor_get(args, 1);
+    let mut cmd = std::process::Command::new(lean_string_to_str(cmd_name));
+
+    let argv = crate::lean_ctor_get(args, 2);
+    for i in 0..crate::lean_array_size(argv) {
+        cmd.arg(lean_string_to_str(crate::lean_array_get_core(argv, i)));
+    }
+
+    let cwd = crate::lean_ctor_get(args, 3);
+    if !lean_is_scalar(cwd) {
+        cmd.current_dir(lean_string_to_str(crate::lean_ctor_get(cwd, 0)));
+    }
+
+    let env = crate::lean_ctor_get(args, 4);
+    for i in 0..crate::lean_array_size(env) {
+        let pair = crate::lean_array_get_core(env, i);
+        let key = lean_string_to_str(crate::lean_ctor_get(pair, 0));
+        let val = crate::lean_ctor_get(pair, 1);
+        if lean_is_scalar(val) {
+            cmd.env_remove(key);
+        } else {
+            cmd.env(key, lean_string_to_str(crate::lean_ctor_get(val, 0)));
+        }
+    }
+
+    #[cfg(unix)]
+    if crate::lean_ctor_get_uint8(args, 0) != 0 {
+        use std::os::unix::process::CommandExt;
+        cmd.pre_exec(|| {
+            if libc::setsid() < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+            Ok(())
+        });
+    }
+
+    cmd.stdin(mk_stdio(crate::lean_ctor_get_uint8(stdio, 0)))
+        .stdout(mk_stdio(crate::lean_ctor_get_uint8(stdio, 1)))
+        .stderr(mk_stdio(crate::lean_ctor_get_uint8(stdio, 2)));
+
+    let result = cmd.spawn();
+    crate::lean_inc(cmd_name);
+    crate::lean_dec(args);
+    let mut child = match result {
+        Ok(child) => child,
+        Err(e) => return lean_io_result_mk_error(lean_io_error_from_std(&e, cmd_name)),
+    };
+    crate::lean_dec(cmd_name);
+
+    let pid = child.id();
+    let stdin = match child.stdin.take() {
+        Some(p) => lean_io_mk_handle(HandleStream::ChildStdin(p)),
+        None => lean_box(0),
+    };
+    let stdout = match child.stdout.take() {
+        Some(p) => lean_io_mk_handle(HandleStream::ChildStdout(p)),
+        None => lean_box(0),
+    };
+    let stderr = match child.stderr.take() {
+        Some(p) => lean_io_mk_handle(HandleStream::ChildStderr(p)),
+        None => lean_box(0),
+    };
+    children()
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(pid, std::sync::Arc::new(std::sync::Mutex::new(child)));
+    lean_io_result_mk_ok(mk_child(stdin, stdout, stderr, pid))
 }
 
-/// Wait for a child process to exit.
+/// How often `lean_io_process_child_wait` checks on a child where it cannot
+/// wait without reaping.
+#[cfg(not(unix))]
+const CHILD_POLL_INTERVAL: std::time::Duration = std::time::Duration::from_millis(10);
+
+/// Wait for a child process to exit and return its exit code. A child killed
+/// by a signal reports `128 + signal`.
 pub unsafe fn lean_io_process_child_wait(
-    _child: *mut LeanObject,
+    _cfg: *mut LeanObject,
+    child: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(0))
+    let pid = child_pid(child);
+    let Some(c) = find_child(pid) else {
+        return not_a_child(pid);
+    };
+    // Wait without reaping, so that `kill` cannot race with pid reuse
+    #[cfg(unix)]
+    let r = match has_exited(pid, true) {
+        Ok(_) => match reap(pid) {
+            Some(r) => r,
+            None => return not_a_child(pid),
+        },
+        Err(e) => Err(e),
+    };
+    // Poll rather than block in `Child::wait` holding the lock, which would
+    // keep `kill` from taking it
+    #[cfg(not(unix))]
+    let r = loop {
+        let status = c.lock().unwrap_or_else(|e| e.into_inner()).try_wait();
+        match status {
+            Ok(Some(status)) => {
+                children()
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .remove(&pid);
+                break Ok(status);
+            }
+            Ok(None) => std::thread::sleep(CHILD_POLL_INTERVAL),
+            Err(e) => break Err(e),
+        }
+    };
+    drop(c);
+    match r {
+        Ok(status) => lean_io_result_mk_ok(lean_box_uint32(exit_code(status))),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
+    }
 }
 
-/// Try to wait for a child process (non-blocking).
+/// Check whether a child process has exited without blocking.
+/// Returns `some exitCode` or `none` if it is still running.
 pub unsafe fn lean_io_process_child_try_wait(
-    _child: *mut LeanObject,
+    _cfg: *mut LeanObject,
+    child: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(0))
+    let pid = child_pid(child);
+    let Some(c) = find_child(pid) else {
+        return not_a_child(pid);
+    };
+    #[cfg(unix)]
+    let r = match has_exited(pid, false) {
+        Ok(true) => match reap(pid) {
+            Some(r) => r.map(Some),
+            None => return not_a_child(pid),
+        },
+        Ok(false) => Ok(None),
+        Err(e) => Err(e),
+    };
+    #[cfg(not(unix))]
+    let r = c.lock().unwrap_or_else(|e| e.into_inner()).try_wait();
+    drop(c);
+    match r {
+        Ok(Some(status)) => {
+            #[cfg(not(unix))]
+            children()
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .remove(&pid);
+            let some = crate::lean_alloc_ctor(1, 1, 0);
+            crate::lean_ctor_set(some, 0, lean_box_uint32(exit_code(status)));
+            lean_io_result_mk_ok(some)
+        }
+        Ok(None) => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
+    }
 }
 
-/// Kill a child process.
+/// Send SIGKILL to a child process. Killing a child that has already been
+/// waited for does nothing.
 pub unsafe fn lean_io_process_child_kill(
-    _child: *mut LeanObject,
+    _cfg: *mut LeanObject,
+    child: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(0))
+    let pid = child_pid(child);
+    // Signal by pid so that a concurrent `wait` does not block us. Holding
+    // the registry lock keeps the child from being reaped meanwhile.
+    #[cfg(unix)]
+    let r = {
+        let children = children().lock().unwrap_or_else(|e| e.into_inner());
+        if !children.contains_key(&pid) || libc::kill(pid as libc::pid_t, libc::SIGKILL) == 0 {
+            Ok(())
+        } else {
+            Err(std::io::Error::last_os_error())
+        }
+    };
+    #[cfg(not(unix))]
+    let r = match find_child(pid) {
+        Some(c) => c.lock().unwrap_or_else(|e| e.into_inner()).kill(),
+        None => Ok(()),
+    };
+    match r {
+        Ok(()) => lean_io_result_mk_ok(lean_box(0)),
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, lean_box(0))),
+    }
 }
 
-/// Take the stdin handle from a child process.
+/// Take the stdin handle from a child process, returning it together with
+/// the child (whose stdin is now `()`). Consumes `child`.
 pub unsafe fn lean_io_process_child_take_stdin(
-    _child: *mut LeanObject,
+    _cfg: *mut LeanObject,
+    child: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(0))
+    let stdin = crate::lean_ctor_get(child, 0);
+    let stdout = crate::lean_ctor_get(child, 1);
+    let stderr = crate::lean_ctor_get(child, 2);
+    crate::lean_inc(stdin);
+    crate::lean_inc(stdout);
+    crate::lean_inc(stderr);
+    let rest = mk_child(lean_box(0), stdout, stderr, child_pid(child));
+    crate::lean_dec(child);
+    let pair = crate::lean_alloc_ctor(0, 2, 0);
+    crate::lean_ctor_set(pair, 0, stdin);
+    crate::lean_ctor_set(pair, 1, rest);
+    lean_io_result_mk_ok(pair)
 }
 
 /// Get the PID of a child process.
-pub unsafe fn lean_io_process_child_pid(
-    _child: *mut LeanObject,
-    _unit: *mut LeanObject,
-) -> *mut LeanObject {
-    lean_io_result_mk_ok(lean_box(0))
+pub unsafe fn lean_io_process_child_pid(_cfg: *mut LeanObject, child: *mut LeanObject) -> u32 {
+    child_pid(child)
 }
 
 /// Get the current working directory (process variant).
@@ -1229,55 +1853,416 @@ pub unsafe fn lean_io_process_get_pid(_unit: *mut LeanObject) -> *mut LeanObject
 }
 
 // ---------------------------------------------------------------------------
-// Directory and metadata stubs
+// Directory and metadata
 // ---------------------------------------------------------------------------
 
-/// Read directory entries (stub: returns empty list).
+/// Read the entries of a directory as an `Array IO.FS.DirEntry`, where each
+/// entry is `{ root := path, fileName }`. `.` and `..` are not included.
 pub unsafe fn lean_io_read_dir(path: *mut LeanObject, _unit: *mut LeanObject) -> *mut LeanObject {
+    let entries = match std::fs::read_dir(lean_string_to_str(path)) {
+        Ok(entries) => entries,
+        Err(e) => return lean_io_result_mk_error(lean_io_error_from_std(&e, path)),
+    };
+    let mut arr = crate::lean_mk_empty_array_with_capacity(lean_box(0));
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) => {
+                crate::lean_dec(arr);
+                return lean_io_result_mk_error(lean_io_error_from_std(&e, path));
+            }
+        };
+        crate::lean_inc(path);
+        let dir_entry = crate::lean_alloc_ctor(0, 2, 0);
+        crate::lean_ctor_set(dir_entry, 0, path);
+        crate::lean_ctor_set(
+            dir_entry,
+            1,
+            lean_mk_string(&entry.file_name().to_string_lossy()),
+        );
+        arr = crate::lean_array_push(arr, dir_entry);
+    }
     crate::lean_dec(path);
-    lean_io_result_mk_ok(lean_box(0)) // empty list (nil)
+    lean_io_result_mk_ok(arr)
 }
 
-/// Get file metadata (stub: returns error).
+/// Build an `IO.FS.SystemTime` (`sec : Int`, `nsec : UInt32`).
+unsafe fn mk_system_time(sec: i64, nsec: u32) -> *mut LeanObject {
+    let t = crate::lean_alloc_ctor(0, 1, 4);
+    crate::lean_ctor_set(t, 0, crate::int::lean_int64_to_int(sec));
+    crate::lean_ctor_set_uint32(t, 0, nsec);
+    t
+}
+
+#[cfg(unix)]
+unsafe fn mk_times(md: &std::fs::Metadata) -> (*mut LeanObject, *mut LeanObject) {
+    use std::os::unix::fs::MetadataExt;
+    (
+        mk_system_time(md.atime(), md.atime_nsec() as u32),
+        mk_system_time(md.mtime(), md.mtime_nsec() as u32),
+    )
+}
+
+#[cfg(not(unix))]
+unsafe fn mk_times(md: &std::fs::Metadata) -> (*mut LeanObject, *mut LeanObject) {
+    let conv = |t: std::io::Result<std::time::SystemTime>| {
+        let d = t
+            .ok()
+            .and_then(|t| t.duration_since(std::time::UNIX_EPOCH).ok())
+            .unwrap_or_default();
+        mk_system_time(d.as_secs() as i64, d.subsec_nanos())
+    };
+    (conv(md.accessed()), conv(md.modified()))
+}
+
+/// Build an `IO.FS.Metadata`: accessed and modified times as objects, then
+/// `byteSize : UInt64` and `type : FileType` (dir=0, file=1, symlink=2,
+/// other=3) in the scalar area.
+unsafe fn mk_metadata(md: &std::fs::Metadata) -> *mut LeanObject {
+    let ft = md.file_type();
+    let kind = if ft.is_dir() {
+        0
+    } else if ft.is_file() {
+        1
+    } else if ft.is_symlink() {
+        2
+    } else {
+        3
+    };
+    let (accessed, modified) = mk_times(md);
+    let o = crate::lean_alloc_ctor(0, 2, 9);
+    crate::lean_ctor_set(o, 0, accessed);
+    crate::lean_ctor_set(o, 1, modified);
+    crate::lean_ctor_set_uint64(o, 0, md.len());
+    crate::lean_ctor_set_uint8(o, 8, kind);
+    o
+}
+
+unsafe fn metadata_result(
+    r: std::io::Result<std::fs::Metadata>,
+    path: *mut LeanObject,
+) -> *mut LeanObject {
+    match r {
+        Ok(md) => {
+            crate::lean_dec(path);
+            lean_io_result_mk_ok(mk_metadata(&md))
+        }
+        Err(e) => lean_io_result_mk_error(lean_io_error_from_std(&e, path)),
+    }
+}
+
+/// Get file metadata, following symlinks.
 pub unsafe fn lean_io_metadata(path: *mut LeanObject, _unit: *mut LeanObject) -> *mut LeanObject {
-    crate::lean_dec(path);
-    let msg = lean_mk_string("metadata not implemented");
-    lean_io_result_mk_error(lean_mk_io_user_error(msg))
+    metadata_result(std::fs::metadata(lean_string_to_str(path)), path)
 }
 
-/// Get symlink metadata (stub: returns error).
+/// Get file metadata without following a final symlink.
 pub unsafe fn lean_io_symlink_metadata(
     path: *mut LeanObject,
     _unit: *mut LeanObject,
 ) -> *mut LeanObject {
-    lean_io_metadata(path, _unit)
+    metadata_result(std::fs::symlink_metadata(lean_string_to_str(path)), path)
 }
 
-// ---------------------------------------------------------------------------
-// ShareCommon stubs
-// ---------------------------------------------------------------------------
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-/// Check if two objects are pointer-equal.
-pub unsafe fn lean_sharecommon_eq(a: *mut LeanObject, b: *mut LeanObject) -> u8 {
-    (a == b) as u8
-}
+    unsafe fn to_string(err: *mut LeanObject) -> String {
+        let s = lean_io_error_to_string(err);
+        let r = lean_string_to_str(s).to_string();
+        crate::lean_dec(s);
+        r
+    }
 
-/// Hash an object by its pointer address.
-pub unsafe fn lean_sharecommon_hash(a: *mut LeanObject) -> u64 {
-    a as u64
-}
+    #[test]
+    fn io_error_to_string() {
+        unsafe {
+            assert_eq!(
+                to_string(lean_mk_io_user_error(lean_mk_string("boom"))),
+                "boom"
+            );
+            assert_eq!(to_string(lean_mk_io_error_eof(lean_box(0))), "end of file");
+            assert_eq!(
+                to_string(lean_mk_io_error_no_file_or_directory(
+                    lean_mk_string("a.txt"),
+                    2,
+                    lean_mk_string("No such file")
+                )),
+                "no such file or directory (error code: 2)\n  file: a.txt"
+            );
+            assert_eq!(
+                to_string(lean_mk_io_error_resource_busy(16, lean_mk_string("Busy"))),
+                "resource busy (error code: 16, busy)"
+            );
+            assert_eq!(
+                to_string(lean_mk_io_error_permission_denied(
+                    13,
+                    lean_mk_string("Permission denied")
+                )),
+                "permission denied (error code: 13)"
+            );
+            let unsupported = std::io::Error::new(std::io::ErrorKind::Unsupported, "no flock");
+            assert_eq!(
+                to_string(lean_io_error_from_std(&unsupported, lean_box(0))),
+                "unsupported operation (error code: 0, no flock)"
+            );
+        }
+    }
 
-/// Share-common with state: return (a, st) pair.
-pub unsafe fn lean_state_sharecommon(a: *mut LeanObject, st: *mut LeanObject) -> *mut LeanObject {
-    crate::lean_inc(a);
-    let pair = crate::lean_alloc_ctor(0, 2, 0);
-    crate::lean_ctor_set(pair, 0, a);
-    crate::lean_ctor_set(pair, 1, st);
-    pair
-}
+    /// Spawn `cmd args` with null stdin and stderr, returning the `Child`.
+    #[cfg(unix)]
+    unsafe fn spawn(cmd: &str, args: &[&str], stdout: u8) -> *mut LeanObject {
+        let stdio = crate::lean_alloc_ctor(0, 0, 3);
+        crate::lean_ctor_set_uint8(stdio, 0, 2);
+        crate::lean_ctor_set_uint8(stdio, 1, stdout);
+        crate::lean_ctor_set_uint8(stdio, 2, 2);
+        let mut argv = crate::lean_mk_empty_array();
+        for a in args {
+            argv = crate::lean_array_push(argv, lean_mk_string(a));
+        }
+        let spawn_args = crate::lean_alloc_ctor(0, 5, 1);
+        crate::lean_ctor_set(spawn_args, 0, stdio);
+        crate::lean_ctor_set(spawn_args, 1, lean_mk_string(cmd));
+        crate::lean_ctor_set(spawn_args, 2, argv);
+        crate::lean_ctor_set(spawn_args, 3, lean_box(0));
+        crate::lean_ctor_set(spawn_args, 4, crate::lean_mk_empty_array());
+        crate::lean_ctor_set_uint8(spawn_args, 0, 0);
+        ok_value(lean_io_process_spawn(spawn_args, lean_box(0)))
+    }
+
+    unsafe fn ok_value(r: *mut LeanObject) -> *mut LeanObject {
+        assert!(lean_io_result_is_ok(r));
+        let v = lean_io_result_get_value(r);
+        crate::lean_inc(v);
+        crate::lean_dec(r);
+        v
+    }
+
+    #[cfg(unix)]
+    unsafe fn wait(child: *mut LeanObject) -> u32 {
+        lean_unbox_uint32(ok_value(lean_io_process_child_wait(
+            lean_box(0),
+            child,
+            lean_box(0),
+        )))
+    }
+
+    #[cfg(unix)]
+    fn is_registered(pid: u32) -> bool {
+        find_child(pid).is_some()
+    }
+
+    /// Waiting reaps the child: it leaves the registry, and later waits fail
+    #[test]
+    #[cfg(unix)]
+    fn process_spawn_wait() {
+        unsafe {
+            let child = spawn("sh", &["-c", "exit 3"], 2);
+            let pid = lean_io_process_child_pid(lean_box(0), child);
+            assert!(is_registered(pid));
+            assert_eq!(wait(child), 3);
+            assert!(!is_registered(pid));
+            let r = lean_io_process_child_wait(lean_box(0), child, lean_box(0));
+            assert!(!lean_io_result_is_ok(r));
+            crate::lean_dec(r);
+            // Killing a reaped child must not signal whoever reuses its pid
+            ok_value(lean_io_process_child_kill(lean_box(0), child, lean_box(0)));
+            crate::lean_dec(child);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn process_stdout_pipe() {
+        unsafe {
+            let child = spawn("sh", &["-c", "echo hello"], 0);
+            let stdout = crate::lean_ctor_get(child, 1);
+            let line = ok_value(lean_io_prim_handle_get_line(stdout, lean_box(0)));
+            assert_eq!(lean_string_to_str(line), "hello\n");
+            crate::lean_dec(line);
+            // tryWait reaps the child too once it reports an exit code
+            let pid = lean_io_process_child_pid(lean_box(0), child);
+            let code = loop {
+                let r = ok_value(lean_io_process_child_try_wait(
+                    lean_box(0),
+                    child,
+                    lean_box(0),
+                ));
+                if !lean_is_scalar(r) {
+                    break r;
+                }
+                std::thread::sleep(std::time::Duration::from_millis(1));
+            };
+            assert_eq!(lean_unbox_uint32(crate::lean_ctor_get(code, 0)), 0);
+            crate::lean_dec(code);
+            assert!(!is_registered(pid));
+            crate::lean_dec(child);
+        }
+    }
+
+    #[test]
+    #[cfg(unix)]
+    fn process_kill() {
+        unsafe {
+            let child = spawn("sleep", &["10"], 2);
+            let pid = lean_io_process_child_pid(lean_box(0), child);
+            let r = ok_value(lean_io_process_child_try_wait(
+                lean_box(0),
+                child,
+                lean_box(0),
+            ));
+            assert!(lean_is_scalar(r));
+            ok_value(lean_io_process_child_kill(lean_box(0), child, lean_box(0)));
+            assert_eq!(wait(child), 128 + libc::SIGKILL as u32);
+            assert!(!is_registered(pid));
+            crate::lean_dec(child);
+        }
+    }
+
+    /// A fresh, empty directory under the system temp directory.
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir = std::env::temp_dir().join(format!("lean-rt-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir(&dir).unwrap();
+        dir
+    }
+
+    /// `(sec, nsec)` of an `IO.FS.SystemTime`
+    unsafe fn system_time(t: *mut LeanObject) -> (i64, u32) {
+        (
+            crate::int::lean_scalar_to_int64(crate::lean_ctor_get(t, 0)),
+            crate::lean_ctor_get_uint32(t, 0),
+        )
+    }
 
-/// Quick share-common (no-op: return the object).
-pub unsafe fn lean_sharecommon_quick(a: *mut LeanObject) -> *mut LeanObject {
-    crate::lean_inc(a);
-    a
+    #[test]
+    fn read_dir_and_metadata() {
+        let dir = temp_dir("read-dir");
+        let file = dir.join("a.txt");
+        std::fs::write(&file, b"hello").unwrap();
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        let root = dir.to_str().unwrap();
+        unsafe {
+            let entries = ok_value(lean_io_read_dir(lean_mk_string(root), lean_box(0)));
+            let mut names = Vec::new();
+            for i in 0..crate::lean_array_size(entries) {
+                let entry = crate::lean_array_get_core(entries, i);
+                assert_eq!(lean_string_to_str(crate::lean_ctor_get(entry, 0)), root);
+                names.push(lean_string_to_str(crate::lean_ctor_get(entry, 1)).to_string());
+            }
+            names.sort();
+            assert_eq!(names, ["a.txt", "sub"]);
+            crate::lean_dec(entries);
+
+            let path = lean_mk_string(file.to_str().unwrap());
+            let md = ok_value(lean_io_metadata(path, lean_box(0)));
+            assert_eq!(crate::lean_ctor_get_uint64(md, 0), 5);
+            assert_eq!(crate::lean_ctor_get_uint8(md, 8), 1);
+            let modified = std::fs::metadata(&file)
+                .unwrap()
+                .modified()
+                .unwrap()
+                .duration_since(std::time::UNIX_EPOCH)
+                .unwrap();
+            assert_eq!(
+                system_time(crate::lean_ctor_get(md, 1)),
+                (modified.as_secs() as i64, modified.subsec_nanos())
+            );
+            crate::lean_dec(md);
+
+            let path = lean_mk_string(dir.join("sub").to_str().unwrap());
+            let md = ok_value(lean_io_symlink_metadata(path, lean_box(0)));
+            assert_eq!(crate::lean_ctor_get_uint8(md, 8), 0);
+            crate::lean_dec(md);
+
+            let path = lean_mk_string(dir.join("missing").to_str().unwrap());
+            let r = lean_io_metadata(path, lean_box(0));
+            assert!(!lean_io_result_is_ok(r));
+            crate::lean_dec(r);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// flock locks belong to the open file, so two handles on the same file
+    /// contend even within one process
+    #[test]
+    #[cfg(unix)]
+    fn handle_lock_try_lock() {
+        let dir = temp_dir("flock");
+        let file = dir.join("lock");
+        std::fs::write(&file, b"").unwrap();
+        let open = || unsafe {
+            let path = lean_mk_string(file.to_str().unwrap());
+            ok_value(lean_io_prim_handle_mk(path, 2, 0, lean_box(0)))
+        };
+        let (h1, h2) = (open(), open());
+        unsafe {
+            let try_lock =
+                |h, exclusive| ok_value(lean_io_prim_handle_try_lock(h, exclusive, lean_box(0)));
+            ok_value(lean_io_prim_handle_lock(h1, 1, lean_box(0)));
+            assert_eq!(try_lock(h2, 1), lean_box(0));
+            assert_eq!(try_lock(h2, 0), lean_box(0));
+
+            // A blocking lock waits for the holder to unlock
+            let h2_addr = h2 as usize;
+            let waiter = std::thread::spawn(move || {
+                ok_value(lean_io_prim_handle_lock(
+                    h2_addr as *mut LeanObject,
+                    0,
+                    lean_box(0),
+                ));
+            });
+            std::thread::sleep(std::time::Duration::from_millis(20));
+            assert!(!waiter.is_finished());
+            ok_value(lean_io_prim_handle_unlock(h1, lean_box(0)));
+            waiter.join().unwrap();
+            // Shared locks can be held together, but block exclusive ones
+            assert_eq!(try_lock(h1, 0), lean_box(1));
+            assert_eq!(try_lock(h1, 1), lean_box(0));
+            ok_value(lean_io_prim_handle_unlock(h2, lean_box(0)));
+            assert_eq!(try_lock(h1, 1), lean_box(1));
+            ok_value(lean_io_prim_handle_unlock(h1, lean_box(0)));
+            crate::lean_dec(h1);
+            crate::lean_dec(h2);
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    unsafe fn list(items: &[*mut LeanObject]) -> *mut LeanObject {
+        let mut l = lean_box(0);
+        for &x in items.iter().rev() {
+            let cons = crate::lean_alloc_ctor(1, 2, 0);
+            crate::lean_ctor_set(cons, 0, x);
+            crate::lean_ctor_set(cons, 1, l);
+            l = cons;
+        }
+        l
+    }
+
+    #[test]
+    fn wait_any_result_is_borrowed() {
+        unsafe {
+            let pending = crate::lean_promise_task_new();
+            let done = crate::lean_task_pure(lean_box(1));
+            crate::lean_inc(pending);
+            crate::lean_inc(done);
+            let tasks = list(&[pending, done]);
+            assert_eq!(lean_io_wait_any_core(tasks), done);
+            // Only the list and this test hold `done`
+            assert_eq!((*done).rc, 2);
+            crate::lean_dec(tasks);
+            crate::lean_task_resolve(pending, lean_box(0));
+            crate::lean_dec(pending);
+            crate::lean_dec(done);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "empty task list")]
+    fn wait_any_rejects_empty_list() {
+        unsafe {
+            lean_io_wait_any_core(lean_box(0));
+        }
+    }
 }
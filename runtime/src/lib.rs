@@ -2,15 +2,20 @@
 //!
 //! This is a low-level runtime library for compiled Lean programs.
 
+pub mod allocprof;
 pub mod array;
 pub mod bignat;
 pub mod r#box;
+#[cfg(feature = "c-api")]
+pub mod capi;
 pub mod closure;
+pub mod compact;
 pub mod ctor;
 pub mod debug;
 pub mod external;
 pub mod float;
 pub mod floatarray;
+pub mod heap;
 pub mod int;
 pub mod io;
 pub mod misc;
@@ -21,13 +26,18 @@ pub mod panic;
 pub mod platform;
 pub mod rc;
 pub mod sarray;
+pub mod sharecommon;
 pub mod sint;
 pub mod stref;
 pub mod string;
+pub mod task;
 pub mod thunk;
 pub mod uint;
 
 // Re-export commonly used items
+pub use allocprof::{
+    lean_alloc_prof_begin, lean_alloc_prof_count, lean_alloc_prof_end, AllocKind, AllocStats,
+};
 pub use array::{
     lean_alloc_array, lean_array_data, lean_array_fget, lean_array_fget_borrowed, lean_array_fset,
     lean_array_fswap, lean_array_get, lean_array_get_borrowed, lean_array_get_core,
@@ -57,10 +67,17 @@ pub use r#box::{
 };
 
 pub use closure::{
-    lean_alloc_closure, lean_apply_1, lean_apply_2, lean_apply_3, lean_apply_4, lean_apply_5,
-    lean_apply_6, lean_apply_7, lean_apply_8, lean_apply_m, lean_closure_get,
-    lean_closure_object_size, lean_closure_set, LeanClosure, LeanFn1, LeanFn2, LeanFn3, LeanFn4,
-    LeanFn5, LeanFn6, LeanFn7, LeanFn8,
+    lean_alloc_closure, lean_apply_1, lean_apply_10, lean_apply_11, lean_apply_12, lean_apply_13,
+    lean_apply_14, lean_apply_15, lean_apply_16, lean_apply_2, lean_apply_3, lean_apply_4,
+    lean_apply_5, lean_apply_6, lean_apply_7, lean_apply_8, lean_apply_9, lean_apply_m,
+    lean_apply_n, lean_closure_get, lean_closure_object_size, lean_closure_set, LeanClosure,
+    LeanFn1, LeanFn10, LeanFn11, LeanFn12, LeanFn13, LeanFn14, LeanFn15, LeanFn16, LeanFn2,
+    LeanFn3, LeanFn4, LeanFn5, LeanFn6, LeanFn7, LeanFn8, LeanFn9, LeanFnN, LEAN_CLOSURE_MAX_ARGS,
+};
+
+pub use compact::{
+    lean_compact_object, lean_compacted_region_free, lean_compacted_region_is_memory_mapped,
+    lean_read_module_data, lean_save_compacted_region, lean_save_module_data, CompactedRegion,
 };
 
 pub use ctor::{
@@ -259,6 +276,8 @@ pub use io::{
     lean_io_create_tempdir,
     lean_io_create_tempfile,
     lean_io_current_dir,
+    lean_io_error_from_std,
+    lean_io_error_to_string,
     lean_io_exit,
     // Heartbeats
     lean_io_get_num_heartbeats,
@@ -271,6 +290,8 @@ pub use io::{
     lean_io_hard_link,
     lean_io_map_task,
     lean_io_metadata,
+    lean_io_mk_handle,
+    lean_io_mk_world,
     // Time
     lean_io_mono_ms_now,
     lean_io_mono_nanos_now,
@@ -300,7 +321,7 @@ pub use io::{
     lean_io_process_set_current_dir,
     // Process stubs
     lean_io_process_spawn,
-    // Promise stubs
+    // Promises
     lean_io_promise_new,
     lean_io_promise_resolve,
     lean_io_promise_result_opt,
@@ -312,6 +333,7 @@ pub use io::{
     lean_io_remove_dir,
     lean_io_remove_file,
     lean_io_rename,
+    lean_io_result_get_error,
     lean_io_result_get_value,
     lean_io_result_is_ok,
     lean_io_result_mk_error,
@@ -321,6 +343,7 @@ pub use io::{
     // Timeit / allocprof
     lean_io_timeit,
     lean_io_wait_any_core,
+    lean_io_with_handle,
     lean_mk_io_error_already_exists,
     lean_mk_io_error_already_exists_file,
     lean_mk_io_error_eof,
@@ -348,18 +371,8 @@ pub use io::{
     // IO error constructors
     lean_mk_io_user_error,
     lean_option_get_or_block,
-    // ShareCommon
-    lean_sharecommon_eq,
-    lean_sharecommon_hash,
-    lean_sharecommon_quick,
-    lean_state_sharecommon,
-    lean_task_bind,
-    lean_task_get,
-    lean_task_get_own,
-    lean_task_map,
-    lean_task_pure,
-    // Task stubs
-    lean_task_spawn,
+    HandleStream,
+    LeanHandle,
 };
 
 pub use misc::{
@@ -374,12 +387,16 @@ pub use nat::{
     lean_unsigned_to_nat, lean_usize_to_nat,
 };
 
+pub use heap::{lean_heap_stats, HeapStats, LEAN_PAGE_SIZE};
+
 pub use owned::LeanOwnedValue;
 
 pub use object::{
-    lean_alloc_object, lean_ctor_object_size, lean_free_object, LeanObject, LEAN_ARRAY_TAG,
-    LEAN_BIGINT_TAG, LEAN_CLOSURE_TAG, LEAN_EXTERNAL_TAG, LEAN_MAX_CTOR_TAG, LEAN_MPZ_TAG,
-    LEAN_OBJECT_SIZE, LEAN_REF_TAG, LEAN_SCALAR_ARRAY_TAG, LEAN_STRING_TAG, LEAN_THUNK_TAG,
+    lean_alloc_object, lean_ctor_object_size, lean_free_object, lean_get_allocated_bytes,
+    lean_get_num_heartbeats, lean_inc_heartbeat, lean_set_heartbeats, LeanObject, LEAN_ARRAY_TAG,
+    LEAN_BIGINT_TAG, LEAN_CLOSURE_TAG, LEAN_EXTERNAL_TAG, LEAN_MAX_CTOR_TAG,
+    LEAN_MAX_SMALL_OBJECT_SIZE, LEAN_MPZ_TAG, LEAN_OBJECT_SIZE, LEAN_REF_TAG,
+    LEAN_SCALAR_ARRAY_TAG, LEAN_STRING_TAG, LEAN_TASK_TAG, LEAN_THUNK_TAG,
 };
 
 pub use panic::{
@@ -389,7 +406,8 @@ pub use panic::{
 
 pub use rc::{
     lean_dec, lean_dec_ref, lean_free_object_full, lean_free_object_only, lean_inc, lean_inc_n,
-    lean_inc_ref, lean_inc_ref_n, lean_is_exclusive, lean_mark_persistent,
+    lean_inc_ref, lean_inc_ref_n, lean_is_exclusive, lean_is_mt, lean_is_shared, lean_mark_mt,
+    lean_mark_persistent,
 };
 
 pub use sarray::{
@@ -413,6 +431,10 @@ pub use string::{
     lean_string_utf8_next_fast_cold, lean_string_utf8_prev, lean_string_utf8_set, LeanString,
 };
 
+pub use sharecommon::{
+    lean_sharecommon_eq, lean_sharecommon_hash, lean_sharecommon_quick, lean_state_sharecommon,
+};
+
 pub use sint::{
     lean_bool_to_int16, lean_bool_to_int32, lean_bool_to_int64, lean_bool_to_int8,
     lean_bool_to_isize, lean_bool_to_uint16, lean_bool_to_uint32, lean_bool_to_uint64,
@@ -488,17 +510,26 @@ pub use external::{
     LeanExternal, LeanExternalClass, LeanExternalFinalize, LeanExternalForeach,
 };
 
+pub use task::{
+    lean_finalize_task_manager, lean_init_task_manager, lean_init_task_manager_using,
+    lean_promise_task_new, lean_task_bind, lean_task_bind_core, lean_task_cancel,
+    lean_task_check_canceled, lean_task_get, lean_task_get_own, lean_task_get_state,
+    lean_task_is_finished, lean_task_map, lean_task_map_core, lean_task_others_active,
+    lean_task_pure, lean_task_resolve, lean_task_spawn, lean_task_spawn_core, lean_task_wait_any,
+    LeanTask, LEAN_MAX_PRIO,
+};
+
 pub use platform::{
-    lean_dbg_sleep, lean_expr_data, lean_finalize_task_manager, lean_get_githash,
-    lean_get_max_ctor_fields, lean_get_max_ctor_scalars_size, lean_get_max_ctor_tag,
-    lean_get_usize_size, lean_hashmap_mk_idx, lean_hashset_mk_idx, lean_init_task_manager,
-    lean_initialize_runtime_module, lean_internal_has_llvm_backend, lean_internal_is_stage0,
-    lean_io_initializing, lean_io_mark_end_initialization, lean_io_result_show_error,
-    lean_is_exclusive_obj, lean_manual_get_root, lean_name_hash, lean_nat_div_exact, lean_nat_gcd,
-    lean_ptr_addr, lean_runtime_forget, lean_runtime_mark_multi_threaded,
-    lean_runtime_mark_persistent, lean_set_panic_messages, lean_strict_and, lean_strict_or,
-    lean_system_platform_target, lean_version_get_is_release, lean_version_get_major,
-    lean_version_get_minor, lean_version_get_patch, lean_version_get_special_desc, lean_void_mk,
+    lean_dbg_sleep, lean_expr_data, lean_get_githash, lean_get_max_ctor_fields,
+    lean_get_max_ctor_scalars_size, lean_get_max_ctor_tag, lean_get_usize_size,
+    lean_hashmap_mk_idx, lean_hashset_mk_idx, lean_initialize_runtime_module,
+    lean_internal_has_llvm_backend, lean_internal_is_stage0, lean_io_initializing,
+    lean_io_mark_end_initialization, lean_io_result_show_error, lean_is_exclusive_obj,
+    lean_manual_get_root, lean_name_hash, lean_nat_div_exact, lean_nat_gcd, lean_ptr_addr,
+    lean_runtime_forget, lean_runtime_mark_multi_threaded, lean_runtime_mark_persistent,
+    lean_set_panic_messages, lean_strict_and, lean_strict_or, lean_system_platform_target,
+    lean_version_get_is_release, lean_version_get_major, lean_version_get_minor,
+    lean_version_get_patch, lean_version_get_special_desc, lean_void_mk,
 };
 
 // Convenience functions
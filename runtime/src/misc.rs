@@ -67,9 +67,9 @@ pub unsafe fn lean_dbg_trace(s: *mut LeanObject, thunk: *mut LeanObject) -> *mut
     crate::lean_apply_1(thunk, crate::lean_box(0))
 }
 
-/// dbg_trace_if_shared: if the object is shared (rc > 1), print trace.
+/// dbg_trace_if_shared: if the object is shared, print trace.
 pub unsafe fn lean_dbg_trace_if_shared(s: *mut LeanObject, a: *mut LeanObject) -> *mut LeanObject {
-    if !crate::lean_is_scalar(a) && (*a).rc > 1 {
+    if !crate::lean_is_scalar(a) && crate::lean_is_shared(a) {
         eprintln!("[lean-debug] shared: {}", crate::lean_string_to_str(s));
     }
     crate::lean_dec(s);
@@ -1,6 +1,6 @@
 //! Core Lean object representation
 
-use std::alloc::{alloc, dealloc, Layout};
+use std::cell::Cell;
 
 pub const LEAN_CLOSURE_TAG: u8 = 245;
 pub const LEAN_ARRAY_TAG: u8 = 246;
@@ -9,6 +9,7 @@ pub const LEAN_STRING_TAG: u8 = 249;
 pub const LEAN_MPZ_TAG: u8 = 250;
 pub const LEAN_THUNK_TAG: u8 = 247;
 pub const LEAN_BIGINT_TAG: u8 = 251;
+pub const LEAN_TASK_TAG: u8 = 252;
 pub const LEAN_REF_TAG: u8 = 253;
 pub const LEAN_EXTERNAL_TAG: u8 = 254;
 pub const LEAN_MAX_CTOR_TAG: u8 = 244;
@@ -51,20 +52,58 @@ pub const fn lean_ctor_object_size(num_objs: u32, scalar_sz: u32) -> usize {
     LEAN_OBJECT_SIZE + (num_objs as usize) * 8 + scalar_sz as usize
 }
 
+/// Objects up to this size count as small allocations (and heartbeats).
+pub const LEAN_MAX_SMALL_OBJECT_SIZE: usize = 4096;
+
+thread_local! {
+    static HEARTBEATS: Cell<u64> = const { Cell::new(0) };
+    /// Bytes of objects allocated minus bytes freed by this thread, wrapping
+    static ALLOCATED_BYTES: Cell<usize> = const { Cell::new(0) };
+}
+
+/// Count one heartbeat on the current thread.
+#[inline(always)]
+pub fn lean_inc_heartbeat() {
+    HEARTBEATS.with(|h| h.set(h.get().wrapping_add(1)));
+}
+
+/// Number of heartbeats counted on the current thread. Like upstream Lean,
+/// every small object allocation is a heartbeat.
+pub fn lean_get_num_heartbeats() -> u64 {
+    HEARTBEATS.with(|h| h.get())
+}
+
+/// Reset the current thread's heartbeat counter to `n`.
+pub fn lean_set_heartbeats(n: u64) {
+    HEARTBEATS.with(|h| h.set(n));
+}
+
+/// Bytes of Lean objects allocated by the current thread minus those it
+/// freed, wrapping around. Only the difference between two readings on the
+/// same thread is meaningful, and it is negative (as `isize`) if the thread
+/// freed more than it allocated in between, e.g. objects of other threads.
+/// Memory owned by objects outside their allocation (e.g. big number limbs)
+/// is not counted.
+pub fn lean_get_allocated_bytes() -> usize {
+    ALLOCATED_BYTES.with(|b| b.get())
+}
+
 #[inline]
 pub unsafe fn lean_alloc_object(size: usize) -> *mut LeanObject {
-    let layout = Layout::from_size_align_unchecked(size, 8);
-    let ptr = alloc(layout) as *mut LeanObject;
-    if ptr.is_null() {
-        std::alloc::handle_alloc_error(layout);
+    if size <= LEAN_MAX_SMALL_OBJECT_SIZE {
+        lean_inc_heartbeat();
     }
+    let ptr = crate::heap::lean_heap_alloc(size) as *mut LeanObject;
+    ALLOCATED_BYTES.with(|b| b.set(b.get().wrapping_add(size)));
+    crate::allocprof::on_alloc(ptr, size);
     ptr
 }
 
 #[inline]
 pub unsafe fn lean_free_object(o: *mut LeanObject, size: usize) {
+    crate::allocprof::on_free(o);
+    ALLOCATED_BYTES.with(|b| b.set(b.get().wrapping_sub(size)));
     #[cfg(any(debug_assertions, feature = "runtime-debug"))]
     crate::debug::lean_debug_poison(o);
-    let layout = Layout::from_size_align_unchecked(size, 8);
-    dealloc(o as *mut u8, layout);
+    crate::heap::lean_heap_free(o as *mut u8, size);
 }
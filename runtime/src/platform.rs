@@ -82,18 +82,17 @@ pub unsafe fn lean_initialize_runtime_module() -> *mut LeanObject {
 
 pub fn lean_io_mark_end_initialization() {}
 pub fn lean_set_panic_messages(_val: u8) {}
-pub fn lean_init_task_manager() {}
-pub fn lean_finalize_task_manager() {}
 
-// IO result error display
+// IO result error display, as done for an uncaught error from `main`
 pub unsafe fn lean_io_result_show_error(r: *mut LeanObject) {
     if crate::io::lean_io_result_is_ok(r) {
         return;
     }
-    let err = crate::lean_ctor_get(r, 0);
-    // Try to extract a string from the error
-    eprintln!("[lean-runtime] IO error");
-    let _ = err;
+    let err = crate::io::lean_io_result_get_error(r);
+    crate::lean_inc(err);
+    let s = crate::io::lean_io_error_to_string(err);
+    eprintln!("uncaught exception: {}", crate::lean_string_to_str(s));
+    crate::lean_dec(s);
 }
 
 // IO initializing flag
@@ -164,9 +163,13 @@ pub unsafe fn lean_manual_get_root(_unit: *mut LeanObject) -> *mut LeanObject {
     crate::string::lean_mk_string("")
 }
 
-// Runtime mark functions (IO-wrapped stubs)
-pub unsafe fn lean_runtime_mark_multi_threaded(_unit: *mut LeanObject) -> *mut LeanObject {
-    crate::io::lean_io_result_mk_ok(crate::lean_box(0))
+// Runtime mark functions
+pub unsafe fn lean_runtime_mark_multi_threaded(
+    a: *mut LeanObject,
+    _unit: *mut LeanObject,
+) -> *mut LeanObject {
+    crate::lean_mark_mt(a);
+    crate::io::lean_io_result_mk_ok(a)
 }
 
 pub unsafe fn lean_runtime_mark_persistent(
@@ -1,7 +1,17 @@
 //! Reference counting
+//!
+//! Follows the `lean.h` convention: `rc > 0` is a single-threaded object updated
+//! with plain arithmetic, `rc < 0` is a multi-threaded object whose count is
+//! `-rc` and is updated atomically, and `rc == 0` is persistent (never freed).
 
 use crate::object::*;
 use crate::r#box::lean_is_scalar;
+use std::sync::atomic::{AtomicI32, Ordering};
+
+#[inline(always)]
+unsafe fn rc_atomic<'a>(o: *mut LeanObject) -> &'a AtomicI32 {
+    AtomicI32::from_ptr(&raw mut (*o).rc)
+}
 
 #[inline(always)]
 pub unsafe fn lean_inc(o: *mut LeanObject) {
@@ -20,8 +30,8 @@ pub unsafe fn lean_dec(o: *mut LeanObject) {
 /// Increment reference count by n. Used for bulk inc operations.
 #[inline(always)]
 pub unsafe fn lean_inc_n(o: *mut LeanObject, n: u32) {
-    if !lean_is_scalar(o) && (*o).rc > 0 {
-        (*o).rc += n as i32;
+    if !lean_is_scalar(o) {
+        lean_inc_ref_n(o, n);
     }
 }
 
@@ -31,6 +41,8 @@ pub unsafe fn lean_inc_ref(o: *mut LeanObject) {
     crate::debug::lean_debug_assert_valid(o, "lean_inc_ref");
     if (*o).rc > 0 {
         (*o).rc += 1;
+    } else if (*o).rc != 0 {
+        rc_atomic(o).fetch_sub(1, Ordering::Relaxed);
     }
 }
 
@@ -41,6 +53,8 @@ pub unsafe fn lean_inc_ref_n(o: *mut LeanObject, n: u32) {
     crate::debug::lean_debug_assert_valid(o, "lean_inc_ref_n");
     if (*o).rc > 0 {
         (*o).rc += n as i32;
+    } else if (*o).rc != 0 {
+        rc_atomic(o).fetch_sub(n as i32, Ordering::Relaxed);
     }
 }
 
@@ -48,11 +62,34 @@ pub unsafe fn lean_inc_ref_n(o: *mut LeanObject, n: u32) {
 pub unsafe fn lean_dec_ref(o: *mut LeanObject) {
     #[cfg(any(debug_assertions, feature = "runtime-debug"))]
     crate::debug::lean_debug_assert_valid(o, "lean_dec_ref");
-    if (*o).rc > 0 {
-        (*o).rc -= 1;
-        if (*o).rc == 0 {
-            lean_free_object_full(o);
-        }
+    if lean_dec_ref_core(o) {
+        lean_free_object_full(o);
+    }
+}
+
+/// Drop one reference without freeing. Returns true if it was the last one.
+#[inline(always)]
+unsafe fn lean_dec_ref_core(o: *mut LeanObject) -> bool {
+    let rc = (*o).rc;
+    if rc > 1 {
+        (*o).rc = rc - 1;
+        false
+    } else if rc == 1 {
+        (*o).rc = 0;
+        true
+    } else if rc != 0 {
+        rc_atomic(o).fetch_add(1, Ordering::AcqRel) == -1
+    } else {
+        false
+    }
+}
+
+/// Drop a reference held by an object being freed, queueing the child if it
+/// was the last one.
+#[inline(always)]
+unsafe fn dec_child(child: *mut LeanObject, worklist: &mut Vec<*mut LeanObject>) {
+    if !lean_is_scalar(child) && lean_dec_ref_core(child) {
+        worklist.push(child);
     }
 }
 
@@ -68,13 +105,7 @@ pub unsafe fn lean_free_object_full(o: *mut LeanObject) {
             let num_objs = (*obj).num_objs();
             let objs = (*obj).obj_fields_ptr();
             for i in 0..num_objs {
-                let child = *objs.add(i as usize);
-                if !lean_is_scalar(child) && (*child).rc > 0 {
-                    (*child).rc -= 1;
-                    if (*child).rc == 0 {
-                        worklist.push(child);
-                    }
-                }
+                dec_child(*objs.add(i as usize), &mut worklist);
             }
             lean_free_object(obj, lean_ctor_object_size(num_objs, (*obj).cs_sz as u32));
         } else if tag == LEAN_CLOSURE_TAG {
@@ -95,13 +126,7 @@ pub unsafe fn lean_free_object_full(o: *mut LeanObject) {
             let arr = obj as *mut crate::array::LeanArray;
             let data = (*arr).data_ptr();
             for i in 0..(*arr).size {
-                let child = *data.add(i);
-                if !lean_is_scalar(child) && (*child).rc > 0 {
-                    (*child).rc -= 1;
-                    if (*child).rc == 0 {
-                        worklist.push(child);
-                    }
-                }
+                dec_child(*data.add(i), &mut worklist);
             }
             lean_free_object(
                 obj,
@@ -128,6 +153,17 @@ pub unsafe fn lean_free_object_full(o: *mut LeanObject) {
                 lean_dec(closure);
             }
             lean_free_object(obj, std::mem::size_of::<crate::thunk::LeanThunk>());
+        } else if tag == LEAN_TASK_TAG {
+            let task = obj as *mut crate::task::LeanTask;
+            let value = *(*task).value.get_mut();
+            if !value.is_null() {
+                lean_dec(value);
+            }
+            let imp = (*task).imp;
+            if !imp.is_null() {
+                crate::task::lean_free_task_imp(imp);
+            }
+            lean_free_object(obj, std::mem::size_of::<crate::task::LeanTask>());
         } else if tag == LEAN_REF_TAG {
             let r = obj as *mut crate::stref::LeanRef;
             let value = (*r).value;
@@ -192,6 +228,69 @@ pub unsafe fn lean_is_exclusive(o: *mut LeanObject) -> bool {
     (*o).rc == 1
 }
 
+/// True if `o` has more than one reference (in either RC mode).
+#[inline(always)]
+pub unsafe fn lean_is_shared(o: *mut LeanObject) -> bool {
+    let rc = (*o).rc;
+    !(-1..=1).contains(&rc)
+}
+
+/// True if `o` is a multi-threaded object (negative RC).
+#[inline(always)]
+pub unsafe fn lean_is_mt(o: *mut LeanObject) -> bool {
+    (*o).rc < 0
+}
+
+/// Mark `o` and every single-threaded object reachable from it as
+/// multi-threaded, so their counts are updated atomically from now on.
+/// Persistent and already multi-threaded objects are not traversed.
+pub unsafe fn lean_mark_mt(o: *mut LeanObject) {
+    if lean_is_scalar(o) || (*o).rc <= 0 {
+        return;
+    }
+    let mut worklist: Vec<*mut LeanObject> = vec![o];
+    let visit = |child: *mut LeanObject, worklist: &mut Vec<*mut LeanObject>| {
+        if !child.is_null() && !lean_is_scalar(child) && (*child).rc > 0 {
+            worklist.push(child);
+        }
+    };
+    while let Some(obj) = worklist.pop() {
+        if (*obj).rc <= 0 {
+            continue;
+        }
+        (*obj).rc = -(*obj).rc;
+        let tag = (*obj).tag;
+        if tag <= LEAN_MAX_CTOR_TAG {
+            let objs = (*obj).obj_fields_ptr();
+            for i in 0..(*obj).num_objs() {
+                visit(*objs.add(i as usize), &mut worklist);
+            }
+        } else if tag == LEAN_CLOSURE_TAG {
+            let closure = obj as *mut crate::closure::LeanClosure;
+            let args = (*closure).fixed_args_ptr();
+            for i in 0..(*closure).num_fixed {
+                visit(*args.add(i as usize), &mut worklist);
+            }
+        } else if tag == LEAN_ARRAY_TAG {
+            let arr = obj as *mut crate::array::LeanArray;
+            let data = (*arr).data_ptr();
+            for i in 0..(*arr).size {
+                visit(*data.add(i), &mut worklist);
+            }
+        } else if tag == LEAN_THUNK_TAG {
+            let thunk = obj as *mut crate::thunk::LeanThunk;
+            visit((*thunk).value, &mut worklist);
+            visit((*thunk).closure, &mut worklist);
+        } else if tag == LEAN_TASK_TAG {
+            let task = obj as *mut crate::task::LeanTask;
+            visit(*(*task).value.get_mut(), &mut worklist);
+        } else if tag == LEAN_REF_TAG {
+            let r = obj as *mut crate::stref::LeanRef;
+            visit((*r).value, &mut worklist);
+        }
+    }
+}
+
 #[inline(always)]
 pub unsafe fn lean_mark_persistent(o: *mut LeanObject) {
     (*o).rc = 0;
@@ -343,7 +442,7 @@ mod tests {
     #[test]
     fn free_closure_with_fixed_args() {
         unsafe {
-            unsafe fn dummy(_: *mut LeanObject, _: *mut LeanObject) -> *mut LeanObject {
+            unsafe extern "C" fn dummy(_: *mut LeanObject, _: *mut LeanObject) -> *mut LeanObject {
                 crate::lean_box(0)
             }
             let s = crate::lean_mk_string("captured");
@@ -375,4 +474,51 @@ mod tests {
             lean_dec(arr);
         }
     }
+
+    #[test]
+    fn mark_mt_walks_graph() {
+        unsafe {
+            let s = crate::lean_mk_string("shared");
+            let arr = crate::lean_array_push(crate::lean_mk_empty_array(), s);
+            let parent = crate::lean_alloc_ctor(0, 1, 0);
+            crate::lean_ctor_set(parent, 0, arr);
+            lean_mark_mt(parent);
+            assert_eq!((*parent).rc, -1);
+            assert_eq!((*arr).rc, -1);
+            assert_eq!((*s).rc, -1);
+            assert!(lean_is_mt(s));
+            lean_inc_ref(parent);
+            assert_eq!((*parent).rc, -2);
+            assert!(lean_is_shared(parent));
+            assert!(!lean_is_exclusive(parent));
+            lean_dec_ref(parent);
+            assert_eq!((*parent).rc, -1);
+            lean_dec(parent); // frees the whole graph
+        }
+    }
+
+    #[test]
+    fn mt_inc_dec_across_threads() {
+        struct Ptr(*mut LeanObject);
+        unsafe impl Send for Ptr {}
+        unsafe impl Sync for Ptr {}
+        unsafe {
+            let obj = crate::lean_mk_string("contended");
+            lean_mark_mt(obj);
+            let p = Ptr(obj);
+            std::thread::scope(|scope| {
+                for _ in 0..4 {
+                    let p = &p;
+                    scope.spawn(move || {
+                        for _ in 0..10_000 {
+                            lean_inc_ref(p.0);
+                            lean_dec_ref(p.0);
+                        }
+                    });
+                }
+            });
+            assert_eq!((*obj).rc, -1);
+            lean_dec(obj);
+        }
+    }
 }
@@ -0,0 +1,399 @@
+//! Maximal sharing (`ShareCommon`)
+//!
+//! `shareCommon'` rebuilds an object graph bottom-up so that structurally
+//! equal subterms become the same object, as upstream Lean's
+//! `sharecommon.cpp` does. Equality and hashing are shallow: two objects are
+//! equal when they have the same kind, header and scalar data, and their
+//! children are the same objects. Since children are shared first, this is
+//! structural equality of the whole subterm.
+//!
+//! Constructors and arrays are rebuilt from their shared children, reusing
+//! the original object when no child changed. Strings, scalar arrays and big
+//! numbers are deduplicated as they are. Closures, thunks, tasks, references
+//! and external objects are kept, without visiting what they reference.
+//!
+//! The stateful variant keeps the set of shared objects between calls in an
+//! external object, so later values are shared with earlier ones.
+
+use crate::array::LeanArray;
+use crate::bignat::lean_bignat_value;
+use crate::external::{lean_alloc_external, lean_get_external_class, LeanExternalClass};
+use crate::int::lean_bigint_value;
+use crate::object::*;
+use crate::r#box::lean_is_scalar;
+use crate::rc::{lean_dec, lean_inc, lean_is_exclusive};
+use crate::sarray::LeanSArray;
+use crate::string::LeanString;
+use std::collections::{HashMap, HashSet};
+use std::hash::{DefaultHasher, Hash, Hasher};
+
+/// The bytes that determine `o` given the identity of its children, or
+/// `None` for objects compared by address or value.
+unsafe fn contents(o: *mut LeanObject) -> Option<&'static [u8]> {
+    let (data, len) = match (*o).tag {
+        0..=LEAN_MAX_CTOR_TAG => (
+            (*o).obj_fields_ptr() as *const u8,
+            (*o).num_objs() as usize * 8 + (*o).cs_sz as usize,
+        ),
+        LEAN_ARRAY_TAG => {
+            let arr = o as *mut LeanArray;
+            ((*arr).data_ptr() as *const u8, (*arr).size * 8)
+        }
+        LEAN_SCALAR_ARRAY_TAG => {
+            let arr = o as *mut LeanSArray;
+            (
+                (*arr).data_ptr() as *const u8,
+                (*arr).size * (*o).other as usize,
+            )
+        }
+        LEAN_STRING_TAG => {
+            let s = o as *mut LeanString;
+            ((*s).data_ptr() as *const u8, (*s).byte_len)
+        }
+        _ => return None,
+    };
+    Some(std::slice::from_raw_parts(data, len))
+}
+
+/// Shallow structural equality of two objects (both borrowed).
+pub unsafe fn lean_sharecommon_eq(a: *mut LeanObject, b: *mut LeanObject) -> u8 {
+    if a == b {
+        return 1;
+    }
+    if lean_is_scalar(a) || lean_is_scalar(b) {
+        return 0;
+    }
+    let tag = (*a).tag;
+    if tag != (*b).tag || (*a).other != (*b).other {
+        return 0;
+    }
+    let eq = match tag {
+        0..=LEAN_MAX_CTOR_TAG if (*a).cs_sz != (*b).cs_sz => false,
+        LEAN_MPZ_TAG => lean_bignat_value(a) == lean_bignat_value(b),
+        LEAN_BIGINT_TAG => lean_bigint_value(a) == lean_bigint_value(b),
+        _ => match (contents(a), contents(b)) {
+            (Some(x), Some(y)) => x == y,
+            _ => false,
+        },
+    };
+    eq as u8
+}
+
+/// Shallow structural hash of an object (borrowed), consistent with
+/// [`lean_sharecommon_eq`].
+pub unsafe fn lean_sharecommon_hash(a: *mut LeanObject) -> u64 {
+    if lean_is_scalar(a) {
+        return a as u64;
+    }
+    let mut h = DefaultHasher::new();
+    (*a).tag.hash(&mut h);
+    (*a).other.hash(&mut h);
+    match (*a).tag {
+        LEAN_MPZ_TAG => lean_bignat_value(a).hash(&mut h),
+        LEAN_BIGINT_TAG => lean_bigint_value(a).hash(&mut h),
+        _ => match contents(a) {
+            Some(bytes) => bytes.hash(&mut h),
+            None => (a as usize).hash(&mut h),
+        },
+    }
+    h.finish()
+}
+
+/// Set key comparing objects with `lean_sharecommon_eq`.
+#[derive(Clone, Copy)]
+struct Shared(*mut LeanObject);
+
+impl Hash for Shared {
+    fn hash<H: Hasher>(&self, h: &mut H) {
+        h.write_u64(unsafe { lean_sharecommon_hash(self.0) });
+    }
+}
+
+impl PartialEq for Shared {
+    fn eq(&self, other: &Self) -> bool {
+        unsafe { lean_sharecommon_eq(self.0, other.0) != 0 }
+    }
+}
+
+impl Eq for Shared {}
+
+/// The maximally shared objects seen so far. With `owned`, the set holds a
+/// reference to each of them; otherwise they are kept alive by the result
+/// being built.
+struct ShareCommon {
+    set: HashSet<Shared>,
+    owned: bool,
+}
+
+impl ShareCommon {
+    /// The shared object equal to `o` (owned), adding `o` if there is none.
+    unsafe fn intern(&mut self, o: *mut LeanObject) -> *mut LeanObject {
+        if let Some(&Shared(existing)) = self.set.get(&Shared(o)) {
+            if existing != o {
+                lean_inc(existing);
+                lean_dec(o);
+            }
+            return existing;
+        }
+        if self.owned {
+            lean_inc(o);
+        }
+        self.set.insert(Shared(o));
+        o
+    }
+
+    /// Maximally share `root` (borrowed), returning a new reference.
+    unsafe fn run(&mut self, root: *mut LeanObject) -> *mut LeanObject {
+        // Results for inputs reached more than once, by address
+        let mut cache: HashMap<*mut LeanObject, *mut LeanObject> = HashMap::new();
+        let mut results: Vec<*mut LeanObject> = Vec::new();
+        // Objects to visit, and whether their children are already on `results`
+        let mut todo = vec![(root, false)];
+        while let Some((o, children_done)) = todo.pop() {
+            if lean_is_scalar(o) {
+                results.push(o);
+                continue;
+            }
+            let tag = (*o).tag;
+            let (fields, num_fields) = match tag {
+                // Persistent objects are kept as they are
+                _ if (*o).rc == 0 => {
+                    results.push(self.intern(o));
+                    continue;
+                }
+                0..=LEAN_MAX_CTOR_TAG => ((*o).obj_fields_ptr(), (*o).num_objs() as usize),
+                LEAN_ARRAY_TAG => {
+                    let arr = o as *mut LeanArray;
+                    ((*arr).data_ptr(), (*arr).size)
+                }
+                LEAN_SCALAR_ARRAY_TAG | LEAN_STRING_TAG | LEAN_MPZ_TAG | LEAN_BIGINT_TAG => {
+                    lean_inc(o);
+                    results.push(self.intern(o));
+                    continue;
+                }
+                _ => {
+                    lean_inc(o);
+                    results.push(o);
+                    continue;
+                }
+            };
+            if !children_done {
+                if let Some(&r) = cache.get(&o) {
+                    lean_inc(r);
+                    results.push(r);
+                    continue;
+                }
+                todo.push((o, true));
+                todo.extend((0..num_fields).rev().map(|i| (*fields.add(i), false)));
+                continue;
+            }
+
+            let children = results.split_off(results.len() - num_fields);
+            let unchanged = (0..num_fields).all(|i| children[i] == *fields.add(i));
+            let candidate = if unchanged {
+                for &c in &children {
+                    lean_dec(c);
+                }
+                lean_inc(o);
+                o
+            } else if tag == LEAN_ARRAY_TAG {
+                let arr = crate::array::lean_alloc_array(num_fields, num_fields);
+                let data = (*(arr as *mut LeanArray)).data_ptr();
+                std::ptr::copy_nonoverlapping(children.as_ptr(), data, num_fields);
+                arr
+            } else {
+                let scalar_sz = (*o).cs_sz as usize;
+                let ctor =
+                    lean_alloc_object(lean_ctor_object_size(num_fields as u32, scalar_sz as u32));
+                *ctor = LeanObject { rc: 1, ..*o };
+                std::ptr::copy_nonoverlapping(
+                    children.as_ptr(),
+                    (*ctor).obj_fields_ptr(),
+                    num_fields,
+                );
+                std::ptr::copy_nonoverlapping(
+                    (*o).scalar_data_ptr(),
+                    (*ctor).scalar_data_ptr(),
+                    scalar_sz,
+                );
+                ctor
+            };
+            let r = self.intern(candidate);
+            if !lean_is_exclusive(o) {
+                cache.insert(o, r);
+            }
+            results.push(r);
+        }
+        results.pop().unwrap()
+    }
+}
+
+/// `ShareCommon.shareCommon'`: maximally share `a` (borrowed).
+pub unsafe fn lean_sharecommon_quick(a: *mut LeanObject) -> *mut LeanObject {
+    ShareCommon {
+        set: HashSet::new(),
+        owned: false,
+    }
+    .run(a)
+}
+
+static STATE_CLASS: LeanExternalClass = LeanExternalClass {
+    finalize: Some(state_finalize),
+    foreach: None,
+};
+
+unsafe extern "C" fn state_finalize(data: *mut std::ffi::c_void) {
+    let set = Box::from_raw(data as *mut HashSet<Shared>);
+    for Shared(o) in *set {
+        lean_dec(o);
+    }
+}
+
+/// `ShareCommon.State.shareCommon`: maximally share `a` (owned) together
+/// with the objects already in `st` (owned), returning the shared value and
+/// the new state. The state factory `tc` (borrowed) is not needed, as the
+/// state is kept in an external object; any state not created here, such as
+/// the factory's initial one, counts as empty.
+pub unsafe fn lean_state_sharecommon(
+    _tc: *mut LeanObject,
+    st: *mut LeanObject,
+    a: *mut LeanObject,
+) -> *mut LeanObject {
+    let is_state = !lean_is_scalar(st)
+        && (*st).tag == LEAN_EXTERNAL_TAG
+        && std::ptr::eq(lean_get_external_class(st), &STATE_CLASS);
+    let set = if !is_state {
+        lean_dec(st);
+        HashSet::new()
+    } else if lean_is_exclusive(st) {
+        let ext = st as *mut crate::external::LeanExternal;
+        let set = Box::from_raw((*ext).data as *mut HashSet<Shared>);
+        // Free the object without finalizing the set we took
+        lean_free_object(st, std::mem::size_of::<crate::external::LeanExternal>());
+        *set
+    } else {
+        // The old state stays valid
+        let set = (*(crate::external::lean_get_external_data(st) as *mut HashSet<Shared>)).clone();
+        for &Shared(o) in &set {
+            lean_inc(o);
+        }
+        lean_dec(st);
+        set
+    };
+    let mut sc = ShareCommon { set, owned: true };
+    let r = sc.run(a);
+    lean_dec(a);
+    let st = lean_alloc_external(
+        &STATE_CLASS as *const _ as *mut _,
+        Box::into_raw(Box::new(sc.set)) as *mut std::ffi::c_void,
+    );
+    let pair = crate::lean_alloc_ctor(0, 2, 0);
+    crate::lean_ctor_set(pair, 0, r);
+    crate::lean_ctor_set(pair, 1, st);
+    pair
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::*;
+
+    /// `n :: ... :: 1 :: []` as fresh, unshared cells.
+    unsafe fn list(n: usize) -> *mut LeanObject {
+        let mut l = lean_box(0);
+        for i in 1..=n {
+            let cons = lean_alloc_ctor(1, 2, 0);
+            lean_ctor_set(cons, 0, lean_mk_string(&format!("s{}", i)));
+            lean_ctor_set(cons, 1, l);
+            l = cons;
+        }
+        l
+    }
+
+    #[test]
+    fn shallow_equality() {
+        unsafe {
+            let a = lean_mk_string("x");
+            let b = lean_mk_string("x");
+            assert_eq!(lean_sharecommon_eq(a, b), 1);
+            assert_eq!(lean_sharecommon_hash(a), lean_sharecommon_hash(b));
+            let pa = lean_alloc_ctor(0, 1, 0);
+            lean_ctor_set(pa, 0, a);
+            let pb = lean_alloc_ctor(0, 1, 0);
+            lean_ctor_set(pb, 0, b);
+            // Children are compared by address
+            assert_eq!(lean_sharecommon_eq(pa, pb), 0);
+            let n = lean_alloc_bignat(num_bigint::BigUint::from(1u8) << 80u32);
+            let m = lean_alloc_bignat(num_bigint::BigUint::from(1u8) << 80u32);
+            assert_eq!(lean_sharecommon_eq(n, m), 1);
+            assert_eq!(lean_sharecommon_eq(n, pa), 0);
+            for o in [pa, pb, n, m] {
+                lean_dec(o);
+            }
+        }
+    }
+
+    #[test]
+    fn quick_shares_equal_subterms() {
+        unsafe {
+            // #[[s2, s1], [s2, s1], [s3, s2, s1]]
+            let arr = lean_mk_empty_array_with_capacity(lean_box(3));
+            let arr = lean_array_push(arr, list(2));
+            let arr = lean_array_push(arr, list(2));
+            let arr = lean_array_push(arr, list(3));
+            let r = lean_sharecommon_quick(arr);
+            let (a, b, c) = (
+                lean_array_get_core(r, 0),
+                lean_array_get_core(r, 1),
+                lean_array_get_core(r, 2),
+            );
+            assert_eq!(a, b);
+            assert_eq!(lean_ctor_get(c, 1), a);
+            assert_eq!(lean_string_to_str(lean_ctor_get(a, 0)), "s2");
+            lean_dec(arr);
+            assert!(!lean_is_exclusive(a));
+            lean_dec(r);
+        }
+    }
+
+    #[test]
+    fn quick_reuses_maximally_shared_input() {
+        unsafe {
+            let l = list(3);
+            let r = lean_sharecommon_quick(l);
+            assert_eq!(r, l);
+            lean_dec(r);
+            lean_dec(l);
+
+            let deep = list(100_000);
+            let r = lean_sharecommon_quick(deep);
+            assert_eq!(r, deep);
+            lean_dec(r);
+            lean_dec(deep);
+        }
+    }
+
+    #[test]
+    fn state_persists_between_calls() {
+        unsafe {
+            let first = list(2);
+            let p = lean_state_sharecommon(lean_box(0), lean_box(0), first);
+            let (r1, st) = (lean_ctor_get(p, 0), lean_ctor_get(p, 1));
+            lean_inc(r1);
+            lean_inc(st);
+            lean_dec(p);
+
+            // A shared state is copied, and stays usable
+            lean_inc(st);
+            let second = list(3);
+            lean_inc(second);
+            let q = lean_state_sharecommon(lean_box(0), st, second);
+            assert_eq!(lean_ctor_get(lean_ctor_get(q, 0), 1), r1);
+            lean_dec(q);
+            let q = lean_state_sharecommon(lean_box(0), st, second);
+            assert_eq!(lean_ctor_get(lean_ctor_get(q, 0), 1), r1);
+            lean_dec(q);
+            lean_dec(r1);
+        }
+    }
+}
@@ -1,6 +1,7 @@
 //! ST/IO Ref primitives.
 
 use crate::object::LeanObject;
+use std::sync::atomic::{AtomicPtr, Ordering};
 
 const LEAN_REF_TAG: u8 = 253;
 
@@ -10,6 +11,30 @@ pub struct LeanRef {
     pub value: *mut LeanObject,
 }
 
+/// The value slot of a multi-threaded ref. A reader briefly swaps in null, so
+/// other threads spin until the value is put back.
+#[inline(always)]
+unsafe fn mt_value<'a>(r: *mut LeanRef) -> &'a AtomicPtr<LeanObject> {
+    AtomicPtr::from_ptr(&raw mut (*r).value)
+}
+
+/// Swap `new_val` into a multi-threaded ref, returning the old value.
+unsafe fn mt_exchange(r: *mut LeanRef, new_val: *mut LeanObject) -> *mut LeanObject {
+    crate::lean_mark_mt(new_val);
+    let slot = mt_value(r);
+    loop {
+        let cur = slot.load(Ordering::Acquire);
+        if !cur.is_null()
+            && slot
+                .compare_exchange_weak(cur, new_val, Ordering::AcqRel, Ordering::Acquire)
+                .is_ok()
+        {
+            return cur;
+        }
+        std::hint::spin_loop();
+    }
+}
+
 /// ST.mkRef: create a new mutable reference.
 pub unsafe fn lean_st_mk_ref(a: *mut LeanObject) -> *mut LeanObject {
     let size = std::mem::size_of::<LeanRef>();
@@ -26,6 +51,17 @@ pub unsafe fn lean_st_mk_ref(a: *mut LeanObject) -> *mut LeanObject {
 /// ST.Ref.get: read the reference value (borrowed ref arg).
 pub unsafe fn lean_st_ref_get(ref_obj: *mut LeanObject) -> *mut LeanObject {
     let r = ref_obj as *mut LeanRef;
+    if crate::lean_is_mt(ref_obj) {
+        loop {
+            let v = mt_value(r).swap(std::ptr::null_mut(), Ordering::AcqRel);
+            if !v.is_null() {
+                crate::lean_inc(v);
+                mt_value(r).store(v, Ordering::Release);
+                return crate::io::lean_io_result_mk_ok(v);
+            }
+            std::hint::spin_loop();
+        }
+    }
     let v = (*r).value;
     crate::lean_inc(v);
     crate::io::lean_io_result_mk_ok(v)
@@ -37,8 +73,11 @@ pub unsafe fn lean_st_ref_set(
     new_val: *mut LeanObject,
 ) -> *mut LeanObject {
     let r = ref_obj as *mut LeanRef;
-    let old = (*r).value;
-    (*r).value = new_val;
+    let old = if crate::lean_is_mt(ref_obj) {
+        mt_exchange(r, new_val)
+    } else {
+        std::mem::replace(&mut (*r).value, new_val)
+    };
     crate::lean_dec(old);
     crate::io::lean_io_result_mk_ok(crate::lean_box(0))
 }
@@ -54,17 +93,17 @@ pub unsafe fn lean_st_ref_swap(
     new_val: *mut LeanObject,
 ) -> *mut LeanObject {
     let r = ref_obj as *mut LeanRef;
-    let old = (*r).value;
-    (*r).value = new_val;
+    let old = if crate::lean_is_mt(ref_obj) {
+        mt_exchange(r, new_val)
+    } else {
+        std::mem::replace(&mut (*r).value, new_val)
+    };
     crate::io::lean_io_result_mk_ok(old)
 }
 
 /// ST.Ref.take: take the value out, replacing with unit.
 pub unsafe fn lean_st_ref_take(ref_obj: *mut LeanObject) -> *mut LeanObject {
-    let r = ref_obj as *mut LeanRef;
-    let old = (*r).value;
-    (*r).value = crate::lean_box(0);
-    crate::io::lean_io_result_mk_ok(old)
+    lean_st_ref_swap(ref_obj, crate::lean_box(0))
 }
 
 /// ST.Ref.ptrEq: check if two refs are the same pointer.
@@ -0,0 +1,832 @@
+//! Task primitives and the task manager.
+//!
+//! A task is a heap object (tag 252) whose `value` is null until it has
+//! finished. Pending tasks carry a `LeanTaskImp` with the closure still to run,
+//! the task they are waiting on, and the tasks waiting on them.
+//!
+//! Until `lean_init_task_manager` is called, tasks are evaluated eagerly on the
+//! calling thread, like the C runtime does when no task manager exists. The
+//! exception is mapping or binding an unresolved promise: evaluating that
+//! eagerly would block forever, so it starts the task manager with the default
+//! number of workers. Once the manager is running, tasks at priority `0..=LEAN_MAX_PRIO` run on a pool of worker
+//! threads (highest priority first), and tasks above `LEAN_MAX_PRIO`
+//! (`Task.Priority.dedicated`) get a thread of their own. Tasks created by the
+//! manager, their closures, and their results are marked multi-threaded.
+//!
+//! The result task of a promise has no closure; it stays pending until
+//! `lean_task_resolve` is called on it.
+
+use crate::object::{LeanObject, LEAN_TASK_TAG};
+use std::cell::Cell;
+use std::collections::BinaryHeap;
+use std::sync::atomic::{AtomicBool, AtomicPtr, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, OnceLock};
+use std::thread::JoinHandle;
+
+/// `Task.Priority.max`; anything above runs on a dedicated thread.
+pub const LEAN_MAX_PRIO: u32 = 8;
+
+#[repr(C)]
+pub struct LeanTask {
+    pub header: LeanObject,
+    pub value: AtomicPtr<LeanObject>,
+    pub imp: *mut LeanTaskImp,
+}
+
+/// Scheduling state of a pending task. Only touched with the manager lock held.
+pub struct LeanTaskImp {
+    /// `Unit -> α` closure still to run (owned). Null while the task is running.
+    pub closure: *mut LeanObject,
+    /// Task that must finish before `closure` may run (owned), or null.
+    pub dep: *mut LeanObject,
+    /// Tasks whose `dep` is this task (each entry owns a reference).
+    pub waiters: Vec<*mut LeanObject>,
+    pub prio: u32,
+    /// Set by `IO.cancel`; read by the task itself through `IO.checkCanceled`.
+    pub canceled: AtomicBool,
+    /// True while a thread is evaluating `closure`.
+    pub running: bool,
+}
+
+impl LeanTaskImp {
+    fn new(closure: *mut LeanObject, dep: *mut LeanObject, prio: u32) -> *mut LeanTaskImp {
+        Box::into_raw(Box::new(LeanTaskImp {
+            closure,
+            dep,
+            waiters: Vec::new(),
+            prio,
+            canceled: AtomicBool::new(false),
+            running: false,
+        }))
+    }
+}
+
+/// Task pointer handed between threads; ownership of one reference travels with it.
+struct TaskRef(*mut LeanObject);
+
+unsafe impl Send for TaskRef {}
+
+struct QueuedTask {
+    prio: u32,
+    seq: u64,
+    task: TaskRef,
+}
+
+impl PartialEq for QueuedTask {
+    fn eq(&self, other: &Self) -> bool {
+        self.prio == other.prio && self.seq == other.seq
+    }
+}
+
+impl Eq for QueuedTask {}
+
+impl PartialOrd for QueuedTask {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for QueuedTask {
+    // Higher priority first, FIFO within a priority.
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.prio
+            .cmp(&other.prio)
+            .then_with(|| other.seq.cmp(&self.seq))
+    }
+}
+
+struct ManagerState {
+    active: bool,
+    shutting_down: bool,
+    queue: BinaryHeap<QueuedTask>,
+    next_seq: u64,
+    max_workers: usize,
+    num_workers: usize,
+    idle_workers: usize,
+    /// Workers currently blocked in `lean_task_get`; they don't count towards `max_workers`.
+    blocked_workers: usize,
+    /// Pool workers, joined by `lean_finalize_task_manager`
+    threads: Vec<JoinHandle<()>>,
+    /// Running dedicated threads. They are detached, so finished ones don't
+    /// hold on to their resources; finalization waits for the count to drop.
+    dedicated_threads: usize,
+}
+
+struct TaskManager {
+    state: Mutex<ManagerState>,
+    /// Signalled when work is queued or the manager shuts down.
+    work_cv: Condvar,
+    /// Signalled whenever a task finishes.
+    done_cv: Condvar,
+}
+
+thread_local! {
+    static CURRENT_TASK: Cell<*mut LeanObject> = const { Cell::new(std::ptr::null_mut()) };
+    static IS_WORKER: Cell<bool> = const { Cell::new(false) };
+}
+
+fn manager() -> &'static TaskManager {
+    static MANAGER: OnceLock<TaskManager> = OnceLock::new();
+    MANAGER.get_or_init(|| TaskManager {
+        state: Mutex::new(ManagerState {
+            active: false,
+            shutting_down: false,
+            queue: BinaryHeap::new(),
+            next_seq: 0,
+            max_workers: 1,
+            num_workers: 0,
+            idle_workers: 0,
+            blocked_workers: 0,
+            threads: Vec::new(),
+            dedicated_threads: 0,
+        }),
+        work_cv: Condvar::new(),
+        done_cv: Condvar::new(),
+    })
+}
+
+fn lock_state() -> MutexGuard<'static, ManagerState> {
+    manager().state.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// ---------------------------------------------------------------------------
+// Task manager lifecycle
+// ---------------------------------------------------------------------------
+
+/// Start the task manager with one worker per available CPU.
+///
+/// `lean_task_map_core` and `lean_task_bind_core` do this themselves when
+/// given a task that has not finished yet, such as an unresolved promise, so
+/// that something is left to run the continuation. Call this or
+/// `lean_init_task_manager_using` first to choose the number of workers.
+pub fn lean_init_task_manager() {
+    lean_init_task_manager_using(default_num_workers() as u32);
+}
+
+/// Start the task manager with at most `num_workers` pool threads.
+/// Does nothing if it is already running.
+pub fn lean_init_task_manager_using(num_workers: u32) {
+    let mut st = lock_state();
+    activate(&mut st, num_workers as usize);
+}
+
+fn default_num_workers() -> usize {
+    std::thread::available_parallelism().map_or(1, |n| n.get())
+}
+
+fn activate(st: &mut ManagerState, num_workers: usize) {
+    if !st.active {
+        st.active = true;
+        st.max_workers = num_workers.max(1);
+    }
+}
+
+/// Run all queued tasks to completion, then stop and join every thread.
+pub fn lean_finalize_task_manager() {
+    let m = manager();
+    let mut st = lock_state();
+    if !st.active {
+        return;
+    }
+    st.shutting_down = true;
+    m.work_cv.notify_all();
+    // Finishing tasks may start more threads, so keep joining until none are left.
+    loop {
+        let threads = std::mem::take(&mut st.threads);
+        if threads.is_empty() {
+            if st.dedicated_threads == 0 {
+                break;
+            }
+            st = m.done_cv.wait(st).unwrap_or_else(|e| e.into_inner());
+            continue;
+        }
+        drop(st);
+        for handle in threads {
+            let _ = handle.join();
+        }
+        st = lock_state();
+    }
+    st.shutting_down = false;
+    st.active = false;
+}
+
+fn spawn_worker(st: &mut ManagerState) {
+    st.num_workers += 1;
+    let handle = std::thread::spawn(|| unsafe { worker_loop() });
+    st.threads.push(handle);
+}
+
+unsafe fn worker_loop() {
+    let m = manager();
+    IS_WORKER.with(|w| w.set(true));
+    let mut st = lock_state();
+    loop {
+        // Surplus workers started while another worker was blocked retire here.
+        if st.num_workers - st.blocked_workers > st.max_workers {
+            break;
+        }
+        if let Some(q) = st.queue.pop() {
+            drop(st);
+            run_task(q.task.0);
+            st = lock_state();
+            continue;
+        }
+        if st.shutting_down {
+            break;
+        }
+        st.idle_workers += 1;
+        st = m.work_cv.wait(st).unwrap_or_else(|e| e.into_inner());
+        st.idle_workers -= 1;
+    }
+    st.num_workers -= 1;
+}
+
+/// Make a task whose dependency is resolved runnable. Takes ownership of one
+/// reference to `t`.
+unsafe fn enqueue(st: &mut ManagerState, t: *mut LeanObject) {
+    let prio = (*(*(t as *mut LeanTask)).imp).prio;
+    if prio > LEAN_MAX_PRIO {
+        let task = TaskRef(t);
+        st.dedicated_threads += 1;
+        std::thread::spawn(move || {
+            let task = task;
+            unsafe { run_task(task.0) };
+            lock_state().dedicated_threads -= 1;
+            manager().done_cv.notify_all();
+        });
+        return;
+    }
+    let seq = st.next_seq;
+    st.next_seq += 1;
+    st.queue.push(QueuedTask {
+        prio,
+        seq,
+        task: TaskRef(t),
+    });
+    if st.idle_workers > 0 {
+        manager().work_cv.notify_one();
+    } else if st.num_workers - st.blocked_workers < st.max_workers {
+        spawn_worker(st);
+    }
+}
+
+/// Queue `t` once its dependency (if any) has finished. Takes ownership of one
+/// reference to `t`.
+unsafe fn submit(st: &mut ManagerState, t: *mut LeanObject) {
+    let imp = (*(t as *mut LeanTask)).imp;
+    let dep = std::mem::replace(&mut (*imp).dep, std::ptr::null_mut());
+    if dep.is_null() {
+        enqueue(st, t);
+        return;
+    }
+    let dep_task = dep as *mut LeanTask;
+    if (*dep_task).value.load(Ordering::Acquire).is_null() {
+        (*(*dep_task).imp).waiters.push(t);
+    } else {
+        enqueue(st, t);
+    }
+    // The closure of `t` holds its own reference to the dependency.
+    crate::lean_dec(dep);
+}
+
+/// Store the result of `t` and release everything waiting on it.
+unsafe fn resolve(st: &mut ManagerState, t: *mut LeanObject, v: *mut LeanObject) {
+    let task = t as *mut LeanTask;
+    let imp = Box::from_raw((*task).imp);
+    (*task).imp = std::ptr::null_mut();
+    crate::lean_mark_mt(v);
+    (*task).value.store(v, Ordering::Release);
+    for w in imp.waiters {
+        enqueue(st, w);
+    }
+    manager().done_cv.notify_all();
+}
+
+unsafe fn run_task(t: *mut LeanObject) {
+    let task = t as *mut LeanTask;
+    let closure = {
+        let _st = lock_state();
+        (*(*task).imp).running = true;
+        std::mem::replace(&mut (*(*task).imp).closure, std::ptr::null_mut())
+    };
+    let prev = CURRENT_TASK.with(|c| c.replace(t));
+    let v = crate::lean_apply_1(closure, crate::lean_box(0));
+    CURRENT_TASK.with(|c| c.set(prev));
+    let mut st = lock_state();
+    if v.is_null() {
+        // A bind continuation installed a new closure and dependency; wait again.
+        (*(*task).imp).running = false;
+        submit(&mut st, t);
+    } else {
+        resolve(&mut st, t, v);
+        drop(st);
+        crate::lean_dec(t);
+    }
+}
+
+unsafe fn alloc_task(value: *mut LeanObject, imp: *mut LeanTaskImp) -> *mut LeanObject {
+    let obj = crate::object::lean_alloc_object(std::mem::size_of::<LeanTask>());
+    let t = obj as *mut LeanTask;
+    (*t).header.rc = 1;
+    (*t).header.tag = LEAN_TASK_TAG;
+    (*t).header.other = 0;
+    (*t).header.cs_sz = 0;
+    std::ptr::write(&mut (*t).value, AtomicPtr::new(value));
+    (*t).imp = imp;
+    obj
+}
+
+/// Allocate a pending task and hand it to the manager (lock held).
+unsafe fn alloc_pending(
+    st: &mut ManagerState,
+    closure: *mut LeanObject,
+    dep: *mut LeanObject,
+    prio: u32,
+) -> *mut LeanObject {
+    crate::lean_mark_mt(closure);
+    let imp = LeanTaskImp::new(closure, dep, prio);
+    let t = alloc_task(std::ptr::null_mut(), imp);
+    (*t).rc = -1;
+    crate::lean_inc_ref(t);
+    submit(st, t);
+    t
+}
+
+/// Release the scheduling state of a task that is being freed.
+pub(crate) unsafe fn lean_free_task_imp(imp: *mut LeanTaskImp) {
+    let imp = Box::from_raw(imp);
+    if !imp.closure.is_null() {
+        crate::lean_dec(imp.closure);
+    }
+    if !imp.dep.is_null() {
+        crate::lean_dec(imp.dep);
+    }
+    for w in imp.waiters {
+        crate::lean_dec(w);
+    }
+}
+
+// ---------------------------------------------------------------------------
+// Task API
+// ---------------------------------------------------------------------------
+
+/// Wrap a value as an already finished task.
+pub unsafe fn lean_task_pure(a: *mut LeanObject) -> *mut LeanObject {
+    alloc_task(a, std::ptr::null_mut())
+}
+
+/// Run the closure `c : Unit -> α` as a task at priority `prio`.
+pub unsafe fn lean_task_spawn_core(c: *mut LeanObject, prio: u32) -> *mut LeanObject {
+    let mut st = lock_state();
+    if !st.active {
+        drop(st);
+        return lean_task_pure(crate::lean_apply_1(c, crate::lean_box(0)));
+    }
+    alloc_pending(&mut st, c, std::ptr::null_mut(), prio)
+}
+
+/// Task.spawn
+pub unsafe fn lean_task_spawn(c: *mut LeanObject, prio: *mut LeanObject) -> *mut LeanObject {
+    lean_task_spawn_core(c, crate::lean_unbox(prio) as u32)
+}
+
+unsafe extern "C" fn task_map_fn(
+    f: *mut LeanObject,
+    t: *mut LeanObject,
+    _unit: *mut LeanObject,
+) -> *mut LeanObject {
+    let v = lean_task_get_own(t);
+    crate::lean_apply_1(f, v)
+}
+
+/// Run `f` on the value of `t` once it is available. Starts the task manager
+/// if `t` has not finished yet.
+pub unsafe fn lean_task_map_core(
+    f: *mut LeanObject,
+    t: *mut LeanObject,
+    prio: u32,
+    _sync: bool,
+) -> *mut LeanObject {
+    let mut st = lock_state();
+    if !st.active {
+        if lean_task_is_finished(t) {
+            drop(st);
+            return lean_task_pure(crate::lean_apply_1(f, lean_task_get_own(t)));
+        }
+        // Waiting on an unresolved promise needs someone to run the
+        // continuation; see `lean_init_task_manager`.
+        activate(&mut st, default_num_workers());
+    }
+    let c = crate::lean_alloc_closure(task_map_fn as *const (), 3, 2);
+    crate::lean_closure_set(c, 0, f);
+    crate::lean_closure_set(c, 1, t);
+    crate::lean_inc_ref(t);
+    alloc_pending(&mut st, c, t, prio)
+}
+
+/// Task.map
+pub unsafe fn lean_task_map(
+    f: *mut LeanObject,
+    t: *mut LeanObject,
+    prio: *mut LeanObject,
+    sync: u8,
+) -> *mut LeanObject {
+    lean_task_map_core(f, t, crate::lean_unbox(prio) as u32, sync != 0)
+}
+
+unsafe extern "C" fn task_bind_fn2(t: *mut LeanObject, _unit: *mut LeanObject) -> *mut LeanObject {
+    lean_task_get_own(t)
+}
+
+unsafe extern "C" fn task_bind_fn1(
+    x: *mut LeanObject,
+    f: *mut LeanObject,
+    _unit: *mut LeanObject,
+) -> *mut LeanObject {
+    let t = crate::lean_apply_1(f, lean_task_get_own(x));
+    if !(*(t as *mut LeanTask))
+        .value
+        .load(Ordering::Acquire)
+        .is_null()
+    {
+        return lean_task_get_own(t);
+    }
+    // The result is another pending task: re-arm the current task to forward its
+    // value, and signal `run_task` by returning null.
+    let c = crate::lean_alloc_closure(task_bind_fn2 as *const (), 2, 1);
+    crate::lean_closure_set(c, 0, t);
+    crate::lean_mark_mt(c);
+    crate::lean_inc_ref(t);
+    let current = CURRENT_TASK.with(|c| c.get());
+    let _st = lock_state();
+    let imp = (*(current as *mut LeanTask)).imp;
+    (*imp).closure = c;
+    (*imp).dep = t;
+    std::ptr::null_mut()
+}
+
+/// Run `f` on the value of `x` once it is available, and finish with the value
+/// of the task `f` returns. Starts the task manager if `x` has not finished yet.
+pub unsafe fn lean_task_bind_core(
+    x: *mut LeanObject,
+    f: *mut LeanObject,
+    prio: u32,
+    _sync: bool,
+) -> *mut LeanObject {
+    let mut st = lock_state();
+    if !st.active {
+        if lean_task_is_finished(x) {
+            drop(st);
+            return crate::lean_apply_1(f, lean_task_get_own(x));
+        }
+        // Waiting on an unresolved promise needs someone to run the
+        // continuation; see `lean_init_task_manager`.
+        activate(&mut st, default_num_workers());
+    }
+    let c = crate::lean_alloc_closure(task_bind_fn1 as *const (), 3, 2);
+    crate::lean_closure_set(c, 0, x);
+    crate::lean_closure_set(c, 1, f);
+    crate::lean_inc_ref(x);
+    alloc_pending(&mut st, c, x, prio)
+}
+
+/// Task.bind
+pub unsafe fn lean_task_bind(
+    x: *mut LeanObject,
+    f: *mut LeanObject,
+    prio: *mut LeanObject,
+    sync: u8,
+) -> *mut LeanObject {
+    lean_task_bind_core(x, f, crate::lean_unbox(prio) as u32, sync != 0)
+}
+
+/// Allocate the unresolved result task of a promise. It has no closure and
+/// finishes only through `lean_task_resolve`.
+pub unsafe fn lean_promise_task_new() -> *mut LeanObject {
+    let imp = LeanTaskImp::new(std::ptr::null_mut(), std::ptr::null_mut(), 0);
+    let t = alloc_task(std::ptr::null_mut(), imp);
+    (*t).rc = -1;
+    t
+}
+
+/// Finish a promise task with `v`, waking its waiters and continuations.
+/// Returns false (and drops `v`) if it was already resolved.
+pub unsafe fn lean_task_resolve(t: *mut LeanObject, v: *mut LeanObject) -> bool {
+    let mut st = lock_state();
+    if lean_task_is_finished(t) {
+        drop(st);
+        crate::lean_dec(v);
+        return false;
+    }
+    resolve(&mut st, t, v);
+    true
+}
+
+/// Block until `done` holds, re-checking it whenever a task finishes.
+unsafe fn wait_until(mut done: impl FnMut() -> bool) {
+    let m = manager();
+    let mut st = lock_state();
+    if done() {
+        return;
+    }
+    let is_worker = IS_WORKER.with(|w| w.get());
+    if is_worker {
+        // Keep the pool busy while this worker is parked.
+        st.blocked_workers += 1;
+        if st.idle_workers == 0
+            && !st.queue.is_empty()
+            && st.num_workers - st.blocked_workers < st.max_workers
+        {
+            spawn_worker(&mut st);
+        }
+    }
+    while !done() {
+        st = m.done_cv.wait(st).unwrap_or_else(|e| e.into_inner());
+    }
+    if is_worker {
+        st.blocked_workers -= 1;
+    }
+}
+
+/// Block until `t` has finished.
+unsafe fn wait_for(t: *mut LeanObject) {
+    wait_until(|| lean_task_is_finished(t));
+}
+
+/// Task.get: the value of `t`, blocking until it is available (borrowed result).
+pub unsafe fn lean_task_get(t: *mut LeanObject) -> *mut LeanObject {
+    let task = t as *mut LeanTask;
+    let v = (*task).value.load(Ordering::Acquire);
+    if !v.is_null() {
+        return v;
+    }
+    wait_for(t);
+    (*task).value.load(Ordering::Acquire)
+}
+
+/// Task.get, consuming `t` (owned result).
+pub unsafe fn lean_task_get_own(t: *mut LeanObject) -> *mut LeanObject {
+    let v = lean_task_get(t);
+    crate::lean_inc(v);
+    crate::lean_dec(t);
+    v
+}
+
+/// Whether `t` has finished.
+pub unsafe fn lean_task_is_finished(t: *mut LeanObject) -> bool {
+    !(*(t as *mut LeanTask))
+        .value
+        .load(Ordering::Acquire)
+        .is_null()
+}
+
+/// Block until one of `tasks` has finished and return it (borrowed).
+pub unsafe fn lean_task_wait_any(tasks: &[*mut LeanObject]) -> *mut LeanObject {
+    let mut found = std::ptr::null_mut();
+    wait_until(|| {
+        found = tasks
+            .iter()
+            .copied()
+            .find(|&t| lean_task_is_finished(t))
+            .unwrap_or(std::ptr::null_mut());
+        !found.is_null()
+    });
+    found
+}
+
+// ---------------------------------------------------------------------------
+// Cancellation and state
+// ---------------------------------------------------------------------------
+
+/// Request cooperative cancellation of `t`. The task keeps running until it
+/// observes the flag through `lean_task_check_canceled`.
+pub unsafe fn lean_task_cancel(t: *mut LeanObject) {
+    let _st = lock_state();
+    let imp = (*(t as *mut LeanTask)).imp;
+    if !imp.is_null() {
+        (*imp).canceled.store(true, Ordering::Release);
+    }
+}
+
+/// Whether the task running on this thread has been canceled. Always false
+/// outside of a task.
+pub unsafe fn lean_task_check_canceled() -> bool {
+    let t = CURRENT_TASK.with(|c| c.get());
+    if t.is_null() {
+        return false;
+    }
+    // The running task keeps its `imp` until it is resolved by this thread.
+    let imp = (*(t as *mut LeanTask)).imp;
+    !imp.is_null() && (*imp).canceled.load(Ordering::Acquire)
+}
+
+/// `IO.TaskState`: 0 = waiting, 1 = running, 2 = finished.
+pub unsafe fn lean_task_get_state(t: *mut LeanObject) -> u8 {
+    if lean_task_is_finished(t) {
+        return 2;
+    }
+    let _st = lock_state();
+    let imp = (*(t as *mut LeanTask)).imp;
+    if imp.is_null() {
+        2
+    } else if (*imp).running {
+        1
+    } else {
+        0
+    }
+}
+
+/// Whether a task other than the one running on this thread is queued or
+/// running, that is, whether anything could still wake a thread that blocks
+/// now.
+pub fn lean_task_others_active() -> bool {
+    let st = lock_state();
+    let running = st.num_workers - st.idle_workers + st.dedicated_threads;
+    let own = !CURRENT_TASK.with(|c| c.get()).is_null() as usize;
+    !st.queue.is_empty() || running > own
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Barrier;
+
+    unsafe extern "C" fn add_one(x: *mut LeanObject) -> *mut LeanObject {
+        crate::lean_box(crate::lean_unbox(x) + 1)
+    }
+
+    unsafe extern "C" fn return_41(_unit: *mut LeanObject) -> *mut LeanObject {
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        crate::lean_box(41)
+    }
+
+    unsafe extern "C" fn spawn_add_one(x: *mut LeanObject) -> *mut LeanObject {
+        let c = crate::lean_alloc_closure(return_41 as *const (), 1, 0);
+        let t = lean_task_spawn_core(c, 0);
+        let f = crate::lean_alloc_closure(add_one as *const (), 1, 0);
+        let r = lean_task_map_core(f, t, 0, false);
+        crate::lean_dec(x);
+        r
+    }
+
+    #[test]
+    fn task_pure_is_finished() {
+        unsafe {
+            let t = lean_task_pure(crate::lean_box(7));
+            assert!(lean_task_is_finished(t));
+            assert_eq!(crate::lean_unbox(lean_task_get_own(t)), 7);
+        }
+    }
+
+    #[test]
+    fn spawn_and_map() {
+        lean_init_task_manager();
+        unsafe {
+            let c = crate::lean_alloc_closure(return_41 as *const (), 1, 0);
+            let t = lean_task_spawn_core(c, 0);
+            let f = crate::lean_alloc_closure(add_one as *const (), 1, 0);
+            let t2 = lean_task_map_core(f, t, 0, false);
+            assert_eq!(crate::lean_unbox(lean_task_get_own(t2)), 42);
+        }
+    }
+
+    #[test]
+    fn bind_forwards_inner_task() {
+        lean_init_task_manager();
+        unsafe {
+            let c = crate::lean_alloc_closure(return_41 as *const (), 1, 0);
+            let x = lean_task_spawn_core(c, 0);
+            let f = crate::lean_alloc_closure(spawn_add_one as *const (), 1, 0);
+            let t = lean_task_bind_core(x, f, 0, false);
+            assert_eq!(crate::lean_unbox(lean_task_get_own(t)), 42);
+        }
+    }
+
+    static BARRIER: Barrier = Barrier::new(2);
+
+    unsafe extern "C" fn meet(_unit: *mut LeanObject) -> *mut LeanObject {
+        BARRIER.wait();
+        crate::lean_box(1)
+    }
+
+    #[test]
+    fn dedicated_tasks_run_concurrently() {
+        lean_init_task_manager();
+        unsafe {
+            // Both tasks must be running at once to get past the barrier.
+            let a = lean_task_spawn_core(crate::lean_alloc_closure(meet as *const (), 1, 0), 9);
+            let b = lean_task_spawn_core(crate::lean_alloc_closure(meet as *const (), 1, 0), 9);
+            assert_eq!(crate::lean_unbox(lean_task_get_own(a)), 1);
+            assert_eq!(crate::lean_unbox(lean_task_get_own(b)), 1);
+        }
+    }
+
+    /// Dedicated threads are not kept around once their task has finished.
+    #[test]
+    fn dedicated_threads_are_released() {
+        lean_init_task_manager();
+        let workers = lock_state().threads.len();
+        unsafe {
+            for _ in 0..20 {
+                let t =
+                    lean_task_spawn_core(crate::lean_alloc_closure(add_one as *const (), 1, 0), 9);
+                assert_eq!(crate::lean_unbox(lean_task_get_own(t)), 1);
+            }
+        }
+        assert!(lock_state().threads.len() < workers + 20);
+        let deadline = std::time::Instant::now() + std::time::Duration::from_secs(10);
+        while lock_state().dedicated_threads > 0 {
+            assert!(
+                std::time::Instant::now() < deadline,
+                "dedicated threads still running"
+            );
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    unsafe extern "C" fn some_or_zero(o: *mut LeanObject) -> *mut LeanObject {
+        let r = if crate::lean_is_scalar(o) {
+            crate::lean_box(0)
+        } else {
+            let v = crate::lean_ctor_get(o, 0);
+            crate::lean_inc(v);
+            v
+        };
+        crate::lean_dec(o);
+        r
+    }
+
+    #[test]
+    fn promise_resolution_wakes_map() {
+        lean_init_task_manager();
+        unsafe {
+            let r = crate::lean_io_promise_new(crate::lean_box(0));
+            let p = crate::lean_io_result_get_value(r);
+            let t = crate::lean_io_promise_result_opt(p);
+            let f = crate::lean_alloc_closure(some_or_zero as *const (), 1, 0);
+            let mapped = lean_task_map_core(f, t, 0, false);
+            assert!(!lean_task_is_finished(mapped));
+            crate::lean_dec(crate::lean_io_promise_resolve(
+                crate::lean_box(5),
+                p,
+                crate::lean_box(0),
+            ));
+            assert_eq!(crate::lean_unbox(lean_task_get_own(mapped)), 5);
+            crate::lean_dec(r);
+        }
+    }
+
+    #[test]
+    fn dropped_promise_resolves_to_none() {
+        unsafe {
+            let r = crate::lean_io_promise_new(crate::lean_box(0));
+            let p = crate::lean_io_result_get_value(r);
+            let t = crate::lean_io_promise_result_opt(p);
+            assert!(!lean_task_is_finished(t));
+            crate::lean_dec(r);
+            assert!(crate::lean_is_scalar(lean_task_get(t)));
+            crate::lean_dec(t);
+        }
+    }
+
+    static STARTED: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "C" fn run_until_canceled(_unit: *mut LeanObject) -> *mut LeanObject {
+        STARTED.store(true, Ordering::Release);
+        while !lean_task_check_canceled() {
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        crate::lean_box(3)
+    }
+
+    #[test]
+    fn cancel_is_observed_by_running_task() {
+        lean_init_task_manager();
+        unsafe {
+            assert!(!lean_task_check_canceled());
+            let c = crate::lean_alloc_closure(run_until_canceled as *const (), 1, 0);
+            let t = lean_task_spawn_core(c, 9);
+            while !STARTED.load(Ordering::Acquire) {
+                std::thread::yield_now();
+            }
+            assert_eq!(lean_task_get_state(t), 1);
+            lean_task_cancel(t);
+            assert_eq!(crate::lean_unbox(lean_task_get(t)), 3);
+            assert_eq!(lean_task_get_state(t), 2);
+            crate::lean_dec(t);
+        }
+    }
+
+    #[test]
+    fn wait_any_returns_finished_task() {
+        unsafe {
+            let pending = lean_promise_task_new();
+            let done = lean_task_pure(crate::lean_box(1));
+            assert_eq!(lean_task_get_state(pending), 0);
+            assert_eq!(lean_task_wait_any(&[pending, done]), done);
+            lean_task_resolve(pending, crate::lean_box(0));
+            crate::lean_dec(pending);
+            crate::lean_dec(done);
+        }
+    }
+}
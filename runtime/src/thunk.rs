@@ -1,6 +1,7 @@
 //! Thunk primitives.
 
 use crate::object::LeanObject;
+use std::sync::atomic::{AtomicPtr, Ordering};
 
 const LEAN_THUNK_TAG: u8 = 247;
 
@@ -42,7 +43,11 @@ pub unsafe fn lean_thunk_pure(v: *mut LeanObject) -> *mut LeanObject {
 /// Get the value of a thunk, forcing it if needed.
 pub unsafe fn lean_thunk_get(t: *mut LeanObject) -> *mut LeanObject {
     let thunk = t as *mut LeanThunk;
-    let v = (*thunk).value;
+    let v = if crate::lean_is_mt(t) {
+        AtomicPtr::from_ptr(&raw mut (*thunk).value).load(Ordering::Acquire)
+    } else {
+        (*thunk).value
+    };
     if !v.is_null() {
         return v;
     }
@@ -53,6 +58,9 @@ pub unsafe fn lean_thunk_get(t: *mut LeanObject) -> *mut LeanObject {
 /// Force a thunk by evaluating its closure.
 pub unsafe fn lean_thunk_get_core(t: *mut LeanObject) -> *mut LeanObject {
     let thunk = t as *mut LeanThunk;
+    if crate::lean_is_mt(t) {
+        return lean_thunk_get_core_mt(thunk);
+    }
     let c = (*thunk).closure;
     if c.is_null() {
         return (*thunk).value; // Already forced or no closure
@@ -65,6 +73,27 @@ pub unsafe fn lean_thunk_get_core(t: *mut LeanObject) -> *mut LeanObject {
     result
 }
 
+/// Force a thunk shared between threads: the first thread to take the closure
+/// evaluates it, the others wait for the value to be published.
+unsafe fn lean_thunk_get_core_mt(thunk: *mut LeanThunk) -> *mut LeanObject {
+    let value = AtomicPtr::from_ptr(&raw mut (*thunk).value);
+    let closure = AtomicPtr::from_ptr(&raw mut (*thunk).closure);
+    let c = closure.swap(std::ptr::null_mut(), Ordering::AcqRel);
+    if !c.is_null() {
+        let result = crate::lean_apply_1(c, crate::lean_box(0));
+        crate::lean_mark_mt(result);
+        value.store(result, Ordering::Release);
+        return result;
+    }
+    loop {
+        let v = value.load(Ordering::Acquire);
+        if !v.is_null() {
+            return v;
+        }
+        std::thread::yield_now();
+    }
+}
+
 /// Thunk.get : Thunk A -> A (owned version)
 pub unsafe fn lean_thunk_get_own(t: *mut LeanObject) -> *mut LeanObject {
     let r = lean_thunk_get(t);
@@ -104,7 +133,7 @@ mod tests {
     fn thunk_from_closure_forces() {
         unsafe {
             // Create a closure that returns lean_box(99)
-            unsafe fn return_99(_arg: *mut LeanObject) -> *mut LeanObject {
+            unsafe extern "C" fn return_99(_arg: *mut LeanObject) -> *mut LeanObject {
                 crate::lean_box(99)
             }
             let c = crate::lean_alloc_closure(return_99 as *const (), 1, 0);
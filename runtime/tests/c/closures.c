@@ -0,0 +1,86 @@
+/* Closures written in C and applied through the exported runtime API.
+ * Built and run by tests/c_api.rs. */
+#include <stdio.h>
+
+#include "lean_runtime.h"
+
+static lean_object *sum10(lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4,
+                          lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8,
+                          lean_object *a9, lean_object *a10) {
+    return lean_box(lean_unbox(a1) + lean_unbox(a2) + lean_unbox(a3) + lean_unbox(a4) +
+                    lean_unbox(a5) + lean_unbox(a6) + lean_unbox(a7) + lean_unbox(a8) +
+                    lean_unbox(a9) + lean_unbox(a10));
+}
+
+static lean_object *sum16(lean_object *a1, lean_object *a2, lean_object *a3, lean_object *a4,
+                          lean_object *a5, lean_object *a6, lean_object *a7, lean_object *a8,
+                          lean_object *a9, lean_object *a10, lean_object *a11, lean_object *a12,
+                          lean_object *a13, lean_object *a14, lean_object *a15,
+                          lean_object *a16) {
+    return lean_box(lean_unbox(sum10(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10)) +
+                    lean_unbox(a11) + lean_unbox(a12) + lean_unbox(a13) + lean_unbox(a14) +
+                    lean_unbox(a15) + lean_unbox(a16));
+}
+
+/* More than 16 arguments arrive as an array */
+static lean_object *sum20(lean_object **args) {
+    size_t sum = 0;
+    for (int i = 0; i < 20; i++) {
+        sum += lean_unbox(args[i]);
+    }
+    return lean_box(sum);
+}
+
+static int failures = 0;
+
+static void check(char const *what, lean_object *r, size_t expected) {
+    size_t got = lean_unbox(r);
+    if (got != expected) {
+        printf("%s: got %zu, expected %zu\n", what, got, expected);
+        failures++;
+    }
+}
+
+int main(void) {
+    lean_initialize_runtime_module();
+    lean_object *args[20];
+    for (int i = 0; i < 20; i++) {
+        args[i] = lean_box(i + 1);
+    }
+
+    lean_object *f = lean_alloc_closure((void *)sum10, 10, 0);
+    check("lean_apply_10", lean_apply_10(f, args[0], args[1], args[2], args[3], args[4],
+                                         args[5], args[6], args[7], args[8], args[9]), 55);
+
+    f = lean_alloc_closure((void *)sum10, 10, 1);
+    lean_closure_set(f, 0, lean_box(100));
+    check("lean_apply_9 with a fixed argument",
+          lean_apply_9(f, args[0], args[1], args[2], args[3], args[4], args[5], args[6],
+                       args[7], args[8]), 145);
+
+    f = lean_alloc_closure((void *)sum16, 16, 0);
+    lean_object *p = lean_apply_n(f, 12, args);
+    check("lean_apply_4 after a partial lean_apply_n",
+          lean_apply_4(p, args[12], args[13], args[14], args[15]), 136);
+
+    f = lean_alloc_closure((void *)sum16, 16, 0);
+    check("lean_apply_16", lean_apply_16(f, args[0], args[1], args[2], args[3], args[4],
+                                         args[5], args[6], args[7], args[8], args[9],
+                                         args[10], args[11], args[12], args[13], args[14],
+                                         args[15]), 136);
+
+    f = lean_alloc_closure((void *)sum20, 20, 3);
+    for (int i = 0; i < 3; i++) {
+        lean_closure_set(f, i, args[i]);
+    }
+    check("lean_apply_m", lean_apply_m(f, 17, args + 3), 210);
+
+    if (lean_io_mk_world() != lean_box(0)) {
+        printf("lean_io_mk_world is not a scalar\n");
+        failures++;
+    }
+    if (failures == 0) {
+        printf("ok\n");
+    }
+    return failures != 0;
+}
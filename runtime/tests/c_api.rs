@@ -0,0 +1,69 @@
+//! Links C code against the static library, through `include/lean_runtime.h`.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Native libraries the Rust standard library needs in a static link.
+const NATIVE_LIBS: &[&str] = &["-lpthread", "-ldl", "-lm"];
+
+/// Build the static library with the C exports and return its path. Tests
+/// only build the rlib they link, so run cargo for the `staticlib` output,
+/// which lands in the target directory above this test's `deps/`.
+fn static_lib() -> PathBuf {
+    let exe = std::env::current_exe().unwrap();
+    let profile_dir = exe.parent().and_then(Path::parent).unwrap();
+    let mut cargo = Command::new(env!("CARGO"));
+    cargo.args([
+        "build",
+        "-q",
+        "--lib",
+        "-p",
+        "lean-runtime",
+        "--features",
+        "c-api",
+    ]);
+    if profile_dir.ends_with("release") {
+        cargo.arg("--release");
+    }
+    assert!(
+        cargo.status().unwrap().success(),
+        "building the static library failed"
+    );
+    profile_dir.join("liblean_runtime.a")
+}
+
+/// Compile and run `tests/c/<name>.c`, returning its output, or `None`
+/// when there is no C compiler.
+fn run_c(name: &str) -> Option<String> {
+    let dir = env!("CARGO_MANIFEST_DIR");
+    let exe = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let compiled = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".into()))
+        .arg(format!("{dir}/tests/c/{name}.c"))
+        .arg(format!("-I{dir}/include"))
+        .arg(static_lib())
+        .args(NATIVE_LIBS)
+        .arg("-o")
+        .arg(&exe)
+        .status();
+    match compiled {
+        Ok(status) => assert!(status.success(), "compiling {name}.c failed"),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            eprintln!("skipping {name}.c: no C compiler");
+            return None;
+        }
+        Err(e) => panic!("running the C compiler: {e}"),
+    }
+    let out = Command::new(&exe).output().unwrap();
+    let stdout = String::from_utf8(out.stdout).unwrap();
+    assert!(out.status.success(), "{name} failed:\n{stdout}");
+    Some(stdout)
+}
+
+/// C functions of every closure calling convention, applied from C
+#[test]
+#[cfg(unix)]
+fn c_closures() {
+    if let Some(out) = run_c("closures") {
+        assert_eq!(out, "ok\n");
+    }
+}
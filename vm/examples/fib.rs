@@ -149,7 +149,7 @@ fn main() {
 
     // Run it
     let mut vm = VM::new();
-    vm.load_module(module.clone());
+    vm.load_module(module.clone()).unwrap();
 
     match vm.run() {
         Ok(result) => {
@@ -178,7 +178,7 @@ fn main() {
         b.emit(Opcode::Ret);
         b.finish()
     };
-    vm2.load_module(module2);
+    vm2.load_module(module2).unwrap();
 
     match vm2.run() {
         Ok(result) => {
@@ -46,7 +46,7 @@ fn main() {
 
     // Run it
     let mut vm = VM::new();
-    vm.load_module(module);
+    vm.load_module(module).unwrap();
 
     match vm.run() {
         Ok(_) => {}
@@ -26,13 +26,43 @@
 //!
 //! Functions:
 //!   functions: [Function; num_functions]
+//!
+//! Constant pool (version >= 2, literals for LoadConst):
+//!   num_pool_constants: u32
+//!   pool: [Constant; num_pool_constants]
+//!     kind: u8, followed by
+//!       0 String:    len: u32, [u8; len] (UTF-8)
+//!       1 Nat:       u64
+//!       2 BigNat:    len: u32, [u8; len] (decimal digits)
+//!       3 Int:       i64
+//!       4 BigInt:    len: u32, [u8; len] (decimal digits, optional leading '-')
+//!       5 Float:     u64 (IEEE 754 bits)
+//!       6 ByteArray: len: u32, [u8; len]
+//!
+//! Debug info (version >= 3, optional):
+//!   has_debug_info: u8 (0 or 1), and if 1:
+//!   debug_version: u32
+//!   length: u32 (bytes that follow; sections of unknown versions are skipped)
+//!   functions: [FunctionDebugInfo; num_functions], in function order
+//!     decl_name: len: u32, [u8; len] (Lean declaration name, may be empty)
+//!     file: len: u32, [u8; len] (source file, may be empty)
+//!     num_positions: u32
+//!     positions: [(offset: u32, line: u32, column: u32); num_positions]
+//!     num_local_names: u32
+//!     local_names: [len: u32, [u8; len]; num_local_names] (by local index)
 //! ```
+//!
+//! Version 1 files have no constant pool and versions 1 and 2 have no debug
+//! info; both are still accepted.
 
 use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
 use std::io::{self, Read, Write};
 
 pub const MAGIC: &[u8; 4] = b"LNBC";
-pub const VERSION: u32 = 1;
+pub const VERSION: u32 = 3;
+
+/// Version of the debug info section written by [`Module::serialize`].
+pub const DEBUG_VERSION: u32 = 1;
 
 /// Bytecode opcodes
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -47,11 +77,12 @@ pub enum Opcode {
     LoadModuleConst = 0x06, // Load pre-initialized module constant
 
     // Constructors
-    AllocCtor = 0x10,  // Allocate constructor: tag, num_fields
-    CtorGet = 0x11,    // Get field: field_index
-    CtorSet = 0x12,    // Set field: field_index (obj, val on stack)
-    CtorSetTag = 0x13, // Set tag: new_tag
-    GetTag = 0x14,     // Push tag of TOS
+    AllocCtor = 0x10,       // Allocate constructor: tag, num_fields
+    CtorGet = 0x11,         // Get field: field_index
+    CtorSet = 0x12,         // Set field: field_index (obj, val on stack, both consumed)
+    CtorSetTag = 0x13,      // Set tag of TOS (consumed): new_tag
+    GetTag = 0x14,          // Push tag of TOS
+    AllocCtorScalar = 0x15, // Allocate constructor with scalar area: tag, num_fields, scalar_size (u16)
 
     // Closures
     AllocClosure = 0x20, // Allocate closure: func_id, arity, num_captured
@@ -59,10 +90,10 @@ pub enum Opcode {
     ClosureSet = 0x22,   // Set captured variable
 
     // Reference counting
-    Inc = 0x30,         // Increment refcount
-    Dec = 0x31,         // Decrement refcount
-    IsShared = 0x32,    // Push 1 if refcount > 1, else 0
-    IsExclusive = 0x33, // Push 1 if refcount == 1, else 0
+    Inc = 0x30,         // Increment refcount of TOS (consumed)
+    Dec = 0x31,         // Decrement refcount of TOS (consumed)
+    IsShared = 0x32,    // Push 1 if shared (more than one reference), else 0
+    IsExclusive = 0x33, // Push 1 if single-threaded with refcount 1, else 0
 
     // Function calls
     Call = 0x40,       // Call function: func_id, num_args
@@ -109,8 +140,8 @@ pub enum Opcode {
     BoolLit = 0x91, // Push bool: 0 or 1
 
     // Projections (for scalar fields in constructors)
-    ScalarProj = 0xA0, // Get scalar field: num_objs, offset, size
-    ScalarSet = 0xA1,  // Set scalar field: num_objs, offset, size
+    ScalarProj = 0xA0, // Get scalar field: num_objs, offset (u16), type
+    ScalarSet = 0xA1,  // Set scalar field: num_objs, offset (u16), type
 
     // Debug
     Trace = 0xF0, // Print debug info (if trace feature enabled)
@@ -120,7 +151,7 @@ impl Opcode {
     pub fn from_u8(byte: u8) -> Option<Opcode> {
         match byte {
             0x01..=0x06
-            | 0x10..=0x14
+            | 0x10..=0x15
             | 0x20..=0x22
             | 0x30..=0x33
             | 0x40..=0x46
@@ -162,11 +193,240 @@ pub struct Function {
     pub code: Vec<u8>,
 }
 
-/// Constant in the constant pool
-#[derive(Debug, Clone)]
+/// Constant in the constant pool, pushed by `LoadConst`
+#[derive(Debug, Clone, PartialEq)]
 pub enum Constant {
     String(String),
+    /// Nat literal that fits in a u64
     Nat(u64),
+    /// Nat literal of any size, in decimal
+    BigNat(String),
+    /// Int literal that fits in an i64
+    Int(i64),
+    /// Int literal of any size, in decimal with an optional leading `-`
+    BigInt(String),
+    Float(f64),
+    ByteArray(Vec<u8>),
+}
+
+impl Constant {
+    fn serialize<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        match self {
+            Constant::String(s) => {
+                w.write_u8(0)?;
+                write_bytes(w, s.as_bytes())
+            }
+            Constant::Nat(n) => {
+                w.write_u8(1)?;
+                w.write_u64::<LittleEndian>(*n)
+            }
+            Constant::BigNat(digits) => {
+                w.write_u8(2)?;
+                write_bytes(w, digits.as_bytes())
+            }
+            Constant::Int(i) => {
+                w.write_u8(3)?;
+                w.write_i64::<LittleEndian>(*i)
+            }
+            Constant::BigInt(digits) => {
+                w.write_u8(4)?;
+                write_bytes(w, digits.as_bytes())
+            }
+            Constant::Float(f) => {
+                w.write_u8(5)?;
+                w.write_u64::<LittleEndian>(f.to_bits())
+            }
+            Constant::ByteArray(bytes) => {
+                w.write_u8(6)?;
+                write_bytes(w, bytes)
+            }
+        }
+    }
+
+    fn deserialize<R: Read>(r: &mut R) -> io::Result<Self> {
+        let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+        let kind = r.read_u8()?;
+        Ok(match kind {
+            0 => Constant::String(
+                String::from_utf8(read_bytes(r)?).map_err(|e| invalid(e.to_string()))?,
+            ),
+            1 => Constant::Nat(r.read_u64::<LittleEndian>()?),
+            2 | 4 => {
+                let digits =
+                    String::from_utf8(read_bytes(r)?).map_err(|e| invalid(e.to_string()))?;
+                let unsigned = if kind == 4 {
+                    digits.strip_prefix('-').unwrap_or(&digits)
+                } else {
+                    &digits
+                };
+                if unsigned.is_empty() || !unsigned.bytes().all(|b| b.is_ascii_digit()) {
+                    return Err(invalid(format!("invalid numeric constant: {:?}", digits)));
+                }
+                if kind == 2 {
+                    Constant::BigNat(digits)
+                } else {
+                    Constant::BigInt(digits)
+                }
+            }
+            3 => Constant::Int(r.read_i64::<LittleEndian>()?),
+            5 => Constant::Float(f64::from_bits(r.read_u64::<LittleEndian>()?)),
+            6 => Constant::ByteArray(read_bytes(r)?),
+            _ => return Err(invalid(format!("invalid constant kind: {}", kind))),
+        })
+    }
+}
+
+fn write_bytes<W: Write>(w: &mut W, bytes: &[u8]) -> io::Result<()> {
+    w.write_u32::<LittleEndian>(bytes.len() as u32)?;
+    w.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(r: &mut R) -> io::Result<Vec<u8>> {
+    let len = r.read_u32::<LittleEndian>()? as usize;
+    let mut buf = vec![0u8; len];
+    r.read_exact(&mut buf)?;
+    Ok(buf)
+}
+
+fn read_string<R: Read>(r: &mut R) -> io::Result<String> {
+    String::from_utf8(read_bytes(r)?).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+}
+
+/// Source position of an instruction. Lines and columns are 1-based, as in
+/// Lean's error messages; 0 means unknown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SourcePos {
+    /// Offset of the first instruction at this position
+    pub offset: u32,
+    pub line: u32,
+    pub column: u32,
+}
+
+/// Debug information for one function.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FunctionDebugInfo {
+    /// Name of the Lean declaration, e.g. `List.map` for `l_List_map`
+    pub decl_name: String,
+    /// Source file of the declaration
+    pub file: String,
+    /// Positions sorted by offset. Each applies to the instructions up to
+    /// the next entry.
+    pub positions: Vec<SourcePos>,
+    /// Names of local variables by index; empty for unnamed locals
+    pub local_names: Vec<String>,
+}
+
+impl FunctionDebugInfo {
+    /// Source position of the instruction at `offset`, if known.
+    pub fn position(&self, offset: usize) -> Option<SourcePos> {
+        let i = self
+            .positions
+            .partition_point(|p| p.offset as usize <= offset);
+        i.checked_sub(1).map(|i| self.positions[i])
+    }
+
+    /// Name of local variable `idx`, if known.
+    pub fn local_name(&self, idx: usize) -> Option<&str> {
+        self.local_names
+            .get(idx)
+            .map(String::as_str)
+            .filter(|name| !name.is_empty())
+    }
+
+    fn serialize<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_bytes(w, self.decl_name.as_bytes())?;
+        write_bytes(w, self.file.as_bytes())?;
+        w.write_u32::<LittleEndian>(self.positions.len() as u32)?;
+        for p in &self.positions {
+            w.write_u32::<LittleEndian>(p.offset)?;
+            w.write_u32::<LittleEndian>(p.line)?;
+            w.write_u32::<LittleEndian>(p.column)?;
+        }
+        w.write_u32::<LittleEndian>(self.local_names.len() as u32)?;
+        for name in &self.local_names {
+            write_bytes(w, name.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn deserialize<R: Read>(r: &mut R) -> io::Result<Self> {
+        let decl_name = read_string(r)?;
+        let file = read_string(r)?;
+        let num_positions = r.read_u32::<LittleEndian>()? as usize;
+        let mut positions = Vec::with_capacity(num_positions.min(1 << 16));
+        for _ in 0..num_positions {
+            positions.push(SourcePos {
+                offset: r.read_u32::<LittleEndian>()?,
+                line: r.read_u32::<LittleEndian>()?,
+                column: r.read_u32::<LittleEndian>()?,
+            });
+        }
+        if !positions.is_sorted_by_key(|p| p.offset) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("unsorted debug positions in {}", decl_name),
+            ));
+        }
+        let num_local_names = r.read_u32::<LittleEndian>()? as usize;
+        let mut local_names = Vec::with_capacity(num_local_names.min(1 << 16));
+        for _ in 0..num_local_names {
+            local_names.push(read_string(r)?);
+        }
+        Ok(FunctionDebugInfo {
+            decl_name,
+            file,
+            positions,
+            local_names,
+        })
+    }
+}
+
+/// Optional debug information of a module, mapping bytecode back to the
+/// Lean source.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DebugInfo {
+    /// One entry per function, parallel to [`Module::functions`]
+    pub functions: Vec<FunctionDebugInfo>,
+}
+
+/// Type of a scalar constructor field, the last operand of `ScalarProj`
+/// and `ScalarSet`. Offsets are relative to the start of the scalar area,
+/// as in the runtime's `lean_ctor_get_uint64` and friends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ScalarType {
+    UInt8 = 0,
+    UInt16 = 1,
+    UInt32 = 2,
+    UInt64 = 3,
+    USize = 4,
+    Float = 5,
+    Float32 = 6,
+}
+
+impl ScalarType {
+    pub fn from_u8(byte: u8) -> Option<ScalarType> {
+        match byte {
+            0 => Some(ScalarType::UInt8),
+            1 => Some(ScalarType::UInt16),
+            2 => Some(ScalarType::UInt32),
+            3 => Some(ScalarType::UInt64),
+            4 => Some(ScalarType::USize),
+            5 => Some(ScalarType::Float),
+            6 => Some(ScalarType::Float32),
+            _ => None,
+        }
+    }
+
+    /// Size of the field in bytes.
+    pub fn size(self) -> usize {
+        match self {
+            ScalarType::UInt8 => 1,
+            ScalarType::UInt16 => 2,
+            ScalarType::UInt32 | ScalarType::Float32 => 4,
+            ScalarType::UInt64 | ScalarType::USize | ScalarType::Float => 8,
+        }
+    }
 }
 
 /// Complete bytecode module
@@ -179,6 +439,7 @@ pub struct Module {
     pub functions: Vec<Function>,
     pub entry: u32,
     pub init_func: Option<u32>,
+    pub debug_info: Option<DebugInfo>,
 }
 
 impl Module {
@@ -186,6 +447,11 @@ impl Module {
         Self::default()
     }
 
+    /// Debug information for function `func_idx`, if the module has any.
+    pub fn function_debug_info(&self, func_idx: usize) -> Option<&FunctionDebugInfo> {
+        self.debug_info.as_ref()?.functions.get(func_idx)
+    }
+
     /// Serialize module to bytes
     pub fn serialize<W: Write>(&self, w: &mut W) -> io::Result<()> {
         // Header
@@ -228,6 +494,27 @@ impl Module {
             w.write_all(&func.code)?;
         }
 
+        // Constant pool
+        w.write_u32::<LittleEndian>(self.constants.len() as u32)?;
+        for c in &self.constants {
+            c.serialize(w)?;
+        }
+
+        // Debug info
+        match &self.debug_info {
+            Some(debug_info) => {
+                w.write_u8(1)?;
+                let mut section = Vec::new();
+                for i in 0..self.functions.len() {
+                    let info = debug_info.functions.get(i).cloned().unwrap_or_default();
+                    info.serialize(&mut section)?;
+                }
+                w.write_u32::<LittleEndian>(DEBUG_VERSION)?;
+                write_bytes(w, &section)?;
+            }
+            None => w.write_u8(0)?,
+        }
+
         Ok(())
     }
 
@@ -241,7 +528,7 @@ impl Module {
         }
 
         let version = r.read_u32::<LittleEndian>()?;
-        if version != VERSION {
+        if !(1..=VERSION).contains(&version) {
             return Err(io::Error::new(
                 io::ErrorKind::InvalidData,
                 format!("unsupported version: {}", version),
@@ -320,18 +607,209 @@ impl Module {
             });
         }
 
+        // Constant pool
+        let mut constants = Vec::new();
+        if version >= 2 {
+            let num_pool_constants = r.read_u32::<LittleEndian>()? as usize;
+            for _ in 0..num_pool_constants {
+                constants.push(Constant::deserialize(r)?);
+            }
+        }
+
+        // Debug info
+        let mut debug_info = None;
+        if version >= 3 && r.read_u8()? != 0 {
+            let debug_version = r.read_u32::<LittleEndian>()?;
+            let section = read_bytes(r)?;
+            if debug_version == DEBUG_VERSION {
+                let mut section = &section[..];
+                let functions = (0..functions.len())
+                    .map(|_| FunctionDebugInfo::deserialize(&mut section))
+                    .collect::<io::Result<_>>()?;
+                debug_info = Some(DebugInfo { functions });
+            }
+        }
+
         Ok(Module {
             strings,
-            constants: Vec::new(),
+            constants,
             externs,
             const_decls,
             functions,
             entry,
             init_func,
+            debug_info,
         })
     }
 }
 
+/// Decode the instruction at `pc` for display, e.g. `Call func=3, args=2`.
+/// Returns the text and the offset of the next instruction. Local variable
+/// names are taken from `debug` when available.
+///
+/// Panics if the instruction is truncated.
+pub fn disasm_instruction(
+    code: &[u8],
+    pc: usize,
+    debug: Option<&FunctionDebugInfo>,
+) -> (String, usize) {
+    let op_byte = code[pc];
+    let Some(op) = Opcode::from_u8(op_byte) else {
+        return (format!("<invalid 0x{:02x}>", op_byte), pc + 1);
+    };
+    let mut pc = pc + 1;
+
+    let operands = match op {
+        Opcode::LoadLocal | Opcode::StoreLocal => {
+            let idx = code_u16(code, &mut pc);
+            match debug.and_then(|d| d.local_name(idx as usize)) {
+                Some(name) => format!("{} ({})", idx, name),
+                None => format!("{}", idx),
+            }
+        }
+        Opcode::AllocCtor => {
+            let tag = code[pc];
+            pc += 1;
+            let fields = code[pc];
+            pc += 1;
+            format!("tag={}, fields={}", tag, fields)
+        }
+        Opcode::AllocCtorScalar => {
+            let tag = code[pc];
+            pc += 1;
+            let fields = code[pc];
+            pc += 1;
+            let scalar_size = code_u16(code, &mut pc);
+            format!("tag={}, fields={}, scalar={}", tag, fields, scalar_size)
+        }
+        Opcode::CtorGet | Opcode::CtorSet | Opcode::CtorSetTag => {
+            let idx = code[pc];
+            pc += 1;
+            format!("{}", idx)
+        }
+        Opcode::Call | Opcode::TailCall => {
+            let func_id = code_u32(code, &mut pc);
+            let num_args = code[pc];
+            pc += 1;
+            format!("func={}, args={}", func_id, num_args)
+        }
+        Opcode::CallExtern => {
+            let ext_id = code_u32(code, &mut pc);
+            let num_args = code[pc];
+            pc += 1;
+            format!("extern={}, args={}", ext_id, num_args)
+        }
+        Opcode::Jump | Opcode::JumpIf | Opcode::JumpIfNot => {
+            let offset = code_i32(code, &mut pc);
+            let target = (pc as i32 + offset) as usize;
+            format!("{} (-> {})", offset, target)
+        }
+        Opcode::Switch => {
+            let num_cases = code_u16(code, &mut pc);
+            let mut s = format!("cases={} [", num_cases);
+            for i in 0..num_cases {
+                let offset = code_i32(code, &mut pc);
+                if i > 0 {
+                    s.push_str(", ");
+                }
+                s.push_str(&format!("{}", offset));
+            }
+            let default = code_i32(code, &mut pc);
+            s.push_str(&format!("] default={}", default));
+            s
+        }
+        Opcode::NatLit => {
+            let val = code_u64(code, &mut pc);
+            format!("{}", val)
+        }
+        Opcode::StringLit => {
+            let str_id = code_u32(code, &mut pc);
+            format!("str[{}]", str_id)
+        }
+        Opcode::BoolLit => {
+            let val = code[pc];
+            pc += 1;
+            format!("{}", val != 0)
+        }
+        Opcode::Apply | Opcode::TailApply => {
+            let num_args = code[pc];
+            pc += 1;
+            format!("args={}", num_args)
+        }
+        Opcode::PartialApp => {
+            let func_id = code_u32(code, &mut pc);
+            let arity = code[pc];
+            pc += 1;
+            let num_args = code[pc];
+            pc += 1;
+            format!("func={}, arity={}, args={}", func_id, arity, num_args)
+        }
+        Opcode::AllocClosure => {
+            let func_id = code_u32(code, &mut pc);
+            let arity = code[pc];
+            pc += 1;
+            let num_captured = code[pc];
+            pc += 1;
+            format!(
+                "func={}, arity={}, captured={}",
+                func_id, arity, num_captured
+            )
+        }
+        Opcode::ClosureGet | Opcode::ClosureSet => {
+            let idx = code[pc];
+            pc += 1;
+            format!("{}", idx)
+        }
+        Opcode::LoadConst => {
+            let const_id = code_u32(code, &mut pc);
+            format!("const={}", const_id)
+        }
+        Opcode::ScalarProj | Opcode::ScalarSet => {
+            let num_objs = code[pc];
+            pc += 1;
+            let offset = code_u16(code, &mut pc);
+            let ty = code[pc];
+            pc += 1;
+            match ScalarType::from_u8(ty) {
+                Some(ty) => format!("num_objs={}, offset={}, {:?}", num_objs, offset, ty),
+                None => format!(
+                    "num_objs={}, offset={}, <invalid type {}>",
+                    num_objs, offset, ty
+                ),
+            }
+        }
+        _ => String::new(),
+    };
+
+    if operands.is_empty() {
+        (format!("{:?}", op), pc)
+    } else {
+        (format!("{:?} {}", op, operands), pc)
+    }
+}
+
+fn code_u16(code: &[u8], pc: &mut usize) -> u16 {
+    let val = u16::from_le_bytes([code[*pc], code[*pc + 1]]);
+    *pc += 2;
+    val
+}
+
+fn code_u32(code: &[u8], pc: &mut usize) -> u32 {
+    let val = u32::from_le_bytes([code[*pc], code[*pc + 1], code[*pc + 2], code[*pc + 3]]);
+    *pc += 4;
+    val
+}
+
+fn code_i32(code: &[u8], pc: &mut usize) -> i32 {
+    code_u32(code, pc) as i32
+}
+
+fn code_u64(code: &[u8], pc: &mut usize) -> u64 {
+    let lo = code_u32(code, pc) as u64;
+    let hi = code_u32(code, pc) as u64;
+    lo | (hi << 32)
+}
+
 /// Bytecode builder for constructing function bodies
 #[derive(Default)]
 pub struct BytecodeBuilder {
@@ -403,3 +881,50 @@ impl BytecodeBuilder {
         self.code.len()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestModule;
+    use crate::VM;
+
+    /// Constant pool literals survive a round-trip through the file format
+    #[test]
+    fn constant_pool_round_trip() {
+        let big = "123456789012345678901234567890";
+        let mut m = TestModule::new()
+            .function(
+                "main",
+                0,
+                0,
+                "LoadConst 0; LoadConst 1; LoadConst 2; LoadConst 3; LoadConst 4
+                 AllocCtor 0 5; Ret",
+            )
+            .build();
+        m.constants = vec![
+            Constant::BigNat(big.to_string()),
+            Constant::Float(2.5),
+            Constant::BigInt(format!("-{}", big)),
+            Constant::ByteArray(vec![1, 2, 3]),
+            Constant::String("hi".to_string()),
+        ];
+
+        let mut bytes = Vec::new();
+        m.serialize(&mut bytes).unwrap();
+        let loaded = Module::deserialize(&mut &bytes[..]).unwrap();
+        assert_eq!(loaded.constants, m.constants);
+
+        let mut vm = VM::new();
+        vm.load_module(loaded).unwrap();
+        let result = vm.run().unwrap();
+        unsafe {
+            use lean_runtime::*;
+            let field = |i| lean_ctor_get(result.as_ptr(), i);
+            assert_eq!(lean_bignat_value(field(0)).to_string(), big);
+            assert_eq!(lean_unbox_float(field(1)), 2.5);
+            assert_eq!(lean_bigint_value(field(2)).to_string(), format!("-{}", big));
+            assert_eq!(lean_byte_array_size(field(3)), lean_box(3));
+            assert_eq!(lean_string_to_str(field(4)), "hi");
+        }
+    }
+}
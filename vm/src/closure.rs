@@ -0,0 +1,278 @@
+//! Closures over bytecode functions
+//!
+//! A bytecode closure is an ordinary runtime closure whose function pointer is
+//! a native trampoline and whose first fixed argument is the boxed function
+//! id. The closure's arity and fixed arguments count this hidden argument, so
+//! runtime code (`lean_apply_m`, thunks, tasks, ...) can apply it like any
+//! native closure: once saturated, the trampoline calls back into the VM that
+//! is executing on the current thread.
+//!
+//! The VM itself recognizes its trampolines and applies such closures
+//! directly, hiding the function id from `ClosureGet`/`ClosureSet`.
+//!
+//! Runtime code has no way to propagate a [`VMError`]. When a closure it
+//! applies fails, the trampoline records the error on the VM and returns
+//! `()`; further closure calls are skipped, and the VM re-raises the error as
+//! soon as the runtime call returns to it.
+
+use crate::linker::FUNC_ID_RESOLVED_BIT;
+use crate::value::LeanValue;
+use crate::vm::{VMError, VM};
+use lean_runtime::*;
+use std::cell::Cell;
+
+thread_local! {
+    /// The VM currently executing on this thread, if any.
+    static CURRENT_VM: Cell<*mut VM> = const { Cell::new(std::ptr::null_mut()) };
+}
+
+/// Registers a VM as the target of trampolines on this thread, restoring the
+/// previous one when dropped.
+pub(crate) struct EnterGuard(*mut VM);
+
+impl Drop for EnterGuard {
+    fn drop(&mut self) {
+        CURRENT_VM.with(|c| c.set(self.0));
+    }
+}
+
+/// Make `vm` the target of trampolines called on this thread while the
+/// returned guard is alive.
+///
+/// The VM must not move while the guard is alive; `execute_inner` holds the
+/// guard for the duration of a `&mut self` borrow, which guarantees this.
+pub(crate) fn enter(vm: &mut VM) -> EnterGuard {
+    EnterGuard(CURRENT_VM.with(|c| c.replace(vm)))
+}
+
+/// Split a function id into (module index, function index).
+pub(crate) fn decode_func_id(func_id: u32) -> (usize, usize) {
+    if func_id & FUNC_ID_RESOLVED_BIT != 0 {
+        // Pre-resolved by the linker: single linked module
+        (0, (func_id & !FUNC_ID_RESOLVED_BIT) as usize)
+    } else {
+        ((func_id >> 16) as usize, (func_id & 0xFFFF) as usize)
+    }
+}
+
+unsafe fn call(id: *mut LeanObject, args: &[*mut LeanObject]) -> *mut LeanObject {
+    let vm = CURRENT_VM.with(|c| c.get());
+    if vm.is_null() {
+        lean_internal_panic(c"bytecode closure applied outside of the VM".as_ptr());
+    }
+    let args = args.iter().map(|&a| LeanValue::from_raw(a)).collect();
+    // SAFETY: `vm` was registered by `enter` and is still executing further
+    // up this thread's stack, which is blocked in the runtime call that
+    // invoked us. The VM is re-entrant through `call_by_id`.
+    let vm = &mut *vm;
+    if vm.closure_error.is_some() {
+        // An earlier call failed; unwind back to the VM without doing work
+        return lean_box(0);
+    }
+    match vm.call_by_id(lean_unbox(id) as u32, args) {
+        Ok(v) => v.into_raw(),
+        Err(e) => {
+            vm.closure_error = Some(e);
+            lean_box(0)
+        }
+    }
+}
+
+macro_rules! trampolines {
+    ($($name:ident($($arg:ident),*);)*) => {
+        $(
+            #[allow(clippy::too_many_arguments)]
+            unsafe extern "C" fn $name(id: *mut LeanObject $(, $arg: *mut LeanObject)*) -> *mut LeanObject {
+                call(id, &[$($arg),*])
+            }
+        )*
+
+        /// The trampoline for a closure of runtime arity `arity` (including
+        /// the function id), at most [`LEAN_CLOSURE_MAX_ARGS`].
+        fn trampoline(arity: u32) -> *const () {
+            const TRAMPOLINES: &[*const ()] = &[$($name as *const ()),*];
+            TRAMPOLINES[arity as usize - 1]
+        }
+    };
+}
+
+trampolines! {
+    trampoline_1();
+    trampoline_2(a1);
+    trampoline_3(a1, a2);
+    trampoline_4(a1, a2, a3);
+    trampoline_5(a1, a2, a3, a4);
+    trampoline_6(a1, a2, a3, a4, a5);
+    trampoline_7(a1, a2, a3, a4, a5, a6);
+    trampoline_8(a1, a2, a3, a4, a5, a6, a7);
+    trampoline_9(a1, a2, a3, a4, a5, a6, a7, a8);
+    trampoline_10(a1, a2, a3, a4, a5, a6, a7, a8, a9);
+    trampoline_11(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10);
+    trampoline_12(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11);
+    trampoline_13(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12);
+    trampoline_14(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13);
+    trampoline_15(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14);
+    trampoline_16(a1, a2, a3, a4, a5, a6, a7, a8, a9, a10, a11, a12, a13, a14, a15);
+}
+
+/// Whether `o` is a closure over a bytecode function rather than a native one.
+pub fn is_bytecode_closure(v: &LeanValue) -> bool {
+    let o = v.as_ptr();
+    unsafe {
+        if lean_is_scalar(o) || lean_obj_tag(o) != LEAN_CLOSURE_TAG {
+            return false;
+        }
+        let c = o as *const LeanClosure;
+        let arity = (*c).arity as u32;
+        (1..=LEAN_CLOSURE_MAX_ARGS).contains(&arity) && (*c).fun == trampoline(arity)
+    }
+}
+
+/// Most parameters of a bytecode function that can be wrapped in a closure:
+/// the trampolines take their arguments directly, as closures of at most
+/// [`LEAN_CLOSURE_MAX_ARGS`] arguments do, including the function id.
+pub const MAX_CLOSURE_ARITY: u32 = LEAN_CLOSURE_MAX_ARGS - 1;
+
+/// Allocate a closure over the bytecode function `func_id` of the given
+/// arity, with `fixed` as its first arguments.
+pub fn alloc_bytecode_closure(
+    func_id: u32,
+    arity: u32,
+    fixed: Vec<LeanValue>,
+) -> Result<LeanValue, VMError> {
+    if arity > MAX_CLOSURE_ARITY {
+        return Err(VMError::ClosureArity(arity));
+    }
+    unsafe {
+        let closure = lean_alloc_closure(trampoline(arity + 1), arity + 1, fixed.len() as u32 + 1);
+        lean_closure_set(closure, 0, lean_box(func_id as usize));
+        for (i, arg) in fixed.into_iter().enumerate() {
+            lean_closure_set(closure, i as u32 + 1, arg.into_raw());
+        }
+        Ok(LeanValue::from_raw(closure))
+    }
+}
+
+/// The function id of a bytecode closure.
+///
+/// # Safety
+/// `o` must satisfy [`is_bytecode_closure`].
+pub unsafe fn bytecode_closure_func_id(o: *mut LeanObject) -> u32 {
+    lean_unbox(lean_closure_get(o, 0)) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestModule;
+    use crate::value::LeanValue;
+    use crate::vm::{Limits, VMError};
+
+    /// Externs like `String.foldl` call back into bytecode, which can itself
+    /// call such externs
+    #[test]
+    fn reentrant_externs() {
+        let mut vm = TestModule::new()
+            .externs(&[("lean_string_foldl", 3), ("lean_string_any", 2)])
+            // main: "hello world".foldl step 0
+            .function(
+                "main",
+                0,
+                0,
+                r#"AllocClosure step 2 0; NatLit 0; StringLit "hello world"
+                   CallExtern lean_string_foldl 3; Ret"#,
+            )
+            // step acc c: if "lo".any (eq c) then acc + 1 else acc
+            .function(
+                "step",
+                2,
+                2,
+                r#"StringLit "lo"; LoadLocal 1; AllocClosure eq 2 1
+                   CallExtern lean_string_any 2; JumpIfNot @skip
+                   LoadLocal 0; NatLit 1; NatAdd; Ret
+                   skip: LoadLocal 0; Ret"#,
+            )
+            // eq c d: c == d
+            .function("eq", 2, 2, "LoadLocal 0; LoadLocal 1; NatEq; Ret")
+            .load();
+        assert_eq!(vm.run().unwrap().unbox(), 5);
+        assert_eq!(vm.depth(), 0);
+    }
+
+    /// `f (f x)`, with both applications made by the runtime
+    fn runtime_apply_twice(args: &[LeanValue]) -> Result<LeanValue, VMError> {
+        unsafe {
+            use lean_runtime::*;
+            let f = args[0].as_ptr();
+            lean_inc(f);
+            lean_inc(f);
+            lean_inc(args[1].as_ptr());
+            let once = lean_apply_1(f, args[1].as_ptr());
+            Ok(LeanValue::from_raw(lean_apply_1(f, once)))
+        }
+    }
+
+    /// Bytecode closures can be applied by runtime code, and errors they
+    /// raise come back out of the VM instead of aborting
+    #[test]
+    fn closures_applied_by_runtime() {
+        let mut m = TestModule::new().externs(&[("test_apply_twice", 2), ("lean_panic_fn", 2)]);
+        // twice_f x: test_apply_twice (closure f) x
+        for f in ["succ", "fail", "spin"] {
+            m = m.function(
+                &format!("twice_{}", f),
+                1,
+                1,
+                &format!(
+                    "AllocClosure {} 1 0; LoadLocal 0; CallExtern test_apply_twice 2; Ret",
+                    f
+                ),
+            );
+        }
+        let m = m
+            .function("succ", 1, 1, "LoadLocal 0; NatSucc; Ret")
+            .function(
+                "fail",
+                1,
+                1,
+                r#"LoadLocal 0; StringLit "boom"; CallExtern lean_panic_fn 2; Ret"#,
+            )
+            .function("spin", 1, 1, "spin: Jump @spin");
+
+        let mut vm = crate::VM::new();
+        vm.register_extern("test_apply_twice", runtime_apply_twice);
+        vm.load_module(m.build()).unwrap();
+        let five = [LeanValue::from_nat(5)];
+        assert_eq!(vm.run_function("twice_succ", &five).unwrap().unbox(), 7);
+
+        let err = vm.run_function("twice_fail", &five).unwrap_err();
+        assert!(matches!(err.root(), VMError::Panic(msg) if msg == "boom"));
+        assert_eq!(
+            err.to_string(),
+            "in extern test_apply_twice: in extern lean_panic_fn: panic: boom\n  \
+             at fail (offset 8)\n  at twice_fail (offset 10)"
+        );
+        assert_eq!(vm.depth(), 0);
+        assert!(vm.value_stack().is_empty());
+
+        vm.set_limits(Limits {
+            max_instructions: Some(1000),
+            ..Default::default()
+        });
+        let err = vm.run_function("twice_spin", &five).unwrap_err();
+        assert!(matches!(err.root(), VMError::OutOfFuel(1000)));
+        vm.set_limits(Limits::default());
+        assert_eq!(vm.run_function("twice_succ", &five).unwrap().unbox(), 7);
+    }
+
+    /// Runtime closures take at most 16 arguments, one of which a bytecode
+    /// closure spends on the function id
+    #[test]
+    fn closure_arity_limit() {
+        let mut vm = TestModule::new()
+            .function("main", 0, 0, "AllocClosure f 16 0; Ret")
+            .function("f", 16, 16, "LoadLocal 0; Ret")
+            .load();
+        let err = vm.run().unwrap_err();
+        assert!(matches!(err.root(), VMError::ClosureArity(16)));
+    }
+}
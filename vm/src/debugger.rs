@@ -0,0 +1,433 @@
+//! Interactive bytecode debugger
+//!
+//! [`Debugger`] is a [`Hook`] that stops execution at breakpoints or after
+//! stepping, and then reads commands until told to resume. `lean4-vm debug`
+//! runs it on stdin/stdout; type `help` at the prompt for the commands.
+
+use crate::bytecode::disasm_instruction;
+use crate::closure::{bytecode_closure_func_id, decode_func_id, is_bytecode_closure};
+use crate::value::LeanValue;
+use crate::vm::{Hook, VMError, VM};
+use lean_runtime::*;
+use std::io::{BufRead, Write};
+
+type Result<T> = std::result::Result<T, VMError>;
+
+/// Nesting depth up to which `print` shows object fields.
+const PRINT_DEPTH: usize = 3;
+
+/// Maximum number of array elements or string characters shown.
+const PRINT_ELEMS: usize = 8;
+
+const HELP: &str = "\
+Commands:
+  s, step              execute one instruction
+  n, next              execute one instruction, stepping over calls
+  finish               run until the current function returns
+  c, continue          run until a breakpoint is hit
+  b, break LOC         set a breakpoint at FUNCTION, FUNCTION:OFFSET or OFFSET
+  d, delete [N]        delete breakpoint N, or all breakpoints
+  breakpoints          list breakpoints
+  bt, backtrace        show the call stack
+  l, list              disassemble the current function
+  locals [FRAME]       show the local variables of a frame (0 is innermost)
+  p, print N [FRAME]   show local variable N in more detail
+  stack                show the value stack, top first
+  q, quit              abort execution
+  h, help              show this help
+An empty line repeats the previous command.";
+
+/// When to stop next, apart from breakpoints.
+enum Mode {
+    /// Before the next instruction
+    Step,
+    /// Before the next instruction at this call depth or shallower
+    Next(usize),
+    /// Before the next instruction shallower than this call depth
+    Finish(usize),
+    Continue,
+}
+
+struct Breakpoint {
+    mod_idx: usize,
+    func_idx: usize,
+    offset: usize,
+}
+
+/// Interactive debugger reading commands from `input` and writing to `output`.
+pub struct Debugger<R, W> {
+    input: R,
+    output: W,
+    mode: Mode,
+    breakpoints: Vec<Breakpoint>,
+    last_command: String,
+}
+
+impl<R: BufRead, W: Write> Debugger<R, W> {
+    /// A debugger that stops before the first instruction.
+    pub fn new(input: R, output: W) -> Self {
+        Debugger {
+            input,
+            output,
+            mode: Mode::Step,
+            breakpoints: Vec::new(),
+            last_command: String::new(),
+        }
+    }
+
+    /// Consume the debugger, returning its output.
+    pub fn into_output(self) -> W {
+        self.output
+    }
+
+    /// Read and run commands until one resumes execution.
+    fn command_loop(&mut self, vm: &VM) -> std::io::Result<bool> {
+        loop {
+            write!(self.output, "(ldb) ")?;
+            self.output.flush()?;
+            let mut line = String::new();
+            if self.input.read_line(&mut line)? == 0 {
+                // End of input
+                return Ok(false);
+            }
+            let line = match line.trim() {
+                "" => self.last_command.clone(),
+                line => line.to_string(),
+            };
+            self.last_command = line.clone();
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let Some((&cmd, args)) = words.split_first() else {
+                continue;
+            };
+            let depth = vm.depth();
+            match cmd {
+                "s" | "step" => self.mode = Mode::Step,
+                "n" | "next" => self.mode = Mode::Next(depth),
+                "finish" => self.mode = Mode::Finish(depth),
+                "c" | "continue" => self.mode = Mode::Continue,
+                "q" | "quit" => return Ok(false),
+                "b" | "break" => match args {
+                    [loc] => self.add_breakpoint(vm, loc)?,
+                    _ => writeln!(self.output, "usage: break LOCATION")?,
+                },
+                "d" | "delete" => match args {
+                    [] => self.breakpoints.clear(),
+                    [n] => match n.parse::<usize>() {
+                        Ok(n) if n < self.breakpoints.len() => {
+                            self.breakpoints.remove(n);
+                        }
+                        _ => writeln!(self.output, "no breakpoint {}", n)?,
+                    },
+                    _ => writeln!(self.output, "usage: delete [N]")?,
+                },
+                "breakpoints" => {
+                    for (i, b) in self.breakpoints.iter().enumerate() {
+                        let frame = vm.stack_frame(b.mod_idx, b.func_idx, b.offset);
+                        writeln!(self.output, "  {}: {}", i, frame)?;
+                    }
+                }
+                "bt" | "backtrace" => {
+                    for (i, frame) in vm.call_stack().iter().enumerate() {
+                        writeln!(self.output, "  #{} {}", i, frame)?;
+                    }
+                }
+                "l" | "list" => self.list(vm)?,
+                "locals" => match parse_frame(args.first()) {
+                    Some(frame) => self.locals(vm, frame)?,
+                    None => writeln!(self.output, "usage: locals [FRAME]")?,
+                },
+                "p" | "print" => {
+                    let idx = args.first().and_then(|n| n.parse::<usize>().ok());
+                    match (idx, parse_frame(args.get(1))) {
+                        (Some(idx), Some(frame)) => self.print_local(vm, frame, idx)?,
+                        _ => writeln!(self.output, "usage: print N [FRAME]")?,
+                    }
+                }
+                "stack" => {
+                    for (i, v) in vm.value_stack().iter().rev().enumerate() {
+                        writeln!(self.output, "  [{}] {}", i, describe(vm, v, 1))?;
+                    }
+                }
+                "h" | "help" => writeln!(self.output, "{}", HELP)?,
+                _ => writeln!(self.output, "unknown command: {} (try help)", cmd)?,
+            }
+            if matches!(
+                cmd,
+                "s" | "step" | "n" | "next" | "finish" | "c" | "continue"
+            ) {
+                return Ok(true);
+            }
+        }
+    }
+
+    /// Parse `FUNCTION`, `FUNCTION:OFFSET` or `OFFSET` and add a breakpoint.
+    fn add_breakpoint(&mut self, vm: &VM, loc: &str) -> std::io::Result<()> {
+        let (name, offset) = match loc.rsplit_once(':') {
+            Some((name, offset)) => (Some(name), offset.parse().ok()),
+            None => match loc.parse::<usize>() {
+                Ok(offset) => (None, Some(offset)),
+                Err(_) => (Some(loc), Some(0)),
+            },
+        };
+        let func = match name {
+            Some(name) => find_function(vm, name),
+            None => vm.frame_position(0).map(|(m, f, _)| (m, f)),
+        };
+        let (Some((mod_idx, func_idx)), Some(offset)) = (func, offset) else {
+            return writeln!(self.output, "cannot set a breakpoint at {}", loc);
+        };
+        let frame = vm.stack_frame(mod_idx, func_idx, offset);
+        writeln!(
+            self.output,
+            "Breakpoint {} at {}",
+            self.breakpoints.len(),
+            frame
+        )?;
+        self.breakpoints.push(Breakpoint {
+            mod_idx,
+            func_idx,
+            offset,
+        });
+        Ok(())
+    }
+
+    /// Show where execution stopped.
+    fn show_location(&mut self, vm: &VM) -> std::io::Result<()> {
+        let Some((mod_idx, func_idx, offset)) = vm.frame_position(0) else {
+            return Ok(());
+        };
+        let func = &vm.modules()[mod_idx].functions[func_idx];
+        let debug = vm.modules()[mod_idx].function_debug_info(func_idx);
+        let (text, _) = disasm_instruction(&func.code, offset, debug);
+        writeln!(self.output, "{}", vm.stack_frame(mod_idx, func_idx, offset))?;
+        writeln!(self.output, "=> {:04}: {}", offset, text)
+    }
+
+    fn list(&mut self, vm: &VM) -> std::io::Result<()> {
+        let Some((mod_idx, func_idx, current)) = vm.frame_position(0) else {
+            return Ok(());
+        };
+        let func = &vm.modules()[mod_idx].functions[func_idx];
+        let debug = vm.modules()[mod_idx].function_debug_info(func_idx);
+        let mut pc = 0;
+        while pc < func.code.len() {
+            let (text, next) = disasm_instruction(&func.code, pc, debug);
+            let marker = if pc == current { "=>" } else { "  " };
+            writeln!(self.output, "{} {:04}: {}", marker, pc, text)?;
+            pc = next;
+        }
+        Ok(())
+    }
+
+    fn locals(&mut self, vm: &VM, frame: usize) -> std::io::Result<()> {
+        let (Some(locals), Some((mod_idx, func_idx, _))) =
+            (vm.frame_locals(frame), vm.frame_position(frame))
+        else {
+            return writeln!(self.output, "no frame {}", frame);
+        };
+        let debug = vm.modules()[mod_idx].function_debug_info(func_idx);
+        for (i, v) in locals.iter().enumerate() {
+            match debug.and_then(|d| d.local_name(i)) {
+                Some(name) => writeln!(self.output, "  [{}] {} = {}", i, name, describe(vm, v, 1))?,
+                None => writeln!(self.output, "  [{}] {}", i, describe(vm, v, 1))?,
+            }
+        }
+        Ok(())
+    }
+
+    fn print_local(&mut self, vm: &VM, frame: usize, idx: usize) -> std::io::Result<()> {
+        match vm.frame_locals(frame).and_then(|locals| locals.get(idx)) {
+            Some(v) => writeln!(self.output, "{}", describe(vm, v, PRINT_DEPTH)),
+            None => writeln!(self.output, "no local {} in frame {}", idx, frame),
+        }
+    }
+}
+
+impl<R: BufRead + 'static, W: Write + 'static> Hook for Debugger<R, W> {
+    fn before_instruction(&mut self, vm: &VM) -> Result<()> {
+        let depth = vm.depth();
+        let Some((mod_idx, func_idx, offset)) = vm.frame_position(0) else {
+            return Ok(());
+        };
+        let hit = self
+            .breakpoints
+            .iter()
+            .position(|b| (b.mod_idx, b.func_idx, b.offset) == (mod_idx, func_idx, offset));
+        let stop = match self.mode {
+            Mode::Step => true,
+            Mode::Next(d) => depth <= d,
+            Mode::Finish(d) => depth < d,
+            Mode::Continue => false,
+        };
+        if !stop && hit.is_none() {
+            return Ok(());
+        }
+        let resume = (|| {
+            if let Some(i) = hit {
+                writeln!(self.output, "Breakpoint {}", i)?;
+            }
+            self.show_location(vm)?;
+            self.command_loop(vm)
+        })()
+        .map_err(|e| VMError::IOError(e.to_string()))?;
+        if resume {
+            Ok(())
+        } else {
+            Err(VMError::Interrupted)
+        }
+    }
+}
+
+/// Resolve a breakpoint function name, either the compiled name or the Lean
+/// declaration name from debug info.
+fn find_function(vm: &VM, name: &str) -> Option<(usize, usize)> {
+    vm.find_function(name).or_else(|| {
+        vm.modules()
+            .iter()
+            .enumerate()
+            .find_map(|(mod_idx, module)| {
+                let debug = module.debug_info.as_ref()?;
+                let func_idx = debug.functions.iter().position(|d| d.decl_name == name)?;
+                Some((mod_idx, func_idx))
+            })
+    })
+}
+
+fn parse_frame(arg: Option<&&str>) -> Option<usize> {
+    arg.map_or(Some(0), |n| n.parse().ok())
+}
+
+/// Render a value like `lean_debug_dump`, showing fields of nested objects
+/// up to `depth` levels deep.
+pub fn describe(vm: &VM, v: &LeanValue, depth: usize) -> String {
+    if v.is_scalar() {
+        return format!("{}", v.unbox());
+    }
+    let o = v.as_ptr();
+    unsafe {
+        let rc = (*o).rc;
+        let tag = lean_obj_tag(o);
+        let fields = |items: Vec<LeanValue>, total: usize| {
+            if depth == 0 {
+                return "[..]".to_string();
+            }
+            let mut parts: Vec<String> = items
+                .iter()
+                .take(PRINT_ELEMS)
+                .map(|f| describe(vm, f, depth - 1))
+                .collect();
+            if total > PRINT_ELEMS {
+                parts.push("..".to_string());
+            }
+            format!("[{}]", parts.join(", "))
+        };
+        match tag {
+            0..=LEAN_MAX_CTOR_TAG => {
+                let num_objs = (*o).num_objs();
+                let items = (0..num_objs).map(|i| v.ctor_get(i)).collect();
+                let scalars = match (*o).cs_sz as usize {
+                    0 => String::new(),
+                    n => format!(" +{} scalar bytes", n),
+                };
+                format!(
+                    "ctor {} rc={} {}{}",
+                    tag,
+                    rc,
+                    fields(items, num_objs as usize),
+                    scalars
+                )
+            }
+            LEAN_CLOSURE_TAG => {
+                let c = o as *const LeanClosure;
+                if is_bytecode_closure(v) {
+                    let (mod_idx, func_idx) = decode_func_id(bytecode_closure_func_id(o));
+                    let name = vm
+                        .modules()
+                        .get(mod_idx)
+                        .and_then(|m| m.functions.get(func_idx))
+                        .map_or("?", |f| f.name.as_str());
+                    let fixed = (1..(*c).num_fixed as u32)
+                        .map(|i| v.closure_get(i))
+                        .collect();
+                    format!(
+                        "closure {} rc={} arity={} {}",
+                        name,
+                        rc,
+                        (*c).arity - 1,
+                        fields(fixed, (*c).num_fixed as usize - 1)
+                    )
+                } else {
+                    let fixed = (0..(*c).num_fixed as u32)
+                        .map(|i| v.closure_get(i))
+                        .collect();
+                    format!(
+                        "closure <native> rc={} arity={} {}",
+                        rc,
+                        (*c).arity,
+                        fields(fixed, (*c).num_fixed as usize)
+                    )
+                }
+            }
+            LEAN_ARRAY_TAG => {
+                let size = lean_array_size(o);
+                let items = (0..size.min(PRINT_ELEMS))
+                    .map(|i| LeanValue::from_raw_inc(lean_array_get_core(o, i)))
+                    .collect();
+                format!("array rc={} size={} {}", rc, size, fields(items, size))
+            }
+            LEAN_SCALAR_ARRAY_TAG => {
+                format!(
+                    "sarray rc={} size={}",
+                    rc,
+                    lean_runtime::sarray::lean_sarray_size(o)
+                )
+            }
+            LEAN_STRING_TAG => {
+                let s = lean_string_to_str(o);
+                let mut shown: String = s.chars().take(80).collect();
+                if shown.len() < s.len() {
+                    shown.push_str("..");
+                }
+                format!("string rc={} {:?}", rc, shown)
+            }
+            LEAN_MPZ_TAG => format!("nat rc={} {}", rc, lean_bignat_value(o)),
+            LEAN_BIGINT_TAG => format!("int rc={} {}", rc, lean_bigint_value(o)),
+            LEAN_THUNK_TAG => format!("thunk rc={}", rc),
+            LEAN_TASK_TAG => format!("task rc={}", rc),
+            LEAN_REF_TAG => format!("ref rc={}", rc),
+            LEAN_EXTERNAL_TAG => format!("external rc={}", rc),
+            _ => format!("object tag={} rc={}", tag, rc),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::panic_module;
+    use crate::vm::VMError;
+    use crate::VM;
+    use std::io::Cursor;
+
+    /// The debugger stops at breakpoints and can quit, driven by the hook API
+    #[test]
+    fn breakpoint() {
+        type ScriptedDebugger = Debugger<Cursor<&'static [u8]>, Vec<u8>>;
+        let script: &[u8] = b"break fail\ncontinue\nbt\nlocals\nquit\n";
+        let mut vm = VM::new();
+        vm.load_module(panic_module()).unwrap();
+        vm.set_hook(Box::new(ScriptedDebugger::new(
+            Cursor::new(script),
+            Vec::new(),
+        )));
+        let err = vm.run().unwrap_err();
+        assert!(matches!(err.root(), VMError::Interrupted));
+
+        let hook: Box<dyn std::any::Any> = vm.take_hook().unwrap();
+        let debugger = hook.downcast::<ScriptedDebugger>().unwrap();
+        let output = String::from_utf8(debugger.into_output()).unwrap();
+        assert!(output.contains("Breakpoint 0\nfail (offset 0)\n=> 0000: LoadLocal 0"));
+        assert!(output.contains("  #0 fail (offset 0)\n  #1 main (offset 1)\n"));
+        assert!(output.contains("  [0] 0\n"));
+    }
+}
@@ -416,7 +416,10 @@ fn array_set_panic(args: &[LeanValue]) -> Result<LeanValue> {
         let size = lean_unbox(lean_array_get_size(arr));
         let i = lean_unbox(idx);
         if i >= size {
-            panic!("Array.set!: index {} out of bounds (size {})", i, size);
+            return Err(VMError::Panic(format!(
+                "Array.set!: index {} out of bounds (size {})",
+                i, size
+            )));
         }
         let result = lean_array_fset(arr, idx, val);
         Ok(LeanValue::from_raw(result))
@@ -431,7 +434,10 @@ fn array_get_panic(args: &[LeanValue]) -> Result<LeanValue> {
         let size = lean_unbox(lean_array_get_size(arr));
         let i = lean_unbox(idx);
         if i >= size {
-            panic!("Array.get!: index {} out of bounds (size {})", i, size);
+            return Err(VMError::Panic(format!(
+                "Array.get!: index {} out of bounds (size {})",
+                i, size
+            )));
         }
         let result = lean_array_fget(arr, idx);
         Ok(LeanValue::from_raw(result))
@@ -19,8 +19,12 @@ pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
         ("lean_io_prim_handle_flush", io_handle_flush),
         ("lean_io_prim_handle_get_line", io_handle_get_line),
         ("lean_io_prim_handle_put_str", io_handle_put_str),
-        ("lean_io_prim_handle_read_bytes", io_handle_read_bytes),
+        ("lean_io_prim_handle_read", io_handle_read),
+        ("lean_io_prim_handle_read_bytes", io_handle_read),
         ("lean_io_prim_handle_write", io_handle_write),
+        ("lean_io_prim_handle_lock", io_handle_lock),
+        ("lean_io_prim_handle_try_lock", io_handle_try_lock),
+        ("lean_io_prim_handle_unlock", io_handle_unlock),
         ("lean_io_stdin", io_stdin),
         ("lean_io_stderr", io_stderr),
         // Environment
@@ -34,6 +38,16 @@ pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
         ("lean_io_file_exists", io_file_exists),
         ("lean_io_create_dir", io_create_dir),
         ("lean_io_read_dir", io_read_dir),
+        ("lean_io_metadata", io_metadata),
+        ("lean_io_symlink_metadata", io_symlink_metadata),
+        // Compacted regions
+        ("lean_save_module_data", io_save_module_data),
+        ("lean_read_module_data", io_read_module_data),
+        (
+            "lean_compacted_region_is_memory_mapped",
+            compacted_region_is_memory_mapped,
+        ),
+        ("lean_compacted_region_free", compacted_region_free),
     ]
 }
 
@@ -107,74 +121,91 @@ fn io_write_text_file(args: &[LeanValue]) -> Result<LeanValue> {
     }
 }
 
-fn io_handle_mk(_args: &[LeanValue]) -> Result<LeanValue> {
-    Ok(io_result_ok_val(LeanValue::unit()))
+// Handles are runtime handle objects (stdio handles are the scalars 0/1/2),
+// so files and child process pipes share one implementation.
+fn io_handle_mk(args: &[LeanValue]) -> Result<LeanValue> {
+    // Handle.mk path mode
+    unsafe {
+        let mode = lean_unbox(args[1].as_ptr()) as u8;
+        lean_inc(args[0].as_ptr());
+        let r = lean_io_prim_handle_mk(args[0].as_ptr(), mode, 0, lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
 }
 
 fn io_handle_is_eof(_args: &[LeanValue]) -> Result<LeanValue> {
     Ok(io_result_ok_val(LeanValue::from_bool(false)))
 }
 
-fn io_handle_flush(_args: &[LeanValue]) -> Result<LeanValue> {
-    use std::io::Write;
-    let _ = std::io::stdout().flush();
-    Ok(io_result_ok_val(LeanValue::unit()))
+fn io_handle_flush(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_io_prim_handle_flush(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
 }
 
-fn io_handle_get_line(_args: &[LeanValue]) -> Result<LeanValue> {
-    let mut line = String::new();
-    match std::io::stdin().read_line(&mut line) {
-        Ok(_) => {
-            if line.ends_with('\n') {
-                line.pop();
-                if line.ends_with('\r') {
-                    line.pop();
-                }
-            }
-            Ok(io_result_ok_val(LeanValue::from_string(&line)))
-        }
-        Err(e) => Ok(io_result_error(&format!("get_line: {}", e))),
+fn io_handle_get_line(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_io_prim_handle_get_line(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
     }
 }
 
 fn io_handle_put_str(args: &[LeanValue]) -> Result<LeanValue> {
     unsafe {
-        let str_ptr = args[1].as_ptr();
-        let cstr = lean_string_cstr(str_ptr);
-        let len = lean_string_byte_len(str_ptr);
-        let s = std::str::from_utf8(std::slice::from_raw_parts(cstr, len)).unwrap_or("");
-        print!("{}", s);
-        Ok(io_result_ok_val(LeanValue::unit()))
+        lean_inc(args[1].as_ptr());
+        let r = lean_io_prim_handle_put_str(args[0].as_ptr(), args[1].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
     }
 }
 
-fn io_handle_read_bytes(_args: &[LeanValue]) -> Result<LeanValue> {
+fn io_handle_read(args: &[LeanValue]) -> Result<LeanValue> {
     unsafe {
-        let arr = lean_mk_empty_byte_array(lean_box(0));
-        Ok(io_result_ok_val(LeanValue::from_raw(arr)))
+        let r = lean_io_prim_handle_read(args[0].as_ptr(), args[1].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
     }
 }
 
-fn io_handle_write(_args: &[LeanValue]) -> Result<LeanValue> {
-    Ok(io_result_ok_val(LeanValue::from_small_nat(0)))
+fn io_handle_write(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        lean_inc(args[1].as_ptr());
+        let r = lean_io_prim_handle_write(args[0].as_ptr(), args[1].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
 }
 
-fn io_stdin(_args: &[LeanValue]) -> Result<LeanValue> {
+fn io_handle_lock(args: &[LeanValue]) -> Result<LeanValue> {
+    // Handle.lock h exclusive
     unsafe {
-        let handle = lean_alloc_ctor(0, 1, 0);
-        lean_ctor_set(handle, 0, lean_box(0)); // fd = 0
-        Ok(LeanValue::from_raw(handle))
+        let exclusive = lean_unbox(args[1].as_ptr()) as u8;
+        let r = lean_io_prim_handle_lock(args[0].as_ptr(), exclusive, lean_box(0));
+        Ok(LeanValue::from_raw(r))
     }
 }
 
-fn io_stderr(_args: &[LeanValue]) -> Result<LeanValue> {
+fn io_handle_try_lock(args: &[LeanValue]) -> Result<LeanValue> {
     unsafe {
-        let handle = lean_alloc_ctor(0, 1, 0);
-        lean_ctor_set(handle, 0, lean_box(2)); // fd = 2
-        Ok(LeanValue::from_raw(handle))
+        let exclusive = lean_unbox(args[1].as_ptr()) as u8;
+        let r = lean_io_prim_handle_try_lock(args[0].as_ptr(), exclusive, lean_box(0));
+        Ok(LeanValue::from_raw(r))
     }
 }
 
+fn io_handle_unlock(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_io_prim_handle_unlock(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
+}
+
+fn io_stdin(_args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe { Ok(LeanValue::from_raw(lean_box(0))) }
+}
+
+fn io_stderr(_args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe { Ok(LeanValue::from_raw(lean_box(2))) }
+}
+
 fn io_getenv(args: &[LeanValue]) -> Result<LeanValue> {
     unsafe {
         let name_ptr = args[0].as_ptr();
@@ -304,23 +335,60 @@ fn io_create_dir(args: &[LeanValue]) -> Result<LeanValue> {
 
 fn io_read_dir(args: &[LeanValue]) -> Result<LeanValue> {
     unsafe {
-        let path_ptr = args[0].as_ptr();
-        let cstr = lean_string_cstr(path_ptr);
-        let len = lean_string_byte_len(path_ptr);
-        let path_str = std::str::from_utf8(std::slice::from_raw_parts(cstr, len)).unwrap_or("");
+        lean_inc(args[0].as_ptr());
+        let r = lean_io_read_dir(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
+}
 
-        match std::fs::read_dir(path_str) {
-            Ok(entries) => {
-                let arr = lean_mk_empty_array_with_capacity(lean_box(16));
-                let mut arr = arr;
-                for entry in entries.flatten() {
-                    let name = entry.file_name().to_string_lossy().to_string();
-                    let name_obj = LeanValue::from_string(&name).into_raw();
-                    arr = lean_array_push(arr, name_obj);
-                }
-                Ok(io_result_ok_val(LeanValue::from_raw(arr)))
-            }
-            Err(e) => Ok(io_result_error(&format!("read_dir: {}", e))),
-        }
+fn io_metadata(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        lean_inc(args[0].as_ptr());
+        let r = lean_io_metadata(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
+}
+
+fn io_symlink_metadata(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        lean_inc(args[0].as_ptr());
+        let r = lean_io_symlink_metadata(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
+}
+
+fn io_save_module_data(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_save_module_data(
+            args[0].as_ptr(),
+            args[1].as_ptr(),
+            args[2].as_ptr(),
+            lean_box(0),
+        );
+        Ok(LeanValue::from_raw(r))
+    }
+}
+
+fn io_read_module_data(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_read_module_data(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
+}
+
+// A CompactedRegion is a USize holding the address of the runtime's region
+fn compacted_region_is_memory_mapped(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let region = lean_unbox(args[0].as_ptr());
+        Ok(LeanValue::from_bool(
+            lean_compacted_region_is_memory_mapped(region) != 0,
+        ))
+    }
+}
+
+fn compacted_region_free(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_compacted_region_free(lean_unbox(args[0].as_ptr()), lean_box(0));
+        Ok(LeanValue::from_raw(r))
     }
 }
@@ -1,7 +1,8 @@
-//! Miscellaneous operations (panic, sorry, name, char)
+//! Miscellaneous operations (panic, sorry, name, char, sharecommon)
 
 use super::{ExternFn, Result};
 use crate::value::LeanValue;
+use crate::VMError;
 use lean_runtime::*;
 
 pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
@@ -11,6 +12,11 @@ pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
         ("lean_panic_fn", panic_fn),
         // Name operations
         ("lean_name_eq", name_eq),
+        // ShareCommon
+        ("lean_sharecommon_eq", sharecommon_eq),
+        ("lean_sharecommon_hash", sharecommon_hash),
+        ("lean_state_sharecommon", state_sharecommon),
+        ("lean_sharecommon_quick", sharecommon_quick),
         // Note: l_Char_ofNat comes from Init bytecode
     ]
 }
@@ -21,17 +27,12 @@ fn sorry(_args: &[LeanValue]) -> Result<LeanValue> {
 }
 
 fn panic_fn(args: &[LeanValue]) -> Result<LeanValue> {
-    unsafe {
-        if args.len() >= 2 && !lean_is_scalar(args[1].as_ptr()) {
-            let cstr = lean_string_cstr(args[1].as_ptr());
-            let len = lean_string_byte_len(args[1].as_ptr());
-            let slice = std::slice::from_raw_parts(cstr, len);
-            if let Ok(msg) = std::str::from_utf8(slice) {
-                panic!("lean_panic: {}", msg);
-            }
-        }
-    }
-    panic!("lean_panic");
+    // panic_fn (default : α) (msg : String)
+    let msg = match args.get(1) {
+        Some(msg) if !msg.is_scalar() => unsafe { lean_string_to_str(msg.as_ptr()).to_string() },
+        _ => String::new(),
+    };
+    Err(VMError::Panic(msg))
 }
 
 fn name_eq(args: &[LeanValue]) -> Result<LeanValue> {
@@ -40,3 +41,109 @@ fn name_eq(args: &[LeanValue]) -> Result<LeanValue> {
         Ok(LeanValue::from_bool(result != 0))
     }
 }
+
+fn sharecommon_eq(args: &[LeanValue]) -> Result<LeanValue> {
+    // Object.eq (a b : @& Object) : Bool
+    unsafe {
+        let result = lean_sharecommon_eq(args[0].as_ptr(), args[1].as_ptr());
+        Ok(LeanValue::from_bool(result != 0))
+    }
+}
+
+fn sharecommon_hash(args: &[LeanValue]) -> Result<LeanValue> {
+    // Object.hash (a : @& Object) : UInt64
+    unsafe {
+        let result = lean_sharecommon_hash(args[0].as_ptr());
+        Ok(LeanValue::from_raw(lean_uint64_to_nat(result)))
+    }
+}
+
+fn state_sharecommon(args: &[LeanValue]) -> Result<LeanValue> {
+    // State.shareCommon {σ : @& StateFactory} (s : State σ) (a : α) : α × State σ
+    unsafe {
+        Ok(LeanValue::from_raw(lean_state_sharecommon(
+            args[0].as_ptr(),
+            args[1].clone().into_raw(),
+            args[2].clone().into_raw(),
+        )))
+    }
+}
+
+fn sharecommon_quick(args: &[LeanValue]) -> Result<LeanValue> {
+    // shareCommon' (a : @& α)
+    unsafe {
+        Ok(LeanValue::from_raw(lean_sharecommon_quick(
+            args[0].as_ptr(),
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestModule;
+    use crate::value;
+    use crate::VM;
+
+    /// ShareCommon's state and object primitives are reachable as externs
+    #[test]
+    fn sharecommon_externs() {
+        let mut vm = TestModule::new()
+            .externs(&[
+                ("lean_state_sharecommon", 3),
+                ("lean_sharecommon_eq", 2),
+                ("lean_sharecommon_hash", 1),
+            ])
+            .function(
+                "share",
+                2,
+                2,
+                "UnitLit; LoadLocal 0; LoadLocal 1; CallExtern lean_state_sharecommon 3; Ret",
+            )
+            .function(
+                "eq",
+                2,
+                2,
+                "LoadLocal 0; LoadLocal 1; CallExtern lean_sharecommon_eq 2; Ret",
+            )
+            .function(
+                "hash",
+                1,
+                1,
+                "LoadLocal 0; CallExtern lean_sharecommon_hash 1; Ret",
+            )
+            .load();
+
+        // Two equal pairs of distinct strings
+        let pair = || {
+            let p = value::alloc_ctor(0, 2, 0);
+            unsafe {
+                for i in 0..2 {
+                    let s = LeanValue::from_string(&format!("s{}", i));
+                    lean_runtime::lean_ctor_set(p.as_ptr(), i, s.into_raw());
+                }
+            }
+            p
+        };
+        let (x, y) = (pair(), pair());
+        let eq = |vm: &mut VM, a: &LeanValue, b: &LeanValue| {
+            let r = vm.run_function("eq", &[a.clone(), b.clone()]).unwrap();
+            r.unbox() != 0
+        };
+        // Shallow: equal strings, but pairs of different children
+        assert!(eq(&mut vm, &x.ctor_get(0), &y.ctor_get(0)));
+        assert!(!eq(&mut vm, &x.ctor_get(0), &y.ctor_get(1)));
+        assert!(!eq(&mut vm, &x, &y));
+        let hash = vm.run_function("hash", &[x.ctor_get(1)]).unwrap();
+        unsafe {
+            let expected = lean_runtime::lean_sharecommon_hash(y.ctor_get(1).as_ptr());
+            assert_eq!(lean_runtime::lean_uint64_of_nat(hash.as_ptr()), expected);
+        }
+
+        // The state returned by the first call shares y with x
+        let r = vm.run_function("share", &[LeanValue::unit(), x]).unwrap();
+        let (x, st) = (r.ctor_get(0), r.ctor_get(1));
+        let r = vm.run_function("share", &[st, y]).unwrap();
+        assert_eq!(r.ctor_get(0).as_ptr(), x.as_ptr());
+    }
+}
@@ -13,7 +13,7 @@
 //! - refs: IO.Ref and ST.Ref mutable references
 //! - system: System operations (platform, exit, time, process)
 //! - format: Format/pretty-printing operations
-//! - misc: Miscellaneous (panic, sorry, name, char)
+//! - misc: Miscellaneous (panic, sorry, name, char, sharecommon)
 //! - thunk: Thunk/lazy evaluation operations
 //! - task: Task/Thread concurrency operations
 
@@ -35,11 +35,16 @@ mod thunk;
 mod uint;
 
 use crate::value::LeanValue;
-use crate::VMError;
+use crate::{VMError, VM};
 
 pub type Result<T> = std::result::Result<T, VMError>;
 pub type ExternFn = fn(&[LeanValue]) -> Result<LeanValue>;
 
+/// Extern that receives the running VM. Higher-order externs use this to
+/// apply the closures they are given via [`VM::apply`], re-entering the
+/// interpreter. Arguments follow the same convention as [`ExternFn`].
+pub type VmExternFn = fn(&mut VM, &[LeanValue]) -> Result<LeanValue>;
+
 // ============================================================================
 // Macros for generating extern wrappers
 // ============================================================================
@@ -673,6 +678,18 @@ pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
     builtins
 }
 
+/// Register all built-in externs that call back into the VM
+pub fn get_vm_builtins() -> Vec<(&'static str, VmExternFn)> {
+    let mut builtins = Vec::new();
+
+    builtins.extend(string::get_vm_builtins());
+    builtins.extend(thunk::get_vm_builtins());
+    builtins.extend(task::get_vm_builtins());
+    builtins.extend(system::get_vm_builtins());
+
+    builtins
+}
+
 /// Create an IO result (Ok constructor with value + world token)
 pub fn io_result_ok_val(val: LeanValue) -> LeanValue {
     unsafe {
@@ -682,18 +699,11 @@ pub fn io_result_ok_val(val: LeanValue) -> LeanValue {
     }
 }
 
-/// Create an IO error result
+/// Create an IO error result carrying `IO.Error.userError msg`
 pub fn io_result_error(msg: &str) -> LeanValue {
     unsafe {
-        use lean_runtime::{lean_alloc_ctor, lean_ctor_set};
-        // IO.Error.userError (msg : String) : IO.Error
+        use lean_runtime::{lean_io_result_mk_error, lean_mk_io_user_error};
         let msg_obj = LeanValue::from_string(msg).into_raw();
-        let err = lean_alloc_ctor(6, 1, 0); // userError is tag 6
-        lean_ctor_set(err, 0, msg_obj);
-
-        // EStateM.Result.error (error : ε) : Result ε σ α
-        let result = lean_alloc_ctor(1, 1, 0); // error is tag 1
-        lean_ctor_set(result, 0, err);
-        LeanValue::from_raw(result)
+        LeanValue::from_raw(lean_io_result_mk_error(lean_mk_io_user_error(msg_obj)))
     }
 }
@@ -1,7 +1,8 @@
 //! String operations
 
-use super::{delegate, delegate_bool, ExternFn, Result};
+use super::{delegate, delegate_bool, ExternFn, Result, VmExternFn};
 use crate::value::LeanValue;
+use crate::VM;
 use lean_runtime::*;
 
 pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
@@ -27,7 +28,6 @@ pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
         ("lean_string_push", string_push),
         ("lean_string_of_usize", string_of_usize),
         ("lean_string_isempty", string_isempty),
-        ("lean_string_foldl", string_foldl),
         ("lean_string_dec_lt", string_dec_lt),
         ("lean_substring_tostring", substring_tostring),
         ("lean_string_iterator_mk", string_iterator_mk),
@@ -38,6 +38,12 @@ pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
         ("lean_string_memcmp", string_memcmp),
         ("lean_string_data", string_data),
         ("lean_string_isprefixof", string_isprefixof),
+    ]
+}
+
+pub fn get_vm_builtins() -> Vec<(&'static str, VmExternFn)> {
+    vec![
+        ("lean_string_foldl", string_foldl as VmExternFn),
         ("lean_string_any", string_any),
     ]
 }
@@ -105,10 +111,15 @@ fn string_isempty(args: &[LeanValue]) -> Result<LeanValue> {
     }
 }
 
-fn string_foldl(args: &[LeanValue]) -> Result<LeanValue> {
-    // string_foldl f init s - fold over characters
-    // For now, just return the initial value (stub)
-    Ok(args[1].clone())
+fn string_foldl(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    // String.foldl f init s
+    let s = unsafe { lean_string_to_str(args[2].as_ptr()) };
+    let mut acc = args[1].clone();
+    for c in s.chars() {
+        let c = LeanValue::from_small_nat(c as usize);
+        acc = vm.apply(args[0].clone(), vec![acc, c])?;
+    }
+    Ok(acc)
 }
 
 fn substring_tostring(_args: &[LeanValue]) -> Result<LeanValue> {
@@ -139,9 +150,15 @@ fn string_isprefixof(args: &[LeanValue]) -> Result<LeanValue> {
     }
 }
 
-fn string_any(_args: &[LeanValue]) -> Result<LeanValue> {
+fn string_any(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
     // String.any : String → (Char → Bool) → Bool
-    // TODO: implement properly - requires handling bytecode closures
+    let s = unsafe { lean_string_to_str(args[0].as_ptr()) };
+    for c in s.chars() {
+        let c = LeanValue::from_small_nat(c as usize);
+        if vm.apply(args[1].clone(), vec![c])?.tag() != 0 {
+            return Ok(LeanValue::from_bool(true));
+        }
+    }
     Ok(LeanValue::from_bool(false))
 }
 
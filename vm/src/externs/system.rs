@@ -1,7 +1,8 @@
 //! System operations (platform, exit, time, random, process)
 
-use super::{io_result_error, io_result_ok_val, ExternFn, Result};
+use super::{io_result_ok_val, ExternFn, Result, VmExternFn};
 use crate::value::LeanValue;
+use crate::vm::VM;
 use lean_runtime::*;
 
 pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
@@ -27,14 +28,17 @@ pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
         // Note: l_System_Platform_numBits comes from Init bytecode
         ("lean_io_exit", io_exit),
         ("lean_io_get_num_heartbeats", io_get_num_heartbeats),
+        ("lean_io_set_heartbeats", io_set_heartbeats),
         ("lean_io_mono_nanos_now", io_mono_nanos_now),
         ("lean_io_get_random_bytes", io_get_random_bytes),
-        // Command line
-        ("lean_io_get_args", io_get_args),
         // Process operations
         ("lean_io_process_spawn", io_process_spawn),
         ("lean_io_process_wait", io_process_wait),
+        ("lean_io_process_child_wait", io_process_wait),
+        ("lean_io_process_child_try_wait", io_process_try_wait),
+        ("lean_io_process_child_kill", io_process_kill),
         ("lean_io_process_child_take_stdin", io_process_take_stdin),
+        ("lean_io_process_child_pid", io_process_pid),
         // Timing
         ("lean_io_getenv_arg", io_getenv_arg),
         ("lean_io_sleep", io_sleep),
@@ -42,6 +46,13 @@ pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
     ]
 }
 
+pub fn get_vm_builtins() -> Vec<(&'static str, VmExternFn)> {
+    vec![
+        // Command line
+        ("lean_io_get_args", io_get_args as VmExternFn),
+    ]
+}
+
 fn system_platform_nbits(_args: &[LeanValue]) -> Result<LeanValue> {
     Ok(LeanValue::from_small_nat(std::mem::size_of::<usize>() * 8))
 }
@@ -100,9 +111,18 @@ fn io_exit(args: &[LeanValue]) -> Result<LeanValue> {
     std::process::exit(code);
 }
 
+// Heartbeats are counted by the runtime allocator, which also serves every
+// object the VM allocates.
 fn io_get_num_heartbeats(_args: &[LeanValue]) -> Result<LeanValue> {
-    // Return 0 for now (used for profiling)
-    Ok(io_result_ok_val(LeanValue::from_small_nat(0)))
+    unsafe { Ok(LeanValue::from_raw(lean_io_get_num_heartbeats(lean_box(0)))) }
+}
+
+fn io_set_heartbeats(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        lean_inc(args[0].as_ptr());
+        let r = lean_io_set_heartbeats(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
 }
 
 fn io_mono_nanos_now(_args: &[LeanValue]) -> Result<LeanValue> {
@@ -139,76 +159,62 @@ fn io_get_random_bytes(args: &[LeanValue]) -> Result<LeanValue> {
     }
 }
 
-fn io_get_args(_args: &[LeanValue]) -> Result<LeanValue> {
-    // Return command line arguments as Array String
+/// The program arguments set with [`VM::set_args`], as `Array String`.
+fn io_get_args(vm: &mut VM, _args: &[LeanValue]) -> Result<LeanValue> {
     unsafe {
-        let args: Vec<String> = std::env::args().collect();
-        let arr = lean_mk_empty_array_with_capacity(lean_box(args.len()));
-        let mut arr = arr;
-        for arg in args {
-            let s = LeanValue::from_string(&arg).into_raw();
+        let mut arr = lean_mk_empty_array_with_capacity(lean_box(vm.args().len()));
+        for arg in vm.args() {
+            let s = LeanValue::from_string(arg).into_raw();
             arr = lean_array_push(arr, s);
         }
         Ok(io_result_ok_val(LeanValue::from_raw(arr)))
     }
 }
 
+// Processes are runtime child objects; the VM passes the implicit
+// `StdioConfig` argument through like compiled code does.
 fn io_process_spawn(args: &[LeanValue]) -> Result<LeanValue> {
-    // Spawn a child process
-    // Args: (config : Process.SpawnArgs, world)
-    // SpawnArgs contains: cmd, args, cwd, env, stdin, stdout, stderr
     unsafe {
-        // For simplicity, extract cmd as first string field
-        let config = args[0].as_ptr();
-        if lean_is_scalar(config) {
-            return Ok(io_result_error("process_spawn: invalid config"));
-        }
+        lean_inc(args[0].as_ptr());
+        let r = lean_io_process_spawn(args[0].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
+}
 
-        // Extract command (field 0)
-        let cmd_obj = lean_ctor_get(config, 0);
-        let cmd_cstr = lean_string_cstr(cmd_obj);
-        let cmd_len = lean_string_byte_len(cmd_obj);
-        let cmd = std::str::from_utf8(std::slice::from_raw_parts(cmd_cstr, cmd_len)).unwrap_or("");
-
-        // Extract args array (field 1)
-        let args_arr = lean_ctor_get(config, 1);
-        let args_size = lean_unbox(lean_array_get_size(args_arr));
-
-        let mut cmd_args: Vec<String> = Vec::new();
-        for i in 0..args_size {
-            let arg_obj = lean_array_uget(args_arr, i);
-            let arg_cstr = lean_string_cstr(arg_obj);
-            let arg_len = lean_string_byte_len(arg_obj);
-            if let Ok(arg) = std::str::from_utf8(std::slice::from_raw_parts(arg_cstr, arg_len)) {
-                cmd_args.push(arg.to_string());
-            }
-        }
+fn io_process_wait(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_io_process_child_wait(args[0].as_ptr(), args[1].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
+}
 
-        match std::process::Command::new(cmd).args(&cmd_args).spawn() {
-            Ok(child) => {
-                // Return child process handle (store pid as boxed value)
-                let pid = child.id();
-                // Store the child in a global map for later wait
-                // For now, just return the pid as a handle
-                let handle = lean_alloc_ctor(0, 1, 0);
-                lean_ctor_set(handle, 0, lean_box(pid as usize));
-                Ok(io_result_ok_val(LeanValue::from_raw(handle)))
-            }
-            Err(e) => Ok(io_result_error(&format!("process_spawn: {}", e))),
-        }
+fn io_process_try_wait(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_io_process_child_try_wait(args[0].as_ptr(), args[1].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
     }
 }
 
-fn io_process_wait(args: &[LeanValue]) -> Result<LeanValue> {
-    // Wait for a child process (stub - would need process handle storage)
-    let _ = args;
-    // Return exit code 0 as stub
-    Ok(io_result_ok_val(LeanValue::from_small_nat(0)))
+fn io_process_kill(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let r = lean_io_process_child_kill(args[0].as_ptr(), args[1].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
 }
 
-fn io_process_take_stdin(_args: &[LeanValue]) -> Result<LeanValue> {
-    // Take stdin handle from child process (stub)
-    Ok(io_result_ok_val(LeanValue::unit()))
+fn io_process_take_stdin(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        lean_inc(args[1].as_ptr());
+        let r = lean_io_process_child_take_stdin(args[0].as_ptr(), args[1].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
+    }
+}
+
+fn io_process_pid(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        let pid = lean_io_process_child_pid(args[0].as_ptr(), args[1].as_ptr());
+        Ok(LeanValue::from_raw(lean_box_uint32(pid)))
+    }
 }
 
 fn io_getenv_arg(_args: &[LeanValue]) -> Result<LeanValue> {
@@ -1,194 +1,704 @@
 //! Task/Thread concurrency operations
 
-use super::{io_result_ok_val, ExternFn, Result};
-use crate::value::LeanValue;
+use super::{io_result_error, io_result_ok_val, ExternFn, Result, VmExternFn};
+use crate::value::{alloc_ctor, LeanValue};
+use crate::VM;
 use lean_runtime::*;
+use std::ffi::c_void;
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::Duration;
 
 pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
     vec![
-        // Task operations
-        ("lean_task_spawn", task_spawn as ExternFn),
-        ("lean_task_pure", task_pure),
-        ("lean_task_get_own", task_get_own),
-        ("lean_task_bind", task_bind),
-        ("lean_task_map", task_map),
+        ("lean_task_pure", task_pure as ExternFn),
+        ("lean_io_cancel", io_cancel),
+        ("lean_io_check_canceled", io_check_canceled),
+        ("lean_io_get_task_state", io_get_task_state),
         // Promise operations
         ("lean_io_promise_new", promise_new),
         ("lean_io_promise_resolve", promise_resolve),
         ("lean_io_promise_result", promise_result),
-        // Mutex operations (stubs)
+        ("lean_io_promise_result_opt", promise_result_opt),
+        ("lean_option_get_or_block", option_get_or_block),
+        // Mutex operations
         ("lean_io_mutex_new", mutex_new),
         ("lean_io_mutex_lock", mutex_lock),
+        ("lean_io_mutex_try_lock", mutex_try_lock),
         ("lean_io_mutex_unlock", mutex_unlock),
-        // Condition variable (stubs)
+        ("lean_io_basemutex_new", mutex_new),
+        ("lean_io_basemutex_lock", mutex_lock),
+        ("lean_io_basemutex_try_lock", mutex_try_lock),
+        ("lean_io_basemutex_unlock", mutex_unlock),
+        // Condition variable
         ("lean_io_condvar_new", condvar_new),
         ("lean_io_condvar_wait", condvar_wait),
         ("lean_io_condvar_notify_one", condvar_notify_one),
         ("lean_io_condvar_notify_all", condvar_notify_all),
-        // Channel operations (stubs)
-        ("lean_io_channel_new", channel_new),
-        ("lean_io_channel_send", channel_send),
-        ("lean_io_channel_recv", channel_recv),
     ]
 }
 
-// Task: represented as a ctor with tag 1 (completed) holding the value
-// or tag 0 (pending) holding a thunk
+pub fn get_vm_builtins() -> Vec<(&'static str, VmExternFn)> {
+    vec![
+        ("lean_task_spawn", task_spawn as VmExternFn),
+        ("lean_task_get_own", task_get_own),
+        ("lean_task_get", task_get_own),
+        ("lean_task_map", task_map),
+        ("lean_task_bind", task_bind),
+        ("lean_io_as_task", io_as_task),
+        ("lean_io_map_task", io_map_task),
+        ("lean_io_bind_task", io_bind_task),
+        ("lean_io_wait", io_wait),
+        ("lean_io_wait_any", io_wait_any),
+    ]
+}
+
+// VM tasks are constructors, forced on `Task.get` and updated in place:
+// - tag 0: spawned, field 0 is `fn : Unit → α`
+// - tag 1: finished, field 0 is the value
+// - tag 2: `Task.map f t`, tag 3: `Task.bind t f`
+// - tag 4: `IO.mapTask f t`, tag 5: `IO.bindTask t f`
+// Runtime tasks (e.g. promise results) can appear wherever a task is expected.
+// The VM is single-threaded, so spawned work runs right away and
+// continuations are deferred only while their input is still pending.
 
-fn task_spawn(args: &[LeanValue]) -> Result<LeanValue> {
-    // For the bytecode VM, we don't actually spawn threads
-    // Instead, we immediately evaluate the thunk and return a completed task
-    // The thunk is a closure that should be called
+fn finished(value: LeanValue) -> LeanValue {
     unsafe {
-        // args[0] is priority (ignored), args[1] is the thunk
-        let thunk = if args.len() > 1 {
-            args[1].as_ptr()
-        } else {
-            args[0].as_ptr()
-        };
+        let task = lean_alloc_ctor(1, 1, 0);
+        lean_ctor_set(task, 0, value.into_raw());
+        LeanValue::from_raw(task)
+    }
+}
 
-        // For now, just wrap the thunk as a "pending" task
-        // Real implementation would evaluate it
-        lean_inc(thunk);
-        let task = lean_alloc_ctor(0, 1, 0); // tag 0 = pending
-        lean_ctor_set(task, 0, thunk);
-        Ok(io_result_ok_val(LeanValue::from_raw(task)))
+fn deferred(tag: u32, a: &LeanValue, b: &LeanValue) -> LeanValue {
+    unsafe {
+        let task = lean_alloc_ctor(tag, 2, 0);
+        lean_ctor_set(task, 0, a.clone().into_raw());
+        lean_ctor_set(task, 1, b.clone().into_raw());
+        LeanValue::from_raw(task)
     }
 }
 
-fn task_pure(args: &[LeanValue]) -> Result<LeanValue> {
-    // Create a completed task with the given value
+fn is_runtime_task(task: &LeanValue) -> bool {
+    unsafe { lean_obj_tag(task.as_ptr()) == LEAN_TASK_TAG }
+}
+
+fn is_ready(task: &LeanValue) -> bool {
+    if is_runtime_task(task) {
+        unsafe { lean_task_is_finished(task.as_ptr()) }
+    } else {
+        task.tag() == 1
+    }
+}
+
+/// Convert an `EStateM.Result` into `Except IO.Error α`.
+fn io_result_to_except(r: LeanValue) -> LeanValue {
+    let tag = if r.tag() == 0 { 1 } else { 0 };
+    let e = alloc_ctor(tag, 1, 0);
     unsafe {
-        lean_inc(args[0].as_ptr());
-        let task = lean_alloc_ctor(1, 1, 0); // tag 1 = completed
-        lean_ctor_set(task, 0, args[0].as_ptr());
-        Ok(LeanValue::from_raw(task))
+        lean_ctor_set(e.as_ptr(), 0, r.ctor_get(0).into_raw());
     }
+    e
+}
+
+fn run_map(vm: &mut VM, f: &LeanValue, t: &LeanValue) -> Result<LeanValue> {
+    let x = force(vm, t)?;
+    vm.apply(f.clone(), vec![x])
+}
+
+fn run_bind(vm: &mut VM, t: &LeanValue, f: &LeanValue) -> Result<LeanValue> {
+    let x = force(vm, t)?;
+    let next = vm.apply(f.clone(), vec![x])?;
+    force(vm, &next)
+}
+
+fn run_io_map(vm: &mut VM, f: &LeanValue, t: &LeanValue) -> Result<LeanValue> {
+    let x = force(vm, t)?;
+    let r = vm.apply(f.clone(), vec![x, LeanValue::unit()])?;
+    Ok(io_result_to_except(r))
 }
 
-fn task_get_own(args: &[LeanValue]) -> Result<LeanValue> {
-    // Get the value from a task (blocks if pending)
+fn run_io_bind(vm: &mut VM, t: &LeanValue, f: &LeanValue) -> Result<LeanValue> {
+    let x = force(vm, t)?;
+    let r = vm.apply(f.clone(), vec![x, LeanValue::unit()])?;
+    if r.tag() == 0 {
+        force(vm, &r.ctor_get(0))
+    } else {
+        Ok(io_result_to_except(r))
+    }
+}
+
+/// Wait for a task, running any deferred work and caching its value.
+pub(crate) fn force(vm: &mut VM, task: &LeanValue) -> Result<LeanValue> {
+    let t = task.as_ptr();
     unsafe {
-        let task = args[0].as_ptr();
-        let tag = lean_obj_tag(task);
-        if tag == 1 {
-            // Completed - return the value
-            let val = lean_ctor_get(task, 0);
-            lean_inc(val);
-            Ok(LeanValue::from_raw(val))
+        if lean_obj_tag(t) == LEAN_TASK_TAG {
+            return Ok(LeanValue::from_raw_inc(lean_task_get(t)));
+        }
+    }
+    let value = match task.tag() {
+        1 => return Ok(task.ctor_get(0)),
+        0 => vm.apply(task.ctor_get(0), vec![LeanValue::unit()])?,
+        2 => run_map(vm, &task.ctor_get(0), &task.ctor_get(1))?,
+        3 => run_bind(vm, &task.ctor_get(0), &task.ctor_get(1))?,
+        4 => run_io_map(vm, &task.ctor_get(0), &task.ctor_get(1))?,
+        5 => run_io_bind(vm, &task.ctor_get(0), &task.ctor_get(1))?,
+        _ => return Err(crate::VMError::TypeMismatch("task expected")),
+    };
+    unsafe {
+        for i in 0..(*t).num_objs() {
+            lean_dec(lean_ctor_get(t, i));
+            lean_ctor_set(t, i, lean_box(0));
+        }
+        lean_ctor_set(t, 0, value.clone().into_raw());
+        (*t).tag = 1;
+    }
+    Ok(value)
+}
+
+fn task_spawn(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    // Task.spawn fn prio
+    let value = vm.apply(args[0].clone(), vec![LeanValue::unit()])?;
+    Ok(finished(value))
+}
+
+fn task_pure(args: &[LeanValue]) -> Result<LeanValue> {
+    // Create a completed task with the given value
+    Ok(finished(args[0].clone()))
+}
+
+fn task_get_own(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    force(vm, &args[0])
+}
+
+fn task_map(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    // Task.map f t prio sync
+    let (f, t) = (&args[0], &args[1]);
+    if is_ready(t) {
+        Ok(finished(run_map(vm, f, t)?))
+    } else {
+        Ok(deferred(2, f, t))
+    }
+}
+
+fn task_bind(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    // Task.bind t f prio sync
+    let (t, f) = (&args[0], &args[1]);
+    if is_ready(t) {
+        let x = force(vm, t)?;
+        vm.apply(f.clone(), vec![x])
+    } else {
+        Ok(deferred(3, t, f))
+    }
+}
+
+fn io_as_task(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    // IO.asTask act prio
+    let r = vm.apply(args[0].clone(), vec![LeanValue::unit()])?;
+    Ok(io_result_ok_val(finished(io_result_to_except(r))))
+}
+
+fn io_map_task(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    // IO.mapTask f t prio sync
+    let (f, t) = (&args[0], &args[1]);
+    let task = if is_ready(t) {
+        finished(run_io_map(vm, f, t)?)
+    } else {
+        deferred(4, f, t)
+    };
+    Ok(io_result_ok_val(task))
+}
+
+fn io_bind_task(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    // IO.bindTask t f prio sync
+    let (t, f) = (&args[0], &args[1]);
+    let task = if is_ready(t) {
+        let x = force(vm, t)?;
+        let r = vm.apply(f.clone(), vec![x, LeanValue::unit()])?;
+        if r.tag() == 0 {
+            r.ctor_get(0)
         } else {
-            // Pending - for the VM, we'd need to evaluate the thunk
-            // For now, return unit
-            Ok(LeanValue::unit())
+            finished(io_result_to_except(r))
         }
+    } else {
+        deferred(5, t, f)
+    };
+    Ok(io_result_ok_val(task))
+}
+
+fn io_wait(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    Ok(io_result_ok_val(force(vm, &args[0])?))
+}
+
+/// IO.cancel t: VM tasks only run when forced, so only runtime tasks can
+/// observe the request.
+fn io_cancel(args: &[LeanValue]) -> Result<LeanValue> {
+    if is_runtime_task(&args[0]) {
+        unsafe { lean_io_cancel_core(args[0].as_ptr()) };
     }
+    Ok(io_result_ok_val(LeanValue::unit()))
 }
 
-fn task_bind(args: &[LeanValue]) -> Result<LeanValue> {
-    // Bind operation on tasks (stub)
-    let _ = args;
-    task_pure(&[LeanValue::unit()])
+fn io_check_canceled(_args: &[LeanValue]) -> Result<LeanValue> {
+    let canceled = unsafe { lean_io_check_canceled_core() } != 0;
+    Ok(io_result_ok_val(LeanValue::from_bool(canceled)))
 }
 
-fn task_map(args: &[LeanValue]) -> Result<LeanValue> {
-    // Map a function over a task (stub)
-    let _ = args;
-    task_pure(&[LeanValue::unit()])
+/// IO.getTaskState t: a VM task is finished or still waiting to be forced.
+fn io_get_task_state(args: &[LeanValue]) -> Result<LeanValue> {
+    let state = if is_runtime_task(&args[0]) {
+        unsafe { lean_io_get_task_state_core(args[0].as_ptr()) }
+    } else if is_ready(&args[0]) {
+        2
+    } else {
+        0
+    };
+    Ok(io_result_ok_val(LeanValue::from_small_nat(state as usize)))
+}
+
+fn io_wait_any(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    // IO.waitAny tasks h: the first finished task, else wait for a runtime
+    // task or run a deferred VM task
+    let mut tasks = Vec::new();
+    let mut l = args[0].clone();
+    while !l.is_scalar() {
+        tasks.push(l.ctor_get(0));
+        l = l.ctor_get(1);
+    }
+    if tasks.is_empty() {
+        return Err(crate::VMError::Panic("IO.waitAny: empty task list".into()));
+    }
+    let task = if let Some(t) = tasks.iter().find(|t| is_ready(t)) {
+        t.clone()
+    } else if tasks.iter().all(is_runtime_task) {
+        // The chosen task is borrowed from the list
+        unsafe { LeanValue::from_raw_inc(lean_io_wait_any_core(args[0].as_ptr())) }
+    } else {
+        tasks.into_iter().find(|t| !is_runtime_task(t)).unwrap()
+    };
+    Ok(io_result_ok_val(force(vm, &task)?))
 }
 
-// Promise: like a one-shot channel or future
+// Promises are runtime promise objects; their result is a runtime task.
 fn promise_new(_args: &[LeanValue]) -> Result<LeanValue> {
-    // Create a new unfulfilled promise
-    // Represented as ctor with tag 0 (unfulfilled) and no value
+    unsafe { Ok(LeanValue::from_raw(lean_io_promise_new(lean_box(0)))) }
+}
+
+fn promise_resolve(args: &[LeanValue]) -> Result<LeanValue> {
+    // Promise.resolve value promise
     unsafe {
-        let promise = lean_alloc_ctor(0, 1, 0);
-        lean_ctor_set(promise, 0, lean_box(0)); // placeholder
-        Ok(io_result_ok_val(LeanValue::from_raw(promise)))
+        lean_inc(args[0].as_ptr());
+        let r = lean_io_promise_resolve(args[0].as_ptr(), args[1].as_ptr(), lean_box(0));
+        Ok(LeanValue::from_raw(r))
     }
 }
 
-fn promise_resolve(args: &[LeanValue]) -> Result<LeanValue> {
-    // Resolve a promise with a value
+fn promise_result_opt(args: &[LeanValue]) -> Result<LeanValue> {
     unsafe {
-        let promise = args[0].as_ptr();
-        let val = args[1].as_ptr();
-        lean_inc(val);
-        lean_ctor_set(promise, 0, val);
-        // Change tag to 1 (fulfilled) - we can't actually change tag, so this is approximate
-        Ok(io_result_ok_val(LeanValue::unit()))
+        Ok(LeanValue::from_raw(lean_io_promise_result_opt(
+            args[0].as_ptr(),
+        )))
     }
 }
 
+unsafe extern "C" fn option_get_or_block_owned(o: *mut LeanObject) -> *mut LeanObject {
+    let v = lean_option_get_or_block(o);
+    lean_dec(o);
+    v
+}
+
 fn promise_result(args: &[LeanValue]) -> Result<LeanValue> {
-    // Get the promise's associated task/result
+    // Promise.result: the result task with `Option.getOrBlock` mapped over it
     unsafe {
-        let promise = args[0].as_ptr();
-        let val = lean_ctor_get(promise, 0);
-        lean_inc(val);
-        Ok(io_result_ok_val(LeanValue::from_raw(val)))
+        let t = lean_io_promise_result_opt(args[0].as_ptr());
+        let f = lean_alloc_closure(option_get_or_block_owned as *const (), 1, 0);
+        Ok(LeanValue::from_raw(lean_task_map_core(f, t, 0, true)))
     }
 }
 
-// Mutex stubs
+fn option_get_or_block(args: &[LeanValue]) -> Result<LeanValue> {
+    unsafe {
+        Ok(LeanValue::from_raw(lean_option_get_or_block(
+            args[0].as_ptr(),
+        )))
+    }
+}
+
+// Mutex and Condvar are external objects wrapping Rust sync primitives; the
+// external class finalizers free them with the object.
+//
+// The VM runs its own tasks eagerly on the calling thread, so while one of
+// these blocks only runtime tasks can make progress. Waits check for them
+// periodically and fail with a deadlock error once none is left.
+
+/// How long a blocked primitive waits between deadlock checks.
+const DEADLOCK_CHECK_INTERVAL: Duration = Duration::from_millis(50);
+
+const MUTEX_DEADLOCK: &str = "deadlock: no task is left to unlock the mutex";
+const CONDVAR_DEADLOCK: &str = "deadlock: no task is left to notify the condition variable";
+
+/// Wait on `cv` while `blocked` holds. Gives the guard back as an error once
+/// no other task could change that.
+fn wait_while<'a, T>(
+    cv: &Condvar,
+    mut guard: MutexGuard<'a, T>,
+    mut blocked: impl FnMut(&mut T) -> bool,
+) -> std::result::Result<MutexGuard<'a, T>, MutexGuard<'a, T>> {
+    while blocked(&mut guard) {
+        let (g, timeout) = cv
+            .wait_timeout(guard, DEADLOCK_CHECK_INTERVAL)
+            .unwrap_or_else(|e| e.into_inner());
+        guard = g;
+        if timeout.timed_out() && blocked(&mut guard) && !lean_task_others_active() {
+            return Err(guard);
+        }
+    }
+    Ok(guard)
+}
+
+/// A mutex that can be locked and unlocked from separate extern calls.
+struct BaseMutex {
+    locked: Mutex<bool>,
+    unlocked: Condvar,
+}
+
+impl BaseMutex {
+    /// Returns false if the mutex stays locked for good.
+    fn lock(&self) -> bool {
+        let locked = self.locked.lock().unwrap_or_else(|e| e.into_inner());
+        Self::acquire(&self.unlocked, locked)
+    }
+
+    fn acquire(unlocked: &Condvar, locked: MutexGuard<'_, bool>) -> bool {
+        match wait_while(unlocked, locked, |locked| *locked) {
+            Ok(mut locked) => {
+                *locked = true;
+                true
+            }
+            Err(_) => false,
+        }
+    }
+
+    fn try_lock(&self) -> bool {
+        let mut locked = self.locked.lock().unwrap_or_else(|e| e.into_inner());
+        !std::mem::replace(&mut *locked, true)
+    }
+
+    fn unlock(&self) {
+        *self.locked.lock().unwrap_or_else(|e| e.into_inner()) = false;
+        self.unlocked.notify_one();
+    }
+}
+
+unsafe extern "C" fn finalize_mutex(data: *mut c_void) {
+    drop(Box::from_raw(data as *mut BaseMutex));
+}
+
+unsafe extern "C" fn finalize_condvar(data: *mut c_void) {
+    drop(Box::from_raw(data as *mut Condvar));
+}
+
+static MUTEX_CLASS: LeanExternalClass = LeanExternalClass {
+    finalize: Some(finalize_mutex),
+    foreach: None,
+};
+
+static CONDVAR_CLASS: LeanExternalClass = LeanExternalClass {
+    finalize: Some(finalize_condvar),
+    foreach: None,
+};
+
+unsafe fn alloc_sync_object<T>(cls: &'static LeanExternalClass, data: T) -> LeanValue {
+    let cls = cls as *const LeanExternalClass as *mut LeanExternalClass;
+    let obj = lean_alloc_external(cls, Box::into_raw(Box::new(data)) as *mut c_void);
+    LeanValue::from_raw(obj)
+}
+
+unsafe fn external_data<'a, T>(v: &LeanValue) -> &'a T {
+    &*(lean_get_external_data(v.as_ptr()) as *const T)
+}
+
 fn mutex_new(_args: &[LeanValue]) -> Result<LeanValue> {
-    // Create a new mutex (stub - single-threaded VM doesn't need real mutex)
     unsafe {
-        let mutex = lean_alloc_ctor(0, 1, 0);
-        lean_ctor_set(mutex, 0, lean_box(0)); // unlocked
-        Ok(io_result_ok_val(LeanValue::from_raw(mutex)))
+        let m = BaseMutex {
+            locked: Mutex::new(false),
+            unlocked: Condvar::new(),
+        };
+        Ok(io_result_ok_val(alloc_sync_object(&MUTEX_CLASS, m)))
     }
 }
 
 fn mutex_lock(args: &[LeanValue]) -> Result<LeanValue> {
-    // Lock a mutex (stub - always succeeds)
-    let _ = args;
+    if !unsafe { external_data::<BaseMutex>(&args[0]).lock() } {
+        return Ok(io_result_error(MUTEX_DEADLOCK));
+    }
     Ok(io_result_ok_val(LeanValue::unit()))
 }
 
+fn mutex_try_lock(args: &[LeanValue]) -> Result<LeanValue> {
+    let acquired = unsafe { external_data::<BaseMutex>(&args[0]).try_lock() };
+    Ok(io_result_ok_val(LeanValue::from_bool(acquired)))
+}
+
 fn mutex_unlock(args: &[LeanValue]) -> Result<LeanValue> {
-    // Unlock a mutex (stub)
-    let _ = args;
+    unsafe { external_data::<BaseMutex>(&args[0]).unlock() };
     Ok(io_result_ok_val(LeanValue::unit()))
 }
 
-// Condition variable stubs
 fn condvar_new(_args: &[LeanValue]) -> Result<LeanValue> {
     unsafe {
-        let cv = lean_alloc_ctor(0, 0, 0);
-        Ok(io_result_ok_val(LeanValue::from_raw(cv)))
+        Ok(io_result_ok_val(alloc_sync_object(
+            &CONDVAR_CLASS,
+            Condvar::new(),
+        )))
     }
 }
 
+/// Condvar.wait condvar mutex: atomically release `mutex` and wait for a
+/// notification, then re-acquire `mutex` before returning. As with any
+/// condition variable the wakeup may be spurious, so callers re-check their
+/// condition; a wait that no task could end fails instead.
 fn condvar_wait(args: &[LeanValue]) -> Result<LeanValue> {
-    let _ = args;
+    unsafe {
+        let cv = external_data::<Condvar>(&args[0]);
+        let m = external_data::<BaseMutex>(&args[1]);
+        let mut locked = m.locked.lock().unwrap_or_else(|e| e.into_inner());
+        *locked = false;
+        m.unlocked.notify_one();
+        let (locked, timeout) = cv
+            .wait_timeout(locked, DEADLOCK_CHECK_INTERVAL)
+            .unwrap_or_else(|e| e.into_inner());
+        let deadlocked = timeout.timed_out() && !lean_task_others_active();
+        if !BaseMutex::acquire(&m.unlocked, locked) {
+            return Ok(io_result_error(MUTEX_DEADLOCK));
+        }
+        if deadlocked {
+            return Ok(io_result_error(CONDVAR_DEADLOCK));
+        }
+    }
     Ok(io_result_ok_val(LeanValue::unit()))
 }
 
 fn condvar_notify_one(args: &[LeanValue]) -> Result<LeanValue> {
-    let _ = args;
+    unsafe { external_data::<Condvar>(&args[0]).notify_one() };
     Ok(io_result_ok_val(LeanValue::unit()))
 }
 
 fn condvar_notify_all(args: &[LeanValue]) -> Result<LeanValue> {
-    let _ = args;
+    unsafe { external_data::<Condvar>(&args[0]).notify_all() };
     Ok(io_result_ok_val(LeanValue::unit()))
 }
 
-// Channel stubs
-fn channel_new(_args: &[LeanValue]) -> Result<LeanValue> {
-    unsafe {
-        let ch = lean_alloc_ctor(0, 1, 0);
-        lean_ctor_set(ch, 0, lean_box(0));
-        Ok(io_result_ok_val(LeanValue::from_raw(ch)))
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestModule;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    /// The value of an `EStateM.Result.ok`
+    fn ok(r: Result<LeanValue>) -> LeanValue {
+        let r = r.unwrap();
+        assert_eq!(r.tag(), 0);
+        r.ctor_get(0)
     }
-}
 
-fn channel_send(args: &[LeanValue]) -> Result<LeanValue> {
-    let _ = args;
-    Ok(io_result_ok_val(LeanValue::unit()))
-}
+    /// Run `f fixed.. ()` as a runtime task on a worker thread.
+    unsafe fn spawn(f: *const (), fixed: &[&LeanValue]) -> *mut LeanObject {
+        lean_init_task_manager();
+        let n = fixed.len() as u32;
+        let c = lean_alloc_closure(f, n + 1, n);
+        for (i, v) in fixed.iter().enumerate() {
+            lean_closure_set(c, i as u32, (*v).clone().into_raw());
+        }
+        lean_task_spawn_core(c, 0)
+    }
 
-fn channel_recv(args: &[LeanValue]) -> Result<LeanValue> {
-    let _ = args;
-    Ok(io_result_ok_val(LeanValue::unit()))
+    fn list(items: Vec<LeanValue>) -> LeanValue {
+        let mut l = LeanValue::unit();
+        for x in items.into_iter().rev() {
+            let cons = alloc_ctor(1, 2, 0);
+            unsafe {
+                lean_ctor_set(cons.as_ptr(), 0, x.into_raw());
+                lean_ctor_set(cons.as_ptr(), 1, l.into_raw());
+            }
+            l = cons;
+        }
+        l
+    }
+
+    unsafe extern "C" fn slow_five(_unit: *mut LeanObject) -> *mut LeanObject {
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        lean_box(5)
+    }
+
+    #[test]
+    fn wait_any() {
+        let mut vm = VM::new();
+        let unit = LeanValue::unit;
+        unsafe {
+            let promise = LeanValue::from_raw(lean_io_promise_new(lean_box(0))).ctor_get(0);
+            let pending = LeanValue::from_raw(lean_io_promise_result_opt(promise.as_ptr()));
+
+            // A finished VM task wins over a pending runtime task
+            let tasks = list(vec![pending.clone(), finished(LeanValue::from_nat(7))]);
+            let r = ok(io_wait_any(&mut vm, &[tasks, unit(), unit()]));
+            assert_eq!(r.unbox(), 7);
+
+            // Otherwise wait for whichever runtime task finishes first
+            let slow = LeanValue::from_raw(spawn(slow_five as *const (), &[]));
+            let tasks = list(vec![pending.clone(), slow.clone()]);
+            let r = ok(io_wait_any(&mut vm, &[tasks, unit(), unit()]));
+            assert_eq!(r.unbox(), 5);
+            assert!(lean_task_is_finished(slow.as_ptr()));
+        }
+
+        let err = io_wait_any(&mut vm, &[unit(), unit(), unit()]).unwrap_err();
+        assert!(matches!(err, crate::VMError::Panic(_)));
+    }
+
+    #[test]
+    fn cancel_and_task_state() {
+        let state = |t: &LeanValue| ok(io_get_task_state(&[t.clone(), LeanValue::unit()])).unbox();
+        let unit = LeanValue::unit;
+        let vm_task = deferred(2, &unit(), &finished(unit()));
+        assert_eq!(state(&finished(unit())), 2);
+        assert_eq!(state(&vm_task), 0);
+        ok(io_cancel(&[vm_task.clone(), unit()]));
+        assert_eq!(state(&vm_task), 0);
+
+        unsafe {
+            let promise = LeanValue::from_raw(lean_io_promise_new(lean_box(0))).ctor_get(0);
+            let pending = LeanValue::from_raw(lean_io_promise_result_opt(promise.as_ptr()));
+            assert_eq!(state(&pending), 0);
+            ok(io_cancel(&[pending.clone(), unit()]));
+            let r = lean_io_promise_resolve(lean_box(1), promise.as_ptr(), lean_box(0));
+            lean_dec(r);
+            assert_eq!(state(&pending), 2);
+        }
+        // Not inside a runtime task
+        assert_eq!(ok(io_check_canceled(&[unit()])).unbox(), 0);
+    }
+
+    #[test]
+    fn mutex_lock_unlock() {
+        let m = ok(mutex_new(&[LeanValue::unit()]));
+        let m = std::slice::from_ref(&m);
+        ok(mutex_lock(m));
+        assert_eq!(ok(mutex_try_lock(m)).unbox(), 0);
+        ok(mutex_unlock(m));
+        assert_eq!(ok(mutex_try_lock(m)).unbox(), 1);
+        ok(mutex_unlock(m));
+    }
+
+    static SIGNALLED: AtomicBool = AtomicBool::new(false);
+
+    unsafe extern "C" fn signal(
+        m: *mut LeanObject,
+        cv: *mut LeanObject,
+        _unit: *mut LeanObject,
+    ) -> *mut LeanObject {
+        let (m, cv) = (LeanValue::from_raw(m), LeanValue::from_raw(cv));
+        ok(mutex_lock(std::slice::from_ref(&m)));
+        SIGNALLED.store(true, Ordering::SeqCst);
+        ok(condvar_notify_one(std::slice::from_ref(&cv)));
+        ok(mutex_unlock(std::slice::from_ref(&m)));
+        lean_box(0)
+    }
+
+    /// A waiter releases the mutex so the task can take it and notify
+    #[test]
+    fn condvar_wait_notify_across_task() {
+        let m = ok(mutex_new(&[LeanValue::unit()]));
+        let cv = ok(condvar_new(&[LeanValue::unit()]));
+        ok(mutex_lock(std::slice::from_ref(&m)));
+        unsafe {
+            let t = spawn(signal as *const (), &[&m, &cv]);
+            while !SIGNALLED.load(Ordering::SeqCst) {
+                ok(condvar_wait(&[cv.clone(), m.clone()]));
+            }
+            ok(mutex_unlock(std::slice::from_ref(&m)));
+            lean_dec(lean_task_get_own(t));
+        }
+    }
+
+    /// Waits that nothing could end fail instead of hanging the VM
+    #[test]
+    fn deadlock_is_an_error() {
+        let error = |r: LeanValue| unsafe {
+            assert_eq!(r.tag(), 1);
+            let msg = LeanValue::from_raw(lean_io_error_to_string(r.ctor_get(0).into_raw()));
+            lean_string_to_str(msg.as_ptr()).to_string()
+        };
+        // main: let m ← BaseMutex.new; let cv ← Condvar.new; m.lock
+        //       repeat cv.wait m until it fails
+        let mut vm = TestModule::new()
+            .externs(&[
+                ("lean_io_basemutex_new", 1),
+                ("lean_io_condvar_new", 1),
+                ("lean_io_basemutex_lock", 2),
+                ("lean_io_condvar_wait", 3),
+            ])
+            .function(
+                "main",
+                0,
+                3,
+                "UnitLit; CallExtern lean_io_basemutex_new 1; CtorGet 0; StoreLocal 0
+                 UnitLit; CallExtern lean_io_condvar_new 1; CtorGet 0; StoreLocal 1
+                 LoadLocal 0; UnitLit; CallExtern lean_io_basemutex_lock 2; Pop
+                 wait: LoadLocal 1; LoadLocal 0; UnitLit; CallExtern lean_io_condvar_wait 3
+                 StoreLocal 2; LoadLocal 2; JumpIf @done; Jump @wait
+                 done: LoadLocal 2; Ret",
+            )
+            // relock m: m.lock, with m already held by this thread
+            .function(
+                "relock",
+                1,
+                1,
+                "LoadLocal 0; UnitLit; CallExtern lean_io_basemutex_lock 2; Pop
+                 LoadLocal 0; UnitLit; CallExtern lean_io_basemutex_lock 2; Ret",
+            )
+            .load();
+        assert_eq!(error(vm.run().unwrap()), CONDVAR_DEADLOCK);
+        let m = ok(mutex_new(&[LeanValue::unit()]));
+        assert_eq!(
+            error(vm.run_function("relock", &[m]).unwrap()),
+            MUTEX_DEADLOCK
+        );
+    }
+
+    /// `Task.get` runs a suspended map once and updates the VM task in place
+    /// with its value
+    #[test]
+    fn task_forced_in_place() {
+        // main: let p ← Promise.new; let t := Task.map fresh p.result?
+        //       p.resolve (); pure ⟨t.get, t.get, t⟩
+        let mut vm = TestModule::new()
+            .externs(&[
+                ("lean_io_promise_new", 1),
+                ("lean_io_promise_result_opt", 1),
+                ("lean_task_map", 4),
+                ("lean_io_promise_resolve", 3),
+                ("lean_task_get_own", 1),
+            ])
+            .function(
+                "main",
+                0,
+                2,
+                "UnitLit; CallExtern lean_io_promise_new 1; CtorGet 0; StoreLocal 0
+                 AllocClosure fresh 1 0; LoadLocal 0; CallExtern lean_io_promise_result_opt 1
+                 UnitLit; BoolLit 0; CallExtern lean_task_map 4; StoreLocal 1
+                 UnitLit; LoadLocal 0; UnitLit; CallExtern lean_io_promise_resolve 3; Pop
+                 LoadLocal 1; CallExtern lean_task_get_own 1
+                 LoadLocal 1; CallExtern lean_task_get_own 1
+                 LoadLocal 1; AllocCtor 0 3; Ret",
+            )
+            // fresh x: ⟨x⟩, a new object on every call
+            .function("fresh", 1, 1, "LoadLocal 0; AllocCtor 0 1; Ret")
+            .load();
+        let result = vm.run().unwrap();
+        let (first, second) = (result.ctor_get(0), result.ctor_get(1));
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        let forced = result.ctor_get(2);
+        assert_eq!(forced.tag(), 1);
+        assert_eq!(forced.ctor_get(0).as_ptr(), first.as_ptr());
+    }
 }
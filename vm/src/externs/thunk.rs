@@ -1,56 +1,132 @@
 //! Thunk (lazy evaluation) operations
+//!
+//! VM thunks are constructors holding either the value or a suspended
+//! computation, and are updated in place once forced:
+//! - tag 0: `Thunk.mk fn`, field 0 is `fn : Unit → α`
+//! - tag 1: evaluated, field 0 is the value
+//! - tag 2: `Thunk.map f x`, fields are `f` and `x`
+//! - tag 3: `Thunk.bind x f`, fields are `x` and `f`
 
-use super::{ExternFn, Result};
+use super::{ExternFn, Result, VmExternFn};
 use crate::value::LeanValue;
+use crate::VM;
 use lean_runtime::*;
 
 pub fn get_builtins() -> Vec<(&'static str, ExternFn)> {
     vec![
         ("lean_thunk_pure", thunk_pure as ExternFn),
-        ("lean_thunk_get_own", thunk_get_own),
+        ("lean_mk_thunk", mk_thunk),
         ("lean_thunk_map", thunk_map),
         ("lean_thunk_bind", thunk_bind),
     ]
 }
 
-fn thunk_pure(args: &[LeanValue]) -> Result<LeanValue> {
-    // Create a thunk that immediately returns the given value
-    // We represent as a ctor with tag 1 (evaluated) holding the value
-    unsafe {
-        lean_inc(args[0].as_ptr());
-        let thunk = lean_alloc_ctor(1, 1, 0); // tag 1 = evaluated
-        lean_ctor_set(thunk, 0, args[0].as_ptr());
-        Ok(LeanValue::from_raw(thunk))
-    }
+pub fn get_vm_builtins() -> Vec<(&'static str, VmExternFn)> {
+    vec![
+        ("lean_thunk_get_own", thunk_get_own as VmExternFn),
+        ("lean_thunk_get", thunk_get_own),
+    ]
 }
 
-fn thunk_get_own(args: &[LeanValue]) -> Result<LeanValue> {
-    // Get the value from a thunk (force evaluation)
-    // For pure thunks, just return the value
+fn suspended(tag: u32, a: &LeanValue, b: Option<&LeanValue>) -> LeanValue {
     unsafe {
-        let thunk = args[0].as_ptr();
-        let tag = lean_obj_tag(thunk);
-        if tag == 1 {
-            // Already evaluated
-            let val = lean_ctor_get(thunk, 0);
-            lean_inc(val);
-            Ok(LeanValue::from_raw(val))
-        } else {
-            // Unevaluated thunk - would need to run the computation
-            // For now, return unit
-            Ok(LeanValue::unit())
+        let num_objs = if b.is_some() { 2 } else { 1 };
+        let thunk = lean_alloc_ctor(tag, num_objs, 0);
+        lean_ctor_set(thunk, 0, a.clone().into_raw());
+        if let Some(b) = b {
+            lean_ctor_set(thunk, 1, b.clone().into_raw());
         }
+        LeanValue::from_raw(thunk)
     }
 }
 
+fn thunk_pure(args: &[LeanValue]) -> Result<LeanValue> {
+    // Create a thunk that immediately returns the given value
+    Ok(suspended(1, &args[0], None))
+}
+
+fn mk_thunk(args: &[LeanValue]) -> Result<LeanValue> {
+    Ok(suspended(0, &args[0], None))
+}
+
 fn thunk_map(args: &[LeanValue]) -> Result<LeanValue> {
-    // Map a function over a thunk (stub - returns unit thunk)
-    let _ = args;
-    thunk_pure(&[LeanValue::unit()])
+    // Thunk.map f x
+    Ok(suspended(2, &args[0], Some(&args[1])))
 }
 
 fn thunk_bind(args: &[LeanValue]) -> Result<LeanValue> {
-    // Bind operation on thunks (stub - returns unit thunk)
-    let _ = args;
-    thunk_pure(&[LeanValue::unit()])
+    // Thunk.bind x f
+    Ok(suspended(3, &args[0], Some(&args[1])))
+}
+
+/// Force a thunk, evaluating and caching its value if needed.
+pub(crate) fn force(vm: &mut VM, thunk: &LeanValue) -> Result<LeanValue> {
+    let t = thunk.as_ptr();
+    unsafe {
+        if lean_obj_tag(t) == LEAN_THUNK_TAG {
+            // Runtime thunk over a native closure
+            return Ok(LeanValue::from_raw_inc(lean_thunk_get(t)));
+        }
+    }
+    let value = match thunk.tag() {
+        1 => return Ok(thunk.ctor_get(0)),
+        0 => vm.apply(thunk.ctor_get(0), vec![LeanValue::unit()])?,
+        2 => {
+            let x = force(vm, &thunk.ctor_get(1))?;
+            vm.apply(thunk.ctor_get(0), vec![x])?
+        }
+        3 => {
+            let x = force(vm, &thunk.ctor_get(0))?;
+            let next = vm.apply(thunk.ctor_get(1), vec![x])?;
+            force(vm, &next)?
+        }
+        _ => return Err(crate::VMError::TypeMismatch("thunk expected")),
+    };
+    // Cache the value; the suspended computation is released.
+    unsafe {
+        let num_objs = (*t).num_objs();
+        for i in 0..num_objs {
+            lean_dec(lean_ctor_get(t, i));
+            lean_ctor_set(t, i, lean_box(0));
+        }
+        lean_ctor_set(t, 0, value.clone().into_raw());
+        (*t).tag = 1;
+    }
+    Ok(value)
+}
+
+fn thunk_get_own(vm: &mut VM, args: &[LeanValue]) -> Result<LeanValue> {
+    force(vm, &args[0])
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestModule;
+
+    /// `Thunk.get` runs the suspended computation once and updates the VM
+    /// thunk in place with its value
+    #[test]
+    fn thunk_forced_in_place() {
+        // main: let x := Thunk.mk fresh; pure ⟨x.get, x.get, x⟩
+        let mut vm = TestModule::new()
+            .externs(&[("lean_mk_thunk", 1), ("lean_thunk_get_own", 1)])
+            .function(
+                "main",
+                0,
+                1,
+                "AllocClosure fresh 1 0; CallExtern lean_mk_thunk 1; StoreLocal 0
+                 LoadLocal 0; CallExtern lean_thunk_get_own 1
+                 LoadLocal 0; CallExtern lean_thunk_get_own 1
+                 LoadLocal 0; AllocCtor 0 3; Ret",
+            )
+            // fresh x: ⟨x⟩, a new object on every call
+            .function("fresh", 1, 1, "LoadLocal 0; AllocCtor 0 1; Ret")
+            .load();
+        let result = vm.run().unwrap();
+        let (first, second) = (result.ctor_get(0), result.ctor_get(1));
+        assert_eq!(first.as_ptr(), second.as_ptr());
+        let forced = result.ctor_get(2);
+        assert_eq!(forced.tag(), 1);
+        assert_eq!(forced.ctor_get(0).as_ptr(), first.as_ptr());
+    }
 }
@@ -17,21 +17,31 @@
 //! fn main() {
 //!     let module = Module::deserialize(&mut &BYTECODE[..]).unwrap();
 //!     let mut vm = VM::new();
-//!     vm.load_module(module);
+//!     vm.load_module(module).unwrap();
 //!     let result = vm.run().unwrap();
 //! }
 //! ```
 
 pub mod bytecode;
+pub mod closure;
+pub mod debugger;
 pub mod externs;
 pub mod linker;
+pub mod profiler;
+#[cfg(test)]
+mod test_util;
 pub mod value;
+pub mod verify;
 pub mod vm;
 
-pub use bytecode::{BytecodeBuilder, ExternDecl, Function, Module, Opcode};
+pub use bytecode::{
+    disasm_instruction, BytecodeBuilder, Constant, DebugInfo, ExternDecl, Function,
+    FunctionDebugInfo, Module, Opcode, ScalarType, SourcePos,
+};
 pub use linker::{link, FUNC_ID_RESOLVED_BIT};
 pub use value::LeanValue;
-pub use vm::{VMError, VM};
+pub use verify::{verify_module, verify_module_after, VerifyError};
+pub use vm::{Hook, Limits, SourceLocation, StackFrame, VMError, VM};
 
 /// Generate a standalone executable that embeds the given bytecode.
 ///
@@ -64,10 +74,13 @@ fn main() {{
     }};
 
     let mut vm = VM::new();
-    vm.load_module(module);
+    if let Err(e) = vm.load_module(module) {{
+        eprintln!("Error loading bytecode: {{}}", e);
+        std::process::exit(1);
+    }}
 
-    match vm.run() {{
-        Ok(_) => {{}},
+    match vm.run_main(std::env::args().skip(1).collect()) {{
+        Ok(code) => std::process::exit(code as i32),
         Err(e) => {{
             eprintln!("Runtime error: {{}}", e);
             std::process::exit(1);
@@ -118,10 +131,13 @@ fn main() {{
     }};
 
     let mut vm = VM::new();
-    vm.load_module(module);
+    if let Err(e) = vm.load_module(module) {{
+        eprintln!("Error loading bytecode: {{}}", e);
+        std::process::exit(1);
+    }}
 
-    match vm.run() {{
-        Ok(_) => {{}},
+    match vm.run_main(std::env::args().skip(1).collect()) {{
+        Ok(code) => std::process::exit(code as i32),
         Err(e) => {{
             eprintln!("Runtime error: {{}}", e);
             std::process::exit(1);
@@ -192,7 +208,7 @@ mod tests {
         if stdlib_path.exists() {
             let file = fs::File::open(&stdlib_path).unwrap();
             let module = Module::deserialize(&mut BufReader::new(file)).unwrap();
-            vm.load_module(module);
+            vm.load_module(module).unwrap();
             return;
         }
 
@@ -211,7 +227,7 @@ mod tests {
         for path in bc_files {
             let file = fs::File::open(&path).unwrap();
             let module = Module::deserialize(&mut BufReader::new(file)).unwrap();
-            vm.load_module(module);
+            vm.load_module(module).unwrap();
         }
     }
 
@@ -223,7 +239,8 @@ mod tests {
 
         let mut vm = VM::new();
         load_init_modules(&mut vm);
-        vm.load_module(module);
+        vm.load_module(module)
+            .map_err(|e| format!("Failed to load {:?}: {}", path, e))?;
 
         vm.run()
             .map(|_| ())
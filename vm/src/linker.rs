@@ -12,7 +12,9 @@
 /// When set, the lower 31 bits are an absolute function index (no module resolution needed).
 pub const FUNC_ID_RESOLVED_BIT: u32 = 0x80000000;
 
-use crate::bytecode::{ConstantDecl, ExternDecl, Function, Module, Opcode};
+use crate::bytecode::{
+    Constant, ConstantDecl, DebugInfo, ExternDecl, Function, FunctionDebugInfo, Module, Opcode,
+};
 use std::collections::HashMap;
 use std::io;
 
@@ -22,6 +24,7 @@ struct ModuleRemap {
     functions: HashMap<u32, u32>,       // old func id -> new func id
     externs: HashMap<u32, ExternRemap>, // old extern id -> remap info
     constants: HashMap<u32, u32>,       // old const id -> new const id
+    pool_base: u32,                     // offset of this module's constant pool
 }
 
 /// What to do with an extern reference
@@ -54,6 +57,9 @@ pub fn link(modules: Vec<Module>) -> io::Result<Module> {
     let mut const_pool: Vec<ConstantDecl> = Vec::new();
     let mut const_name_to_id: HashMap<String, u32> = HashMap::new();
 
+    // Concatenated constant pools (LoadConst literals)
+    let mut constant_pool: Vec<Constant> = Vec::new();
+
     // Per-module remapping tables
     let mut remaps: Vec<ModuleRemap> = Vec::new();
 
@@ -64,7 +70,9 @@ pub fn link(modules: Vec<Module>) -> io::Result<Module> {
             functions: HashMap::new(),
             externs: HashMap::new(),
             constants: HashMap::new(),
+            pool_base: constant_pool.len() as u32,
         };
+        constant_pool.extend(module.constants.iter().cloned());
 
         // Dedupe strings
         for (old_id, s) in module.strings.iter().enumerate() {
@@ -135,8 +143,10 @@ pub fn link(modules: Vec<Module>) -> io::Result<Module> {
         }
     }
 
-    // Third pass: rewrite bytecode with new IDs
+    // Third pass: rewrite bytecode with new IDs. Rewriting keeps instruction
+    // sizes, so debug info offsets stay valid.
     let mut linked_functions: Vec<Function> = Vec::new();
+    let mut linked_debug_info: Vec<FunctionDebugInfo> = Vec::new();
     for (module_idx, module) in modules.iter().enumerate() {
         let remap = &remaps[module_idx];
         for (old_func_id, func) in module.functions.iter().enumerate() {
@@ -153,6 +163,12 @@ pub fn link(modules: Vec<Module>) -> io::Result<Module> {
                 num_locals: func.num_locals,
                 code: new_code,
             });
+            linked_debug_info.push(
+                module
+                    .function_debug_info(old_func_id)
+                    .cloned()
+                    .unwrap_or_default(),
+            );
         }
     }
 
@@ -190,12 +206,18 @@ pub fn link(modules: Vec<Module>) -> io::Result<Module> {
 
     Ok(Module {
         strings: string_pool,
-        constants: Vec::new(),
+        constants: constant_pool,
         externs: extern_pool,
         const_decls: linked_constants,
         functions: linked_functions,
         entry,
         init_func: init_func_id,
+        debug_info: modules
+            .iter()
+            .any(|m| m.debug_info.is_some())
+            .then_some(DebugInfo {
+                functions: linked_debug_info,
+            }),
     })
 }
 
@@ -358,6 +380,14 @@ fn rewrite_bytecode(
                 result.push(code[pc]); // fields
                 pc += 1;
             }
+            Opcode::AllocCtorScalar => {
+                result.push(code[pc]); // tag
+                pc += 1;
+                result.push(code[pc]); // fields
+                pc += 1;
+                let scalar_size = read_u16(code, &mut pc);
+                result.extend_from_slice(&scalar_size.to_le_bytes());
+            }
 
             // Instructions with one u8 operand
             Opcode::CtorGet
@@ -396,18 +426,21 @@ fn rewrite_bytecode(
                 result.extend_from_slice(&val.to_le_bytes());
             }
 
-            // ScalarProj/ScalarSet: num_objs (u8), offset (u16)
+            // ScalarProj/ScalarSet: num_objs (u8), offset (u16), type (u8)
             Opcode::ScalarProj | Opcode::ScalarSet => {
                 result.push(code[pc]); // num_objs
                 pc += 1;
                 let offset = read_u16(code, &mut pc);
                 result.extend_from_slice(&offset.to_le_bytes());
+                result.push(code[pc]); // type
+                pc += 1;
             }
 
-            // LoadConst has u32 operand
+            // LoadConst indexes the module's constant pool
             Opcode::LoadConst => {
-                let val = read_u32(code, &mut pc);
-                result.extend_from_slice(&val.to_le_bytes());
+                let old_id = read_u32(code, &mut pc);
+                let new_id = remap.pool_base + old_id;
+                result.extend_from_slice(&new_id.to_le_bytes());
             }
 
             // No operands
@@ -578,4 +611,34 @@ mod tests {
         let result = link(vec![m1, m2]).unwrap();
         assert_eq!(result.strings.len(), 3); // shared, unique1, unique2
     }
+
+    #[test]
+    fn test_constant_pool_concat() {
+        let load_const = vec![Opcode::LoadConst as u8, 0, 0, 0, 0, Opcode::Ret as u8];
+        let mut m1 = Module::new();
+        m1.constants.push(Constant::Nat(1));
+        m1.functions.push(Function {
+            name: "f1".to_string(),
+            arity: 0,
+            num_locals: 0,
+            code: load_const.clone(),
+        });
+
+        let mut m2 = Module::new();
+        m2.constants.push(Constant::Float(2.0));
+        m2.functions.push(Function {
+            name: "f2".to_string(),
+            arity: 0,
+            num_locals: 0,
+            code: load_const,
+        });
+
+        let result = link(vec![m1, m2]).unwrap();
+        assert_eq!(
+            result.constants,
+            vec![Constant::Nat(1), Constant::Float(2.0)]
+        );
+        // f2's LoadConst now refers to the second pool entry
+        assert_eq!(&result.functions[1].code[1..5], &1u32.to_le_bytes());
+    }
 }
@@ -1,10 +1,13 @@
 //! Lean 4 VM - Bytecode interpreter CLI
 
 use clap::{Parser, Subcommand};
-use lean4_vm::{Module, VM};
+use lean4_vm::debugger::Debugger;
+use lean4_vm::profiler::Profiler;
+use lean4_vm::{disasm_instruction, Module, VMError, VM};
+use std::any::Any;
 use std::fs::File;
-use std::io::BufReader;
-use std::path::PathBuf;
+use std::io::{BufReader, BufWriter};
+use std::path::{Path, PathBuf};
 use std::process;
 use std::time::Instant;
 
@@ -34,6 +37,18 @@ struct Cli {
     /// Show wall-clock execution time
     #[arg(long)]
     time: bool,
+
+    /// Profile execution and print a per-function report
+    #[arg(long)]
+    profile: bool,
+
+    /// Profile execution and write folded stacks for flamegraph tools
+    #[arg(long, value_name = "FILE")]
+    profile_folded: Option<PathBuf>,
+
+    /// Arguments passed to the program's `main`
+    #[arg(last = true, value_name = "ARGS")]
+    args: Vec<String>,
 }
 
 #[derive(Subcommand)]
@@ -43,6 +58,11 @@ enum Commands {
         /// Bytecode file to disassemble
         file: PathBuf,
     },
+    /// Run bytecode under the interactive debugger
+    Debug {
+        /// Bytecode file to debug
+        file: PathBuf,
+    },
     /// Create standalone executable from bytecode
     Compile {
         /// Bytecode file to compile
@@ -76,6 +96,14 @@ fn disassemble(module: &Module) {
     }
     println!();
 
+    if !module.constants.is_empty() {
+        println!("Constants");
+        for (i, c) in module.constants.iter().enumerate() {
+            println!("  [{}] {:?}", i, c);
+        }
+        println!();
+    }
+
     println!("Externs");
     for (i, ext) in module.externs.iter().enumerate() {
         println!("  [{}] {} (arity {})", i, ext.name, ext.arity);
@@ -88,167 +116,36 @@ fn disassemble(module: &Module) {
             "  [{}] {} (arity {}, locals {})",
             i, func.name, func.arity, func.num_locals
         );
-        disasm_function(func);
+        disasm_function(func, module.function_debug_info(i));
         println!();
     }
 }
 
-fn disasm_function(func: &lean4_vm::Function) {
-    use lean4_vm::Opcode;
+fn disasm_function(func: &lean4_vm::Function, debug: Option<&lean4_vm::FunctionDebugInfo>) {
+    if let Some(debug) = debug {
+        if !debug.decl_name.is_empty() {
+            println!("    ; {}", debug.decl_name);
+        }
+    }
 
     let code = &func.code;
     let mut pc = 0;
+    let mut last_pos = None;
 
     while pc < code.len() {
-        let start_pc = pc;
-        let op_byte = code[pc];
-        pc += 1;
-
-        let op = match Opcode::from_u8(op_byte) {
-            Some(op) => op,
-            None => {
-                println!("    {:04}: <invalid 0x{:02x}>", start_pc, op_byte);
-                continue;
-            }
-        };
-
-        let operands = match op {
-            Opcode::LoadLocal | Opcode::StoreLocal => {
-                let idx = read_u16(code, &mut pc);
-                format!("{}", idx)
-            }
-            Opcode::AllocCtor => {
-                let tag = code[pc];
-                pc += 1;
-                let fields = code[pc];
-                pc += 1;
-                format!("tag={}, fields={}", tag, fields)
-            }
-            Opcode::CtorGet | Opcode::CtorSet | Opcode::CtorSetTag => {
-                let idx = code[pc];
-                pc += 1;
-                format!("{}", idx)
-            }
-            Opcode::Call | Opcode::TailCall => {
-                let func_id = read_u32(code, &mut pc);
-                let num_args = code[pc];
-                pc += 1;
-                format!("func={}, args={}", func_id, num_args)
-            }
-            Opcode::CallExtern => {
-                let ext_id = read_u32(code, &mut pc);
-                let num_args = code[pc];
-                pc += 1;
-                format!("extern={}, args={}", ext_id, num_args)
-            }
-            Opcode::Jump | Opcode::JumpIf | Opcode::JumpIfNot => {
-                let offset = read_i32(code, &mut pc);
-                let target = (pc as i32 + offset) as usize;
-                format!("{} (-> {})", offset, target)
-            }
-            Opcode::Switch => {
-                let num_cases = read_u16(code, &mut pc);
-                let mut s = format!("cases={} [", num_cases);
-                for i in 0..num_cases {
-                    let offset = read_i32(code, &mut pc);
-                    if i > 0 {
-                        s.push_str(", ");
-                    }
-                    s.push_str(&format!("{}", offset));
-                }
-                let default = read_i32(code, &mut pc);
-                s.push_str(&format!("] default={}", default));
-                s
-            }
-            Opcode::NatLit => {
-                let val = read_u64(code, &mut pc);
-                format!("{}", val)
-            }
-            Opcode::StringLit => {
-                let str_id = read_u32(code, &mut pc);
-                format!("str[{}]", str_id)
-            }
-            Opcode::BoolLit => {
-                let val = code[pc];
-                pc += 1;
-                format!("{}", val != 0)
-            }
-            Opcode::Apply | Opcode::TailApply => {
-                let num_args = code[pc];
-                pc += 1;
-                format!("args={}", num_args)
+        // Source position, whenever it changes
+        if let Some(debug) = debug {
+            let pos = debug.position(pc);
+            if let Some(p) = pos.filter(|p| Some((p.line, p.column)) != last_pos) {
+                println!("    ; {}:{}:{}", debug.file, p.line, p.column);
             }
-            Opcode::PartialApp => {
-                let func_id = read_u32(code, &mut pc);
-                let arity = code[pc];
-                pc += 1;
-                let num_args = code[pc];
-                pc += 1;
-                format!("func={}, arity={}, args={}", func_id, arity, num_args)
-            }
-            Opcode::AllocClosure => {
-                let func_id = read_u32(code, &mut pc);
-                let arity = code[pc];
-                pc += 1;
-                let num_captured = code[pc];
-                pc += 1;
-                format!(
-                    "func={}, arity={}, captured={}",
-                    func_id, arity, num_captured
-                )
-            }
-            Opcode::ClosureGet | Opcode::ClosureSet => {
-                let idx = code[pc];
-                pc += 1;
-                format!("{}", idx)
-            }
-            Opcode::LoadConst => {
-                let const_id = read_u32(code, &mut pc);
-                format!("const={}", const_id)
-            }
-            Opcode::ScalarProj => {
-                let num_objs = code[pc];
-                pc += 1;
-                let offset = read_u16(code, &mut pc);
-                format!("num_objs={}, offset={}", num_objs, offset)
-            }
-            Opcode::ScalarSet => {
-                let num_objs = code[pc];
-                pc += 1;
-                let offset = read_u16(code, &mut pc);
-                format!("num_objs={}, offset={}", num_objs, offset)
-            }
-            _ => String::new(),
-        };
-
-        if operands.is_empty() {
-            println!("    {:04}: {:?}", start_pc, op);
-        } else {
-            println!("    {:04}: {:?} {}", start_pc, op, operands);
+            last_pos = pos.map(|p| (p.line, p.column));
         }
-    }
-}
-
-fn read_u16(code: &[u8], pc: &mut usize) -> u16 {
-    let val = u16::from_le_bytes([code[*pc], code[*pc + 1]]);
-    *pc += 2;
-    val
-}
-
-fn read_u32(code: &[u8], pc: &mut usize) -> u32 {
-    let val = u32::from_le_bytes([code[*pc], code[*pc + 1], code[*pc + 2], code[*pc + 3]]);
-    *pc += 4;
-    val
-}
 
-fn read_i32(code: &[u8], pc: &mut usize) -> i32 {
-    read_u32(code, pc) as i32
-}
-
-fn read_u64(code: &[u8], pc: &mut usize) -> u64 {
-    let lo = read_u32(code, pc) as u64;
-    let hi = read_u32(code, pc) as u64;
-    lo | (hi << 32)
+        let (text, next) = disasm_instruction(code, pc, debug);
+        println!("    {:04}: {}", pc, text);
+        pc = next;
+    }
 }
 
 fn load_module(path: &PathBuf) -> Module {
@@ -262,6 +159,126 @@ fn load_module(path: &PathBuf) -> Module {
     })
 }
 
+/// Load `module`, read from `path`, into the VM, exiting if it is rejected.
+fn add_module(vm: &mut VM, module: Module, path: &Path) {
+    vm.load_module(module).unwrap_or_else(|e| {
+        eprintln!("Error loading {}: {}", path.display(), e);
+        process::exit(1);
+    })
+}
+
+/// Create a VM with the standard library, the modules in `lib_dirs` and
+/// `includes`, and `main_file` loaded. Also returns the number of modules and
+/// functions.
+fn load_program(
+    lib_dirs: &[PathBuf],
+    includes: &[PathBuf],
+    main_file: &PathBuf,
+) -> (VM, usize, usize) {
+    let mut vm = VM::new();
+    let mut total_functions = 0;
+    let mut total_modules = 0;
+
+    // Auto-detect stdlib relative to executable
+    // Priority:
+    // 1. Linked stdlib (vm/bc/stdlib/Init.leanbc) - fastest, production use
+    // 2. Individual init modules (vm/init/*.leanbc) - fallback for development
+    // 3. LEAN_VM env var
+    let mut lib_dirs = lib_dirs.to_vec();
+    let mut include_files = includes.to_vec();
+
+    if let Ok(exe_path) = std::env::current_exe() {
+        if let Some(exe_dir) = exe_path.parent() {
+            // Try linked stdlib first (workspace layout: target/release -> vm/bc/stdlib)
+            let workspace_stdlib = exe_dir.join("../../vm/bc/stdlib/Init.leanbc");
+            let installed_stdlib = exe_dir.join("bc/stdlib/Init.leanbc");
+
+            if workspace_stdlib.is_file() {
+                if let Ok(canonical) = workspace_stdlib.canonicalize() {
+                    include_files.insert(0, canonical);
+                }
+            } else if installed_stdlib.is_file() {
+                include_files.insert(0, installed_stdlib);
+            } else {
+                // Fallback to individual init modules (flat layout)
+                let workspace_init = exe_dir.join("../../vm/init");
+                if workspace_init.is_dir() {
+                    if let Ok(canonical) = workspace_init.canonicalize() {
+                        lib_dirs.insert(0, canonical);
+                    }
+                } else {
+                    let installed_init = exe_dir.join("init");
+                    if installed_init.is_dir() {
+                        lib_dirs.insert(0, installed_init);
+                    }
+                }
+            }
+        }
+    }
+    // Also check LEAN_VM env var for init directory
+    if let Ok(init_path) = std::env::var("LEAN_VM") {
+        let path = PathBuf::from(init_path);
+        if path.is_dir() && !lib_dirs.contains(&path) {
+            lib_dirs.insert(0, path);
+        } else if path.is_file() && !include_files.contains(&path) {
+            include_files.insert(0, path);
+        }
+    }
+
+    // Load modules from library directories
+    for lib_dir in &lib_dirs {
+        if !lib_dir.is_dir() {
+            eprintln!(
+                "Warning: {} is not a directory, skipping",
+                lib_dir.display()
+            );
+            continue;
+        }
+        let mut bc_files: Vec<_> = std::fs::read_dir(lib_dir)
+            .unwrap_or_else(|e| {
+                eprintln!("Error reading {}: {}", lib_dir.display(), e);
+                process::exit(1);
+            })
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .filter(|p| p.extension().is_some_and(|ext| ext == "leanbc"))
+            .collect();
+        bc_files.sort();
+        for bc_path in bc_files {
+            let module = load_module(&bc_path);
+            total_functions += module.functions.len();
+            total_modules += 1;
+            #[cfg(feature = "trace")]
+            eprintln!(
+                "Loaded {} ({} functions)",
+                bc_path.display(),
+                module.functions.len()
+            );
+            add_module(&mut vm, module, &bc_path);
+        }
+    }
+
+    // Load individual include files (including auto-detected stdlib)
+    for inc_path in &include_files {
+        let module = load_module(inc_path);
+        total_functions += module.functions.len();
+        total_modules += 1;
+        #[cfg(feature = "trace")]
+        eprintln!(
+            "Loaded include {} ({} functions)",
+            inc_path.display(),
+            module.functions.len()
+        );
+        add_module(&mut vm, module, inc_path);
+    }
+
+    let module = load_module(main_file);
+    total_functions += module.functions.len();
+    total_modules += 1;
+    add_module(&mut vm, module, main_file);
+    (vm, total_modules, total_functions)
+}
+
 fn main() {
     let cli = Cli::parse();
 
@@ -270,6 +287,21 @@ fn main() {
             let module = load_module(&file);
             disassemble(&module);
         }
+        Some(Commands::Debug { file }) => {
+            let (mut vm, _, _) = load_program(&cli.lib_dirs, &cli.includes, &file);
+            vm.set_hook(Box::new(Debugger::new(
+                std::io::stdin().lock(),
+                std::io::stdout(),
+            )));
+            match vm.run() {
+                Ok(_) => println!("Program finished"),
+                Err(e) if matches!(e.root(), VMError::Interrupted) => {}
+                Err(e) => {
+                    eprintln!("Runtime error: {}", e);
+                    process::exit(1);
+                }
+            }
+        }
         Some(Commands::Compile { file, output }) => {
             let bytecode = std::fs::read(&file).unwrap_or_else(|e| {
                 eprintln!("Error reading {}: {}", file.display(), e);
@@ -360,128 +392,51 @@ fn main() {
         None => {
             let main_file = cli.file.unwrap_or_else(|| {
                 eprintln!("Error: no bytecode file specified");
-                eprintln!("Usage: lean4-vm [OPTIONS] <FILE>");
+                eprintln!("Usage: lean4-vm [OPTIONS] <FILE> [-- <ARGS>...]");
                 eprintln!("       lean4-vm disasm <FILE>");
+                eprintln!("       lean4-vm debug <FILE>");
                 eprintln!("       lean4-vm compile <FILE> -o <OUTPUT>");
                 eprintln!("       lean4-vm link -o <OUTPUT> <FILES>...");
                 process::exit(1);
             });
 
             let load_start = Instant::now();
-            let mut vm = VM::new();
-            let mut total_functions = 0;
-            let mut total_modules = 0;
-
-            // Auto-detect stdlib relative to executable
-            // Priority:
-            // 1. Linked stdlib (vm/bc/stdlib/Init.leanbc) - fastest, production use
-            // 2. Individual init modules (vm/init/*.leanbc) - fallback for development
-            // 3. LEAN_VM env var
-            let mut lib_dirs = cli.lib_dirs.clone();
-            let mut include_files = cli.includes.clone();
-
-            if let Ok(exe_path) = std::env::current_exe() {
-                if let Some(exe_dir) = exe_path.parent() {
-                    // Try linked stdlib first (workspace layout: target/release -> vm/bc/stdlib)
-                    let workspace_stdlib = exe_dir.join("../../vm/bc/stdlib/Init.leanbc");
-                    let installed_stdlib = exe_dir.join("bc/stdlib/Init.leanbc");
-
-                    if workspace_stdlib.is_file() {
-                        if let Ok(canonical) = workspace_stdlib.canonicalize() {
-                            include_files.insert(0, canonical);
-                        }
-                    } else if installed_stdlib.is_file() {
-                        include_files.insert(0, installed_stdlib);
-                    } else {
-                        // Fallback to individual init modules (flat layout)
-                        let workspace_init = exe_dir.join("../../vm/init");
-                        if workspace_init.is_dir() {
-                            if let Ok(canonical) = workspace_init.canonicalize() {
-                                lib_dirs.insert(0, canonical);
-                            }
-                        } else {
-                            let installed_init = exe_dir.join("init");
-                            if installed_init.is_dir() {
-                                lib_dirs.insert(0, installed_init);
-                            }
-                        }
-                    }
-                }
-            }
-            // Also check LEAN_VM env var for init directory
-            if let Ok(init_path) = std::env::var("LEAN_VM") {
-                let path = PathBuf::from(init_path);
-                if path.is_dir() && !lib_dirs.contains(&path) {
-                    lib_dirs.insert(0, path);
-                } else if path.is_file() && !include_files.contains(&path) {
-                    include_files.insert(0, path);
-                }
-            }
+            let (mut vm, total_modules, total_functions) =
+                load_program(&cli.lib_dirs, &cli.includes, &main_file);
+            let load_time = load_start.elapsed();
 
-            // Load modules from library directories
-            for lib_dir in &lib_dirs {
-                if !lib_dir.is_dir() {
-                    eprintln!(
-                        "Warning: {} is not a directory, skipping",
-                        lib_dir.display()
-                    );
-                    continue;
-                }
-                let mut bc_files: Vec<_> = std::fs::read_dir(lib_dir)
-                    .unwrap_or_else(|e| {
-                        eprintln!("Error reading {}: {}", lib_dir.display(), e);
-                        process::exit(1);
-                    })
-                    .filter_map(|e| e.ok())
-                    .map(|e| e.path())
-                    .filter(|p| p.extension().is_some_and(|ext| ext == "leanbc"))
-                    .collect();
-                bc_files.sort();
-                for bc_path in bc_files {
-                    let module = load_module(&bc_path);
-                    total_functions += module.functions.len();
-                    total_modules += 1;
-                    #[cfg(feature = "trace")]
-                    eprintln!(
-                        "Loaded {} ({} functions)",
-                        bc_path.display(),
-                        module.functions.len()
-                    );
-                    vm.load_module(module);
-                }
+            let profiling = cli.profile || cli.profile_folded.is_some();
+            if profiling {
+                vm.set_hook(Box::new(Profiler::new()));
             }
 
-            // Load individual include files (including auto-detected stdlib)
-            for inc_path in &include_files {
-                let module = load_module(inc_path);
-                total_functions += module.functions.len();
-                total_modules += 1;
-                #[cfg(feature = "trace")]
-                eprintln!(
-                    "Loaded include {} ({} functions)",
-                    inc_path.display(),
-                    module.functions.len()
-                );
-                vm.load_module(module);
-            }
-
-            let module = load_module(&main_file);
-            total_functions += module.functions.len();
-            total_modules += 1;
-            vm.load_module(module);
-            let load_time = load_start.elapsed();
-
             let exec_start = Instant::now();
-            let result = vm.run();
+            let result = vm.run_main(cli.args);
             let exec_time = exec_start.elapsed();
 
-            match result {
-                Ok(_) => {}
+            if profiling {
+                let hook: Box<dyn Any> = vm.take_hook().unwrap();
+                let mut profiler = hook.downcast::<Profiler>().unwrap();
+                profiler.finish();
+                if cli.profile {
+                    let _ = profiler.report(&mut std::io::stderr());
+                }
+                if let Some(path) = &cli.profile_folded {
+                    let written = File::create(path)
+                        .and_then(|mut f| profiler.write_folded(&mut BufWriter::new(&mut f)));
+                    if let Err(e) = written {
+                        eprintln!("Error writing {}: {}", path.display(), e);
+                    }
+                }
+            }
+
+            let exit_code = match result {
+                Ok(code) => code,
                 Err(e) => {
                     eprintln!("Runtime error: {}", e);
                     process::exit(1);
                 }
-            }
+            };
 
             // Print timing stats if requested
             if cli.time || cli.stats {
@@ -498,6 +453,8 @@ fn main() {
                     (load_time + exec_time).as_secs_f64()
                 );
             }
+
+            process::exit(exit_code as i32);
         }
     }
 }
@@ -0,0 +1,367 @@
+//! Instrumenting profiler
+//!
+//! [`Profiler`] is a [`Hook`] that counts, per bytecode function, calls and
+//! the instructions and allocations executed in it (self) or below it
+//! (inclusive), and time spent per extern. It keeps a shadow of the VM's
+//! call stack, which it reconciles with [`VM::depth`] as frames are pushed
+//! and popped, and records a call tree for folded-stack output. `lean4-vm
+//! --profile` prints [`Profiler::report`].
+
+use crate::vm::{Hook, VMError, VM};
+use lean_runtime::{
+    lean_alloc_prof_begin, lean_alloc_prof_count, lean_alloc_prof_end, AllocKind, AllocStats,
+};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+
+type Result<T> = std::result::Result<T, VMError>;
+
+/// Statistics for one bytecode function.
+#[derive(Debug, Clone, Default)]
+pub struct FunctionProfile {
+    /// Display name: the Lean declaration name if known, else the compiled name
+    pub name: String,
+    pub calls: u64,
+    pub self_instructions: u64,
+    /// Instructions executed in this function and everything it called.
+    /// Recursive activations are counted once.
+    pub inclusive_instructions: u64,
+    pub self_allocations: u64,
+}
+
+/// Statistics for one extern function.
+#[derive(Debug, Clone, Default)]
+pub struct ExternProfile {
+    pub calls: u64,
+    pub time: Duration,
+}
+
+/// Node of the call tree, one per distinct call path.
+struct Node {
+    func: usize,
+    parent: usize,
+    children: HashMap<usize, usize>,
+    self_instructions: u64,
+}
+
+/// An activation on the shadow call stack.
+struct Active {
+    func: usize,
+    node: usize,
+    /// Instruction count when the activation started
+    entry_instructions: u64,
+}
+
+/// Profiling hook. Install it with [`VM::set_hook`], and after the run take
+/// it back and call [`Profiler::finish`] before reporting.
+pub struct Profiler {
+    start: Instant,
+    elapsed: Duration,
+    alloc_start: Option<AllocStats>,
+    allocs: AllocStats,
+    last_alloc_count: u64,
+    /// Function of the previous instruction
+    last_func: Option<usize>,
+    instructions: u64,
+    functions: Vec<FunctionProfile>,
+    function_ids: HashMap<(usize, usize), usize>,
+    /// Number of activations of each function on the shadow stack
+    active_count: Vec<usize>,
+    externs: HashMap<String, ExternProfile>,
+    /// Call tree; node 0 is the root above the outermost frames
+    nodes: Vec<Node>,
+    stack: Vec<Active>,
+}
+
+impl Default for Profiler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Profiler {
+    /// Start profiling. Allocation counting starts here and stops at
+    /// [`Profiler::finish`].
+    pub fn new() -> Self {
+        Profiler {
+            start: Instant::now(),
+            elapsed: Duration::ZERO,
+            alloc_start: Some(unsafe { lean_alloc_prof_begin() }),
+            allocs: AllocStats::default(),
+            last_alloc_count: lean_alloc_prof_count(),
+            last_func: None,
+            instructions: 0,
+            functions: Vec::new(),
+            function_ids: HashMap::new(),
+            active_count: Vec::new(),
+            externs: HashMap::new(),
+            nodes: vec![Node {
+                func: usize::MAX,
+                parent: 0,
+                children: HashMap::new(),
+                self_instructions: 0,
+            }],
+            stack: Vec::new(),
+        }
+    }
+
+    /// Stop profiling, ending all activations still on the shadow stack.
+    pub fn finish(&mut self) {
+        while !self.stack.is_empty() {
+            self.pop();
+        }
+        if let Some(start) = self.alloc_start.take() {
+            let allocs = unsafe { lean_alloc_prof_end(start) };
+            if let Some(func) = self.last_func {
+                self.functions[func].self_allocations +=
+                    lean_alloc_prof_count() - self.last_alloc_count;
+            }
+            self.allocs = allocs;
+            self.elapsed = self.start.elapsed();
+        }
+    }
+
+    /// Per-function statistics, in order of first call.
+    pub fn functions(&self) -> &[FunctionProfile] {
+        &self.functions
+    }
+
+    pub fn externs(&self) -> &HashMap<String, ExternProfile> {
+        &self.externs
+    }
+
+    /// Total number of instructions executed.
+    pub fn instructions(&self) -> u64 {
+        self.instructions
+    }
+
+    fn function_id(&mut self, vm: &VM, mod_idx: usize, func_idx: usize) -> usize {
+        if let Some(&id) = self.function_ids.get(&(mod_idx, func_idx)) {
+            return id;
+        }
+        let frame = vm.stack_frame(mod_idx, func_idx, 0);
+        let id = self.functions.len();
+        self.functions.push(FunctionProfile {
+            name: frame.decl_name.unwrap_or(frame.function),
+            ..Default::default()
+        });
+        self.function_ids.insert((mod_idx, func_idx), id);
+        self.active_count.push(0);
+        id
+    }
+
+    /// Start an activation of the function in VM frame `frame`.
+    fn push(&mut self, vm: &VM, frame: usize) {
+        let Some((mod_idx, func_idx, _)) = vm.frame_position(frame) else {
+            return;
+        };
+        let func = self.function_id(vm, mod_idx, func_idx);
+        let parent = self.stack.last().map_or(0, |a| a.node);
+        let node = match self.nodes[parent].children.get(&func) {
+            Some(&node) => node,
+            None => {
+                let node = self.nodes.len();
+                self.nodes.push(Node {
+                    func,
+                    parent,
+                    children: HashMap::new(),
+                    self_instructions: 0,
+                });
+                self.nodes[parent].children.insert(func, node);
+                node
+            }
+        };
+        self.active_count[func] += 1;
+        self.stack.push(Active {
+            func,
+            node,
+            entry_instructions: self.instructions,
+        });
+    }
+
+    fn pop(&mut self) {
+        let Some(active) = self.stack.pop() else {
+            return;
+        };
+        self.active_count[active.func] -= 1;
+        if self.active_count[active.func] == 0 {
+            self.functions[active.func].inclusive_instructions +=
+                self.instructions - active.entry_instructions;
+        }
+    }
+
+    /// Bring the shadow stack in line with the VM's frames: end activations
+    /// whose frames were popped, and start ones for frames that were pushed
+    /// before profiling began.
+    fn sync(&mut self, vm: &VM, depth: usize) {
+        while self.stack.len() > depth {
+            self.pop();
+        }
+        while self.stack.len() < depth {
+            self.push(vm, vm.depth() - self.stack.len() - 1);
+        }
+    }
+
+    /// Write a flat report, functions sorted by self instructions.
+    pub fn report(&self, w: &mut impl Write) -> io::Result<()> {
+        let total = self.instructions.max(1) as f64;
+        writeln!(
+            w,
+            "Profile: {} instructions in {:.3}s",
+            self.instructions,
+            self.elapsed.as_secs_f64()
+        )?;
+        writeln!(w)?;
+        writeln!(
+            w,
+            "{:>10} {:>14} {:>7} {:>14} {:>7} {:>10}  function",
+            "calls", "self", "self%", "inclusive", "incl%", "allocs"
+        )?;
+        let mut functions: Vec<_> = self.functions.iter().collect();
+        functions.sort_by_key(|f| std::cmp::Reverse(f.self_instructions));
+        for f in functions {
+            writeln!(
+                w,
+                "{:>10} {:>14} {:>6.2}% {:>14} {:>6.2}% {:>10}  {}",
+                f.calls,
+                f.self_instructions,
+                f.self_instructions as f64 * 100.0 / total,
+                f.inclusive_instructions,
+                f.inclusive_instructions as f64 * 100.0 / total,
+                f.self_allocations,
+                f.name
+            )?;
+        }
+
+        if !self.externs.is_empty() {
+            writeln!(w)?;
+            writeln!(w, "{:>10} {:>12}  extern", "calls", "time")?;
+            let mut externs: Vec<_> = self.externs.iter().collect();
+            externs.sort_by_key(|(_, e)| std::cmp::Reverse(e.time));
+            for (name, e) in externs {
+                writeln!(
+                    w,
+                    "{:>10} {:>11.6}s  {}",
+                    e.calls,
+                    e.time.as_secs_f64(),
+                    name
+                )?;
+            }
+        }
+
+        writeln!(w)?;
+        writeln!(w, "{:>10} {:>14}  allocations", "objects", "bytes")?;
+        for kind in AllocKind::ALL {
+            let (num, bytes) = self.allocs.get(kind);
+            if num > 0 {
+                writeln!(w, "{:>10} {:>14}  {}", num, bytes, kind.name())?;
+            }
+        }
+        let (num, bytes) = self.allocs.total();
+        writeln!(w, "{:>10} {:>14}  total", num, bytes)
+    }
+
+    /// Write the call tree in the folded-stack format read by flamegraph
+    /// tools: one `outer;...;inner count` line per call path, weighted by
+    /// self instructions.
+    pub fn write_folded(&self, w: &mut impl Write) -> io::Result<()> {
+        for (i, node) in self.nodes.iter().enumerate().skip(1) {
+            if node.self_instructions == 0 {
+                continue;
+            }
+            let mut path = Vec::new();
+            let mut n = i;
+            while n != 0 {
+                path.push(self.functions[self.nodes[n].func].name.as_str());
+                n = self.nodes[n].parent;
+            }
+            path.reverse();
+            writeln!(w, "{} {}", path.join(";"), node.self_instructions)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Profiler {
+    fn drop(&mut self) {
+        if let Some(start) = self.alloc_start.take() {
+            unsafe { lean_alloc_prof_end(start) };
+        }
+    }
+}
+
+impl Hook for Profiler {
+    fn before_instruction(&mut self, vm: &VM) -> Result<()> {
+        // Objects allocated since the previous instruction belong to it
+        let alloc_count = lean_alloc_prof_count();
+        if let Some(func) = self.last_func {
+            self.functions[func].self_allocations += alloc_count - self.last_alloc_count;
+        }
+        self.last_alloc_count = alloc_count;
+
+        self.sync(vm, vm.depth());
+        let Some(active) = self.stack.last() else {
+            return Ok(());
+        };
+        self.instructions += 1;
+        self.functions[active.func].self_instructions += 1;
+        self.nodes[active.node].self_instructions += 1;
+        self.last_func = Some(active.func);
+        Ok(())
+    }
+
+    fn on_call(&mut self, vm: &VM) -> Result<()> {
+        // The new frame is innermost; a tail call replaced its caller's
+        self.sync(vm, vm.depth() - 1);
+        self.push(vm, 0);
+        let func = self.stack.last().unwrap().func;
+        self.functions[func].calls += 1;
+        Ok(())
+    }
+
+    fn after_extern(&mut self, _vm: &VM, name: &str, elapsed: Duration) -> Result<()> {
+        let e = self.externs.entry(name.to_string()).or_default();
+        e.calls += 1;
+        e.time += elapsed;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::panic_module;
+    use crate::VM;
+
+    /// The profiler attributes instructions to functions and call paths
+    #[test]
+    fn counts() {
+        let mut vm = VM::new();
+        vm.load_module(panic_module()).unwrap();
+        vm.set_hook(Box::new(Profiler::new()));
+        assert!(vm.run().is_err());
+
+        let hook: Box<dyn std::any::Any> = vm.take_hook().unwrap();
+        let mut profiler = hook.downcast::<Profiler>().unwrap();
+        profiler.finish();
+        assert_eq!(profiler.instructions(), 5);
+        let counts: Vec<_> = profiler
+            .functions()
+            .iter()
+            .map(|f| {
+                (
+                    f.name.as_str(),
+                    f.calls,
+                    f.self_instructions,
+                    f.inclusive_instructions,
+                )
+            })
+            .collect();
+        assert_eq!(counts, [("main", 1, 2, 5), ("fail", 1, 3, 3)]);
+
+        let mut folded = Vec::new();
+        profiler.write_folded(&mut folded).unwrap();
+        assert_eq!(String::from_utf8(folded).unwrap(), "main 2\nmain;fail 3\n");
+    }
+}
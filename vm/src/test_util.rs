@@ -0,0 +1,242 @@
+//! Bytecode modules for unit tests, written in the disassembler's syntax.
+
+use crate::bytecode::{BytecodeBuilder, ExternDecl, Function, Module, Opcode, ScalarType};
+use crate::VM;
+use std::collections::HashMap;
+
+/// Builds a [`Module`] from function bodies written as assembly text, one
+/// instruction per line or separated by `;`:
+///
+/// ```text
+/// LoadLocal 0; CallExtern lean_string_any 2; JumpIfNot @skip
+/// NatLit 1; Ret
+/// skip: UnitLit; Ret
+/// ```
+///
+/// Operands are numbers, `@label` jump targets or raw offsets, `"string"`
+/// literals added to the string table, and names of functions, externs or
+/// scalar types. A `name:` prefix defines a label.
+#[derive(Default)]
+pub struct TestModule {
+    externs: Vec<(String, u8)>,
+    functions: Vec<(String, u8, u16, String)>,
+}
+
+impl TestModule {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Declare externs by name and arity.
+    pub fn externs(mut self, externs: &[(&str, u8)]) -> Self {
+        self.externs.extend(
+            externs
+                .iter()
+                .map(|&(name, arity)| (name.to_string(), arity)),
+        );
+        self
+    }
+
+    /// Add a function whose first `arity` of `num_locals` locals are its
+    /// arguments. The first function added is the entry point.
+    pub fn function(mut self, name: &str, arity: u8, num_locals: u16, code: &str) -> Self {
+        self.functions
+            .push((name.to_string(), arity, num_locals, code.to_string()));
+        self
+    }
+
+    pub fn build(self) -> Module {
+        let mut m = Module::new();
+        m.externs = self
+            .externs
+            .iter()
+            .map(|(name, arity)| ExternDecl {
+                name: name.clone(),
+                arity: *arity,
+            })
+            .collect();
+        let ids = |names: Vec<&String>| -> HashMap<String, u32> {
+            names
+                .into_iter()
+                .enumerate()
+                .map(|(i, name)| (name.clone(), i as u32))
+                .collect()
+        };
+        let funcs = ids(self.functions.iter().map(|f| &f.0).collect());
+        let externs = ids(self.externs.iter().map(|e| &e.0).collect());
+        for (name, arity, num_locals, code) in &self.functions {
+            let code = assemble(code, &funcs, &externs, &mut m.strings);
+            m.functions.push(Function {
+                name: name.clone(),
+                arity: *arity,
+                num_locals: *num_locals,
+                code,
+            });
+        }
+        m
+    }
+
+    /// Load the module into a new VM.
+    pub fn load(self) -> VM {
+        let mut vm = VM::new();
+        vm.load_module(self.build()).unwrap();
+        vm
+    }
+}
+
+/// How an operand is written and encoded.
+#[derive(Clone, Copy)]
+enum Operand {
+    U8,
+    U16,
+    U32,
+    U64,
+    Func,
+    Extern,
+    Str,
+    Label,
+    Scalar,
+}
+
+fn operands(op: Opcode) -> &'static [Operand] {
+    use Operand::*;
+    match op {
+        Opcode::LoadLocal | Opcode::StoreLocal => &[U16],
+        Opcode::AllocCtor => &[U8, U8],
+        Opcode::AllocCtorScalar => &[U8, U8, U16],
+        Opcode::CtorGet
+        | Opcode::CtorSet
+        | Opcode::CtorSetTag
+        | Opcode::ClosureGet
+        | Opcode::ClosureSet
+        | Opcode::Apply
+        | Opcode::TailApply
+        | Opcode::BoolLit => &[U8],
+        Opcode::Call | Opcode::TailCall => &[Func, U8],
+        Opcode::AllocClosure | Opcode::PartialApp => &[Func, U8, U8],
+        Opcode::CallExtern => &[Extern, U8],
+        Opcode::CallImport => &[Str, U8],
+        Opcode::LoadConst | Opcode::LoadModuleConst => &[U32],
+        Opcode::Jump | Opcode::JumpIf | Opcode::JumpIfNot => &[Label],
+        Opcode::NatLit => &[U64],
+        Opcode::StringLit => &[Str],
+        Opcode::ScalarProj | Opcode::ScalarSet => &[U8, U16, Scalar],
+        Opcode::Switch => unimplemented!("Switch in test assembly"),
+        _ => &[],
+    }
+}
+
+fn opcode(name: &str) -> Opcode {
+    (0..=u8::MAX)
+        .filter_map(Opcode::from_u8)
+        .find(|op| format!("{:?}", op) == name)
+        .unwrap_or_else(|| panic!("unknown opcode {}", name))
+}
+
+/// Split an instruction into words, keeping quoted strings whole.
+fn words(insn: &str) -> Vec<&str> {
+    let mut words = Vec::new();
+    let mut rest = insn.trim_start();
+    while !rest.is_empty() {
+        let end = if let Some(quoted) = rest.strip_prefix('"') {
+            quoted.find('"').expect("unterminated string") + 2
+        } else {
+            rest.find(char::is_whitespace).unwrap_or(rest.len())
+        };
+        words.push(&rest[..end]);
+        rest = rest[end..].trim_start();
+    }
+    words
+}
+
+fn assemble(
+    code: &str,
+    funcs: &HashMap<String, u32>,
+    externs: &HashMap<String, u32>,
+    strings: &mut Vec<String>,
+) -> Vec<u8> {
+    let mut b = BytecodeBuilder::new();
+    let mut labels = HashMap::new();
+    let mut label = |b: &mut BytecodeBuilder, name: &str| {
+        *labels
+            .entry(name.to_string())
+            .or_insert_with(|| b.new_label())
+    };
+    let id = |ids: &HashMap<String, u32>, word: &str| {
+        word.parse()
+            .ok()
+            .or_else(|| ids.get(word).copied())
+            .unwrap_or_else(|| panic!("unknown name {}", word))
+    };
+    for insn in code.split([';', '\n']) {
+        let mut words = words(insn);
+        if let Some(name) = words.first().and_then(|w| w.strip_suffix(':')) {
+            let l = label(&mut b, name);
+            b.mark_label(l);
+            words.remove(0);
+        }
+        let Some((op, args)) = words.split_first() else {
+            continue;
+        };
+        let op = opcode(op);
+        let kinds = operands(op);
+        assert_eq!(args.len(), kinds.len(), "operands of {:?}", op);
+        if let [Operand::Label] = kinds {
+            // `@label`, or a raw offset
+            if let Some(target) = args[0].strip_prefix('@') {
+                let l = label(&mut b, target);
+                b.emit_jump(op, l);
+            } else {
+                b.emit(op);
+                b.emit_i32(args[0].parse().expect("jump offset"));
+            }
+            continue;
+        }
+        b.emit(op);
+        for (&arg, kind) in args.iter().zip(kinds) {
+            let num = || -> u64 {
+                arg.parse()
+                    .unwrap_or_else(|_| panic!("bad operand {}", arg))
+            };
+            match kind {
+                Operand::U8 => b.emit_u8(num() as u8),
+                Operand::U16 => b.emit_u16(num() as u16),
+                Operand::U32 => b.emit_u32(num() as u32),
+                Operand::U64 => b.emit_u64(num()),
+                Operand::Func => b.emit_u32(id(funcs, arg)),
+                Operand::Extern => b.emit_u32(id(externs, arg)),
+                Operand::Str => {
+                    let s = arg.trim_matches('"');
+                    let idx = strings.iter().position(|t| t == s).unwrap_or_else(|| {
+                        strings.push(s.to_string());
+                        strings.len() - 1
+                    });
+                    b.emit_u32(idx as u32);
+                }
+                Operand::Scalar => {
+                    let ty = (0..=u8::MAX)
+                        .filter_map(ScalarType::from_u8)
+                        .find(|ty| format!("{:?}", ty) == arg)
+                        .unwrap_or_else(|| panic!("unknown scalar type {}", arg));
+                    b.emit_u8(ty as u8);
+                }
+                Operand::Label => unreachable!(),
+            }
+        }
+    }
+    b.finish()
+}
+
+/// `main` calls `fail ()`, which panics with "boom"
+pub fn panic_module() -> Module {
+    TestModule::new()
+        .externs(&[("lean_panic_fn", 2)])
+        .function("main", 0, 0, "UnitLit; Call fail 1; Ret")
+        .function(
+            "fail",
+            1,
+            1,
+            r#"LoadLocal 0; StringLit "boom"; CallExtern lean_panic_fn 2; Ret"#,
+        )
+        .build()
+}
@@ -1,5 +1,6 @@
 //! Safe wrapper around Lean objects with RAII reference counting
 
+use crate::bytecode::ScalarType;
 use lean_runtime::*;
 
 /// Safe wrapper around LeanObject pointer.
@@ -56,24 +57,17 @@ impl LeanValue {
         unsafe { lean_obj_tag(self.0) as usize }
     }
 
-    /// Check if we have exclusive access (rc=1, can mutate in place)
+    /// Check if we have exclusive access (single-threaded with rc=1, can
+    /// mutate in place)
     #[inline]
     pub fn is_exclusive(&self) -> bool {
-        if self.is_scalar() {
-            false
-        } else {
-            unsafe { (*self.0).rc == 1 }
-        }
+        !self.is_scalar() && unsafe { lean_is_exclusive(self.0) }
     }
 
-    /// Check if object is shared (rc > 1)
+    /// Check if object is shared (more than one reference, in either RC mode)
     #[inline]
     pub fn is_shared(&self) -> bool {
-        if self.is_scalar() {
-            false
-        } else {
-            unsafe { (*self.0).rc > 1 }
-        }
+        !self.is_scalar() && unsafe { lean_is_shared(self.0) }
     }
 
     // === Constructors for common values ===
@@ -168,6 +162,41 @@ impl LeanValue {
         }
     }
 
+    /// Read a scalar field at `offset` in the scalar area, boxed the way the
+    /// VM represents values of that type.
+    pub fn scalar_get(&self, ty: ScalarType, offset: u32) -> LeanValue {
+        unsafe {
+            let o = self.0;
+            let v = match ty {
+                ScalarType::UInt8 => lean_box(lean_ctor_get_uint8(o, offset) as usize),
+                ScalarType::UInt16 => lean_box(lean_ctor_get_uint16(o, offset) as usize),
+                ScalarType::UInt32 => lean_box_uint32(lean_ctor_get_uint32(o, offset)),
+                ScalarType::UInt64 => lean_box_uint64(lean_ctor_get_uint64(o, offset)),
+                ScalarType::USize => lean_box_usize(lean_ctor_get_uint64(o, offset) as usize),
+                ScalarType::Float => lean_box_float(lean_ctor_get_float(o, offset)),
+                ScalarType::Float32 => lean_box_float32(lean_ctor_get_float32(o, offset)),
+            };
+            LeanValue(v)
+        }
+    }
+
+    /// Write a scalar field at `offset` in the scalar area from its boxed
+    /// representation.
+    pub fn scalar_set(&mut self, ty: ScalarType, offset: u32, val: &LeanValue) {
+        unsafe {
+            let (o, v) = (self.0, val.0);
+            match ty {
+                ScalarType::UInt8 => lean_ctor_set_uint8(o, offset, lean_unbox(v) as u8),
+                ScalarType::UInt16 => lean_ctor_set_uint16(o, offset, lean_unbox(v) as u16),
+                ScalarType::UInt32 => lean_ctor_set_uint32(o, offset, lean_unbox_uint32(v)),
+                ScalarType::UInt64 => lean_ctor_set_uint64(o, offset, lean_unbox_uint64(v)),
+                ScalarType::USize => lean_ctor_set_uint64(o, offset, lean_unbox_usize(v) as u64),
+                ScalarType::Float => lean_ctor_set_float(o, offset, lean_unbox_float(v)),
+                ScalarType::Float32 => lean_ctor_set_float32(o, offset, lean_unbox_float32(v)),
+            }
+        }
+    }
+
     // === Closure operations ===
 
     /// Get captured variable from closure
@@ -247,3 +276,33 @@ pub unsafe fn alloc_closure(func_ptr: *const (), arity: u8, num_fixed: u32) -> L
     let obj = lean_alloc_closure(func_ptr, arity as u32, num_fixed);
     LeanValue(obj)
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::test_util::TestModule;
+
+    /// An object handed to a task is multi-threaded (negative RC) and must
+    /// not be updated in place by the thread that still holds it
+    #[test]
+    fn is_shared_after_sharing_with_task() {
+        // main: let x := ⟨()⟩; let p ← Promise.new; p.resolve x;
+        //       pure ⟨isShared x, isExclusive x, x⟩
+        let mut vm = TestModule::new()
+            .externs(&[("lean_io_promise_new", 1), ("lean_io_promise_resolve", 3)])
+            .function(
+                "main",
+                0,
+                2,
+                "UnitLit; AllocCtor 0 1; StoreLocal 0
+                 UnitLit; CallExtern lean_io_promise_new 1; CtorGet 0; StoreLocal 1
+                 LoadLocal 0; LoadLocal 1; UnitLit; CallExtern lean_io_promise_resolve 3; Pop
+                 LoadLocal 0; IsShared; LoadLocal 0; IsExclusive; LoadLocal 0
+                 AllocCtor 0 3; Ret",
+            )
+            .load();
+        let result = vm.run().unwrap();
+        assert_eq!(result.ctor_get(0).unbox(), 1);
+        assert_eq!(result.ctor_get(1).unbox(), 0);
+        assert!(unsafe { lean_runtime::lean_is_mt(result.ctor_get(2).as_ptr()) });
+    }
+}
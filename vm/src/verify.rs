@@ -0,0 +1,470 @@
+//! Bytecode verifier
+//!
+//! Run over every function when a module is loaded, so that malformed code is
+//! rejected up front instead of failing mid-execution. It checks that:
+//! - every opcode is valid and its operands are complete
+//! - local, string, constant, extern and function indices are in range
+//! - jump targets land on instruction boundaries
+//! - the stack never underflows and has the same depth wherever paths merge
+//!
+//! Function ids the linker resolved index the first loaded module, where the VM
+//! runs them. Function ids that refer to another module (`mod_idx != 0`
+//! without the resolved bit) depend on load order and are checked when called.
+
+use crate::bytecode::{Function, Module, Opcode, ScalarType};
+use crate::linker::FUNC_ID_RESOLVED_BIT;
+
+/// Why a module was rejected.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifyError {
+    /// Function name and code offset of the offending instruction, if the
+    /// problem is in a function body.
+    pub location: Option<(String, usize)>,
+    pub message: String,
+}
+
+impl std::fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some((func, offset)) => write!(f, "{} at offset {}: {}", func, offset, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+impl std::error::Error for VerifyError {}
+
+/// Verify all functions and module-level references of `module`, to be loaded
+/// as the first module.
+pub fn verify_module(module: &Module) -> Result<(), VerifyError> {
+    verify_module_after(module, module.functions.len())
+}
+
+/// Verify `module` for loading after others, the first of which has
+/// `resolved_functions` functions.
+pub fn verify_module_after(module: &Module, resolved_functions: usize) -> Result<(), VerifyError> {
+    let num_functions = module.functions.len();
+    let module_error = |message: String| VerifyError {
+        location: None,
+        message,
+    };
+    if num_functions > 0 && module.entry as usize >= num_functions {
+        return Err(module_error(format!(
+            "entry function {} out of range ({} functions)",
+            module.entry, num_functions
+        )));
+    }
+    if let Some(init) = module.init_func {
+        if init as usize >= num_functions {
+            return Err(module_error(format!(
+                "init function {} out of range ({} functions)",
+                init, num_functions
+            )));
+        }
+    }
+    for c in &module.const_decls {
+        if c.init_func as usize >= num_functions {
+            return Err(module_error(format!(
+                "constant {} has init function {} out of range ({} functions)",
+                c.name, c.init_func, num_functions
+            )));
+        }
+    }
+    for func in &module.functions {
+        verify_function(module, resolved_functions, func).map_err(|(offset, message)| {
+            VerifyError {
+                location: Some((func.name.clone(), offset)),
+                message,
+            }
+        })?;
+    }
+    Ok(())
+}
+
+/// A decoded instruction: its stack effect and where control goes next.
+struct Insn {
+    op: Opcode,
+    /// Offset of the following instruction
+    next: usize,
+    pops: usize,
+    pushes: usize,
+    /// Whether execution can continue at `next`
+    falls_through: bool,
+    /// Jump targets, relative to `next`
+    jumps: Vec<i64>,
+}
+
+/// Operand reader that reports truncated instructions.
+struct Operands<'a> {
+    code: &'a [u8],
+    pc: usize,
+}
+
+impl Operands<'_> {
+    fn bytes<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        let bytes = self
+            .code
+            .get(self.pc..self.pc + N)
+            .ok_or("truncated operand")?;
+        self.pc += N;
+        Ok(bytes.try_into().unwrap())
+    }
+
+    fn u8(&mut self) -> Result<u8, String> {
+        Ok(self.bytes::<1>()?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, String> {
+        Ok(u16::from_le_bytes(self.bytes()?))
+    }
+
+    fn u32(&mut self) -> Result<u32, String> {
+        Ok(u32::from_le_bytes(self.bytes()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, String> {
+        Ok(i32::from_le_bytes(self.bytes()?))
+    }
+}
+
+fn check_index(kind: &str, idx: usize, len: usize) -> Result<(), String> {
+    if idx >= len {
+        return Err(format!(
+            "{} index {} out of range ({} available)",
+            kind, idx, len
+        ));
+    }
+    Ok(())
+}
+
+fn check_func_id(module: &Module, resolved_functions: usize, func_id: u32) -> Result<(), String> {
+    let (func_idx, num_functions) = if func_id & FUNC_ID_RESOLVED_BIT != 0 {
+        (func_id & !FUNC_ID_RESOLVED_BIT, resolved_functions)
+    } else if func_id >> 16 == 0 {
+        (func_id & 0xFFFF, module.functions.len())
+    } else {
+        // Refers to another module; resolved against load order at run time
+        return Ok(());
+    };
+    check_index("function", func_idx as usize, num_functions)
+}
+
+/// Decode the instruction at `pc`, checking its operands.
+fn decode(
+    module: &Module,
+    resolved_functions: usize,
+    func: &Function,
+    pc: usize,
+) -> Result<Insn, String> {
+    let op_byte = func.code[pc];
+    let op = Opcode::from_u8(op_byte).ok_or_else(|| format!("invalid opcode 0x{:02x}", op_byte))?;
+    let mut r = Operands {
+        code: &func.code,
+        pc: pc + 1,
+    };
+    let mut jumps = Vec::new();
+    let mut falls_through = true;
+    let (pops, pushes) = match op {
+        Opcode::LoadLocal | Opcode::StoreLocal => {
+            let idx = r.u16()? as usize;
+            check_index("local", idx, func.num_locals as usize)?;
+            if op == Opcode::LoadLocal {
+                (0, 1)
+            } else {
+                (1, 0)
+            }
+        }
+        Opcode::LoadConst => {
+            check_index("constant", r.u32()? as usize, module.constants.len())?;
+            (0, 1)
+        }
+        Opcode::LoadModuleConst => {
+            check_index(
+                "module constant",
+                r.u32()? as usize,
+                module.const_decls.len(),
+            )?;
+            (0, 1)
+        }
+        Opcode::Pop => (1, 0),
+        Opcode::Dup => (1, 2),
+        Opcode::AllocCtor | Opcode::AllocCtorScalar => {
+            let _tag = r.u8()?;
+            let num_fields = r.u8()? as usize;
+            if op == Opcode::AllocCtorScalar {
+                r.u16()?;
+            }
+            (num_fields, 1)
+        }
+        Opcode::CtorGet | Opcode::ClosureGet => {
+            r.u8()?;
+            (1, 1)
+        }
+        Opcode::CtorSet => {
+            r.u8()?;
+            (2, 0)
+        }
+        Opcode::ClosureSet => {
+            r.u8()?;
+            (2, 1)
+        }
+        Opcode::CtorSetTag => {
+            r.u8()?;
+            (1, 0)
+        }
+        Opcode::AllocClosure | Opcode::PartialApp => {
+            check_func_id(module, resolved_functions, r.u32()?)?;
+            let arity = r.u8()?;
+            let num_args = r.u8()?;
+            if num_args > arity {
+                return Err(format!(
+                    "{} arguments for a closure of arity {}",
+                    num_args, arity
+                ));
+            }
+            (num_args as usize, 1)
+        }
+        Opcode::Call | Opcode::TailCall => {
+            check_func_id(module, resolved_functions, r.u32()?)?;
+            let num_args = r.u8()? as usize;
+            if op == Opcode::TailCall {
+                falls_through = false;
+                (num_args, 0)
+            } else {
+                (num_args, 1)
+            }
+        }
+        Opcode::Apply => (r.u8()? as usize + 1, 1),
+        Opcode::TailApply => {
+            falls_through = false;
+            (r.u8()? as usize + 1, 0)
+        }
+        Opcode::CallExtern => {
+            check_index("extern", r.u32()? as usize, module.externs.len())?;
+            (r.u8()? as usize, 1)
+        }
+        Opcode::CallImport => {
+            check_index("string", r.u32()? as usize, module.strings.len())?;
+            (r.u8()? as usize, 1)
+        }
+        Opcode::Jump => {
+            jumps.push(r.i32()? as i64);
+            falls_through = false;
+            (0, 0)
+        }
+        Opcode::JumpIf | Opcode::JumpIfNot => {
+            jumps.push(r.i32()? as i64);
+            (1, 0)
+        }
+        Opcode::Switch => {
+            let num_cases = r.u16()?;
+            for _ in 0..=num_cases {
+                // Cases followed by the default
+                jumps.push(r.i32()? as i64);
+            }
+            falls_through = false;
+            (1, 0)
+        }
+        Opcode::Ret => {
+            falls_through = false;
+            (1, 0)
+        }
+        Opcode::Unreachable => {
+            falls_through = false;
+            (0, 0)
+        }
+        Opcode::Inc | Opcode::Dec => (1, 0),
+        Opcode::Trace => (0, 0),
+        Opcode::IsShared
+        | Opcode::IsExclusive
+        | Opcode::GetTag
+        | Opcode::Box
+        | Opcode::Unbox
+        | Opcode::IsScalar
+        | Opcode::NatSucc
+        | Opcode::StringLength => (1, 1),
+        Opcode::NatLit => {
+            r.bytes::<8>()?;
+            (0, 1)
+        }
+        Opcode::NatAdd
+        | Opcode::NatSub
+        | Opcode::NatMul
+        | Opcode::NatDiv
+        | Opcode::NatMod
+        | Opcode::NatLt
+        | Opcode::NatLe
+        | Opcode::NatEq
+        | Opcode::StringAppend
+        | Opcode::StringEq => (2, 1),
+        Opcode::StringLit => {
+            check_index("string", r.u32()? as usize, module.strings.len())?;
+            (0, 1)
+        }
+        Opcode::UnitLit => (0, 1),
+        Opcode::BoolLit => {
+            r.u8()?;
+            (0, 1)
+        }
+        Opcode::ScalarProj | Opcode::ScalarSet => {
+            let _num_objs = r.u8()?;
+            let _offset = r.u16()?;
+            let ty = r.u8()?;
+            ScalarType::from_u8(ty).ok_or_else(|| format!("invalid scalar type {}", ty))?;
+            if op == Opcode::ScalarProj {
+                (1, 1)
+            } else {
+                (2, 0)
+            }
+        }
+    };
+    Ok(Insn {
+        op,
+        next: r.pc,
+        pops,
+        pushes,
+        falls_through,
+        jumps,
+    })
+}
+
+/// Verify one function. Errors carry the offset of the offending instruction.
+fn verify_function(
+    module: &Module,
+    resolved_functions: usize,
+    func: &Function,
+) -> Result<(), (usize, String)> {
+    let code = &func.code;
+
+    // Decode every instruction, reachable or not, recording boundaries
+    let mut insns: Vec<Option<Insn>> = (0..code.len()).map(|_| None).collect();
+    let mut pc = 0;
+    while pc < code.len() {
+        let insn = decode(module, resolved_functions, func, pc).map_err(|e| (pc, e))?;
+        let next = insn.next;
+        insns[pc] = Some(insn);
+        pc = next;
+    }
+
+    // Resolve jump targets; jumping to the very end returns like falling off it
+    let mut targets: Vec<Vec<usize>> = vec![Vec::new(); code.len()];
+    for (pc, insn) in insns.iter().enumerate() {
+        let Some(insn) = insn else { continue };
+        for &rel in &insn.jumps {
+            let target = insn.next as i64 + rel;
+            let on_boundary = target == code.len() as i64
+                || (0..code.len() as i64).contains(&target) && insns[target as usize].is_some();
+            if !on_boundary {
+                return Err((
+                    pc,
+                    format!("jump target {} is not an instruction boundary", target),
+                ));
+            }
+            targets[pc].push(target as usize);
+        }
+    }
+
+    // Propagate stack depths along all paths from the entry
+    let mut depth: Vec<Option<usize>> = vec![None; code.len() + 1];
+    let mut worklist = vec![0];
+    depth[0] = Some(0);
+    while let Some(pc) = worklist.pop() {
+        if pc == code.len() {
+            continue;
+        }
+        let insn = insns[pc].as_ref().unwrap();
+        let d = depth[pc].unwrap();
+        if d < insn.pops {
+            return Err((
+                pc,
+                format!(
+                    "stack underflow: {:?} needs {} values but the stack has {}",
+                    insn.op, insn.pops, d
+                ),
+            ));
+        }
+        let after = d - insn.pops + insn.pushes;
+        let fallthrough = insn.falls_through.then_some(insn.next);
+        for succ in fallthrough.into_iter().chain(targets[pc].iter().copied()) {
+            match depth[succ] {
+                None => {
+                    depth[succ] = Some(after);
+                    worklist.push(succ);
+                }
+                Some(existing) if existing != after => {
+                    return Err((
+                        pc,
+                        format!(
+                            "stack depth {} on the path to offset {} differs from depth {} on another path",
+                            after, succ, existing
+                        ),
+                    ));
+                }
+                Some(_) => {}
+            }
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_util::TestModule;
+    use crate::vm::VMError;
+    use crate::VM;
+
+    /// Malformed functions are rejected at load time, pointing at the culprit
+    #[test]
+    fn rejects_bad_code() {
+        let load = |code: &str| {
+            VM::new().load_module(TestModule::new().function("main", 0, 1, code).build())
+        };
+        let location = |r: Result<(), VMError>| match r {
+            Err(VMError::Verify(e)) => e.location,
+            other => panic!("expected a verification error, got {:?}", other),
+        };
+
+        // Returning without a value underflows the stack
+        assert_eq!(
+            location(load("UnitLit; Pop; Ret")),
+            Some(("main".to_string(), 2))
+        );
+        // Local index past num_locals
+        assert_eq!(
+            location(load("LoadLocal 1; Ret")),
+            Some(("main".to_string(), 0))
+        );
+        // Jump into the middle of an instruction
+        assert_eq!(
+            location(load("Jump 1; BoolLit 1; Ret")),
+            Some(("main".to_string(), 0))
+        );
+        // Branches that leave different stack depths
+        assert!(location(load("BoolLit 1; JumpIf @l; UnitLit; l: UnitLit; Ret")).is_some());
+
+        assert!(load("UnitLit; Ret").is_ok());
+    }
+
+    /// Function ids the linker resolved index the first loaded module
+    #[test]
+    fn resolved_ids_index_the_first_module() {
+        let call = |idx: u32| format!("Call {} 0; Ret", FUNC_ID_RESOLVED_BIT | idx);
+        let caller = |idx: u32| {
+            TestModule::new()
+                .function("main", 0, 0, &call(idx))
+                .function("g", 0, 0, "UnitLit; Ret")
+                .build()
+        };
+        assert!(VM::new().load_module(caller(1)).is_ok());
+
+        // After a module with one function, index 1 is out of range
+        let mut vm = TestModule::new()
+            .function("f", 0, 0, "NatLit 7; Ret")
+            .load();
+        let err = vm.load_module(caller(1)).unwrap_err();
+        assert!(matches!(err, VMError::Verify(_)), "{}", err);
+        vm.load_module(caller(0)).unwrap();
+        assert_eq!(vm.run().unwrap().unbox(), 7);
+    }
+}
@@ -1,12 +1,15 @@
 //! Virtual machine for executing Lean bytecode
 
-use crate::bytecode::{Module, Opcode};
-use crate::externs::{self, ExternFn};
+use crate::bytecode::{Constant, Module, Opcode, ScalarType};
+use crate::closure::{self, alloc_bytecode_closure, is_bytecode_closure};
+use crate::externs::{self, ExternFn, VmExternFn};
 use crate::linker::FUNC_ID_RESOLVED_BIT;
 use crate::value::{alloc_ctor, LeanValue};
+use crate::verify::{verify_module_after, VerifyError};
 use lean_runtime::*;
 use smallvec::SmallVec;
 use std::collections::HashMap;
+use std::time::{Duration, Instant};
 
 /// Inline capacity for local variables (avoids heap allocation for most functions)
 const LOCALS_INLINE_CAP: usize = 16;
@@ -34,9 +37,95 @@ pub enum VMError {
     InvalidFunctionId(u32),
     InvalidLocalIndex(u16),
     InvalidExternId(u32),
+    InvalidConstantId(u32),
     TypeMismatch(&'static str),
     Unreachable,
     IOError(String),
+    /// The module was rejected by the bytecode verifier
+    Verify(VerifyError),
+    /// `panic!` in Lean code, or a panicking primitive like `Array.get!`
+    Panic(String),
+    /// Execution was stopped by a [`Hook`], e.g. a debugger quitting
+    Interrupted,
+    /// More instructions were executed than [`Limits::max_instructions`]
+    OutOfFuel(u64),
+    /// The call depth exceeded [`Limits::max_frames`]
+    FrameLimit(usize),
+    /// The value stack grew beyond [`Limits::max_stack`]
+    StackLimit(usize),
+    /// Live allocations exceeded [`Limits::max_heap_bytes`]
+    HeapLimit(usize),
+    /// A closure over a function with more parameters than runtime closures
+    /// support, see [`MAX_CLOSURE_ARITY`](crate::closure::MAX_CLOSURE_ARITY)
+    ClosureArity(u32),
+    /// An extern function failed
+    Extern {
+        name: String,
+        error: Box<VMError>,
+    },
+    /// An error raised while executing bytecode, with the VM call stack at
+    /// that point, innermost frame first
+    Trace {
+        error: Box<VMError>,
+        stack: Vec<StackFrame>,
+    },
+}
+
+/// One frame of a VM call stack.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StackFrame {
+    pub function: String,
+    /// Bytecode offset of the instruction being executed
+    pub offset: usize,
+    /// Lean declaration name, from the module's debug info
+    pub decl_name: Option<String>,
+    /// Source position of the instruction, from the module's debug info
+    pub location: Option<SourceLocation>,
+}
+
+impl std::fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.decl_name.as_deref().unwrap_or(&self.function);
+        match &self.location {
+            Some(loc) => write!(f, "{} ({}, offset {})", name, loc, self.offset),
+            None => write!(f, "{} (offset {})", name, self.offset),
+        }
+    }
+}
+
+/// A position in a Lean source file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: String,
+    pub line: u32,
+    pub column: u32,
+}
+
+impl std::fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}:{}", self.file, self.line, self.column)
+    }
+}
+
+impl VMError {
+    /// The underlying error, without extern and stack trace context.
+    pub fn root(&self) -> &VMError {
+        match self {
+            VMError::Extern { error, .. } | VMError::Trace { error, .. } => error.root(),
+            e => e,
+        }
+    }
+
+    /// The VM call stack where the error was raised, innermost frame first.
+    /// For errors raised in nested calls from externs this includes the
+    /// frames of the calling bytecode.
+    pub fn stack(&self) -> Option<&[StackFrame]> {
+        match self {
+            VMError::Trace { stack, .. } => Some(stack),
+            VMError::Extern { error, .. } => error.stack(),
+            _ => None,
+        }
+    }
 }
 
 impl std::fmt::Display for VMError {
@@ -47,9 +136,31 @@ impl std::fmt::Display for VMError {
             VMError::InvalidFunctionId(id) => write!(f, "invalid function id: {}", id),
             VMError::InvalidLocalIndex(idx) => write!(f, "invalid local index: {}", idx),
             VMError::InvalidExternId(id) => write!(f, "invalid extern id: {}", id),
+            VMError::InvalidConstantId(id) => write!(f, "invalid constant id: {}", id),
             VMError::TypeMismatch(msg) => write!(f, "type mismatch: {}", msg),
             VMError::Unreachable => write!(f, "reached unreachable code"),
             VMError::IOError(msg) => write!(f, "IO error: {}", msg),
+            VMError::Verify(e) => write!(f, "invalid bytecode: {}", e),
+            VMError::Panic(msg) => write!(f, "panic: {}", msg),
+            VMError::Interrupted => write!(f, "execution interrupted"),
+            VMError::OutOfFuel(max) => write!(f, "instruction limit of {} exceeded", max),
+            VMError::FrameLimit(max) => write!(f, "call depth limit of {} frames exceeded", max),
+            VMError::StackLimit(max) => write!(f, "value stack limit of {} exceeded", max),
+            VMError::HeapLimit(max) => write!(f, "heap limit of {} bytes exceeded", max),
+            VMError::ClosureArity(arity) => write!(
+                f,
+                "closure over a function of {} parameters (at most {} supported)",
+                arity,
+                closure::MAX_CLOSURE_ARITY
+            ),
+            VMError::Extern { name, error } => write!(f, "in extern {}: {}", name, error),
+            VMError::Trace { error, stack } => {
+                write!(f, "{}", error)?;
+                for frame in stack {
+                    write!(f, "\n  at {}", frame)?;
+                }
+                Ok(())
+            }
         }
     }
 }
@@ -58,12 +169,95 @@ impl std::error::Error for VMError {}
 
 type Result<T> = std::result::Result<T, VMError>;
 
+/// Build the object for a constant pool entry. It is marked persistent, so
+/// it lives as long as the program and is shared without reference counting.
+fn load_constant(c: &Constant) -> LeanValue {
+    unsafe {
+        let o = match c {
+            Constant::String(s) => lean_mk_string(s),
+            Constant::Nat(n) => lean_uint64_to_nat(*n),
+            Constant::BigNat(digits) => lean_cstr_to_nat(digits),
+            Constant::Int(i) => lean_runtime::int::lean_int64_to_int(*i),
+            Constant::BigInt(digits) => lean_cstr_to_int(digits),
+            Constant::Float(f) => lean_box_float(*f),
+            Constant::ByteArray(bytes) => {
+                let arr = lean_alloc_sarray(1, bytes.len(), bytes.len());
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), lean_sarray_data(arr), bytes.len());
+                arr
+            }
+        };
+        if !lean_is_scalar(o) {
+            lean_mark_persistent(o);
+        }
+        LeanValue::from_raw(o)
+    }
+}
+
+/// Check that `obj` is a constructor with a scalar field of type `ty` at
+/// `offset`, so that scalar accesses stay inside the object.
+fn check_scalar_field(obj: &LeanValue, ty: ScalarType, offset: u32) -> Result<()> {
+    let o = obj.as_ptr();
+    unsafe {
+        if lean_is_scalar(o) || lean_obj_tag(o) > LEAN_MAX_CTOR_TAG {
+            return Err(VMError::TypeMismatch("constructor expected"));
+        }
+        if offset as usize + ty.size() > (*o).cs_sz as usize {
+            return Err(VMError::TypeMismatch("scalar field out of bounds"));
+        }
+    }
+    Ok(())
+}
+
+/// Observer of bytecode execution, installed with [`VM::set_hook`].
+/// Debuggers and profilers use it to inspect the VM between instructions
+/// through [`VM::call_stack`], [`VM::frame_locals`] and friends.
+pub trait Hook: std::any::Any {
+    /// Called before each instruction is executed; `vm.frame_position(0)` is
+    /// that instruction. Returning an error aborts execution with it.
+    fn before_instruction(&mut self, vm: &VM) -> Result<()>;
+
+    /// Called when a bytecode function is entered, once its frame is the
+    /// innermost. Tail calls replace the caller's frame at the same depth.
+    fn on_call(&mut self, _vm: &VM) -> Result<()> {
+        Ok(())
+    }
+
+    /// Called when an extern function returns, with the time it took.
+    fn after_extern(&mut self, _vm: &VM, _name: &str, _elapsed: Duration) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Resource limits for bytecode execution, set with [`VM::set_limits`].
+/// `None` means unlimited. Exceeding a limit aborts the evaluation with the
+/// corresponding [`VMError`]; its frames are unwound and the VM stays usable.
+///
+/// Instructions and heap usage are counted per evaluation, which starts when
+/// [`VM::run`], [`VM::run_function`] or [`VM::apply`] is entered while no
+/// bytecode is executing, and includes nested calls from externs.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Limits {
+    /// Instructions executed per evaluation
+    pub max_instructions: Option<u64>,
+    /// Call frames, including those of nested calls from externs
+    pub max_frames: Option<usize>,
+    /// Values on the value stack, shared by all frames
+    pub max_stack: Option<usize>,
+    /// Bytes of objects allocated during the evaluation and still live.
+    /// Only allocations and frees on the evaluating thread are counted, not
+    /// those of tasks. Checked between instructions, so a single extern call
+    /// can overshoot it.
+    pub max_heap_bytes: Option<usize>,
+}
+
 /// Call frame
 struct Frame {
     func_id: u32,
     pc: usize,
+    /// Offset of the instruction being executed, for stack traces
+    insn_pc: usize,
     locals: Locals,
-    #[allow(dead_code)]
+    /// Value stack height when the frame was entered
     stack_base: usize,
 }
 
@@ -72,14 +266,33 @@ pub struct VM {
     stack: Stack,
     frames: Frames,
     modules: Vec<Module>,
+    /// Constant pools of the loaded modules, as persistent objects.
+    constant_pools: Vec<Vec<LeanValue>>,
     func_table: HashMap<String, (usize, usize)>,
     externs: HashMap<String, ExternFn>,
+    /// Externs that take the VM, e.g. to apply closures passed to them.
+    vm_externs: HashMap<String, VmExternFn>,
     #[allow(dead_code)]
     globals: HashMap<String, LeanValue>,
     /// Cache for closed constants (___closed__N functions).
     /// Key is (module_idx, func_idx), value is the cached result.
     /// This prevents re-initialization of closed constants on every reference.
     init_cache: HashMap<(usize, usize), LeanValue>,
+    hook: Option<Box<dyn Hook>>,
+    limits: Limits,
+    /// Whether any per-instruction limit is set
+    limits_set: bool,
+    /// Instructions executed in the current evaluation
+    instructions: u64,
+    /// Allocated bytes of this thread when the current evaluation started
+    heap_base: usize,
+    /// Nesting depth of `execute_inner`
+    executing: usize,
+    /// Error raised by a bytecode closure that runtime code applied, to be
+    /// re-raised when control returns to the VM; see [`closure`]
+    pub(crate) closure_error: Option<VMError>,
+    /// Program arguments, see [`VM::set_args`]
+    args: Vec<String>,
 }
 
 impl Default for VM {
@@ -94,10 +307,20 @@ impl VM {
             stack: SmallVec::new(),
             frames: SmallVec::new(),
             modules: Vec::new(),
+            constant_pools: Vec::new(),
             func_table: HashMap::new(),
             externs: HashMap::new(),
+            vm_externs: HashMap::new(),
             globals: HashMap::new(),
             init_cache: HashMap::new(),
+            hook: None,
+            limits: Limits::default(),
+            limits_set: false,
+            instructions: 0,
+            heap_base: 0,
+            executing: 0,
+            closure_error: None,
+            args: Vec::new(),
         };
         vm.register_builtins();
         vm
@@ -107,19 +330,39 @@ impl VM {
         for (name, func) in externs::get_builtins() {
             self.externs.insert(name.to_string(), func);
         }
+        for (name, func) in externs::get_vm_builtins() {
+            self.vm_externs.insert(name.to_string(), func);
+        }
     }
 
     pub fn register_extern(&mut self, name: &str, func: ExternFn) {
         self.externs.insert(name.to_string(), func);
     }
 
-    pub fn load_module(&mut self, module: Module) {
+    /// Register an extern that receives the running VM, so that it can call
+    /// back into bytecode with [`VM::apply`].
+    pub fn register_vm_extern(&mut self, name: &str, func: VmExternFn) {
+        self.vm_externs.insert(name.to_string(), func);
+    }
+
+    /// Verify and load a module. Modules loaded later may call functions of
+    /// earlier ones; the last one loaded provides the entry point for [`VM::run`].
+    pub fn load_module(&mut self, module: Module) -> Result<()> {
+        // Function ids the linker resolved run against the first module
+        let resolved_functions = self
+            .modules
+            .first()
+            .map_or(module.functions.len(), |m| m.functions.len());
+        verify_module_after(&module, resolved_functions).map_err(VMError::Verify)?;
         let mod_idx = self.modules.len();
         for (func_idx, func) in module.functions.iter().enumerate() {
             self.func_table
                 .insert(func.name.clone(), (mod_idx, func_idx));
         }
+        self.constant_pools
+            .push(module.constants.iter().map(load_constant).collect());
         self.modules.push(module);
+        Ok(())
     }
 
     pub fn run(&mut self) -> Result<LeanValue> {
@@ -130,10 +373,71 @@ impl VM {
         // Earlier modules are includes (Init, etc.)
         let main_mod_idx = self.modules.len() - 1;
         let entry = self.modules[main_mod_idx].entry as usize;
-        self.call_function(main_mod_idx, entry, vec![])?;
+        // `main : List String → IO _` takes the arguments before the world
+        let takes_args = self.modules[main_mod_idx]
+            .functions
+            .get(entry)
+            .is_some_and(|f| f.arity == 2);
+        let args = if takes_args {
+            vec![self.args_list()]
+        } else {
+            vec![]
+        };
+        self.call_function(main_mod_idx, entry, args)?;
         self.execute()
     }
 
+    /// Run the program the way a compiled Lean executable runs `main`: pass
+    /// it `args`, print an uncaught IO error like `lean_io_result_show_error`,
+    /// and return the exit code. That is the `UInt32` returned by
+    /// `main : IO UInt32`, 0 for `IO Unit`, and 1 after an uncaught error.
+    pub fn run_main(&mut self, args: Vec<String>) -> Result<u32> {
+        self.set_args(args);
+        let result = self.run()?;
+        let r = result.as_ptr();
+        unsafe {
+            if lean_is_scalar(r) || lean_obj_tag(r) > 1 || (*r).num_objs() != 2 {
+                // Not the result of an IO action
+                return Ok(0);
+            }
+            if lean_io_result_is_ok(r) {
+                let value = lean_io_result_get_value(r);
+                Ok(if lean_is_scalar(value) {
+                    lean_unbox(value) as u32
+                } else {
+                    0
+                })
+            } else {
+                lean_io_result_show_error(r);
+                Ok(1)
+            }
+        }
+    }
+
+    /// Set the program arguments, which [`VM::run`] passes to a `main` that
+    /// takes them and `IO.getArgs`-style externs return.
+    pub fn set_args(&mut self, args: Vec<String>) {
+        self.args = args;
+    }
+
+    pub fn args(&self) -> &[String] {
+        &self.args
+    }
+
+    /// The program arguments as a `List String`.
+    fn args_list(&self) -> LeanValue {
+        unsafe {
+            let mut list = lean_box(0);
+            for arg in self.args.iter().rev() {
+                let cons = lean_alloc_ctor(1, 2, 0);
+                lean_ctor_set(cons, 0, lean_mk_string(arg));
+                lean_ctor_set(cons, 1, list);
+                list = cons;
+            }
+            LeanValue::from_raw(list)
+        }
+    }
+
     pub fn run_function(&mut self, name: &str, args: &[LeanValue]) -> Result<LeanValue> {
         let (mod_idx, func_idx) = self
             .func_table
@@ -152,7 +456,18 @@ impl VM {
         func_idx: usize,
         args: Vec<LeanValue>,
     ) -> Result<()> {
-        let func = &self.modules[mod_idx].functions[func_idx];
+        if let Some(max) = self.limits.max_frames {
+            if self.frames.len() >= max {
+                return Err(VMError::FrameLimit(max));
+            }
+        }
+        let func = self
+            .modules
+            .get(mod_idx)
+            .and_then(|m| m.functions.get(func_idx))
+            .ok_or(VMError::InvalidFunctionId(
+                (mod_idx as u32) << 16 | func_idx as u32,
+            ))?;
         #[cfg(feature = "trace")]
         eprintln!(
             "    [DEBUG] call_function: mod={}, func={}, name={}, code_len={}",
@@ -172,12 +487,41 @@ impl VM {
         self.frames.push(Frame {
             func_id: (mod_idx as u32) << 16 | (func_idx as u32),
             pc: 0,
+            insn_pc: 0,
             locals,
             stack_base: self.stack.len(),
         });
+        if self.hook.is_some() {
+            self.call_hook(|hook, vm| hook.on_call(vm))?;
+        }
         Ok(())
     }
 
+    /// Apply a closure to arguments and run it to completion, returning the
+    /// result. Both bytecode and native closures are supported, and this may
+    /// be called re-entrantly from externs while the VM is executing.
+    pub fn apply(&mut self, closure: LeanValue, args: Vec<LeanValue>) -> Result<LeanValue> {
+        if is_bytecode_closure(&closure) {
+            return self.apply_bytecode_closure(closure, args);
+        }
+        let raw_args: Vec<*mut LeanObject> = args.into_iter().map(|v| v.into_raw()).collect();
+        let result = unsafe {
+            let result = lean_apply_m(closure.into_raw(), raw_args.len() as u32, raw_args.as_ptr());
+            LeanValue::from_raw(result)
+        };
+        self.check_closure_error(Ok(result))
+    }
+
+    /// Re-raise the error of a bytecode closure that failed while runtime
+    /// code was applying it, once the runtime call that produced `result`
+    /// has returned.
+    fn check_closure_error(&mut self, result: Result<LeanValue>) -> Result<LeanValue> {
+        match self.closure_error.take() {
+            Some(e) => Err(e),
+            None => result,
+        }
+    }
+
     /// Apply arguments to a bytecode function closure.
     /// Handles partial application, exact application, and over-application.
     fn apply_bytecode_closure(
@@ -185,97 +529,60 @@ impl VM {
         closure: LeanValue,
         new_args: Vec<LeanValue>,
     ) -> Result<LeanValue> {
-        unsafe {
+        let (func_id, arity, fixed) = unsafe {
             let closure_ptr = closure.as_ptr() as *const LeanClosure;
-            let arity = (*closure_ptr).arity as usize;
-            let num_fixed = (*closure_ptr).num_fixed as usize;
-            let func_id = (*closure_ptr).fun as usize as u32;
-
-            // Handle pre-resolved func_id (high bit set)
-            let (mod_idx, func_idx) = if func_id & FUNC_ID_RESOLVED_BIT != 0 {
-                (0usize, (func_id & !FUNC_ID_RESOLVED_BIT) as usize)
-            } else {
-                ((func_id >> 16) as usize, (func_id & 0xFFFF) as usize)
-            };
+            // The hidden function id is not part of the user-visible arity
+            let arity = (*closure_ptr).arity as usize - 1;
+            let num_fixed = (*closure_ptr).num_fixed as u32;
+            let fixed: Vec<_> = (1..num_fixed).map(|i| closure.closure_get(i)).collect();
+            (
+                closure::bytecode_closure_func_id(closure.as_ptr()),
+                arity,
+                fixed,
+            )
+        };
+        // Drop the closure (its fixed args have been copied)
+        std::mem::drop(closure);
 
-            #[cfg(feature = "trace")]
-            eprintln!(
-                "[DEBUG] apply_bytecode_closure: arity={}, num_fixed={}, new_args={}, func_id=0x{:x}",
-                arity, num_fixed, new_args.len(), func_id
-            );
+        #[cfg(feature = "trace")]
+        eprintln!(
+            "[DEBUG] apply_bytecode_closure: arity={}, num_fixed={}, new_args={}, func_id=0x{:x}",
+            arity,
+            fixed.len(),
+            new_args.len(),
+            func_id
+        );
 
-            let total_args = num_fixed + new_args.len();
-
-            if total_args < arity {
-                // Partial application: create a new closure with more fixed args
-                let new_closure = lean_alloc_closure(
-                    func_id as usize as *const (),
-                    arity as u32,
-                    total_args as u32,
-                );
-                // Copy existing fixed args
-                for i in 0..num_fixed {
-                    let arg = lean_closure_get(closure.as_ptr(), i as u32);
-                    lean_inc(arg);
-                    lean_closure_set(new_closure, i as u32, arg);
-                }
-                // Add new args
-                for (i, arg) in new_args.into_iter().enumerate() {
-                    lean_closure_set(new_closure, (num_fixed + i) as u32, arg.into_raw());
-                }
-                // Drop the original closure
-                std::mem::drop(closure);
-                Ok(LeanValue::from_raw(new_closure))
-            } else if total_args == arity {
-                // Exact application: collect all args and call the function
-                let mut all_args = Vec::with_capacity(arity);
-                for i in 0..num_fixed {
-                    let arg = lean_closure_get(closure.as_ptr(), i as u32);
-                    lean_inc(arg);
-                    all_args.push(LeanValue::from_raw(arg));
-                }
-                for arg in new_args {
-                    all_args.push(arg);
-                }
-                // Drop the closure (no longer needed)
-                std::mem::drop(closure);
-                // Call the bytecode function
-                self.call_function(mod_idx, func_idx, all_args)?;
-                // The function will push its result when it returns
-                // We need to run until it returns
-                self.execute_until_return()
-            } else {
-                // Over-application: saturate first, then apply remaining args
-                let needed = arity - num_fixed;
-                let (first, rest): (Vec<_>, Vec<_>) = new_args
-                    .into_iter()
-                    .enumerate()
-                    .partition(|(i, _)| *i < needed);
-                let first_args: Vec<_> = first.into_iter().map(|(_, v)| v).collect();
-                let rest_args: Vec<_> = rest.into_iter().map(|(_, v)| v).collect();
-
-                // First, fully saturate
-                let mut all_args = Vec::with_capacity(arity);
-                for i in 0..num_fixed {
-                    let arg = lean_closure_get(closure.as_ptr(), i as u32);
-                    lean_inc(arg);
-                    all_args.push(LeanValue::from_raw(arg));
-                }
-                for arg in first_args {
-                    all_args.push(arg);
-                }
-                // Drop the closure
-                std::mem::drop(closure);
-                // Call the function
-                self.call_function(mod_idx, func_idx, all_args)?;
-                let result = self.execute_until_return()?;
-
-                // Now apply remaining args to the result (which should be a closure)
-                self.apply_bytecode_closure(result, rest_args)
-            }
+        let total_args = fixed.len() + new_args.len();
+        let mut all_args = fixed;
+        if total_args < arity {
+            // Partial application: create a new closure with more fixed args
+            all_args.extend(new_args);
+            alloc_bytecode_closure(func_id, arity as u32, all_args)
+        } else if total_args == arity {
+            // Exact application: call the function and run until it returns
+            all_args.extend(new_args);
+            self.call_by_id(func_id, all_args)
+        } else {
+            // Over-application: saturate first, then apply remaining args
+            let mut rest_args = new_args;
+            let first_args: Vec<_> = rest_args.drain(..arity - all_args.len()).collect();
+            all_args.extend(first_args);
+            let result = self.call_by_id(func_id, all_args)?;
+            // The result should be a closure, bytecode or native
+            self.apply(result, rest_args)
         }
     }
 
+    /// Call the bytecode function `func_id` with exactly its arity of
+    /// arguments and run it to completion. Trampolines of bytecode closures
+    /// applied by the runtime land here.
+    pub(crate) fn call_by_id(&mut self, func_id: u32, args: Vec<LeanValue>) -> Result<LeanValue> {
+        let (mod_idx, func_idx) = closure::decode_func_id(func_id);
+        self.call_function(mod_idx, func_idx, args)?;
+        self.execute_until_return()
+    }
+
     /// Execute until the current call returns, returning the result.
     /// This is used for bytecode closure application where we need the result synchronously.
     fn execute_until_return(&mut self) -> Result<LeanValue> {
@@ -288,7 +595,174 @@ impl VM {
     }
 
     /// Execute VM instructions. If target_depth is Some(n), stop when frame depth <= n.
+    /// Errors are annotated with the call stack where they were raised, and
+    /// the frames this execution ran are unwound.
     fn execute_inner(&mut self, target_depth: Option<usize>) -> Result<LeanValue> {
+        // Let bytecode closures applied by runtime code call back into us
+        let _current = closure::enter(self);
+        if self.executing == 0 {
+            self.instructions = 0;
+            self.heap_base = lean_get_allocated_bytes();
+            self.closure_error = None;
+        }
+        self.executing += 1;
+        let result = self.dispatch(target_depth);
+        self.executing -= 1;
+        result.map_err(|e| {
+            let e = if e.stack().is_some() {
+                // Already traced by a nested execution, which saw all frames
+                e
+            } else {
+                VMError::Trace {
+                    error: Box::new(e),
+                    stack: self.call_stack(),
+                }
+            };
+            self.unwind(target_depth.unwrap_or(0));
+            e
+        })
+    }
+
+    /// Pop frames down to `depth`, with the values they left on the stack.
+    fn unwind(&mut self, depth: usize) {
+        if let Some(frame) = self.frames.get(depth) {
+            let stack_base = frame.stack_base;
+            self.frames.truncate(depth);
+            self.stack.truncate(stack_base);
+        }
+    }
+
+    /// Set the resource limits for subsequent evaluations.
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+        self.limits_set = limits.max_instructions.is_some()
+            || limits.max_stack.is_some()
+            || limits.max_heap_bytes.is_some();
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Instructions executed in the current or last evaluation.
+    pub fn instructions_executed(&self) -> u64 {
+        self.instructions
+    }
+
+    /// Check the per-instruction limits before executing an instruction.
+    fn check_limits(&self) -> Result<()> {
+        if let Some(max) = self.limits.max_instructions {
+            if self.instructions > max {
+                return Err(VMError::OutOfFuel(max));
+            }
+        }
+        if let Some(max) = self.limits.max_stack {
+            if self.stack.len() > max {
+                return Err(VMError::StackLimit(max));
+            }
+        }
+        if let Some(max) = self.limits.max_heap_bytes {
+            let grown = lean_get_allocated_bytes().wrapping_sub(self.heap_base) as isize;
+            if grown > 0 && grown as usize > max {
+                return Err(VMError::HeapLimit(max));
+            }
+        }
+        Ok(())
+    }
+
+    /// Install a hook called before every instruction, replacing any
+    /// previous one.
+    pub fn set_hook(&mut self, hook: Box<dyn Hook>) {
+        self.hook = Some(hook);
+    }
+
+    /// Remove the installed hook and return it.
+    pub fn take_hook(&mut self) -> Option<Box<dyn Hook>> {
+        self.hook.take()
+    }
+
+    /// Run `f` on the installed hook, if any.
+    fn call_hook(&mut self, f: impl FnOnce(&mut dyn Hook, &VM) -> Result<()>) -> Result<()> {
+        // Taken out for the call so that the hook can borrow the VM
+        let Some(mut hook) = self.hook.take() else {
+            return Ok(());
+        };
+        let result = f(hook.as_mut(), self);
+        self.hook = Some(hook);
+        result
+    }
+
+    /// Loaded modules, in load order.
+    pub fn modules(&self) -> &[Module] {
+        &self.modules
+    }
+
+    /// Look up a function by name, returning (module index, function index).
+    pub fn find_function(&self, name: &str) -> Option<(usize, usize)> {
+        self.func_table.get(name).copied()
+    }
+
+    /// Number of active call frames.
+    pub fn depth(&self) -> usize {
+        self.frames.len()
+    }
+
+    /// (module index, function index, offset of the current instruction) of
+    /// call frame `frame`, counting from 0 for the innermost.
+    pub fn frame_position(&self, frame: usize) -> Option<(usize, usize, usize)> {
+        let frame = self.frames.iter().rev().nth(frame)?;
+        let (mod_idx, func_idx) = closure::decode_func_id(frame.func_id);
+        Some((mod_idx, func_idx, frame.insn_pc))
+    }
+
+    /// Local variables of call frame `frame`, counting from 0 for the innermost.
+    pub fn frame_locals(&self, frame: usize) -> Option<&[LeanValue]> {
+        Some(&self.frames.iter().rev().nth(frame)?.locals)
+    }
+
+    /// The value stack, shared by all frames, top last.
+    pub fn value_stack(&self) -> &[LeanValue] {
+        &self.stack
+    }
+
+    /// The current call stack, innermost frame first.
+    pub fn call_stack(&self) -> Vec<StackFrame> {
+        self.frames
+            .iter()
+            .rev()
+            .map(|frame| {
+                let mod_idx = (frame.func_id >> 16) as usize;
+                let func_idx = (frame.func_id & 0xFFFF) as usize;
+                self.stack_frame(mod_idx, func_idx, frame.insn_pc)
+            })
+            .collect()
+    }
+
+    /// Describe the instruction at `offset` in a function, with source
+    /// information if the module has debug info.
+    pub fn stack_frame(&self, mod_idx: usize, func_idx: usize, offset: usize) -> StackFrame {
+        let module = &self.modules[mod_idx];
+        let debug = module
+            .function_debug_info(func_idx)
+            .filter(|d| !d.decl_name.is_empty() || !d.file.is_empty());
+        StackFrame {
+            function: module.functions[func_idx].name.clone(),
+            offset,
+            decl_name: debug
+                .map(|d| d.decl_name.clone())
+                .filter(|name| !name.is_empty()),
+            location: debug.and_then(|d| {
+                let pos = d.position(offset)?;
+                Some(SourceLocation {
+                    file: d.file.clone(),
+                    line: pos.line,
+                    column: pos.column,
+                })
+            }),
+        }
+    }
+
+    fn dispatch(&mut self, target_depth: Option<usize>) -> Result<LeanValue> {
         loop {
             // Check termination conditions
             if self.frames.is_empty() {
@@ -327,12 +801,22 @@ impl VM {
             }
 
             let op_byte = func.code[pc];
-            self.frames.last_mut().unwrap().pc += 1;
+            let frame = self.frames.last_mut().unwrap();
+            frame.insn_pc = pc;
+            frame.pc += 1;
             let op = Opcode::from_u8(op_byte).ok_or(VMError::InvalidOpcode(op_byte))?;
 
             #[cfg(feature = "trace")]
             eprintln!("  [{:04}] {:?}", pc, op);
 
+            self.instructions += 1;
+            if self.limits_set {
+                self.check_limits()?;
+            }
+            if self.hook.is_some() {
+                self.call_hook(|hook, vm| hook.before_instruction(vm))?;
+            }
+
             match op {
                 Opcode::LoadLocal => {
                     let idx = self.read_u16()? as usize;
@@ -391,14 +875,27 @@ impl VM {
                 }
 
                 Opcode::LoadConst => {
-                    let _const_id = self.read_u32()?;
-                    self.stack.push(LeanValue::unit());
+                    let const_id = self.read_u32()?;
+                    let mod_idx = (self.frames.last().unwrap().func_id >> 16) as usize;
+                    let value = self.constant_pools[mod_idx]
+                        .get(const_id as usize)
+                        .ok_or(VMError::InvalidConstantId(const_id))?;
+                    self.stack.push(value.clone());
                 }
 
-                Opcode::AllocCtor => {
+                Opcode::AllocCtor | Opcode::AllocCtorScalar => {
                     let tag = self.read_u8()?;
                     let num_fields = self.read_u8()? as u32;
-                    let obj = alloc_ctor(tag, num_fields, 0);
+                    let scalar_size = if op == Opcode::AllocCtorScalar {
+                        self.read_u16()? as usize
+                    } else {
+                        0
+                    };
+                    // Scalar fields start zeroed and are filled in with ScalarSet
+                    let obj = alloc_ctor(tag, num_fields, scalar_size);
+                    unsafe {
+                        std::ptr::write_bytes((*obj.as_ptr()).scalar_data_ptr(), 0, scalar_size);
+                    }
                     for i in (0..num_fields).rev() {
                         let field = self.stack.pop().ok_or(VMError::StackUnderflow)?;
                         unsafe {
@@ -417,15 +914,22 @@ impl VM {
                 }
 
                 Opcode::CtorSet => {
+                    // Statement, like IR `set x[i] := y`: consumes both operands
                     let idx = self.read_u8()? as u32;
                     let val = self.stack.pop().ok_or(VMError::StackUnderflow)?;
                     let mut obj = self.stack.pop().ok_or(VMError::StackUnderflow)?;
                     obj.ctor_set(idx, val);
-                    self.stack.push(obj);
                 }
 
                 Opcode::CtorSetTag => {
-                    let _tag = self.read_u8()?;
+                    // Statement, like IR `setTag x := c` on a reused object
+                    let tag = self.read_u8()?;
+                    let obj = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                    if !obj.is_scalar() {
+                        unsafe {
+                            (*obj.as_ptr()).tag = tag;
+                        }
+                    }
                 }
 
                 Opcode::GetTag => {
@@ -462,22 +966,15 @@ impl VM {
                         return Err(VMError::StackUnderflow);
                     }
                     let captured: Vec<_> = self.stack.drain(stack_len - num_captured..).collect();
-                    unsafe {
-                        let closure = lean_alloc_closure(
-                            corrected_func_id as usize as *const (),
-                            arity,
-                            num_captured as u32,
-                        );
-                        for (i, val) in captured.into_iter().enumerate() {
-                            lean_closure_set(closure, i as u32, val.into_raw());
-                        }
-                        self.stack.push(LeanValue::from_raw(closure));
-                    }
+                    let closure = alloc_bytecode_closure(corrected_func_id, arity, captured)?;
+                    self.stack.push(closure);
                 }
 
                 Opcode::ClosureGet => {
                     let idx = self.read_u8()? as u32;
                     let closure = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                    // Skip the hidden function id of bytecode closures
+                    let idx = idx + is_bytecode_closure(&closure) as u32;
                     unsafe {
                         let val = lean_closure_get(closure.as_ptr(), idx);
                         lean_inc(val);
@@ -490,21 +987,18 @@ impl VM {
                     let idx = self.read_u8()? as u32;
                     let val = self.stack.pop().ok_or(VMError::StackUnderflow)?;
                     let closure = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                    let idx = idx + is_bytecode_closure(&closure) as u32;
                     unsafe {
                         lean_closure_set(closure.as_ptr(), idx, val.into_raw());
                     }
                     self.stack.push(closure);
                 }
 
-                Opcode::Inc => {
-                    // No-op: Rust's RAII handles reference counting automatically.
-                    // LoadLocal clones (rc+1), Drop decrements (rc-1).
-                    // The IR's Inc/Dec are not needed with RAII semantics.
-                }
-
-                Opcode::Dec => {
-                    // No-op: Rust's RAII handles reference counting automatically.
-                    // See comment on Inc above.
+                Opcode::Inc | Opcode::Dec => {
+                    // Rust's RAII handles reference counting automatically:
+                    // LoadLocal clones (rc+1), Drop decrements (rc-1). The IR's
+                    // Inc/Dec only consume their operand, leaving the count as is.
+                    self.stack.pop().ok_or(VMError::StackUnderflow)?;
                 }
 
                 Opcode::IsShared => {
@@ -618,34 +1112,13 @@ impl VM {
                         }
                     }
 
-                    // Check if this is a bytecode function closure
-                    let raw_closure = closure.as_ptr();
-                    let is_bytecode_closure = unsafe {
-                        if lean_is_scalar(raw_closure) || lean_obj_tag(raw_closure) != 245 {
-                            false
-                        } else {
-                            let closure_obj = raw_closure as *const LeanClosure;
-                            let fun_ptr = (*closure_obj).fun as usize;
-                            // Bytecode function IDs are small (<= 0xFFFFFFFF).
-                            // Real function pointers on 64-bit are much larger.
-                            fun_ptr < 0x100000000
-                        }
-                    };
-
-                    if is_bytecode_closure {
+                    if is_bytecode_closure(&closure) {
                         // Handle bytecode function closure
                         let result = self.apply_bytecode_closure(closure, args)?;
                         self.stack.push(result);
                     } else {
-                        // Transfer ownership to lean_apply_m (it consumes closure and args)
-                        let raw_args: Vec<*mut LeanObject> =
-                            args.into_iter().map(|v| v.into_raw()).collect();
-                        let raw_closure = closure.into_raw();
-                        unsafe {
-                            let result =
-                                lean_apply_m(raw_closure, num_args as u32, raw_args.as_ptr());
-                            self.stack.push(LeanValue::from_raw(result));
-                        }
+                        let result = self.apply(closure, args)?;
+                        self.stack.push(result);
                     }
                 }
 
@@ -658,20 +1131,7 @@ impl VM {
                     let args: Vec<_> = self.stack.drain(stack_len - num_args..).collect();
                     let closure = self.stack.pop().ok_or(VMError::StackUnderflow)?;
 
-                    // Check if this is a bytecode function closure
-                    let raw_closure = closure.as_ptr();
-                    let is_bytecode_closure = unsafe {
-                        if lean_is_scalar(raw_closure) || lean_obj_tag(raw_closure) != 245 {
-                            false
-                        } else {
-                            let closure_obj = raw_closure as *const LeanClosure;
-                            let fun_ptr = (*closure_obj).fun as usize;
-                            // Bytecode function IDs are small (<= 0xFFFFFFFF).
-                            fun_ptr < 0x100000000
-                        }
-                    };
-
-                    if is_bytecode_closure {
+                    if is_bytecode_closure(&closure) {
                         // Pop frame first for tail call semantics
                         self.frames.pop();
                         // Handle bytecode function closure
@@ -681,19 +1141,12 @@ impl VM {
                         }
                         self.stack.push(result);
                     } else {
-                        // Transfer ownership to lean_apply_m (it consumes closure and args)
-                        let raw_args: Vec<*mut LeanObject> =
-                            args.into_iter().map(|v| v.into_raw()).collect();
-                        let raw_closure = closure.into_raw();
                         self.frames.pop();
-                        unsafe {
-                            let result =
-                                lean_apply_m(raw_closure, num_args as u32, raw_args.as_ptr());
-                            if self.frames.is_empty() {
-                                return Ok(LeanValue::from_raw(result));
-                            }
-                            self.stack.push(LeanValue::from_raw(result));
+                        let result = self.apply(closure, args)?;
+                        if self.frames.is_empty() {
+                            return Ok(result);
                         }
+                        self.stack.push(result);
                     }
                 }
 
@@ -722,17 +1175,8 @@ impl VM {
                         return Err(VMError::StackUnderflow);
                     }
                     let args: Vec<_> = self.stack.drain(stack_len - num_args..).collect();
-                    unsafe {
-                        let closure = lean_alloc_closure(
-                            corrected_func_id as usize as *const (),
-                            arity,
-                            num_args as u32,
-                        );
-                        for (i, arg) in args.into_iter().enumerate() {
-                            lean_closure_set(closure, i as u32, arg.into_raw());
-                        }
-                        self.stack.push(LeanValue::from_raw(closure));
-                    }
+                    let closure = alloc_bytecode_closure(corrected_func_id, arity, args)?;
+                    self.stack.push(closure);
                 }
 
                 Opcode::CallExtern => {
@@ -753,13 +1197,34 @@ impl VM {
                     }
 
                     // Check syscall table first (preferred for new bytecode)
+                    let in_extern = |error| VMError::Extern {
+                        name: ext_name.clone(),
+                        error: Box::new(error),
+                    };
+                    let start = self.hook.is_some().then(Instant::now);
                     if let Some(&func) = self.externs.get(&ext_name) {
                         let args: Vec<_> = self.stack.drain(stack_len - num_args..).collect();
-                        let result = func(&args)?;
+                        let result = self.check_closure_error(func(&args));
                         for arg in args {
                             std::mem::forget(arg);
                         }
-                        self.stack.push(result);
+                        self.stack.push(result.map_err(in_extern)?);
+                        if let Some(start) = start {
+                            let elapsed = start.elapsed();
+                            self.call_hook(|hook, vm| hook.after_extern(vm, &ext_name, elapsed))?;
+                        }
+                    } else if let Some(&func) = self.vm_externs.get(&ext_name) {
+                        let args: Vec<_> = self.stack.drain(stack_len - num_args..).collect();
+                        let result = func(self, &args);
+                        let result = self.check_closure_error(result);
+                        for arg in args {
+                            std::mem::forget(arg);
+                        }
+                        self.stack.push(result.map_err(in_extern)?);
+                        if let Some(start) = start {
+                            let elapsed = start.elapsed();
+                            self.call_hook(|hook, vm| hook.after_extern(vm, &ext_name, elapsed))?;
+                        }
                     } else if let Some(&(bc_mod_idx, bc_func_idx)) = self.func_table.get(&ext_name)
                     {
                         // Backwards compat: old bytecode uses CallExtern for imports
@@ -773,17 +1238,9 @@ impl VM {
                             );
                             let args: Vec<_> = self.stack.drain(stack_len - num_args..).collect();
                             let bc_func_id = ((bc_mod_idx as u32) << 16) | (bc_func_idx as u32);
-                            unsafe {
-                                let closure = lean_alloc_closure(
-                                    bc_func_id as usize as *const (),
-                                    bc_arity as u32,
-                                    num_args as u32,
-                                );
-                                for (i, arg) in args.into_iter().enumerate() {
-                                    lean_closure_set(closure, i as u32, arg.into_raw());
-                                }
-                                self.stack.push(LeanValue::from_raw(closure));
-                            }
+                            let closure =
+                                alloc_bytecode_closure(bc_func_id, bc_arity as u32, args)?;
+                            self.stack.push(closure);
                         } else {
                             #[cfg(feature = "trace")]
                             eprintln!(
@@ -794,11 +1251,7 @@ impl VM {
                             self.call_function(bc_mod_idx, bc_func_idx, args)?;
                         }
                     } else {
-                        eprintln!(
-                            "Missing extern: {} (extern_id={} in module {})",
-                            ext_name, extern_id, mod_idx
-                        );
-                        return Err(VMError::InvalidExternId(extern_id as u32));
+                        return Err(in_extern(VMError::InvalidExternId(extern_id as u32)));
                     }
                 }
 
@@ -829,17 +1282,9 @@ impl VM {
                             );
                             let args: Vec<_> = self.stack.drain(stack_len - num_args..).collect();
                             let bc_func_id = ((bc_mod_idx as u32) << 16) | (bc_func_idx as u32);
-                            unsafe {
-                                let closure = lean_alloc_closure(
-                                    bc_func_id as usize as *const (),
-                                    bc_arity as u32,
-                                    num_args as u32,
-                                );
-                                for (i, arg) in args.into_iter().enumerate() {
-                                    lean_closure_set(closure, i as u32, arg.into_raw());
-                                }
-                                self.stack.push(LeanValue::from_raw(closure));
-                            }
+                            let closure =
+                                alloc_bytecode_closure(bc_func_id, bc_arity as u32, args)?;
+                            self.stack.push(closure);
                         } else {
                             // Full application: call the bytecode function
                             #[cfg(feature = "trace")]
@@ -1131,13 +1576,41 @@ impl VM {
                     }
                 }
 
-                Opcode::ScalarProj | Opcode::ScalarSet | Opcode::Trace => {
+                Opcode::ScalarProj => {
+                    let (ty, offset) = self.read_scalar_field()?;
+                    let obj = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                    check_scalar_field(&obj, ty, offset)?;
+                    // obj is dropped here, decrementing its ref count
+                    self.stack.push(obj.scalar_get(ty, offset));
+                }
+
+                Opcode::ScalarSet => {
+                    let (ty, offset) = self.read_scalar_field()?;
+                    let val = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                    let mut obj = self.stack.pop().ok_or(VMError::StackUnderflow)?;
+                    check_scalar_field(&obj, ty, offset)?;
+                    // Statement, like IR `sset x[n, off] := y`
+                    obj.scalar_set(ty, offset, &val);
+                }
+
+                Opcode::Trace => {
                     return Err(VMError::InvalidOpcode(op as u8));
                 }
             }
         }
     }
 
+    /// Read the `num_objs, offset, type` operands of `ScalarProj`/`ScalarSet`.
+    /// `num_objs` is implied by the object itself and is not needed here.
+    fn read_scalar_field(&mut self) -> Result<(ScalarType, u32)> {
+        let _num_objs = self.read_u8()?;
+        let offset = self.read_u16()? as u32;
+        let ty = self.read_u8()?;
+        let ty =
+            ScalarType::from_u8(ty).ok_or(VMError::TypeMismatch("invalid scalar field type"))?;
+        Ok((ty, offset))
+    }
+
     fn read_u8(&mut self) -> Result<u8> {
         let frame = self.frames.last_mut().unwrap();
         let func_id = frame.func_id;
@@ -1186,3 +1659,302 @@ impl VM {
         Ok(lo | (hi << 32))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bytecode::{DebugInfo, FunctionDebugInfo, SourcePos};
+    use crate::linker::link;
+    use crate::test_util::{panic_module, TestModule};
+    use crate::value;
+
+    /// Panics surface as errors carrying the extern name and the call stack
+    #[test]
+    fn panic_stack_trace() {
+        let mut vm = VM::new();
+        vm.load_module(panic_module()).unwrap();
+        let err = vm.run().unwrap_err();
+        assert!(matches!(err.root(), VMError::Panic(msg) if msg == "boom"));
+        let stack: Vec<_> = err
+            .stack()
+            .unwrap()
+            .iter()
+            .map(|f| (f.function.as_str(), f.offset))
+            .collect();
+        assert_eq!(stack, [("fail", 8), ("main", 1)]);
+        assert_eq!(
+            err.to_string(),
+            "in extern lean_panic_fn: panic: boom\n  at fail (offset 8)\n  at main (offset 1)"
+        );
+    }
+
+    /// Debug info survives serialization and linking, and is used in traces
+    #[test]
+    fn debug_info_in_stack_trace() {
+        let mut m = panic_module();
+        m.debug_info = Some(DebugInfo {
+            functions: vec![
+                FunctionDebugInfo::default(),
+                FunctionDebugInfo {
+                    decl_name: "Test.fail".to_string(),
+                    file: "Test.lean".to_string(),
+                    positions: vec![
+                        SourcePos {
+                            offset: 0,
+                            line: 3,
+                            column: 2,
+                        },
+                        SourcePos {
+                            offset: 8,
+                            line: 4,
+                            column: 9,
+                        },
+                    ],
+                    local_names: vec!["x".to_string()],
+                },
+            ],
+        });
+
+        let mut bytes = Vec::new();
+        m.serialize(&mut bytes).unwrap();
+        let loaded = Module::deserialize(&mut &bytes[..]).unwrap();
+        assert_eq!(loaded.debug_info, m.debug_info);
+        let linked = link(vec![loaded]).unwrap();
+        assert_eq!(linked.debug_info, m.debug_info);
+
+        let mut vm = VM::new();
+        vm.load_module(linked).unwrap();
+        let err = vm.run().unwrap_err();
+        let top = &err.stack().unwrap()[0];
+        assert_eq!(top.decl_name.as_deref(), Some("Test.fail"));
+        assert_eq!(top.location.as_ref().unwrap().to_string(), "Test.lean:4:9");
+        assert_eq!(top.to_string(), "Test.fail (Test.lean:4:9, offset 8)");
+    }
+
+    /// Calls into a module that is not loaded fail instead of panicking
+    #[test]
+    fn call_into_missing_module() {
+        // Function 0 of module 5
+        let mut vm = TestModule::new()
+            .function("main", 0, 0, "Call 327680 0; Ret")
+            .load();
+        let err = vm.run().unwrap_err();
+        assert!(matches!(err.root(), VMError::InvalidFunctionId(0x5_0000)));
+    }
+
+    /// A ctor with one object field and a `scalar_size`-byte scalar area,
+    /// whose scalar field at `offset` is set from the argument and read back
+    fn scalar_field_code(ty: ScalarType, offset: u16, scalar_size: u16) -> String {
+        format!(
+            "NatLit 7; AllocCtorScalar 0 1 {scalar_size}; StoreLocal 1
+             LoadLocal 1; LoadLocal 0; ScalarSet 1 {offset} {ty:?}
+             LoadLocal 1; ScalarProj 1 {offset} {ty:?}; Ret"
+        )
+    }
+
+    /// Every scalar type survives a ScalarSet/ScalarProj round trip through
+    /// the scalar area of a ctor that also has object fields
+    #[test]
+    fn scalar_field_round_trip() {
+        use lean_runtime::*;
+        let types = [
+            ScalarType::UInt8,
+            ScalarType::UInt16,
+            ScalarType::UInt32,
+            ScalarType::UInt64,
+            ScalarType::USize,
+            ScalarType::Float,
+            ScalarType::Float32,
+        ];
+        let mut m = TestModule::new();
+        for ty in types {
+            m = m.function(&format!("{:?}", ty), 1, 2, &scalar_field_code(ty, 8, 16));
+        }
+        let mut vm = m.load();
+        let mut round_trip = |ty: ScalarType, v: *mut LeanObject| {
+            let arg = unsafe { LeanValue::from_raw(v) };
+            vm.run_function(&format!("{:?}", ty), &[arg]).unwrap()
+        };
+        unsafe {
+            let r = round_trip(ScalarType::UInt8, lean_box(200));
+            assert_eq!(r.unbox(), 200);
+            let r = round_trip(ScalarType::UInt16, lean_box(60000));
+            assert_eq!(r.unbox(), 60000);
+            let r = round_trip(ScalarType::UInt32, lean_box_uint32(0xdead_beef));
+            assert_eq!(lean_unbox_uint32(r.as_ptr()), 0xdead_beef);
+            // Boxed UInt64 and USize values carry 63 bits
+            let r = round_trip(ScalarType::UInt64, lean_box_uint64((1 << 62) + 1));
+            assert_eq!(lean_unbox_uint64(r.as_ptr()), (1 << 62) + 1);
+            let r = round_trip(ScalarType::USize, lean_box_usize((1 << 61) + 2));
+            assert_eq!(lean_unbox_usize(r.as_ptr()), (1 << 61) + 2);
+            let r = round_trip(ScalarType::Float, lean_box_float(-1.5e300));
+            assert_eq!(lean_unbox_float(r.as_ptr()), -1.5e300);
+            let r = round_trip(ScalarType::Float32, lean_box_float32(2.25));
+            assert_eq!(lean_unbox_float32(r.as_ptr()), 2.25);
+        }
+    }
+
+    /// Fields reaching past the end of the scalar area are rejected at run
+    /// time, as are scalar accesses on non-constructors
+    #[test]
+    fn scalar_field_out_of_bounds() {
+        let mut vm = TestModule::new()
+            .function(
+                "u64_at_4",
+                1,
+                2,
+                &scalar_field_code(ScalarType::UInt64, 4, 8),
+            )
+            .function("u8_at_8", 1, 2, &scalar_field_code(ScalarType::UInt8, 8, 8))
+            .function("u8_at_7", 1, 2, &scalar_field_code(ScalarType::UInt8, 7, 8))
+            .function("proj_nat", 1, 1, "LoadLocal 0; ScalarProj 0 0 UInt8; Ret")
+            .load();
+        let one = [LeanValue::from_small_nat(1)];
+        for name in ["u64_at_4", "u8_at_8"] {
+            let err = vm.run_function(name, &one).unwrap_err();
+            assert!(
+                matches!(
+                    err.root(),
+                    VMError::TypeMismatch("scalar field out of bounds")
+                ),
+                "{}: {}",
+                name,
+                err
+            );
+        }
+        assert_eq!(vm.run_function("u8_at_7", &one).unwrap().unbox(), 1);
+        let err = vm.run_function("proj_nat", &one).unwrap_err();
+        assert!(matches!(
+            err.root(),
+            VMError::TypeMismatch("constructor expected")
+        ));
+    }
+
+    /// CtorSet, CtorSetTag, Inc, Dec and ScalarSet are statements: they
+    /// consume their operands and push nothing
+    #[test]
+    fn statement_stack_effects() {
+        // op x: 99 below the operands is on top of the stack after `op`
+        let code =
+            |operands: &str, op: &str| format!("NatLit 99; LoadLocal 0; {operands}{op}; Ret");
+        let mut vm = TestModule::new()
+            .function("ctor_set", 1, 1, &code("NatLit 5; ", "CtorSet 0"))
+            .function("ctor_set_tag", 1, 1, &code("", "CtorSetTag 3"))
+            .function("inc", 1, 1, &code("", "Inc"))
+            .function("dec", 1, 1, &code("", "Dec"))
+            .function(
+                "scalar_set",
+                1,
+                1,
+                &code("NatLit 5; ", "ScalarSet 1 0 UInt8"),
+            )
+            .load();
+
+        let mut obj = value::alloc_ctor(0, 1, 8);
+        unsafe { lean_runtime::lean_ctor_set(obj.as_ptr(), 0, lean_runtime::lean_box(0)) };
+        obj.scalar_set(ScalarType::UInt8, 0, &LeanValue::from_small_nat(0));
+        let mut run = |name: &str, obj: &LeanValue| {
+            let result = vm.run_function(name, std::slice::from_ref(obj)).unwrap();
+            assert_eq!(result.unbox(), 99, "{}", name);
+            assert!(vm.value_stack().is_empty(), "{}", name);
+        };
+
+        run("ctor_set", &obj);
+        assert_eq!(obj.ctor_get(0).unbox(), 5);
+        run("ctor_set_tag", &obj);
+        assert_eq!(obj.tag(), 3);
+        run("scalar_set", &obj);
+        assert_eq!(obj.scalar_get(ScalarType::UInt8, 0).unbox(), 5);
+        // Reference counts are left to LeanValue
+        for name in ["inc", "dec"] {
+            run(name, &obj);
+            assert!(obj.is_exclusive(), "{}", name);
+        }
+    }
+
+    /// Each limit aborts a runaway evaluation with its own error, and the VM
+    /// can be used again afterwards
+    #[test]
+    fn execution_limits() {
+        let mut vm = TestModule::new()
+            // spin: loop forever
+            .function("spin", 0, 0, "spin: Jump @spin")
+            // deep x: non-tail recursion, leaving a value on the stack per call
+            .function("deep", 1, 1, "UnitLit; LoadLocal 0; Call deep 1; Pop; Ret")
+            // grow xs: tail recursion consing onto an ever longer list
+            .function("grow", 1, 1, "LoadLocal 0; AllocCtor 0 1; TailCall grow 1")
+            .function("id", 1, 1, "LoadLocal 0; Ret")
+            .load();
+        let unit = [LeanValue::unit()];
+        let mut check = |limits: Limits, name: &str, expected: fn(&VMError) -> bool| {
+            vm.set_limits(limits);
+            let err = vm.run_function(name, &unit).unwrap_err();
+            assert!(expected(err.root()), "{}: {}", name, err);
+            assert_eq!(vm.depth(), 0);
+            assert!(vm.value_stack().is_empty());
+            vm.set_limits(Limits::default());
+            assert!(vm.run_function("id", &unit).is_ok());
+        };
+
+        check(
+            Limits {
+                max_instructions: Some(1000),
+                ..Default::default()
+            },
+            "spin",
+            |e| matches!(e, VMError::OutOfFuel(1000)),
+        );
+        check(
+            Limits {
+                max_frames: Some(100),
+                ..Default::default()
+            },
+            "deep",
+            |e| matches!(e, VMError::FrameLimit(100)),
+        );
+        check(
+            Limits {
+                max_stack: Some(50),
+                ..Default::default()
+            },
+            "deep",
+            |e| matches!(e, VMError::StackLimit(50)),
+        );
+        check(
+            Limits {
+                max_heap_bytes: Some(1 << 20),
+                ..Default::default()
+            },
+            "grow",
+            |e| matches!(e, VMError::HeapLimit(_)),
+        );
+    }
+
+    /// `run_main` passes the arguments to `main` and turns its result into
+    /// an exit code
+    #[test]
+    fn main_protocol() {
+        // main args _ = pure (UInt32.ofNat args.head.length)
+        let mut vm = TestModule::new()
+            .function(
+                "main",
+                2,
+                2,
+                "LoadLocal 0; CtorGet 0; StringLength; UnitLit; AllocCtor 0 2; Ret",
+            )
+            .load();
+        assert_eq!(vm.run_main(vec!["hello".to_string()]).unwrap(), 5);
+        assert_eq!(vm.args(), ["hello"]);
+
+        // main _ _ = throw (IO.userError "oops")
+        let mut vm = TestModule::new()
+            .function(
+                "main",
+                2,
+                2,
+                r#"StringLit "oops"; AllocCtor 18 1; UnitLit; AllocCtor 1 2; Ret"#,
+            )
+            .load();
+        assert_eq!(vm.run_main(Vec::new()).unwrap(), 1);
+    }
+}